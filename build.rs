@@ -1,4 +1,8 @@
+use std::process::Command;
+
 fn main() {
+    emit_build_info();
+
     println!("cargo:rerun-if-changed=proto/com/google/transit/realtime/gtfs-realtime.proto");
     println!(
         "cargo:rerun-if-changed=proto/com/google/transit/realtime/gtfs-realtime-service-status.proto"
@@ -14,3 +18,38 @@ fn main() {
         .cargo_out_dir("proto")
         .run_from_script();
 }
+
+/// Embeds the git commit, build time and rustc version for the `/version` endpoint.
+fn emit_build_info() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    // Builds without a `.git` directory, e.g. in Docker, can pass the commit in
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+
+    let git_commit = std::env::var("GIT_COMMIT")
+        .ok()
+        .or_else(|| command_output(Command::new("git").args(["rev-parse", "HEAD"])))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(Command::new(rustc).arg("--version"))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let build_timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    println!("cargo:rustc-env=GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp);
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_string()).filter(|stdout| !stdout.is_empty())
+}
@@ -0,0 +1,243 @@
+use crate::calendar::{self, AlertKind, CalendarEvent, Category};
+use crate::severity::Severity;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::America::New_York;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Current and upcoming elevator and escalator outages.
+pub const OUTAGES_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/nyct%2Fnyct_ene.json";
+/// Every elevator and escalator, which is where their GTFS stop IDs come from.
+pub const EQUIPMENT_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/nyct%2Fnyct_ene_equipments.json";
+
+/// An entry in the outages feed. Every field is a string, including dates and `Y`/`N` flags.
+#[derive(Debug, Deserialize)]
+struct OutageRecord {
+    station: String,
+    #[serde(rename = "trainno", default)]
+    trains: String,
+    equipment: String,
+    #[serde(rename = "equipmenttype")]
+    equipment_type: String,
+    #[serde(default)]
+    serving: String,
+    #[serde(default)]
+    reason: String,
+    #[serde(rename = "outagedate")]
+    outage_date: String,
+    #[serde(rename = "estimatedreturntoservice", default)]
+    estimated_return: String,
+    #[serde(rename = "isupcomingoutage", default)]
+    is_upcoming: String,
+    #[serde(rename = "ismaintenanceoutage", default)]
+    is_maintenance: String,
+}
+
+/// An entry in the equipment list.
+#[derive(Debug, Deserialize)]
+struct EquipmentRecord {
+    #[serde(rename = "equipmentno")]
+    equipment: String,
+    /// One or more stop IDs separated by `/`, e.g. `A27` or `A27/A27N`
+    #[serde(rename = "elevatorsgtfsstopid", default)]
+    stop_ids: String,
+}
+
+fn is_yes(flag: &str) -> bool {
+    flag.eq_ignore_ascii_case("Y")
+}
+
+/// Parses the feed's New York local times, e.g. `12/15/2025 03:08:00 AM`.
+fn parse_local_datetime(value: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(value.trim(), "%m/%d/%Y %I:%M:%S %p").ok()?;
+
+    Some(calendar::new_york_time(naive))
+}
+
+/// Turns the outages and equipment feeds into one calendar event per outage.
+///
+/// Outages of equipment that isn't in the equipment list are still included, but without
+/// stops, so they don't show up on any station's calendar.
+pub fn outages_to_events(
+    outages_json: &[u8],
+    equipment_json: &[u8],
+) -> Result<Vec<CalendarEvent>, serde_json::Error> {
+    let outages: Vec<OutageRecord> = serde_json::from_slice(outages_json)?;
+    let equipment: Vec<EquipmentRecord> = serde_json::from_slice(equipment_json)?;
+
+    let stops_by_equipment: HashMap<String, Vec<String>> = equipment
+        .into_iter()
+        .map(|record| {
+            let stop_ids = record
+                .stop_ids
+                .split('/')
+                .map(str::trim)
+                .filter(|stop_id| !stop_id.is_empty())
+                .map(String::from)
+                .collect();
+            (record.equipment, stop_ids)
+        })
+        .collect();
+
+    Ok(outages
+        .into_iter()
+        .filter_map(|outage| {
+            let start = parse_local_datetime(&outage.outage_date)?;
+            let stop_ids = stops_by_equipment
+                .get(&outage.equipment)
+                .cloned()
+                .unwrap_or_default();
+            Some(outage_to_event(outage, start, stop_ids))
+        })
+        .collect())
+}
+
+fn outage_to_event(
+    outage: OutageRecord,
+    start: DateTime<Utc>,
+    stop_ids: Vec<String>,
+) -> CalendarEvent {
+    let is_elevator = outage.equipment_type.eq_ignore_ascii_case("EL");
+    let (equipment_name, alert_type, severity) = if is_elevator {
+        ("Elevator", "Elevator Outage", Severity::Severe)
+    } else {
+        ("Escalator", "Escalator Outage", Severity::Warning)
+    };
+
+    // No estimated return means the outage is ongoing
+    let end = parse_local_datetime(&outage.estimated_return);
+
+    let mut description = format!(
+        "{} {} at {}",
+        equipment_name, outage.equipment, outage.station
+    );
+    if !outage.serving.is_empty() {
+        description.push_str(&format!("\nServing: {}", outage.serving));
+    }
+    if !outage.reason.is_empty() {
+        description.push_str(&format!("\nReason: {}", outage.reason));
+    }
+    match &end {
+        Some(end) => description.push_str(&format!(
+            "\nEstimated return to service: {}",
+            end.with_timezone(&New_York).format("%b %-d, %Y %-I:%M %p")
+        )),
+        None => description.push_str("\nNo estimated return to service"),
+    }
+
+    let kind = if is_yes(&outage.is_upcoming) || is_yes(&outage.is_maintenance) {
+        AlertKind::Planned
+    } else {
+        AlertKind::Realtime
+    };
+
+    CalendarEvent {
+        uid: format!("mta-outage-{}-{}", outage.equipment, start.timestamp()),
+        summary: format!("{} out of service: {}", equipment_name, outage.station),
+        description,
+        html_description: None,
+        start,
+        end,
+        created_at: start,
+        updated_at: start,
+        feed_time: start,
+        mta_alert_id: outage.equipment,
+        routes: outage
+            .trains
+            .split('/')
+            .map(str::trim)
+            .filter(|train| !train.is_empty())
+            .map(String::from)
+            .collect(),
+        stop_ids,
+        affected_stations: Vec::new(),
+        alert_type: alert_type.to_string(),
+        severity,
+        kind,
+        category: Category::Accessibility,
+        translations: BTreeMap::new(),
+        all_day: false,
+        url: None,
+        sequence: 0,
+        series_sequence: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_events() -> Vec<CalendarEvent> {
+        let outages = std::fs::read("tests/fixtures/ene_outages.json").unwrap();
+        let equipment = std::fs::read("tests/fixtures/ene_equipment.json").unwrap();
+        outages_to_events(&outages, &equipment).unwrap()
+    }
+
+    #[test]
+    fn test_parse_local_datetime() {
+        // EST in December, EDT in June
+        assert_eq!(
+            parse_local_datetime("12/15/2025 03:08:00 AM"),
+            Some(Utc.with_ymd_and_hms(2025, 12, 15, 8, 8, 0).unwrap())
+        );
+        assert_eq!(
+            parse_local_datetime("06/01/2025 11:00:00 PM"),
+            Some(Utc.with_ymd_and_hms(2025, 6, 2, 3, 0, 0).unwrap())
+        );
+        // 2:30 AM doesn't happen on the spring-forward Sunday, so the clocks read 3:30 AM EDT,
+        // and 1:30 AM happens twice on the fall-back Sunday, first in EDT
+        assert_eq!(
+            parse_local_datetime("03/09/2025 02:30:00 AM"),
+            Some(Utc.with_ymd_and_hms(2025, 3, 9, 7, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse_local_datetime("11/02/2025 01:30:00 AM"),
+            Some(Utc.with_ymd_and_hms(2025, 11, 2, 5, 30, 0).unwrap())
+        );
+        assert_eq!(parse_local_datetime(""), None);
+    }
+
+    #[test]
+    fn test_outages_to_events() {
+        let events = fixture_events();
+
+        let elevator = &events[0];
+        assert_eq!(elevator.summary, "Elevator out of service: 125 St");
+        assert_eq!(elevator.mta_alert_id, "EL290X");
+        assert_eq!(elevator.stop_ids, vec!["A15"]);
+        assert_eq!(elevator.routes, vec!["A", "B", "C", "D"]);
+        assert_eq!(elevator.severity, Severity::Severe);
+        assert!(
+            elevator
+                .description
+                .contains("Serving: Street to mezzanine")
+        );
+        assert!(
+            elevator
+                .description
+                .contains("Estimated return to service: Dec 16, 2025 6:00 PM")
+        );
+        assert_eq!(
+            elevator.end,
+            Some(Utc.with_ymd_and_hms(2025, 12, 16, 23, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_outage_without_estimated_return_is_ongoing() {
+        let events = fixture_events();
+
+        let escalator = events.iter().find(|e| e.mta_alert_id == "ES123").unwrap();
+        assert_eq!(escalator.end, None);
+        assert_eq!(escalator.kind, AlertKind::Planned);
+        assert!(
+            escalator
+                .description
+                .contains("No estimated return to service")
+        );
+    }
+}
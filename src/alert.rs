@@ -0,0 +1,202 @@
+use crate::calendar::{self, AlertKind, Language};
+use crate::proto::gtfs_realtime::EntitySelector;
+use crate::proto::gtfs_realtime::{FeedMessage, TimeRange};
+use crate::proto::gtfs_realtime_service_status;
+use crate::severity::Severity;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything the feed says about a single MTA alert, for the alert detail endpoint.
+///
+/// Calendar events are split per active period; this keeps the alert whole.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertDetail {
+    pub id: String,
+    pub alert_type: String,
+    pub header: String,
+    pub description: String,
+    pub severity: Severity,
+    pub kind: AlertKind,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub active_periods: Vec<ActivePeriod>,
+    pub informed_entities: Vec<InformedEntity>,
+    /// The MTA's page for the alert, when it links one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<Language, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivePeriod {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// A route, stop or agency the alert applies to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InformedEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agency_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_id: Option<String>,
+    /// The Mercury sort order, e.g. `MTASBWY:A:26`, which carries the alert's priority
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<String>,
+}
+
+/// Phrases the MTA uses in alerts that change step-free access, such as "This service change
+/// affects one or more ADA accessible stations" or "ADA Note: 86 St is not accessible".
+const ACCESSIBILITY_PHRASES: &[&str] = &["ada accessible", "ada note", "not accessible"];
+
+impl AlertDetail {
+    /// Whether the alert affects riders who need step-free access.
+    pub fn affects_accessibility(&self) -> bool {
+        affects_accessibility(&self.id, &format!("{}\n{}", self.header, self.description))
+    }
+
+    /// Whether the alert is in effect at `time`. Alerts without active periods are in effect
+    /// as long as they're in the feed.
+    pub fn is_active_at(&self, time: DateTime<Utc>) -> bool {
+        self.active_periods.is_empty()
+            || self.active_periods.iter().any(|period| {
+                period.start.is_none_or(|start| start <= time)
+                    && period.end.is_none_or(|end| time < end)
+            })
+    }
+}
+
+/// The elevator an outage alert is about. Mercury gives these alerts IDs made of the stop and
+/// the equipment, e.g. `A27N#EL290X`, and informs only the stop.
+pub fn elevator_outage_id(alert_id: &str) -> Option<&str> {
+    alert_id
+        .split_once('#')
+        .map(|(_, equipment)| equipment)
+        .filter(|equipment| equipment.starts_with("EL"))
+}
+
+/// Whether an alert with this ID and English text affects step-free access: it's an elevator
+/// outage, or a service change the MTA flags as affecting accessible stations.
+pub fn affects_accessibility(alert_id: &str, text: &str) -> bool {
+    if elevator_outage_id(alert_id).is_some() {
+        return true;
+    }
+
+    let text = text.to_lowercase();
+    ACCESSIBILITY_PHRASES
+        .iter()
+        .any(|phrase| text.contains(phrase))
+}
+
+pub fn proto_feed_to_alerts(feed: &FeedMessage) -> Vec<AlertDetail> {
+    let default_time = calendar::feed_time(feed);
+
+    feed.entity
+        .iter()
+        .filter_map(|entity| {
+            let alert = entity.alert.as_ref()?;
+            let alert_type = calendar::alert_type(alert);
+            let (created_at, updated_at) = calendar::alert_timestamps(alert, default_time);
+
+            let header = calendar::find_proto_plain_text(&alert.header_text.translation)
+                .map(calendar::process_text)
+                .unwrap_or_default();
+            let description = alert
+                .description_text
+                .as_ref()
+                .and_then(|desc| calendar::find_proto_plain_text(&desc.translation))
+                .map(calendar::process_text)
+                .unwrap_or_default();
+            let url = calendar::alert_url(alert);
+
+            Some(AlertDetail {
+                id: entity.id().to_string(),
+                severity: calendar::alert_severity(alert, &alert_type),
+                kind: calendar::alert_kind(alert),
+                alert_type,
+                header,
+                description,
+                created_at,
+                updated_at,
+                active_periods: alert.active_period.iter().map(active_period).collect(),
+                informed_entities: alert.informed_entity.iter().map(informed_entity).collect(),
+                url,
+                translations: calendar::alert_translations(alert),
+            })
+        })
+        .collect()
+}
+
+fn active_period(period: &TimeRange) -> ActivePeriod {
+    let timestamp = |seconds: u64| Utc.timestamp_opt(seconds as i64, 0).single();
+
+    ActivePeriod {
+        start: period.start.and_then(timestamp),
+        end: period.end.and_then(timestamp),
+    }
+}
+
+fn informed_entity(selector: &EntitySelector) -> InformedEntity {
+    InformedEntity {
+        agency_id: selector.agency_id.clone(),
+        route_id: selector.route_id.clone(),
+        stop_id: selector.stop_id.clone(),
+        sort_order: gtfs_realtime_service_status::exts::mercury_entity_selector
+            .get(selector)
+            .map(|mercury| mercury.sort_order().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use protobuf::Message;
+
+    #[test]
+    fn test_golden_alert_details() {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+        let alerts = proto_feed_to_alerts(&feed);
+        let events = calendar::proto_feed_to_events(&feed);
+
+        // Each alert keeps all of the active periods its events are split into
+        let period_count: usize = alerts
+            .iter()
+            .map(|alert| alert.active_periods.len().max(1))
+            .sum();
+        assert_eq!(period_count, events.len());
+
+        let elevator = alerts.iter().find(|a| a.id.contains("#EL")).unwrap();
+        assert_eq!(elevator.informed_entities[0].route_id, None);
+        assert!(elevator.informed_entities[0].stop_id.is_some());
+        assert!(elevator.affects_accessibility());
+
+        let delays = alerts.iter().find(|a| a.alert_type == "Delays").unwrap();
+        assert!(!delays.affects_accessibility());
+    }
+
+    #[test]
+    fn test_affects_accessibility() {
+        assert_eq!(elevator_outage_id("A27N#EL290X"), Some("EL290X"));
+        assert_eq!(elevator_outage_id("lmm:alert:491468"), None);
+        assert_eq!(elevator_outage_id("A27N#ES123"), None);
+
+        assert!(affects_accessibility(
+            "lmm:planned_work:1",
+            "♿ This service change affects one or more ADA accessible stations"
+        ));
+        assert!(affects_accessibility(
+            "lmm:planned_work:2",
+            "ADA Note: 86 St is not accessible"
+        ));
+        assert!(!affects_accessibility(
+            "lmm:planned_work:3",
+            "Transfer at 125 St ♿ to continue your trip"
+        ));
+    }
+}
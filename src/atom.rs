@@ -0,0 +1,150 @@
+use crate::calendar::CalendarEvent;
+use crate::rss::escape_xml;
+use chrono::{DateTime, Utc};
+
+/// Generates an Atom 1.0 feed with one entry per event, most recently updated first.
+///
+/// The feed's `<updated>` is the latest alert modification, or `fallback_updated` when there
+/// are no events.
+pub fn generate_atom(
+    events: &[CalendarEvent],
+    title: &str,
+    feed_id: &str,
+    link: &str,
+    fallback_updated: DateTime<Utc>,
+) -> String {
+    let mut entries: Vec<&CalendarEvent> = events.iter().collect();
+    entries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(a.uid.cmp(&b.uid)));
+
+    let updated = entries
+        .first()
+        .map(|event| event.updated_at)
+        .unwrap_or(fallback_updated);
+
+    let mut atom = String::new();
+
+    atom.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    atom.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    atom.push_str(&format!("<id>{}</id>\n", escape_xml(feed_id)));
+    atom.push_str(&format!("<title>{}</title>\n", escape_xml(title)));
+    atom.push_str(&format!(
+        "<updated>{}</updated>\n",
+        format_datetime(&updated)
+    ));
+    atom.push_str(&format!(
+        "<link rel=\"alternate\" href=\"{}\"/>\n",
+        escape_xml(link)
+    ));
+    atom.push_str("<author><name>MTA</name></author>\n");
+
+    for event in entries {
+        atom.push_str(&generate_entry(event));
+    }
+
+    atom.push_str("</feed>\n");
+
+    atom
+}
+
+/// A stable URN for an event, derived from its MTA alert ID.
+pub fn entry_id(event: &CalendarEvent) -> String {
+    let mut id = String::from("urn:nyctraincal:");
+
+    for c in event.uid.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':') {
+            id.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                id.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+
+    id
+}
+
+fn generate_entry(event: &CalendarEvent) -> String {
+    let mut entry = String::new();
+
+    entry.push_str("<entry>\n");
+    entry.push_str(&format!("<id>{}</id>\n", escape_xml(&entry_id(event))));
+    entry.push_str(&format!("<title>{}</title>\n", escape_xml(&event.summary)));
+    entry.push_str(&format!(
+        "<published>{}</published>\n",
+        format_datetime(&event.created_at)
+    ));
+    entry.push_str(&format!(
+        "<updated>{}</updated>\n",
+        format_datetime(&event.updated_at)
+    ));
+    if !event.description.is_empty() {
+        entry.push_str(&format!(
+            "<summary>{}</summary>\n",
+            escape_xml(&event.description)
+        ));
+    }
+    entry.push_str(&format!(
+        "<category term=\"{}\"/>\n",
+        escape_xml(&event.alert_type)
+    ));
+    entry.push_str("</entry>\n");
+
+    entry
+}
+
+fn format_datetime(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_entry_id_is_a_stable_urn() {
+        let event = CalendarEvent::for_test("mta-alert-A27N#EL290X", &[]);
+        assert_eq!(entry_id(&event), "urn:nyctraincal:mta-alert-A27N%23EL290X");
+        assert_eq!(entry_id(&event), entry_id(&event.clone()));
+    }
+
+    #[test]
+    fn test_generate_atom_orders_newest_first() {
+        let older = CalendarEvent {
+            summary: "F: Delays".to_string(),
+            updated_at: Utc.with_ymd_and_hms(2025, 12, 15, 9, 0, 0).unwrap(),
+            ..CalendarEvent::for_test("mta-alert-1", &["F"])
+        };
+        let newer = CalendarEvent {
+            summary: "F: Planned - Reroute".to_string(),
+            updated_at: Utc.with_ymd_and_hms(2025, 12, 15, 11, 30, 0).unwrap(),
+            ..CalendarEvent::for_test("mta-alert-2", &["F"])
+        };
+        let fallback = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+
+        let atom = generate_atom(
+            &[older, newer],
+            "MTA F Train Alerts",
+            "urn:nyctraincal:feed:train:F",
+            "http://localhost:3000/",
+            fallback,
+        );
+
+        assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+        assert!(atom.contains("<updated>2025-12-15T11:30:00Z</updated>\n<link"));
+        let newer_pos = atom.find("urn:nyctraincal:mta-alert-2").unwrap();
+        let older_pos = atom.find("urn:nyctraincal:mta-alert-1").unwrap();
+        assert!(newer_pos < older_pos);
+    }
+
+    #[test]
+    fn test_generate_atom_empty_uses_fallback_updated() {
+        let fallback = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let atom = generate_atom(&[], "Empty", "urn:nyctraincal:feed:empty", "/", fallback);
+
+        assert!(atom.contains("<updated>2025-12-15T12:00:00Z</updated>"));
+        assert!(!atom.contains("<entry>"));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::lines::Line;
+use crate::status::LineStatus;
+
+/// The badge color for `status`, following shields.io's green, yellow, orange and red
+fn status_color(status: LineStatus) -> &'static str {
+    match status {
+        LineStatus::GoodService => "#4C1",
+        LineStatus::PlannedWork => "#DFB317",
+        LineStatus::Delays => "#FE7D37",
+        LineStatus::PartSuspended | LineStatus::Suspended => "#E05D44",
+    }
+}
+
+/// Roughly how wide `text` is in 11px Verdana, which is close enough to size a badge.
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7
+}
+
+/// Renders a shields.io-style badge: the line's bullet in its official color, then its status.
+pub fn render_badge(line: &Line, status: LineStatus) -> String {
+    let label = "train";
+    let bullet_width = 20;
+    let label_width = bullet_width + text_width(label) + 6;
+    let status_width = text_width(status.label()) + 10;
+    let width = label_width + status_width;
+    let bullet_font_size = if line.id.len() > 1 { 8 } else { 11 };
+
+    format!(
+        concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{id} train: {status}">"##,
+            r##"<title>{id} train: {title}</title>"##,
+            r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#BBB" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##,
+            r##"<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#FFF"/></clipPath>"##,
+            r##"<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{status_width}" height="20" fill="{status_color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>"##,
+            r##"<circle cx="10" cy="10" r="8" fill="{color}"/>"##,
+            r##"<text x="10" y="14" fill="{text_color}" font-family="Helvetica,Arial,sans-serif" font-size="{bullet_font_size}" font-weight="bold" text-anchor="middle">{id}</text>"##,
+            r##"<g fill="#FFF" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##,
+            r##"<text x="{label_x}" y="14">{label}</text><text x="{status_x}" y="14">{status}</text></g>"##,
+            "</svg>",
+        ),
+        width = width,
+        label_width = label_width,
+        status_width = status_width,
+        status_color = status_color(status),
+        color = line.color,
+        text_color = line.text_color,
+        bullet_font_size = bullet_font_size,
+        id = line.id,
+        label = label,
+        label_x = bullet_width + (label_width - bullet_width) / 2,
+        status = status.label(),
+        title = match status.indicator() {
+            Some(indicator) => format!("{} {}", indicator, status.label()),
+            None => status.label().to_string(),
+        },
+        status_x = label_width + status_width / 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_badge() {
+        let line = crate::lines::find_line("F").unwrap();
+        let svg = render_badge(line, LineStatus::Delays);
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"113\""));
+        assert!(svg.contains("aria-label=\"F train: Delays\""));
+        assert!(svg.contains("<title>F train: ⚠️ Delays</title>"));
+        assert!(svg.contains("<circle cx=\"10\" cy=\"10\" r=\"8\" fill=\"#FF6319\"/>"));
+        assert!(svg.contains("fill=\"#FE7D37\""));
+
+        let svg = render_badge(line, LineStatus::GoodService);
+        assert!(svg.contains("<title>F train: Good Service</title>"));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::calendar::CalendarEvent;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// A bus route from the static GTFS `routes.txt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BusRoute {
+    /// The route ID alerts refer to the route by, e.g. `M15+`
+    pub route_id: String,
+    /// The name riders know the route by, e.g. `M15-SBS`
+    pub short_name: String,
+    pub long_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RouteRecord {
+    route_id: String,
+    #[serde(default)]
+    route_short_name: String,
+    #[serde(default)]
+    route_long_name: String,
+}
+
+/// The bus routes the server has calendars for, loaded from one or more GTFS `routes.txt` files.
+///
+/// The MTA publishes a separate GTFS feed for each borough plus MTA Bus, so there are hundreds
+/// of routes and no point hardcoding them.
+#[derive(Debug, Default)]
+pub struct BusRoutes {
+    /// Routes keyed by their uppercased route ID and short name, so both can be looked up
+    by_name: HashMap<String, usize>,
+    routes: Vec<BusRoute>,
+}
+
+impl BusRoutes {
+    /// Loads every `routes.txt` in `paths`, e.g. one per borough's GTFS feed.
+    pub fn load<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut bus_routes = BusRoutes::default();
+        for path in paths {
+            let file = std::fs::File::open(path)?;
+            bus_routes.add_csv(file)?;
+        }
+        Ok(bus_routes)
+    }
+
+    pub fn from_csv(reader: impl Read) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut bus_routes = BusRoutes::default();
+        bus_routes.add_csv(reader)?;
+        Ok(bus_routes)
+    }
+
+    fn add_csv(
+        &mut self,
+        reader: impl Read,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for record in csv::Reader::from_reader(reader).deserialize() {
+            let record: RouteRecord = record?;
+            let short_name = if record.route_short_name.is_empty() {
+                record.route_id.clone()
+            } else {
+                record.route_short_name
+            };
+
+            let index = self.routes.len();
+            self.by_name.insert(record.route_id.to_uppercase(), index);
+            self.by_name.insert(short_name.to_uppercase(), index);
+            self.routes.push(BusRoute {
+                route_id: record.route_id,
+                short_name,
+                long_name: record.route_long_name,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Finds a route by its short name or route ID, ignoring case, so `m15-sbs` and `M15+`
+    /// both find the M15 Select Bus Service.
+    pub fn find(&self, name: &str) -> Option<&BusRoute> {
+        self.by_name
+            .get(&name.trim().to_uppercase())
+            .map(|&index| &self.routes[index])
+    }
+
+    /// Copies `events`, titling each one after its routes' short names rather than their
+    /// route IDs, e.g. `M15-SBS: Detour` instead of `M15+: Detour`.
+    pub fn with_route_names(&self, events: &[CalendarEvent]) -> Vec<CalendarEvent> {
+        events
+            .iter()
+            .map(|event| {
+                let names: Vec<&str> = event
+                    .routes
+                    .iter()
+                    .map(|route_id| {
+                        self.find(route_id)
+                            .map_or(route_id.as_str(), |route| route.short_name.as_str())
+                    })
+                    .collect();
+                let names = if names.is_empty() {
+                    "MTA Bus".to_string()
+                } else {
+                    names.join(", ")
+                };
+
+                CalendarEvent {
+                    summary: format!("{}: {}", names, event.alert_type),
+                    ..event.clone()
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_routes() -> BusRoutes {
+        BusRoutes::load(["tests/fixtures/bus_routes.txt"]).unwrap()
+    }
+
+    #[test]
+    fn test_bus_routes_from_gtfs() {
+        let routes = fixture_routes();
+        assert_eq!(routes.len(), 5);
+
+        let sbs = routes.find("m15-sbs").unwrap();
+        assert_eq!(sbs.route_id, "M15+");
+        assert_eq!(routes.find("M15+"), Some(sbs));
+        assert_eq!(routes.find("M15").unwrap().short_name, "M15");
+        assert_eq!(routes.find("Q70-SBS").unwrap().long_name, "LaGuardia Link");
+        assert_eq!(routes.find("M1000"), None);
+    }
+
+    #[test]
+    fn test_bus_fixture_events() {
+        use crate::proto::gtfs_realtime::FeedMessage;
+
+        let text = std::fs::read_to_string("tests/fixtures/bus_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events =
+            fixture_routes().with_route_names(&crate::calendar::proto_feed_to_events(&feed));
+
+        assert_eq!(events[0].routes, vec!["M15+", "M15"]);
+        assert_eq!(events[0].summary, "M15-SBS, M15: Alert");
+        assert_eq!(events[1].summary, "B44: Alert");
+    }
+}
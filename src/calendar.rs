@@ -1,14 +1,20 @@
+use crate::dedup;
+use crate::lines;
+use crate::proto::gtfs_realtime::alert::{Effect, SeverityLevel};
 use crate::proto::gtfs_realtime::translated_string::Translation as ProtoTranslation;
-use crate::proto::gtfs_realtime::{FeedEntity, FeedMessage};
+use crate::proto::gtfs_realtime::{Alert, FeedEntity, FeedMessage};
 use crate::proto::gtfs_realtime_service_status;
-use chrono::{DateTime, TimeZone, Utc};
+use crate::sanitize;
+use crate::sequences::StopSequences;
+use crate::severity::{self, Severity};
+use crate::stops::{self, Stations};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 pub fn proto_feed_to_events(feed: &FeedMessage) -> Vec<CalendarEvent> {
-    let default_time = feed
-        .header
-        .as_ref()
-        .and_then(|h| Utc.timestamp_opt(h.timestamp() as i64, 0).single())
-        .unwrap_or_else(Utc::now);
+    let default_time = feed_time(feed);
 
     feed.entity
         .iter()
@@ -28,56 +34,51 @@ fn proto_entity_to_events(entity: &FeedEntity, default_time: DateTime<Utc>) -> V
         .filter_map(|e| e.route_id.as_ref().map(|s| s.to_string()))
         .collect();
 
-    let route_str = if routes.is_empty() {
-        String::from("MTA")
-    } else {
-        routes.join(", ")
-    };
-
-    let alert_type_str = gtfs_realtime_service_status::exts::mercury_alert
-        .get(alert)
-        .and_then(|mercury| mercury.alert_type.as_ref().map(|s| s.to_string()))
-        .unwrap_or_else(|| "Alert".to_string());
+    let stop_ids: Vec<String> = alert
+        .informed_entity
+        .iter()
+        .filter_map(|e| e.stop_id.as_ref().map(|s| s.to_string()))
+        .collect();
 
-    let summary = format!("{}: {}", route_str, alert_type_str);
+    let alert_type_str = alert_type(alert);
+    let severity = alert_severity(alert, &alert_type_str);
+    let kind = alert_kind(alert);
+    let category = alert_category(alert, &alert_type_str, kind);
 
-    let mut description = String::new();
+    let summary = event_summary(&routes, &alert_type_str);
 
-    if let Some(header_text) = find_proto_plain_text(&alert.header_text.translation) {
-        description.push_str(&process_text(header_text));
-    }
+    let description = alert_description(alert, find_proto_plain_text);
+    let html_description = alert_html_description(alert);
+    let url = alert_url(alert);
+    let translations = alert_translations(alert);
 
-    if let Some(desc) = alert.description_text.as_ref()
-        && let Some(desc_text) = find_proto_plain_text(&desc.translation)
-    {
-        if !description.is_empty() {
-            description.push_str("\n\n");
-        }
-        description.push_str(&process_text(desc_text));
-    }
-
-    let (created_at, updated_at) = gtfs_realtime_service_status::exts::mercury_alert
-        .get(alert)
-        .and_then(|mercury| {
-            let created = Utc.timestamp_opt(mercury.created_at() as i64, 0).single()?;
-            let updated = Utc.timestamp_opt(mercury.updated_at() as i64, 0).single()?;
-            Some((created, updated))
-        })
-        .unwrap_or((default_time, default_time));
+    let (created_at, updated_at) = alert_timestamps(alert, default_time);
 
     let active_periods = &alert.active_period;
     if active_periods.is_empty() {
         return vec![CalendarEvent {
-            uid: format!("mta-alert-{}", entity.id()),
+            uid: event_uid(entity.id(), 0, 1),
             summary: summary.clone(),
             description: description.clone(),
+            html_description: html_description.clone(),
             start: default_time,
             end: None,
             created_at,
             updated_at,
+            feed_time: default_time,
             mta_alert_id: entity.id().to_string(),
             routes: routes.clone(),
+            stop_ids: stop_ids.clone(),
+            affected_stations: Vec::new(),
             alert_type: alert_type_str.clone(),
+            severity,
+            kind,
+            category,
+            translations: translations.clone(),
+            all_day: false,
+            url: url.clone(),
+            sequence: 0,
+            series_sequence: 0,
         }];
     }
 
@@ -92,29 +93,228 @@ fn proto_entity_to_events(entity: &FeedEntity, default_time: DateTime<Utc>) -> V
                 None
             };
 
-            let uid = if active_periods.len() > 1 {
-                format!("mta-alert-{}-{}", entity.id(), idx)
-            } else {
-                format!("mta-alert-{}", entity.id())
-            };
-
             Some(CalendarEvent {
-                uid,
+                uid: event_uid(entity.id(), idx, active_periods.len()),
                 summary: summary.clone(),
                 description: description.clone(),
+                html_description: html_description.clone(),
                 start,
                 end,
                 created_at,
                 updated_at,
+                feed_time: default_time,
                 mta_alert_id: entity.id().to_string(),
                 routes: routes.clone(),
+                stop_ids: stop_ids.clone(),
+                affected_stations: Vec::new(),
                 alert_type: alert_type_str.clone(),
+                severity,
+                kind,
+                category,
+                translations: translations.clone(),
+                all_day: false,
+                url: url.clone(),
+                sequence: 0,
+                series_sequence: 0,
             })
         })
         .collect()
 }
 
-fn find_proto_plain_text(translations: &[ProtoTranslation]) -> Option<&str> {
+/// An alert event's summary, e.g. `F, G: Delays`.
+pub(crate) fn event_summary(routes: &[String], alert_type: &str) -> String {
+    if routes.is_empty() {
+        format!("MTA: {}", alert_type)
+    } else {
+        format!("{}: {}", routes.join(", "), alert_type)
+    }
+}
+
+/// The UID of the event for an alert's `period_idx`th active period out of `period_count`,
+/// e.g. `mta-alert-lmm:planned_work:29343`, or `mta-alert-lmm:planned_work:29343-1` for the
+/// second of several.
+///
+/// It only depends on the alert ID and periods, so calendar apps update the same event on each
+/// refresh rather than adding a copy. The index is only added when an alert has more than one
+/// period, as it always has been, so existing events keep their UIDs. An alert ID that itself
+/// ends in `-` and a number always gets the index, so it can't look like another alert's
+/// period and different alerts can't collide.
+fn event_uid(alert_id: &str, period_idx: usize, period_count: usize) -> String {
+    let ends_with_index = alert_id.rsplit_once('-').is_some_and(|(_, suffix)| {
+        !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit())
+    });
+
+    if period_count > 1 || ends_with_index {
+        format!("mta-alert-{}-{}", alert_id, period_idx)
+    } else {
+        format!("mta-alert-{}", alert_id)
+    }
+}
+
+/// The Mercury alert type, e.g. `Planned - Stops Skipped`. Feeds without Mercury extensions,
+/// such as PATH's, fall back to the standard GTFS-realtime effect, e.g. `Significant Delays`.
+pub(crate) fn alert_type(alert: &Alert) -> String {
+    gtfs_realtime_service_status::exts::mercury_alert
+        .get(alert)
+        .and_then(|mercury| mercury.alert_type.as_ref().map(|s| s.to_string()))
+        .or_else(|| effect_name(alert).map(str::to_string))
+        .unwrap_or_else(|| "Alert".to_string())
+}
+
+/// The alert's GTFS-realtime effect as it's shown to people, if the feed set a meaningful one.
+fn effect_name(alert: &Alert) -> Option<&'static str> {
+    if !alert.has_effect() {
+        return None;
+    }
+
+    match alert.effect() {
+        Effect::NO_SERVICE => Some("No Service"),
+        Effect::REDUCED_SERVICE => Some("Reduced Service"),
+        Effect::SIGNIFICANT_DELAYS => Some("Significant Delays"),
+        Effect::DETOUR => Some("Detour"),
+        Effect::ADDITIONAL_SERVICE => Some("Additional Service"),
+        Effect::MODIFIED_SERVICE => Some("Modified Service"),
+        Effect::STOP_MOVED => Some("Stop Moved"),
+        Effect::ACCESSIBILITY_ISSUE => Some("Accessibility Issue"),
+        Effect::OTHER_EFFECT | Effect::UNKNOWN_EFFECT | Effect::NO_EFFECT => None,
+    }
+}
+
+/// The most important priority among the informed entities, falling back to the alert type and
+/// then to the standard GTFS-realtime severity level.
+pub(crate) fn alert_severity(alert: &Alert, alert_type: &str) -> Severity {
+    alert
+        .informed_entity
+        .iter()
+        .filter_map(|e| gtfs_realtime_service_status::exts::mercury_entity_selector.get(e))
+        .filter_map(|selector| severity::parse_sort_order_priority(selector.sort_order()))
+        .map(Severity::from_priority)
+        .max()
+        .or_else(|| Severity::from_alert_type(alert_type))
+        .or_else(|| match alert.severity_level() {
+            SeverityLevel::INFO => Some(Severity::Info),
+            SeverityLevel::WARNING => Some(Severity::Warning),
+            SeverityLevel::SEVERE => Some(Severity::Severe),
+            SeverityLevel::UNKNOWN_SEVERITY => None,
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn alert_kind(alert: &Alert) -> AlertKind {
+    match gtfs_realtime_service_status::exts::mercury_alert.get(alert) {
+        Some(mercury) => AlertKind::from_mercury(&mercury),
+        None => AlertKind::Realtime,
+    }
+}
+
+/// The alert's category: from its GTFS-realtime effect when the feed sets a telling one, which
+/// the MTA's own feeds don't, and otherwise from its alert type and kind.
+pub(crate) fn alert_category(alert: &Alert, alert_type: &str, kind: AlertKind) -> Category {
+    alert
+        .has_effect()
+        .then(|| Category::from_effect(alert.effect()))
+        .flatten()
+        .unwrap_or_else(|| Category::from_alert_type(alert_type, kind))
+}
+
+/// When the alert was created and last updated, or `default_time` without Mercury data.
+pub(crate) fn alert_timestamps(
+    alert: &Alert,
+    default_time: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    gtfs_realtime_service_status::exts::mercury_alert
+        .get(alert)
+        .and_then(|mercury| {
+            let created = Utc.timestamp_opt(mercury.created_at() as i64, 0).single()?;
+            let updated = Utc.timestamp_opt(mercury.updated_at() as i64, 0).single()?;
+            Some((created, updated))
+        })
+        .unwrap_or((default_time, default_time))
+}
+
+/// The alert's description in each non-English language the MTA translated it into.
+pub(crate) fn alert_translations(alert: &Alert) -> BTreeMap<Language, String> {
+    Language::ALL
+        .into_iter()
+        .filter(|language| *language != Language::English)
+        .map(|language| {
+            let text = alert_description(alert, |t| find_proto_text_in(t, language.code()));
+            (language, text)
+        })
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
+
+/// The feed's timestamp, used for alerts that don't say when they're active.
+pub(crate) fn feed_time(feed: &FeedMessage) -> DateTime<Utc> {
+    feed.header
+        .as_ref()
+        .and_then(|h| Utc.timestamp_opt(h.timestamp() as i64, 0).single())
+        .unwrap_or_else(Utc::now)
+}
+
+/// The alert's header and description text, as picked out of each `TranslatedString` by `find`.
+pub(crate) fn alert_description<'a>(
+    alert: &'a Alert,
+    find: impl Fn(&'a [ProtoTranslation]) -> Option<&'a str>,
+) -> String {
+    let mut description = String::new();
+
+    if let Some(header_text) = find(&alert.header_text.translation) {
+        description.push_str(&process_text(header_text));
+    }
+
+    if let Some(desc) = alert.description_text.as_ref()
+        && let Some(desc_text) = find(&desc.translation)
+    {
+        if !description.is_empty() {
+            description.push_str("\n\n");
+        }
+        description.push_str(&process_text(desc_text));
+    }
+
+    description
+}
+
+/// The link the MTA gives for the alert, if any.
+pub(crate) fn alert_url(alert: &Alert) -> Option<String> {
+    alert
+        .url
+        .as_ref()
+        .and_then(|url| find_proto_plain_text(&url.translation))
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// The alert's header and description as cleaned HTML, when the MTA provides an HTML version.
+pub(crate) fn alert_html_description(alert: &Alert) -> Option<String> {
+    let find = |translations| find_proto_text_in(translations, "en-html");
+    let header = find(&alert.header_text.translation);
+    let description = alert
+        .description_text
+        .as_ref()
+        .and_then(|desc| find(&desc.translation));
+    if header.is_none() && description.is_none() {
+        return None;
+    }
+
+    let html: String = header
+        .into_iter()
+        .chain(description)
+        .map(process_html)
+        .collect();
+    (!html.is_empty()).then_some(html)
+}
+
+/// The plain-text translation for exactly `language`, if the MTA provided one.
+fn find_proto_text_in<'a>(translations: &'a [ProtoTranslation], language: &str) -> Option<&'a str> {
+    translations
+        .iter()
+        .find(|t| t.language.as_deref() == Some(language))
+        .and_then(|t| t.text.as_deref())
+}
+
+pub(crate) fn find_proto_plain_text(translations: &[ProtoTranslation]) -> Option<&str> {
     translations
         .iter()
         .find(|t| t.language.as_deref() == Some("en"))
@@ -133,21 +333,647 @@ fn find_proto_plain_text(translations: &[ProtoTranslation]) -> Option<&str> {
         .or_else(|| translations.first().and_then(|t| t.text.as_deref()))
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The most stations [`CalendarEvent::list_affected_stations`] names.
+pub const MAX_LISTED_STATIONS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct CalendarEvent {
+    #[serde(rename = "id")]
     pub uid: String,
     pub summary: String,
     pub description: String,
+    /// The description as HTML, for calendar apps that show it, when the MTA provides one
+    #[serde(skip)]
+    pub html_description: Option<String>,
     pub start: DateTime<Utc>,
     pub end: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    /// When the MTA last edited the alert, written as `LAST-MODIFIED`
     pub updated_at: DateTime<Utc>,
+    /// When the feed the event comes from was produced, written as `DTSTAMP`
+    #[serde(skip)]
+    pub feed_time: DateTime<Utc>,
     pub mta_alert_id: String,
     pub routes: Vec<String>,
+    pub stop_ids: Vec<String>,
+    /// The stations `stop_ids` are at, as parent stop IDs in the order the event's lines stop
+    /// at them, filled in by [`CalendarEvent::list_affected_stations`]
+    #[serde(skip)]
+    pub affected_stations: Vec<String>,
     pub alert_type: String,
+    pub severity: Severity,
+    pub kind: AlertKind,
+    /// Serialized as `category` and, when it has one, the category's `indicator`
+    #[serde(flatten, serialize_with = "serialize_category")]
+    pub category: Category,
+    /// The description in languages other than English, when the MTA provides it
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<Language, String>,
+    /// Whether the event covers whole days in New York, from `start`'s date to `end`'s
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub all_day: bool,
+    /// Where to see the full alert: the MTA's link when the feed has one, or else the alert's
+    /// detail page, filled in by the server once it knows its public URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The iCalendar `SEQUENCE`, bumped by the server each time the alert is edited
+    #[serde(skip)]
+    pub sequence: u32,
+    /// The `SEQUENCE` of the repeating event the alert's periods are written as, bumped by the
+    /// server each time any of its periods is edited, added or dropped
+    #[serde(skip)]
+    pub series_sequence: u32,
+}
+
+impl CalendarEvent {
+    /// Switches the description to `language`, keeping English when there's no translation.
+    ///
+    /// The summary is built from the MTA's alert type, which is only published in English.
+    pub fn localize(&mut self, language: Language) {
+        if let Some(text) = self.translations.get(&language) {
+            self.description = text.clone();
+            self.html_description = None;
+        }
+    }
+
+    /// The names of the event's lines as its summary gives them, e.g. `Babylon Branch` for the
+    /// LIRR's route `1`, or their route IDs when the summary doesn't name each one.
+    pub fn line_names(&self) -> Vec<&str> {
+        match self.summary.split_once(": ") {
+            Some((names, _)) if names.split(", ").count() == self.routes.len() => {
+                names.split(", ").collect()
+            }
+            _ => self.routes.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Narrows the event's text to the lines a calendar is for, the routes `requested` matches:
+    /// the other lines come out of its summary when it names each line, so an alert for A, C
+    /// and E reads "A: Delays" in the A calendar. Its routes still list every line. Events that
+    /// only affect requested lines, or none, are left as they are.
+    ///
+    /// With `strip_codes`, the other lines' bracketed names also come out of the headline of the
+    /// description, its HTML and its translations, as [`dedup::strip_bracket_codes`] does.
+    pub fn narrow_to_routes(&mut self, requested: impl Fn(&str) -> bool, strip_codes: bool) {
+        let (kept, others): (Vec<usize>, Vec<usize>) =
+            (0..self.routes.len()).partition(|&i| requested(&self.routes[i]));
+        if kept.is_empty() || others.is_empty() {
+            return;
+        }
+
+        if let Some((names, rest)) = self.summary.split_once(": ") {
+            let names: Vec<&str> = names.split(", ").collect();
+            if names.len() == self.routes.len() {
+                let kept_names: Vec<&str> = kept.iter().map(|&i| names[i]).collect();
+                self.summary = format!("{}: {}", kept_names.join(", "), rest);
+            }
+        }
+
+        if strip_codes {
+            let other_routes: Vec<&str> = others.iter().map(|&i| self.routes[i].as_str()).collect();
+            let is_other = |name: &str| {
+                other_routes
+                    .iter()
+                    .any(|route| lines::route_matches(name, route, false))
+                    && !requested(name)
+            };
+            let strip = |text: &str, paragraph_end: &str| match text.find(paragraph_end) {
+                Some(end) => format!(
+                    "{}{}",
+                    dedup::strip_bracket_codes(&text[..end], is_other),
+                    &text[end..]
+                ),
+                None => dedup::strip_bracket_codes(text, is_other),
+            };
+
+            self.description = strip(&self.description, "\n\n");
+            self.html_description = self
+                .html_description
+                .as_deref()
+                .map(|html| strip(html, "</p>"));
+            for description in self.translations.values_mut() {
+                *description = strip(description, "\n\n");
+            }
+        }
+    }
+
+    /// Whether the event's alert affects riders who need step-free access.
+    ///
+    /// Checks the English description, so call this before [`CalendarEvent::localize`].
+    pub fn affects_accessibility(&self) -> bool {
+        crate::alert::affects_accessibility(&self.mta_alert_id, &self.description)
+    }
+
+    /// Adds an "Affected stations:" line to the description, and to its translations and HTML,
+    /// naming the stations the alert is scoped to in the order its lines stop at them. Only the
+    /// first [`MAX_LISTED_STATIONS`] are named, followed by "+N more". Stations the lines' stop
+    /// sequences don't include come last, by stop ID, and stops `stations` doesn't know are
+    /// skipped, so alerts without any add nothing. The stations are also kept, in the same
+    /// order, as `affected_stations`.
+    pub fn list_affected_stations(&mut self, stations: &Stations, sequences: &StopSequences) {
+        let mut stop_ids: Vec<&str> = Vec::new();
+        for stop_id in &self.stop_ids {
+            let station = stops::parent_station(stop_id);
+            if !stop_ids.contains(&station) && stations.get(station).is_some() {
+                stop_ids.push(station);
+            }
+        }
+        if stop_ids.is_empty() {
+            return;
+        }
+
+        let order = sequences.stopping_order(&self.routes, &stop_ids);
+        stop_ids.sort_by_key(|stop_id| {
+            let position = order.and_then(|order| order.iter().position(|stop| stop == stop_id));
+            (position.unwrap_or(usize::MAX), *stop_id)
+        });
+        self.affected_stations = stop_ids.iter().map(|stop_id| stop_id.to_string()).collect();
+        let mut names: Vec<&str> = Vec::new();
+        for stop_id in stop_ids {
+            let name = stations
+                .get(stop_id)
+                .map_or(stop_id, |station| &station.name);
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        let mut listed = names
+            .iter()
+            .take(MAX_LISTED_STATIONS)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if names.len() > MAX_LISTED_STATIONS {
+            listed.push_str(&format!(" +{} more", names.len() - MAX_LISTED_STATIONS));
+        }
+
+        let section = |language: Language| {
+            let label = match language {
+                Language::English => "Affected stations:",
+                Language::Spanish => "Estaciones afectadas:",
+            };
+            format!("{} {}", label, listed)
+        };
+        let append = |description: &mut String, section: String| {
+            if !description.is_empty() {
+                description.push_str("\n\n");
+            }
+            description.push_str(&section);
+        };
+        append(&mut self.description, section(Language::English));
+        for (language, description) in &mut self.translations {
+            append(description, section(*language));
+        }
+        if let Some(html) = &mut self.html_description {
+            html.push_str(&format!(
+                "<p><b>Affected stations:</b> {}</p>",
+                sanitize::escape_html(&listed)
+            ));
+        }
+    }
+
+    /// Gives an event without an end one at the next `until` New York time after it starts, or
+    /// after `now` if it's already started, e.g. the end of the current service day, and adds
+    /// "(until further notice)" to its summary in `language`.
+    ///
+    /// The end moves along each day without changing the event's `SEQUENCE`, since revisions
+    /// are counted on the feed's events before this runs. `SEQUENCE` only goes up once the MTA
+    /// publishes a real end.
+    pub fn until_further_notice(
+        &mut self,
+        now: DateTime<Utc>,
+        until: NaiveTime,
+        language: Language,
+    ) {
+        if self.end.is_some() {
+            return;
+        }
+
+        let from = self.start.max(now).with_timezone(&New_York).naive_local();
+        let mut end = from.date().and_time(until);
+        if end <= from {
+            end += chrono::Duration::days(1);
+        }
+        self.end = Some(new_york_time(end));
+        self.summary.push_str(match language {
+            Language::English => " (until further notice)",
+            Language::Spanish => " (hasta nuevo aviso)",
+        });
+    }
+
+    /// Shortens a description longer than `max_chars` characters, which some calendar apps cut
+    /// off or reject, at the last word that fits, and ends it with a note in `language` linking
+    /// to the event's URL for the full details. The HTML description is dropped, since it can't
+    /// be cut as easily. Nothing changes when `max_chars` is 0.
+    pub fn truncate_description(&mut self, max_chars: usize, language: Language) {
+        if max_chars == 0 || self.description.chars().count() <= max_chars {
+            return;
+        }
+
+        let note = match (language, &self.url) {
+            (Language::English, Some(url)) => format!("… (truncated — full details: {})", url),
+            (Language::English, None) => "… (truncated)".to_string(),
+            (Language::Spanish, Some(url)) => {
+                format!("… (recortado — detalles completos: {})", url)
+            }
+            (Language::Spanish, None) => "… (recortado)".to_string(),
+        };
+
+        // Cut after the last whole word that leaves room for the note
+        let room = max_chars.saturating_sub(note.chars().count());
+        let cut = self
+            .description
+            .char_indices()
+            .nth(room)
+            .map_or(self.description.len(), |(i, _)| i);
+        let mut kept = &self.description[..cut];
+        if !self.description[cut..].starts_with(char::is_whitespace) {
+            // The cut is in the middle of a word
+            kept = kept.rfind(char::is_whitespace).map_or(kept, |i| &kept[..i]);
+        }
+
+        self.description = format!("{}{}", kept.trim_end(), note);
+        self.html_description = None;
+    }
+
+    /// Widens the event to whole New York days, from midnight on its first day to midnight after
+    /// its last, when it covers at least `days` full days, such as a three-week station closure.
+    /// Calendar apps then list it above each day instead of blocking out every hour. Shorter and
+    /// open-ended events are left alone, as is every event when `days` is 0.
+    pub fn spread_over_days(&mut self, days: u32) {
+        let Some(end) = self.end.filter(|_| days > 0 && !self.all_day) else {
+            return;
+        };
+
+        let start = self.start.with_timezone(&New_York).naive_local();
+        let end = end.with_timezone(&New_York).naive_local();
+        let first_day = match start.time() {
+            NaiveTime::MIN => start.date(),
+            _ => start.date() + chrono::Duration::days(1),
+        };
+        let last_day = match end.time() {
+            NaiveTime::MIN => end.date(),
+            _ => end.date() + chrono::Duration::days(1),
+        };
+        if (end.date() - first_day).num_days() < i64::from(days) {
+            return;
+        }
+
+        let midnight = |day: NaiveDate| {
+            New_York
+                .from_local_datetime(&day.and_time(NaiveTime::MIN))
+                .earliest()
+                .map(|midnight| midnight.with_timezone(&Utc))
+        };
+        if let (Some(start), Some(end)) = (midnight(start.date()), midnight(last_day)) {
+            self.start = start;
+            self.end = Some(end);
+            self.all_day = true;
+        }
+    }
+
+    /// An all-day event for the New York day containing `now`, shown in place of alerts so an
+    /// empty calendar doesn't look like a broken subscription.
+    pub fn placeholder(summary: String, now: DateTime<Utc>) -> CalendarEvent {
+        let today = now.with_timezone(&New_York).date_naive();
+        CalendarEvent {
+            uid: format!("placeholder-{}", today.format("%Y%m%d")),
+            alert_type: "No Alerts".to_string(),
+            ..CalendarEvent::all_day(summary, String::new(), now)
+        }
+    }
+
+    /// An all-day event for the New York day containing `last_start`, the start of the last event
+    /// kept, saying that `omitted` later events were left out of a calendar.
+    pub fn omitted_notice(
+        omitted: usize,
+        last_start: DateTime<Utc>,
+        language: Language,
+        unlimited_url: Option<String>,
+    ) -> CalendarEvent {
+        let (summary, mut description) = match (language, omitted) {
+            (Language::English, 1) => (
+                "1 more alert not shown".to_string(),
+                "1 later alert was left out to keep this calendar short.".to_string(),
+            ),
+            (Language::English, _) => (
+                format!("{} more alerts not shown", omitted),
+                format!(
+                    "{} later alerts were left out to keep this calendar short.",
+                    omitted
+                ),
+            ),
+            (Language::Spanish, 1) => (
+                "1 alerta más no mostrada".to_string(),
+                "Se omitió 1 alerta posterior para que este calendario sea breve.".to_string(),
+            ),
+            (Language::Spanish, _) => (
+                format!("{} alertas más no mostradas", omitted),
+                format!(
+                    "Se omitieron {} alertas posteriores para que este calendario sea breve.",
+                    omitted
+                ),
+            ),
+        };
+        if let Some(url) = &unlimited_url {
+            match language {
+                Language::English => description.push_str(&format!(" See every alert at {}", url)),
+                Language::Spanish => {
+                    description.push_str(&format!(" Vea todas las alertas en {}", url))
+                }
+            }
+        }
+
+        CalendarEvent {
+            uid: "omitted-events".to_string(),
+            alert_type: "Alerts Not Shown".to_string(),
+            url: unlimited_url,
+            ..CalendarEvent::all_day(summary, description, last_start)
+        }
+    }
+
+    /// An informational all-day event for the New York day containing `now`.
+    fn all_day(summary: String, description: String, now: DateTime<Utc>) -> CalendarEvent {
+        let today = now.with_timezone(&New_York).date_naive();
+        let midnight = New_York
+            .from_local_datetime(&today.and_time(NaiveTime::MIN))
+            .earliest()
+            .map_or(now, |midnight| midnight.with_timezone(&Utc));
+
+        CalendarEvent {
+            uid: String::new(),
+            summary,
+            description,
+            html_description: None,
+            start: midnight,
+            end: Some(midnight + chrono::Duration::days(1)),
+            // Fixed for the day so regenerating the calendar doesn't change it
+            created_at: midnight,
+            updated_at: midnight,
+            feed_time: midnight,
+            mta_alert_id: String::new(),
+            routes: vec![],
+            stop_ids: vec![],
+            affected_stations: vec![],
+            alert_type: String::new(),
+            severity: Severity::Info,
+            kind: AlertKind::Realtime,
+            category: Category::Other,
+            translations: BTreeMap::new(),
+            all_day: true,
+            url: None,
+            sequence: 0,
+            series_sequence: 0,
+        }
+    }
+}
+
+/// A language calendars can be rendered in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "es")]
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// The code used in the MTA feed's translations and in `?lang=`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        Language::ALL.into_iter().find(|l| l.code() == code)
+    }
+}
+
+/// Whether an alert announces planned service changes or reports a disruption as it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Planned,
+    Realtime,
+}
+
+impl AlertKind {
+    pub const ALL: [AlertKind; 2] = [AlertKind::Planned, AlertKind::Realtime];
+
+    /// The name used for this kind in query parameters, e.g. `?type=planned`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            AlertKind::Planned => "planned",
+            AlertKind::Realtime => "realtime",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<AlertKind> {
+        AlertKind::ALL.into_iter().find(|k| k.query_name() == name)
+    }
+
+    /// Planned work is scheduled through a service plan and comes with a human-readable
+    /// summary of its active periods; real-time alerts such as `Delays` have neither.
+    fn from_mercury(mercury: &gtfs_realtime_service_status::MercuryAlert) -> AlertKind {
+        if mercury.alert_type().starts_with("Planned")
+            || mercury.human_readable_active_period.is_some()
+            || !mercury.service_plan_number.is_empty()
+        {
+            AlertKind::Planned
+        } else {
+            AlertKind::Realtime
+        }
+    }
 }
 
-fn process_text(text: &str) -> String {
+/// What an alert means for riders, in a word or two, e.g. `Delays` or `Planned Work`. It's sent
+/// as the event's first `CATEGORIES` value, which calendar apps can color and filter by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    NoService,
+    ReducedService,
+    Delays,
+    Detour,
+    PlannedWork,
+    ExtraService,
+    StationNotice,
+    Accessibility,
+    Other,
+}
+
+impl Category {
+    pub const ALL: [Category; 9] = [
+        Category::NoService,
+        Category::ReducedService,
+        Category::Delays,
+        Category::Detour,
+        Category::PlannedWork,
+        Category::ExtraService,
+        Category::StationNotice,
+        Category::Accessibility,
+        Category::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::NoService => "No Service",
+            Category::ReducedService => "Reduced Service",
+            Category::Delays => "Delays",
+            Category::Detour => "Detour",
+            Category::PlannedWork => "Planned Work",
+            Category::ExtraService => "Extra Service",
+            Category::StationNotice => "Station Notice",
+            Category::Accessibility => "Accessibility",
+            Category::Other => "Alert",
+        }
+    }
+
+    /// A symbol for how much the category disrupts service, which calendars put before event
+    /// summaries so the worst stands out in a week view. Alerts that are just `Other` don't
+    /// get one.
+    pub fn indicator(&self) -> Option<&'static str> {
+        match self {
+            Category::NoService => Some("⛔"),
+            Category::ReducedService | Category::Delays => Some("⚠️"),
+            Category::Detour => Some("↪️"),
+            Category::PlannedWork => Some("🔧"),
+            Category::ExtraService => Some("➕"),
+            Category::StationNotice => Some("ℹ️"),
+            Category::Accessibility => Some("♿"),
+            Category::Other => None,
+        }
+    }
+
+    /// The category of a GTFS-realtime effect, for the effects that say what riders will see.
+    fn from_effect(effect: Effect) -> Option<Category> {
+        match effect {
+            Effect::NO_SERVICE => Some(Category::NoService),
+            Effect::REDUCED_SERVICE => Some(Category::ReducedService),
+            Effect::SIGNIFICANT_DELAYS => Some(Category::Delays),
+            Effect::DETOUR => Some(Category::Detour),
+            Effect::ADDITIONAL_SERVICE => Some(Category::ExtraService),
+            Effect::STOP_MOVED => Some(Category::StationNotice),
+            Effect::ACCESSIBILITY_ISSUE => Some(Category::Accessibility),
+            Effect::MODIFIED_SERVICE
+            | Effect::OTHER_EFFECT
+            | Effect::UNKNOWN_EFFECT
+            | Effect::NO_EFFECT => None,
+        }
+    }
+
+    /// The category of an MTA alert type such as `Planned - Stops Skipped` or `Delays`. Planned
+    /// work is `Planned Work` whatever it changes, apart from extra service.
+    fn from_alert_type(alert_type: &str, kind: AlertKind) -> Category {
+        let alert_type = alert_type.to_lowercase();
+        let mentions = |words: &[&str]| words.iter().any(|word| alert_type.contains(word));
+
+        if mentions(&["elevator", "escalator", "accessib"]) {
+            Category::Accessibility
+        } else if mentions(&["extra service", "additional service"]) {
+            Category::ExtraService
+        } else if kind == AlertKind::Planned {
+            Category::PlannedWork
+        } else if mentions(&["delay"]) {
+            Category::Delays
+        } else if mentions(&["suspend", "no service", "no scheduled service"]) {
+            Category::NoService
+        } else if mentions(&["reduced"]) {
+            Category::ReducedService
+        } else if mentions(&["detour", "reroute"]) {
+            Category::Detour
+        } else if mentions(&["station notice", "boarding change", "stop moved"]) {
+            Category::StationNotice
+        } else {
+            Category::Other
+        }
+    }
+}
+
+impl Serialize for Category {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+fn serialize_category<S: serde::Serializer>(
+    category: &Category,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+
+    let mut map = serializer.serialize_map(None)?;
+    map.serialize_entry("category", category)?;
+    if let Some(indicator) = category.indicator() {
+        map.serialize_entry("indicator", indicator)?;
+    }
+    map.end()
+}
+
+#[cfg(test)]
+impl CalendarEvent {
+    /// A minimal event for tests, customized with struct update syntax.
+    pub(crate) fn for_test(uid: &str, routes: &[&str]) -> CalendarEvent {
+        let time = Utc.with_ymd_and_hms(2025, 12, 15, 10, 0, 0).unwrap();
+        CalendarEvent {
+            uid: uid.to_string(),
+            summary: String::new(),
+            description: String::new(),
+            html_description: None,
+            start: time,
+            end: None,
+            created_at: time,
+            updated_at: time,
+            feed_time: time,
+            mta_alert_id: uid.to_string(),
+            routes: routes.iter().map(|r| r.to_string()).collect(),
+            stop_ids: vec![],
+            affected_stations: vec![],
+            alert_type: "Delays".to_string(),
+            severity: Severity::Severe,
+            kind: AlertKind::Realtime,
+            category: Category::Other,
+            translations: BTreeMap::new(),
+            all_day: false,
+            url: None,
+            sequence: 0,
+            series_sequence: 0,
+        }
+    }
+}
+
+/// The moment New York wall-clock time `local` refers to. A time that happens twice when the
+/// clocks go back is the first, and a time skipped when they go forward is read as the clocks
+/// would show it an hour later, so 2:30 AM on the spring-forward Sunday is 3:30 AM EDT.
+pub(crate) fn new_york_time(local: NaiveDateTime) -> DateTime<Utc> {
+    New_York
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            New_York
+                .from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .expect("New York's clocks never skip more than an hour")
+        .with_timezone(&Utc)
+}
+
+/// Alert text as plain text, with the MTA's icons as emoji and its HTML converted.
+pub(crate) fn process_text(text: &str) -> String {
+    sanitize::html_to_text(&replace_icons(text))
+}
+
+/// Alert HTML, with the MTA's icons as emoji and only markup calendar apps can show.
+fn process_html(html: &str) -> String {
+    sanitize::clean_html(&replace_icons(html))
+}
+
+fn replace_icons(text: &str) -> String {
     text.replace("[shuttle bus icon]", "🚌")
         .replace("[accessibility icon]", "♿")
         .replace("[elevator icon]", "🛗")
@@ -163,30 +989,640 @@ fn process_text(text: &str) -> String {
         .replace("[construction icon]", "🚧")
         .replace("[detour icon]", "↪️")
         .replace(['\u{200C}', '\u{200B}', '\u{200D}', '\u{FEFF}'], "")
-        .replace("<b>", "")
-        .replace("</b>", "")
-        .replace("<p>", "")
-        .replace("</p>", "\n")
-        .replace("<br>", "\n")
-        .replace("<br/>", "\n")
-        .replace("<br />", "\n")
-        .replace("<ul>", "\n")
-        .replace("</ul>", "\n")
-        .replace("<li>", "• ")
-        .replace("</li>", "\n")
-        .replace("<strong>", "")
-        .replace("</strong>", "")
-        .split('\n')
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .collect::<Vec<_>>()
-        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
+    use protobuf::Message;
+
+    fn alert_entity(id: &str, stop_ids: &[&str]) -> FeedEntity {
+        use crate::proto::gtfs_realtime::{Alert, EntitySelector};
+
+        let mut alert = Alert::new();
+        for stop_id in stop_ids {
+            let mut selector = EntitySelector::new();
+            selector.set_route_id("F".to_string());
+            selector.set_stop_id(stop_id.to_string());
+            alert.informed_entity.push(selector);
+        }
+
+        let mut entity = FeedEntity::new();
+        entity.set_id(id.to_string());
+        entity.alert = Some(alert).into();
+        entity
+    }
+
+    #[test]
+    fn test_informed_stops_give_alert_direction() {
+        use crate::stops::{Direction, alert_direction};
+
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![
+            alert_entity("northbound", &["F20N", "F21N"]),
+            alert_entity("ambiguous", &["F20N", "F20S"]),
+        ];
+
+        let events = proto_feed_to_events(&feed);
+
+        assert_eq!(events[0].stop_ids, vec!["F20N", "F21N"]);
+        assert_eq!(alert_direction(&events[0].stop_ids), Some(Direction::North));
+        assert_eq!(alert_direction(&events[1].stop_ids), None);
+    }
+
+    #[test]
+    fn test_spanish_translation() {
+        use crate::proto::gtfs_realtime::TranslatedString;
+
+        let translated = |en: &str, es: &str| {
+            let mut text = TranslatedString::new();
+            for (language, value) in [("en", en), ("en-html", en), ("es", es)] {
+                let mut translation = ProtoTranslation::new();
+                translation.set_language(language.to_string());
+                translation.set_text(value.to_string());
+                text.translation.push(translation);
+            }
+            text
+        };
+
+        let mut entity = alert_entity("translated", &["F20N"]);
+        let alert = entity.alert.as_mut().unwrap();
+        alert.header_text = Some(translated(
+            "Trains are delayed",
+            "Los trenes tienen demoras",
+        ))
+        .into();
+        let mut english_only = alert_entity("english-only", &[]);
+        english_only.alert.as_mut().unwrap().header_text =
+            Some(translated("Trains are delayed", "")).into();
+
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![entity, english_only];
+        let mut events = proto_feed_to_events(&feed);
+
+        assert_eq!(events[0].description, "Trains are delayed");
+        assert!(events[0].html_description.is_some());
+        events[0].localize(Language::Spanish);
+        assert_eq!(events[0].description, "Los trenes tienen demoras");
+        // The HTML is in English
+        assert_eq!(events[0].html_description, None);
+
+        events[1].localize(Language::Spanish);
+        assert_eq!(events[1].description, "Trains are delayed");
+    }
+
+    #[test]
+    fn test_html_alert_text() {
+        use crate::proto::gtfs_realtime::TranslatedString;
+
+        let translated = |translations: &[(&str, &str)]| {
+            let mut text = TranslatedString::new();
+            for (language, value) in translations {
+                let mut translation = ProtoTranslation::new();
+                translation.set_language(language.to_string());
+                translation.set_text(value.to_string());
+                text.translation.push(translation);
+            }
+            text
+        };
+
+        let header = "<p>[F] trains skip <b>Smith-9 Sts</b> &amp; <b>4 Av-9 St</b></p>";
+        let description = "<p style=\"min-height:10px\"></p><ul><li><p>Take the \
+            <a title=\"\" href=\"https://bustime.mta.info/#B61\" target=\"_blank\">B61</a> \
+            [bus icon] instead</p></li></ul><br>What&#8217;s happening?<br/>Track work";
+        let mut entity = alert_entity("html", &["F22N"]);
+        let alert = entity.alert.as_mut().unwrap();
+        alert.header_text = Some(translated(&[("en", header), ("en-html", header)])).into();
+        alert.description_text =
+            Some(translated(&[("en", description), ("en-html", description)])).into();
+        let mut plain_only = alert_entity("plain-only", &[]);
+        plain_only.alert.as_mut().unwrap().header_text =
+            Some(translated(&[("en", "Trains are delayed")])).into();
+
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![entity, plain_only];
+        let events = proto_feed_to_events(&feed);
+
+        assert_eq!(
+            events[0].description,
+            "[F] trains skip Smith-9 Sts & 4 Av-9 St\n\n\
+             • Take the B61 🚌 instead\n\
+             What’s happening?\n\
+             Track work\n\
+             More info: https://bustime.mta.info/#B61"
+        );
+        assert_eq!(
+            events[0].html_description.as_deref(),
+            Some(
+                "<p>[F] trains skip <b>Smith-9 Sts</b> &amp; <b>4 Av-9 St</b></p>\
+                 <ul><li><p>Take the <a href=\"https://bustime.mta.info/#B61\">B61</a> \
+                 🚌 instead</p></li></ul><br>What’s happening?<br>Track work"
+            )
+        );
+
+        assert_eq!(events[1].description, "Trains are delayed");
+        assert_eq!(events[1].html_description, None);
+    }
+
+    #[test]
+    fn test_mta_alert_url() {
+        use crate::proto::gtfs_realtime::TranslatedString;
+
+        let mut url = TranslatedString::new();
+        let mut translation = ProtoTranslation::new();
+        translation.set_language("en".to_string());
+        translation.set_text(" https://new.mta.info/alerts \n".to_string());
+        url.translation.push(translation);
+        let mut linked = alert_entity("linked", &["F20N"]);
+        linked.alert.as_mut().unwrap().url = Some(url).into();
+
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![linked, alert_entity("unlinked", &["F20N"])];
+        let events = proto_feed_to_events(&feed);
+
+        assert_eq!(
+            events[0].url.as_deref(),
+            Some("https://new.mta.info/alerts")
+        );
+        // Filled in by the server
+        assert_eq!(events[1].url, None);
+    }
+
+    #[test]
+    fn test_uids_are_stable() {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let events = || {
+            let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+            proto_feed_to_events(&feed)
+        };
+
+        let first = events();
+        let uids = |events: &[CalendarEvent]| -> Vec<String> {
+            events.iter().map(|event| event.uid.clone()).collect()
+        };
+        assert_eq!(uids(&first), uids(&events()));
+        assert_eq!(first[0].uid, "mta-alert-lmm:alert:491468");
+
+        // The feed repeats some elevator outages under the same entity ID, but no two alerts
+        // share a UID
+        let mut alert_ids = BTreeMap::new();
+        for event in &first {
+            let alert_id = alert_ids.entry(&event.uid).or_insert(&event.mta_alert_id);
+            assert_eq!(*alert_id, &event.mta_alert_id);
+        }
+    }
+
+    #[test]
+    fn test_uids_never_collide() {
+        use crate::proto::gtfs_realtime::TimeRange;
+
+        let with_periods = |id: &str, count: u64| {
+            let mut entity = alert_entity(id, &[]);
+            for i in 0..count {
+                let mut period = TimeRange::new();
+                period.set_start(1_765_800_000 + i * 86_400);
+                entity.alert.as_mut().unwrap().active_period.push(period);
+            }
+            entity
+        };
+
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![
+            with_periods("A", 2),
+            // Looks like the A alert's second period
+            with_periods("A-1", 1),
+            with_periods("A-1-0", 0),
+            with_periods("B", 1),
+        ];
+        let uids: Vec<String> = proto_feed_to_events(&feed)
+            .into_iter()
+            .map(|event| event.uid)
+            .collect();
+
+        assert_eq!(
+            uids,
+            vec![
+                "mta-alert-A-0",
+                "mta-alert-A-1",
+                "mta-alert-A-1-0",
+                "mta-alert-A-1-0-0",
+                "mta-alert-B",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_event_per_active_period() {
+        let text = std::fs::read_to_string("tests/fixtures/multi_period_alert.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = proto_feed_to_events(&feed);
+
+        // Each weekend is an event of its own, not one spanning the weeks in between
+        let bounds: Vec<_> = events
+            .iter()
+            .map(|event| (event.uid.as_str(), event.start, event.end))
+            .collect();
+        let at = |year, month, day, hour, minute| {
+            Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+                .unwrap()
+        };
+        assert_eq!(
+            bounds,
+            vec![
+                (
+                    "mta-alert-lmm:planned_work:60001-0",
+                    at(2025, 12, 20, 2, 45),
+                    Some(at(2025, 12, 22, 10, 0))
+                ),
+                (
+                    "mta-alert-lmm:planned_work:60001-1",
+                    at(2026, 1, 3, 4, 30),
+                    Some(at(2026, 1, 5, 10, 0))
+                ),
+                (
+                    "mta-alert-lmm:planned_work:60001-2",
+                    at(2026, 1, 10, 11, 0),
+                    Some(at(2026, 1, 12, 3, 0))
+                ),
+            ]
+        );
+        assert!(
+            events
+                .iter()
+                .all(|event| event.description == events[0].description
+                    && event.mta_alert_id == "lmm:planned_work:60001")
+        );
+
+        let ics = crate::ics::generate_ics(&events);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(!ics.contains("RRULE:FREQ=WEEKLY"));
+        for (start, end) in [
+            ("20251219T214500", "20251222T050000"),
+            ("20260102T233000", "20260105T050000"),
+            ("20260110T060000", "20260111T220000"),
+        ] {
+            assert!(ics.contains(&format!(
+                "DTSTART;TZID=America/New_York:{}\r\nDTEND;TZID=America/New_York:{}\r\n",
+                start, end
+            )));
+        }
+    }
+
+    #[test]
+    fn test_golden_alert_kinds() {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+        let events = proto_feed_to_events(&feed);
+
+        let kind_of = |alert_type: &str| {
+            events
+                .iter()
+                .find(|event| event.alert_type == alert_type)
+                .map(|event| event.kind)
+        };
+
+        assert_eq!(kind_of("Planned - Stops Skipped"), Some(AlertKind::Planned));
+        assert_eq!(kind_of("Boarding Change"), Some(AlertKind::Planned));
+        assert_eq!(kind_of("Delays"), Some(AlertKind::Realtime));
+    }
+
+    #[test]
+    fn test_alert_categories() {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+        let events = proto_feed_to_events(&feed);
+        let category_of = |alert_type: &str| {
+            events
+                .iter()
+                .find(|event| event.alert_type == alert_type)
+                .map(|event| event.category)
+        };
+
+        assert_eq!(category_of("Delays"), Some(Category::Delays));
+        assert_eq!(
+            category_of("Planned - Part Suspended"),
+            Some(Category::PlannedWork)
+        );
+        assert_eq!(category_of("Boarding Change"), Some(Category::PlannedWork));
+        assert_eq!(category_of("Extra Service"), Some(Category::ExtraService));
+        assert_eq!(category_of("Alert"), Some(Category::Other));
+
+        let realtime = |alert_type| Category::from_alert_type(alert_type, AlertKind::Realtime);
+        assert_eq!(realtime("Suspended"), Category::NoService);
+        assert_eq!(realtime("Reduced Service"), Category::ReducedService);
+        assert_eq!(realtime("Reroute"), Category::Detour);
+        assert_eq!(realtime("Station Notice"), Category::StationNotice);
+
+        // Feeds that set an effect, without the MTA's alert types
+        let mut detour = alert_entity("detour", &["F20N"]);
+        detour.alert.as_mut().unwrap().set_effect(Effect::DETOUR);
+        let mut modified = alert_entity("modified", &["F20N"]);
+        modified
+            .alert
+            .as_mut()
+            .unwrap()
+            .set_effect(Effect::MODIFIED_SERVICE);
+        let mut feed = FeedMessage::new();
+        feed.entity = vec![detour, modified];
+        let events = proto_feed_to_events(&feed);
+        assert_eq!(events[0].category, Category::Detour);
+        assert_eq!(events[0].category.name(), "Detour");
+        assert_eq!(events[1].category, Category::Other);
+
+        assert_eq!(
+            serde_json::to_string(&Category::NoService).unwrap(),
+            "\"No Service\""
+        );
+    }
+
+    #[test]
+    fn test_line_names() {
+        let mut event = CalendarEvent::for_test("mta-alert-1-0", &["1", "9"]);
+        event.summary = "Babylon Branch, Port Washington Branch: Delays".to_string();
+        assert_eq!(
+            event.line_names(),
+            vec!["Babylon Branch", "Port Washington Branch"]
+        );
+
+        // Summaries that don't name each line fall back to route IDs
+        event.summary = "LIRR: Delays".to_string();
+        assert_eq!(event.line_names(), vec!["1", "9"]);
+        event.routes.clear();
+        assert_eq!(event.line_names(), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_narrow_to_routes() {
+        let mut event = CalendarEvent::for_test("mta-alert-1-0", &["A", "C", "E"]);
+        event.summary = "A, C, E: Delays".to_string();
+        event.description = "[A] [C] [E] trains are delayed.\n\nTake the [C] instead.".to_string();
+        event.html_description =
+            Some("<p>[A][C][E] trains are delayed.</p><p>Take the [C] instead.</p>".to_string());
+        event.translations.insert(
+            Language::Spanish,
+            "Los trenes [A] [C] [E] tienen retrasos.".to_string(),
+        );
+
+        let mut combined = event.clone();
+        combined.narrow_to_routes(|route| route == "A" || route == "C", false);
+        assert_eq!(combined.summary, "A, C: Delays");
+        assert_eq!(combined.description, event.description);
+        assert_eq!(combined.routes, event.routes);
+
+        event.narrow_to_routes(|route| route == "A", true);
+        assert_eq!(event.summary, "A: Delays");
+        assert_eq!(
+            event.description,
+            "[A] trains are delayed.\n\nTake the [C] instead."
+        );
+        assert_eq!(
+            event.html_description.as_deref(),
+            Some("<p>[A] trains are delayed.</p><p>Take the [C] instead.</p>")
+        );
+        assert_eq!(
+            event.translations[&Language::Spanish],
+            "Los trenes [A] tienen retrasos."
+        );
+
+        // Events that only affect the requested lines are untouched
+        let mut only_a = CalendarEvent::for_test("mta-alert-2-0", &["A"]);
+        only_a.summary = "A: Delays".to_string();
+        only_a.description = "[A] [C] trains are delayed.".to_string();
+        let before = only_a.clone();
+        only_a.narrow_to_routes(|route| route == "A", true);
+        assert_eq!(only_a, before);
+    }
+
+    #[test]
+    fn test_spread_over_days() {
+        // New York is UTC-5 in December
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let between = |start, end| CalendarEvent {
+            start,
+            end: Some(end),
+            ..CalendarEvent::for_test("closure", &["F"])
+        };
+
+        // Monday 10:00 to Friday 17:00 covers three full days, Tuesday to Thursday
+        let mut closure = between(at(15, 15), at(19, 22));
+        closure.spread_over_days(3);
+        assert!(closure.all_day);
+        assert_eq!(closure.start, at(15, 5));
+        assert_eq!(closure.end, Some(at(20, 5)));
+
+        // Ending at midnight, the last day is full and the next isn't included
+        let mut closure = between(at(15, 5), at(18, 5));
+        closure.spread_over_days(3);
+        assert!(closure.all_day);
+        assert_eq!(closure.start, at(15, 5));
+        assert_eq!(closure.end, Some(at(18, 5)));
+
+        // Friday 22:00 to Monday 05:00 only covers the weekend
+        let weekend = between(at(13, 3), at(15, 10));
+        for days in [0, 3] {
+            let mut event = weekend.clone();
+            event.spread_over_days(days);
+            assert_eq!(event, weekend);
+        }
+        let mut event = weekend.clone();
+        event.spread_over_days(2);
+        assert!(event.all_day);
+        assert_eq!(event.start, at(12, 5));
+        assert_eq!(event.end, Some(at(16, 5)));
+
+        // Open-ended events never are
+        let mut open_ended = CalendarEvent {
+            end: None,
+            ..weekend.clone()
+        };
+        open_ended.spread_over_days(1);
+        assert!(!open_ended.all_day);
+    }
+
+    #[test]
+    fn test_list_affected_stations() {
+        let csv = "GTFS Stop ID,Stop Name,Borough,Daytime Routes,GTFS Latitude,GTFS Longitude
+D21,Broadway-Lafayette St,M,B D F M,40.725297,-73.996204
+F14,2 Av,M,F,40.723402,-73.989938
+F15,Delancey St-Essex St,M,F,40.718611,-73.988114
+F16,East Broadway,M,F,40.713715,-73.990173
+F18,York St,Bk,F,40.701397,-73.986751
+A41,Jay St-MetroTech,Bk,A C F,40.692338,-73.987342
+F20,Bergen St,Bk,F G,40.686145,-73.990862
+F21,Carroll St,Bk,F G,40.680303,-73.995048
+F22,Smith-9 Sts,Bk,F G,40.67358,-73.995959
+D20,W 4 St-Wash Sq,M,A B C D E F M,40.732338,-74.000495
+D19,14 St,M,F M,40.738228,-73.996209
+L08,Bedford Av,Bk,L,40.717304,-73.956872
+";
+        let stations = Stations::from_csv(csv.as_bytes()).unwrap();
+        let sequences = StopSequences::load("tests/fixtures/gtfs_subway").unwrap();
+        let event = |stop_ids: &[&str]| {
+            let mut event = CalendarEvent::for_test("mta-alert-1-0", &["F"]);
+            event.description = "[F] trains skip some stations".to_string();
+            event.stop_ids = stop_ids.iter().map(|s| s.to_string()).collect();
+            event
+        };
+
+        // In the F's order, once per station, with stops the F doesn't call at last and
+        // unknown stops left out
+        let mut skipping = event(&["F20N", "L08", "D21S", "A41", "F15", "F20S", "Z99"]);
+        skipping.translations.insert(
+            Language::Spanish,
+            "Los trenes [F] omiten estaciones".to_string(),
+        );
+        skipping.html_description = Some("<p>[F] trains skip some stations</p>".to_string());
+        skipping.list_affected_stations(&stations, &sequences);
+        assert_eq!(
+            skipping.description,
+            "[F] trains skip some stations\n\n\
+             Affected stations: Broadway-Lafayette St, Delancey St-Essex St, Jay St-MetroTech, \
+             Bergen St, Bedford Av"
+        );
+        assert_eq!(
+            skipping.translations[&Language::Spanish],
+            "Los trenes [F] omiten estaciones\n\n\
+             Estaciones afectadas: Broadway-Lafayette St, Delancey St-Essex St, \
+             Jay St-MetroTech, Bergen St, Bedford Av"
+        );
+        assert_eq!(
+            skipping.html_description.as_deref(),
+            Some(
+                "<p>[F] trains skip some stations</p><p><b>Affected stations:</b> \
+                 Broadway-Lafayette St, Delancey St-Essex St, Jay St-MetroTech, Bergen St, \
+                 Bedford Av</p>"
+            )
+        );
+        assert_eq!(
+            skipping.affected_stations,
+            vec!["D21", "F15", "A41", "F20", "L08"]
+        );
+
+        let mut suspended = event(&[
+            "F22", "F21", "F20", "A41", "F18", "F16", "F15", "F14", "D21", "D20", "D19",
+        ]);
+        suspended.list_affected_stations(&stations, &sequences);
+        assert!(suspended.description.ends_with(
+            "Affected stations: 14 St, W 4 St-Wash Sq, Broadway-Lafayette St, 2 Av, \
+             Delancey St-Essex St, East Broadway, York St, Jay St-MetroTech +3 more"
+        ));
+
+        // Without stations, or without the stations list, nothing is added
+        for (stop_ids, stations) in [(&[][..], &stations), (&["F20"][..], &Stations::default())] {
+            let mut unchanged = event(stop_ids);
+            unchanged.list_affected_stations(stations, &sequences);
+            assert_eq!(unchanged.description, "[F] trains skip some stations");
+        }
+    }
+
+    #[test]
+    fn test_truncate_description() {
+        let mut event = CalendarEvent {
+            description: "Trains run local between Jay St and Church Av while we make repairs. \
+                Plan ahead and allow extra travel time."
+                .to_string(),
+            html_description: Some("<p>Trains run local</p>".to_string()),
+            url: Some("https://example.com/api/alerts/lmm:alert:1".to_string()),
+            ..CalendarEvent::for_test("mta-alert-lmm:alert:1-0", &["F"])
+        };
+
+        // Short enough, or no limit
+        let unchanged = event.clone();
+        event.truncate_description(event.description.chars().count(), Language::English);
+        event.truncate_description(0, Language::English);
+        assert_eq!(event, unchanged);
+
+        let mut truncated = event.clone();
+        truncated.truncate_description(100, Language::English);
+        assert_eq!(
+            truncated.description,
+            "Trains run local between Jay… (truncated — full details: \
+             https://example.com/api/alerts/lmm:alert:1)"
+        );
+        assert!(truncated.description.chars().count() <= 100);
+        assert_eq!(truncated.html_description, None);
+
+        // Cut between words, the note in the calendar's language, and no link without a URL
+        let mut truncated = CalendarEvent {
+            url: None,
+            ..event.clone()
+        };
+        truncated.truncate_description(38, Language::Spanish);
+        assert_eq!(
+            truncated.description,
+            "Trains run local between… (recortado)"
+        );
+
+        // Characters rather than bytes count
+        let mut truncated = CalendarEvent {
+            description: "é".repeat(30),
+            url: None,
+            ..event
+        };
+        truncated.truncate_description(20, Language::English);
+        assert_eq!(
+            truncated.description,
+            format!("{}… (truncated)", "é".repeat(7))
+        );
+    }
+
+    #[test]
+    fn test_until_further_notice() {
+        // New York is UTC-5 in December
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let service_day_end = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let open_ended = |start| CalendarEvent {
+            start,
+            end: None,
+            summary: "A: Delays".to_string(),
+            ..CalendarEvent::for_test("delays", &["A"])
+        };
+
+        // Started Monday 08:00, so it lasts until 04:00 Tuesday
+        let mut event = open_ended(at(15, 13));
+        event.until_further_notice(at(15, 14), service_day_end, Language::English);
+        assert_eq!(event.end, Some(at(16, 9)));
+        assert_eq!(event.summary, "A: Delays (until further notice)");
+
+        // Past midnight it's still the same service day
+        let mut event = open_ended(at(15, 13));
+        event.until_further_notice(at(16, 7), service_day_end, Language::English);
+        assert_eq!(event.end, Some(at(16, 9)));
+
+        // Still going days later, it runs to the end of today's service
+        let mut event = open_ended(at(12, 13));
+        event.until_further_notice(at(15, 14), service_day_end, Language::Spanish);
+        assert_eq!(event.start, at(12, 13));
+        assert_eq!(event.end, Some(at(16, 9)));
+        assert_eq!(event.summary, "A: Delays (hasta nuevo aviso)");
+
+        // Starting in the future, it lasts until the end of that service day
+        let mut event = open_ended(at(20, 15));
+        event.until_further_notice(at(15, 14), service_day_end, Language::English);
+        assert_eq!(event.end, Some(at(21, 9)));
+
+        // Over the spring-forward weekend, 04:00 Sunday is EDT, and a 02:30 end that the
+        // clocks skip is read as 03:30 EDT
+        let march =
+            |day, hour, minute| Utc.with_ymd_and_hms(2025, 3, day, hour, minute, 0).unwrap();
+        let mut event = open_ended(march(9, 4, 45));
+        event.until_further_notice(march(9, 4, 45), service_day_end, Language::English);
+        assert_eq!(event.end, Some(march(9, 8, 0)));
+        let mut event = open_ended(march(9, 4, 45));
+        let skipped = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        event.until_further_notice(march(9, 4, 45), skipped, Language::English);
+        assert_eq!(event.end, Some(march(9, 7, 30)));
+
+        // Events with an end are left alone
+        let ending = CalendarEvent {
+            end: Some(at(15, 20)),
+            ..open_ended(at(15, 13))
+        };
+        let mut event = ending.clone();
+        event.until_further_notice(at(15, 14), service_day_end, Language::English);
+        assert_eq!(event, ending);
+    }
 
     #[test]
     fn test_process_text_icons() {
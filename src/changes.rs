@@ -0,0 +1,159 @@
+use crate::alert::AlertDetail;
+use crate::lines;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How an alert changed between two snapshots of a feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+impl ChangeKind {
+    /// The name of the server-sent event announcing this change, e.g. `alert_added`.
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "alert_added",
+            ChangeKind::Updated => "alert_updated",
+            ChangeKind::Removed => "alert_removed",
+        }
+    }
+}
+
+/// An alert that was added, updated or removed. Removed alerts are as they last appeared.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertChange {
+    pub kind: ChangeKind,
+    pub alert: AlertDetail,
+}
+
+impl AlertChange {
+    /// Whether the alert informs any of `route_ids` (case-insensitive), or an express variant of
+    /// one of them.
+    pub fn affects_any(&self, route_ids: &[&str]) -> bool {
+        alert_affects_any(&self.alert, route_ids)
+    }
+}
+
+pub fn alert_affects_any(alert: &AlertDetail, route_ids: &[&str]) -> bool {
+    alert.informed_entities.iter().any(|entity| {
+        entity.route_id.as_deref().is_some_and(|route_id| {
+            route_ids
+                .iter()
+                .any(|wanted| lines::route_matches(wanted, route_id, true))
+        })
+    })
+}
+
+/// The changes that turn `previous` into `current`, matching alerts by ID.
+///
+/// Feeds occasionally repeat an alert ID; the last alert with a given ID is the one compared.
+pub fn diff_alerts(previous: &[AlertDetail], current: &[AlertDetail]) -> Vec<AlertChange> {
+    let mut previous = alerts_by_id(previous);
+    let mut changes = Vec::new();
+
+    for (id, alert) in alerts_by_id(current) {
+        let kind = match previous.remove(id) {
+            None => ChangeKind::Added,
+            Some(old) if old != alert => ChangeKind::Updated,
+            Some(_) => continue,
+        };
+        changes.push(AlertChange {
+            kind,
+            alert: alert.clone(),
+        });
+    }
+
+    changes.extend(previous.into_values().map(|alert| AlertChange {
+        kind: ChangeKind::Removed,
+        alert: alert.clone(),
+    }));
+
+    // Stable output for clients and tests
+    changes.sort_by(|a, b| a.alert.id.cmp(&b.alert.id));
+    changes
+}
+
+fn alerts_by_id(alerts: &[AlertDetail]) -> HashMap<&str, &AlertDetail> {
+    alerts
+        .iter()
+        .map(|alert| (alert.id.as_str(), alert))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::InformedEntity;
+    use crate::calendar::AlertKind;
+    use crate::severity::Severity;
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    fn alert(id: &str, route_id: &str, header: &str) -> AlertDetail {
+        let time = Utc.with_ymd_and_hms(2025, 12, 15, 10, 0, 0).unwrap();
+        AlertDetail {
+            id: id.to_string(),
+            alert_type: "Delays".to_string(),
+            header: header.to_string(),
+            description: String::new(),
+            severity: Severity::Severe,
+            kind: AlertKind::Realtime,
+            created_at: time,
+            updated_at: time,
+            active_periods: vec![],
+            informed_entities: vec![InformedEntity {
+                agency_id: None,
+                route_id: Some(route_id.to_string()),
+                stop_id: None,
+                sort_order: None,
+            }],
+            url: None,
+            translations: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_alerts() {
+        let previous = vec![
+            alert("kept", "A", "Delays"),
+            alert("edited", "A", "Delays"),
+            alert("gone", "F", "Delays"),
+        ];
+        let current = vec![
+            alert("kept", "A", "Delays"),
+            alert("edited", "A", "Severe delays"),
+            alert("new", "L", "Delays"),
+        ];
+
+        let changes = diff_alerts(&previous, &current);
+        let changes: Vec<(ChangeKind, &str)> = changes
+            .iter()
+            .map(|change| (change.kind, change.alert.id.as_str()))
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                (ChangeKind::Updated, "edited"),
+                (ChangeKind::Removed, "gone"),
+                (ChangeKind::Added, "new"),
+            ]
+        );
+        assert!(diff_alerts(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_change_affects_routes() {
+        let change = AlertChange {
+            kind: ChangeKind::Added,
+            alert: alert("a", "SI", "Delays"),
+        };
+        assert!(change.affects_any(&["si"]));
+        assert!(!change.affects_any(&["A", "C", "E"]));
+    }
+}
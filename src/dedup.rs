@@ -0,0 +1,376 @@
+use crate::calendar::{self, CalendarEvent};
+use crate::stops;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Merges the near-duplicate alerts the MTA often posts, one for each line with the same text,
+/// into single events.
+///
+/// Events of the same alert type whose headlines match once normalized with
+/// [`normalize_text`], and whose periods overlap, become one event if they're about the same
+/// incident as well: they inform some of the same stations or, for alerts without stops, their
+/// headlines are the same even before the bracketed lines are normalized, as when the MTA posts
+/// "[F] [G] trains are delayed" once for each line. The MTA's generic wording, such as "[H]
+/// trains are running with delays", is used for unrelated incidents, so matching it isn't
+/// enough on its own.
+///
+/// A merged event covers all of its events' periods and lines, takes the longest description,
+/// and keeps the smallest UID so it stays the same across refreshes. Other events are left as
+/// they are. Each merged event takes the place of its earliest-starting one.
+pub fn merge_duplicates(events: Vec<CalendarEvent>) -> Vec<CalendarEvent> {
+    let mut groups: BTreeMap<(String, String), Vec<usize>> = BTreeMap::new();
+    for (i, event) in events.iter().enumerate() {
+        let key = (event.alert_type.clone(), normalize_text(headline(event)));
+        groups.entry(key).or_default().push(i);
+    }
+
+    // Where each event ends up: its own index, or the index of the event it merges into
+    let mut merged_into: Vec<usize> = (0..events.len()).collect();
+    for indexes in groups.values_mut() {
+        indexes.sort_by_key(|&i| events[i].start);
+        // The merged events so far: the first one's index, the members and where they end
+        let mut merges: Vec<(usize, Vec<usize>, Option<DateTime<Utc>>)> = Vec::new();
+        for &i in indexes.iter() {
+            let event = &events[i];
+            let existing = merges.iter_mut().find(|(_, members, end)| {
+                end.is_none_or(|end| event.start < end)
+                    && members
+                        .iter()
+                        .any(|&member| same_incident(&events[member], event))
+            });
+            match existing {
+                Some((first, members, end)) => {
+                    merged_into[i] = *first;
+                    members.push(i);
+                    *end = end.zip(event.end).map(|(a, b)| a.max(b));
+                }
+                None => merges.push((i, vec![i], event.end)),
+            }
+        }
+    }
+
+    let mut merged: BTreeMap<usize, Vec<CalendarEvent>> = BTreeMap::new();
+    for (i, event) in events.into_iter().enumerate() {
+        merged.entry(merged_into[i]).or_default().push(event);
+    }
+    merged.into_values().map(merge).collect()
+}
+
+/// Whether two events with matching headlines are about the same incident: they inform some of
+/// the same stations or, when neither informs any, their headlines name the same lines too.
+fn same_incident(a: &CalendarEvent, b: &CalendarEvent) -> bool {
+    if !a.stop_ids.is_empty() || !b.stop_ids.is_empty() {
+        let b_stations: Vec<&str> = b
+            .stop_ids
+            .iter()
+            .map(|stop_id| stops::parent_station(stop_id))
+            .collect();
+        return a
+            .stop_ids
+            .iter()
+            .any(|stop_id| b_stations.contains(&stops::parent_station(stop_id)));
+    }
+
+    let same_headline = |event: &CalendarEvent| {
+        headline(event)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+
+    same_headline(a) == same_headline(b)
+}
+
+/// The alert's headline: the first paragraph of the description, which is the MTA's header
+/// text.
+fn headline(event: &CalendarEvent) -> &str {
+    event.description.split("\n\n").next().unwrap_or_default()
+}
+
+/// Normalizes alert text for comparing near-duplicates: lowercase, with runs of whitespace
+/// collapsed to single spaces and bracketed line names such as `[F]` or `[6X]` replaced with
+/// `[route]`, so "[F] trains are delayed" and "[G]  Trains are delayed" are the same.
+pub fn normalize_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut rest = 0;
+    for (range, _) in bracket_codes(text) {
+        normalized.push_str(&text[rest..range.start]);
+        normalized.push_str("[route]");
+        rest = range.end;
+    }
+    normalized.push_str(&text[rest..]);
+
+    normalized
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The bracketed line names the MTA writes in alert text, such as `[A]` in "[A] [C] [E] trains
+/// are delayed", with where each one is in `text`, brackets included.
+pub fn bracket_codes(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut codes = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find('[') {
+        let start = offset + open;
+        let after = &text[start + 1..];
+        match after.find(']') {
+            Some(close) if is_route_name(&after[..close]) => {
+                let end = start + close + 2;
+                codes.push((start..end, &after[..close]));
+                offset = end;
+            }
+            _ => offset = start + 1,
+        }
+    }
+    codes
+}
+
+/// Takes the bracketed line names that `remove` matches out of `text`, such as `[C]` and `[E]`
+/// from "[A] [C] [E] trains are delayed" in the A line's calendar.
+///
+/// Only names in a row of them, separated by nothing but whitespace, are taken out, and only
+/// when another in the row stays, so the text still reads: the lines to take instead in "Take
+/// the [C] instead" stay put.
+pub fn strip_bracket_codes(text: &str, remove: impl Fn(&str) -> bool) -> String {
+    let mut rows: Vec<Vec<(Range<usize>, &str)>> = Vec::new();
+    for (range, name) in bracket_codes(text) {
+        match rows.last_mut() {
+            Some(row)
+                if text[row[row.len() - 1].0.end..range.start]
+                    .trim()
+                    .is_empty() =>
+            {
+                row.push((range, name))
+            }
+            _ => rows.push(vec![(range, name)]),
+        }
+    }
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = 0;
+    for row in rows {
+        let kept: Vec<&str> = row
+            .iter()
+            .filter(|(_, name)| !remove(name))
+            .map(|(range, _)| &text[range.clone()])
+            .collect();
+        if kept.is_empty() || kept.len() == row.len() {
+            continue;
+        }
+        let separator = row
+            .get(1)
+            .map_or("", |(next, _)| &text[row[0].0.end..next.start]);
+        stripped.push_str(&text[rest..row[0].0.start]);
+        stripped.push_str(&kept.join(separator));
+        rest = row[row.len() - 1].0.end;
+    }
+    stripped.push_str(&text[rest..]);
+    stripped
+}
+
+/// Whether `name` looks like a line the MTA puts in brackets: a few letters or digits, such as
+/// `F`, `6X` or `SIR`.
+fn is_route_name(name: &str) -> bool {
+    (1..=4).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Combines the events of one group into one.
+fn merge(mut events: Vec<CalendarEvent>) -> CalendarEvent {
+    if events.len() == 1 {
+        return events.remove(0);
+    }
+
+    let uid_source = events
+        .iter()
+        .min_by(|a, b| a.uid.cmp(&b.uid))
+        .cloned()
+        .expect("groups aren't empty");
+    let longest = events
+        .iter()
+        .max_by_key(|event| event.description.chars().count())
+        .cloned()
+        .expect("groups aren't empty");
+
+    let mut routes: Vec<String> = Vec::new();
+    let mut stop_ids: Vec<String> = Vec::new();
+    for event in &events {
+        for route in &event.routes {
+            if !routes.contains(route) {
+                routes.push(route.clone());
+            }
+        }
+        for stop_id in &event.stop_ids {
+            if !stop_ids.contains(stop_id) {
+                stop_ids.push(stop_id.clone());
+            }
+        }
+    }
+
+    CalendarEvent {
+        uid: uid_source.uid,
+        mta_alert_id: uid_source.mta_alert_id,
+        summary: calendar::event_summary(&routes, &longest.alert_type),
+        start: events.iter().map(|event| event.start).min().unwrap(),
+        end: events
+            .iter()
+            .map(|event| event.end)
+            .reduce(|a, b| a.zip(b).map(|(a, b)| a.max(b)))
+            .flatten(),
+        created_at: events.iter().map(|event| event.created_at).min().unwrap(),
+        updated_at: events.iter().map(|event| event.updated_at).max().unwrap(),
+        severity: events.iter().map(|event| event.severity).max().unwrap(),
+        routes,
+        stop_ids,
+        ..longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::proto_feed_to_events;
+    use crate::proto::gtfs_realtime::FeedMessage;
+    use chrono::{TimeZone, Utc};
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    fn fixture_events() -> Vec<CalendarEvent> {
+        let text = std::fs::read_to_string("tests/fixtures/duplicate_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        proto_feed_to_events(&feed)
+    }
+
+    #[test]
+    fn test_generic_delays_on_different_lines_stay_separate() {
+        let text = std::fs::read_to_string("tests/fixtures/shuttle_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = proto_feed_to_events(&feed);
+        assert_eq!(events.len(), 4);
+
+        // "[H] trains are running with delays" and "[A] trains are running with delays", at the
+        // same time, are two incidents
+        let merged = merge_duplicates(events.clone());
+        assert_eq!(merged, events);
+        let routes: Vec<&Vec<String>> = merged.iter().map(|event| &event.routes).collect();
+        assert_eq!(
+            routes,
+            vec![&vec!["GS"], &vec!["FS"], &vec!["H"], &vec!["A"]]
+        );
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(
+            normalize_text("[F] trains are delayed"),
+            normalize_text("[G]  Trains are\n delayed ")
+        );
+        assert_eq!(
+            normalize_text("  [6X] and [SIR] trains\tare DELAYED "),
+            "[route] and [route] trains are delayed"
+        );
+        // Only short names are lines
+        assert_eq!(
+            normalize_text("See [the MTA website] [F"),
+            "see [the mta website] [f"
+        );
+        assert_ne!(
+            normalize_text("[F] trains are delayed"),
+            normalize_text("[F] trains are running local")
+        );
+    }
+
+    #[test]
+    fn test_bracket_codes() {
+        let text =
+            "[A] [C] [E] trains are delayed. Take the [F][M] instead. See [the MTA website] [F";
+        let codes = bracket_codes(text);
+        let names: Vec<&str> = codes.iter().map(|(_, name)| *name).collect();
+        assert_eq!(names, vec!["A", "C", "E", "F", "M"]);
+        assert_eq!(&text[codes[3].0.clone()], "[F]");
+        assert_eq!(bracket_codes("No lines here"), vec![]);
+    }
+
+    #[test]
+    fn test_strip_bracket_codes() {
+        let other = |name: &str| name != "A";
+        assert_eq!(
+            strip_bracket_codes("[A] [C] [E] trains are delayed", other),
+            "[A] trains are delayed"
+        );
+        assert_eq!(
+            strip_bracket_codes("[C][A][E] trains are delayed", other),
+            "[A] trains are delayed"
+        );
+        assert_eq!(
+            strip_bracket_codes("[C] and [A] trains run local", other),
+            "[C] and [A] trains run local"
+        );
+        // A row of only other lines stays, or the sentence would lose its subject
+        assert_eq!(
+            strip_bracket_codes("Take the [C] [E] instead of [A]", other),
+            "Take the [C] [E] instead of [A]"
+        );
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let events = fixture_events();
+        assert_eq!(events.len(), 9);
+
+        let merged = merge_duplicates(events);
+        let summaries: Vec<&str> = merged.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(
+            summaries,
+            vec![
+                "F, G: Significant Delays",
+                "F, G: Significant Delays",
+                "F: Reduced Service",
+                "A: Significant Delays",
+                "C: Significant Delays",
+                "E, M: Significant Delays",
+            ]
+        );
+
+        // New York is UTC-5 in December
+        let at = |hour, minute| Utc.with_ymd_and_hms(2025, 12, 15, hour, minute, 0).unwrap();
+        let morning = &merged[0];
+        assert_eq!(morning.uid, "mta-alert-lmm:alert:70001");
+        assert_eq!(morning.routes, vec!["F", "G"]);
+        assert!(
+            morning
+                .description
+                .ends_with("\n\nAllow additional travel time.")
+        );
+        // The G alert ran half an hour longer
+        assert_eq!(morning.start, at(13, 0));
+        assert_eq!(morning.end, Some(at(15, 30)));
+
+        // The same headline later in the day is a separate problem, and with an open-ended
+        // duplicate it's open-ended
+        let afternoon = &merged[1];
+        assert_eq!(afternoon.uid, "mta-alert-lmm:alert:70003");
+        assert_eq!(afternoon.start, at(19, 0));
+        assert_eq!(afternoon.end, None);
+
+        // A different headline at the same time is a separate alert
+        assert_eq!(merged[2].uid, "mta-alert-lmm:alert:70004");
+
+        // The MTA's generic wording on two lines at once isn't one incident, without stops in
+        // common...
+        assert_eq!(merged[3].uid, "mta-alert-lmm:alert:70006");
+        assert_eq!(merged[3].routes, vec!["A"]);
+        assert_eq!(merged[4].uid, "mta-alert-lmm:alert:70007");
+        assert_eq!(merged[4].routes, vec!["C"]);
+        assert_eq!(merged[4].start, at(13, 15));
+        // ...unless it's the same text naming the same lines, posted for each
+        assert_eq!(merged[5].uid, "mta-alert-lmm:alert:70008");
+        assert_eq!(merged[5].routes, vec!["E", "M"]);
+
+        // Events without duplicates are untouched
+        let unique = vec![CalendarEvent::for_test("mta-alert-1-0", &["A"])];
+        assert_eq!(merge_duplicates(unique.clone()), unique);
+    }
+}
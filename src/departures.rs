@@ -0,0 +1,233 @@
+use crate::proto::gtfs_realtime::FeedMessage;
+use crate::proto::gtfs_realtime::trip_update::stop_time_update::ScheduleRelationship;
+use crate::stops::{self, Direction, Stations};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How many departures per direction are returned when the client doesn't ask for a number.
+pub const DEFAULT_LIMIT: usize = 5;
+/// The most departures per direction a client can ask for with `?limit=`.
+pub const MAX_LIMIT: usize = 20;
+
+const FEED_URL_PREFIX: &str = "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/nyct%2F";
+
+/// The subway's trip-updates feeds and the routes each one carries, as they're named in the
+/// daytime routes of `Stations.csv`. `S` covers all three shuttles, which are split between the
+/// numbered and `ACE` feeds.
+const TRIP_FEEDS: &[(&str, &[&str])] = &[
+    ("gtfs", &["1", "2", "3", "4", "5", "6", "7", "S"]),
+    ("gtfs-ace", &["A", "C", "E", "S"]),
+    ("gtfs-bdfm", &["B", "D", "F", "M"]),
+    ("gtfs-g", &["G"]),
+    ("gtfs-jz", &["J", "Z"]),
+    ("gtfs-nqrw", &["N", "Q", "R", "W"]),
+    ("gtfs-l", &["L"]),
+    ("gtfs-si", &["SI", "SIR"]),
+];
+
+/// The URL of a trip-updates feed, e.g. `gtfs-ace`.
+pub fn feed_url(feed: &str) -> String {
+    format!("{}{}", FEED_URL_PREFIX, feed)
+}
+
+/// The trip-updates feeds needed for departures on `routes`, in a stable order.
+pub fn feed_urls(routes: &[String]) -> Vec<String> {
+    TRIP_FEEDS
+        .iter()
+        .filter(|(_, feed_routes)| {
+            routes
+                .iter()
+                .any(|route| feed_routes.contains(&route.as_str()))
+        })
+        .map(|(feed, _)| feed_url(feed))
+        .collect()
+}
+
+/// A train's predicted stops, from a trip update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trip {
+    pub route_id: String,
+    pub stop_times: Vec<StopTime>,
+}
+
+/// When a train is predicted at a platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopTime {
+    pub stop_id: String,
+    pub time: DateTime<Utc>,
+}
+
+/// The trips in a trip-updates feed. Skipped stops are left out.
+pub fn proto_feed_to_trips(feed: &FeedMessage) -> Vec<Trip> {
+    feed.entity
+        .iter()
+        .filter_map(|entity| {
+            let trip_update = entity.trip_update.as_ref()?;
+
+            let stop_times = trip_update
+                .stop_time_update
+                .iter()
+                .filter(|update| update.schedule_relationship() != ScheduleRelationship::SKIPPED)
+                .filter_map(|update| {
+                    // Departure is missing at the last stop and arrival at the first
+                    let time = update
+                        .departure
+                        .as_ref()
+                        .or(update.arrival.as_ref())
+                        .and_then(|event| event.time)?;
+
+                    Some(StopTime {
+                        stop_id: update.stop_id.clone()?,
+                        time: Utc.timestamp_opt(time, 0).single()?,
+                    })
+                })
+                .collect();
+
+            Some(Trip {
+                route_id: trip_update.trip.route_id.clone()?,
+                stop_times,
+            })
+        })
+        .collect()
+}
+
+/// A train due to leave a stop.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Departure {
+    pub route_id: String,
+    /// The name of the trip's last stop, or its stop ID when station data doesn't have it
+    pub destination: String,
+    pub destination_stop_id: String,
+    pub time: DateTime<Utc>,
+    /// Whole minutes from now until the train leaves
+    pub minutes_away: i64,
+}
+
+/// The next `limit` trains to leave `stop_id` in each direction after `now`.
+///
+/// A station ID such as `A15` covers both of its platforms. Trains that end their trip at the
+/// stop aren't departures and are left out.
+pub fn next_departures(
+    trips: &[Trip],
+    stop_id: &str,
+    stations: &Stations,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> BTreeMap<Direction, Vec<Departure>> {
+    let mut departures: BTreeMap<Direction, Vec<Departure>> = BTreeMap::new();
+
+    for trip in trips {
+        let Some((last, rest)) = trip.stop_times.split_last() else {
+            continue;
+        };
+
+        for stop_time in rest {
+            let Some(direction) = Direction::from_stop_id(&stop_time.stop_id) else {
+                continue;
+            };
+            if stop_time.time < now || !stops::stop_matches(stop_id, &stop_time.stop_id) {
+                continue;
+            }
+
+            let destination_stop_id = stops::parent_station(&last.stop_id).to_string();
+            departures.entry(direction).or_default().push(Departure {
+                route_id: trip.route_id.clone(),
+                destination: stations
+                    .get(&destination_stop_id)
+                    .map_or_else(|| destination_stop_id.clone(), |s| s.name.clone()),
+                destination_stop_id,
+                time: stop_time.time,
+                minutes_away: (stop_time.time - now).num_minutes(),
+            });
+        }
+    }
+
+    for direction_departures in departures.values_mut() {
+        direction_departures.sort_by_key(|departure| departure.time);
+        direction_departures.truncate(limit);
+    }
+
+    departures
+}
+
+/// Parses a `?limit=` value, which must be between 1 and [`MAX_LIMIT`].
+pub fn parse_limit(value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|limit| (1..=MAX_LIMIT).contains(limit))
+        .ok_or_else(|| {
+            format!(
+                "Invalid limit: {}. Expected a whole number from 1 to {}",
+                value, MAX_LIMIT
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_trips() -> Vec<Trip> {
+        let text = std::fs::read_to_string("tests/fixtures/ace_trip_updates.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        proto_feed_to_trips(&feed)
+    }
+
+    fn fixture_stations() -> Stations {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        Stations::from_csv(csv.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_feed_urls() {
+        let routes = |ids: &[&str]| -> Vec<String> { ids.iter().map(|s| s.to_string()).collect() };
+
+        assert_eq!(
+            feed_urls(&routes(&["A", "B", "C", "D"])),
+            vec![feed_url("gtfs-ace"), feed_url("gtfs-bdfm")]
+        );
+        assert_eq!(feed_urls(&routes(&["SIR"])), vec![feed_url("gtfs-si")]);
+        assert_eq!(
+            feed_urls(&routes(&["S"])),
+            vec![feed_url("gtfs"), feed_url("gtfs-ace")]
+        );
+    }
+
+    #[test]
+    fn test_next_departures() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let departures = next_departures(&fixture_trips(), "A15", &fixture_stations(), now, 5);
+
+        let summary = |direction| -> Vec<(&str, &str, i64)> {
+            departures[&direction]
+                .iter()
+                .map(|d| (d.route_id.as_str(), d.destination.as_str(), d.minutes_away))
+                .collect()
+        };
+
+        assert_eq!(
+            summary(Direction::North),
+            vec![("A", "A02", 3), ("C", "A09", 7)]
+        );
+        assert_eq!(
+            summary(Direction::South),
+            vec![("A", "Far Rockaway-Mott Av", 2)]
+        );
+
+        let northbound = next_departures(&fixture_trips(), "A15N", &fixture_stations(), now, 1);
+        assert_eq!(northbound.len(), 1);
+        assert_eq!(northbound[&Direction::North][0].route_id, "A");
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("3"), Ok(3));
+        assert!(parse_limit("0").is_err());
+        assert!(parse_limit("21").is_err());
+        assert!(parse_limit("many").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::calendar::{AlertKind, CalendarEvent, Category};
+use crate::html::format_period;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The most weeks a single alert is listed in, so an alert with a far-off end doesn't fill the
+/// calendar with digests.
+const MAX_WEEKS: usize = 53;
+
+/// How a calendar's planned work is rolled up instead of showing one event per alert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Digest {
+    /// One all-day event per Monday-to-Sunday week in New York
+    Weekly,
+}
+
+impl Digest {
+    pub const ALL: [Digest; 1] = [Digest::Weekly];
+
+    /// The name used for this digest in query parameters, e.g. `?digest=weekly`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            Digest::Weekly => "weekly",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<Digest> {
+        Digest::ALL.into_iter().find(|d| d.query_name() == name)
+    }
+}
+
+/// Replaces the planned work in `events` with one all-day event for each week it affects,
+/// listing that week's alerts. Real-time alerts are kept as they are.
+///
+/// `calendar` identifies the calendar the digests are for, e.g. `subway-f`. It and the ISO
+/// week make up each digest's UID, so regenerating a week's digest updates it in calendar
+/// apps instead of adding another.
+pub fn weekly_digest(events: Vec<CalendarEvent>, calendar: &str) -> Vec<CalendarEvent> {
+    let (planned, mut digested): (Vec<CalendarEvent>, Vec<CalendarEvent>) = events
+        .into_iter()
+        .partition(|event| event.kind == AlertKind::Planned);
+
+    let mut weeks: BTreeMap<NaiveDate, Vec<&CalendarEvent>> = BTreeMap::new();
+    for event in &planned {
+        for monday in weeks_touched(event) {
+            weeks.entry(monday).or_default().push(event);
+        }
+    }
+
+    digested.extend(weeks.into_iter().map(|(monday, mut events)| {
+        events.sort_by_key(|event| event.start);
+        week_event(calendar, monday, &events)
+    }));
+
+    digested
+}
+
+/// The Mondays of the New York weeks `event` is active in. An event without an end only counts
+/// for the week it starts in.
+fn weeks_touched(event: &CalendarEvent) -> Vec<NaiveDate> {
+    let start = event.start.with_timezone(&New_York).date_naive();
+    let first_monday = start - Duration::days(start.weekday().num_days_from_monday().into());
+    let Some(end) = event.end else {
+        return vec![first_monday];
+    };
+
+    let end = end.with_timezone(&New_York).naive_local();
+    first_monday
+        .iter_weeks()
+        .take(MAX_WEEKS)
+        // An event ending at midnight on Monday doesn't touch the week that starts then
+        .take_while(|monday| *monday == first_monday || monday.and_time(NaiveTime::MIN) < end)
+        .collect()
+}
+
+fn week_event(calendar: &str, monday: NaiveDate, events: &[&CalendarEvent]) -> CalendarEvent {
+    let start = new_york_midnight(monday);
+    let week = monday.iso_week();
+
+    let mut routes = BTreeSet::new();
+    let mut stop_ids = BTreeSet::new();
+    for event in events {
+        routes.extend(event.routes.iter().cloned());
+        stop_ids.extend(event.stop_ids.iter().cloned());
+    }
+    let routes: Vec<String> = routes.into_iter().collect();
+
+    let subject = if routes.is_empty() {
+        "MTA".to_string()
+    } else {
+        routes.join(", ")
+    };
+    let count = match events.len() {
+        1 => "1 Planned Service Change".to_string(),
+        n => format!("{} Planned Service Changes", n),
+    };
+
+    let description = events
+        .iter()
+        .map(|event| {
+            let mut item = format!(
+                "• {} ({})",
+                event.summary,
+                format_period(event.start, event.end)
+            );
+            for line in event.description.lines().filter(|line| !line.is_empty()) {
+                item.push_str("\n  ");
+                item.push_str(line);
+            }
+            item
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    CalendarEvent {
+        uid: format!("digest-{}-{}W{:02}", calendar, week.year(), week.week()),
+        summary: format!("{}: {}", subject, count),
+        description,
+        html_description: None,
+        start,
+        end: Some(new_york_midnight(monday + Duration::days(7))),
+        created_at: events
+            .iter()
+            .map(|event| event.created_at)
+            .min()
+            .unwrap_or(start),
+        updated_at: events
+            .iter()
+            .map(|event| event.updated_at)
+            .max()
+            .unwrap_or(start),
+        feed_time: events
+            .iter()
+            .map(|event| event.feed_time)
+            .max()
+            .unwrap_or(start),
+        mta_alert_id: String::new(),
+        routes,
+        stop_ids: stop_ids.into_iter().collect(),
+        affected_stations: Vec::new(),
+        alert_type: "Weekly Digest".to_string(),
+        severity: events
+            .iter()
+            .map(|event| event.severity)
+            .max()
+            .unwrap_or_default(),
+        kind: AlertKind::Planned,
+        category: Category::PlannedWork,
+        translations: BTreeMap::new(),
+        all_day: true,
+        url: None,
+        sequence: 0,
+        series_sequence: 0,
+    }
+}
+
+fn new_york_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    New_York
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(
+            || midnight.and_utc(),
+            |midnight| midnight.with_timezone(&Utc),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::severity::Severity;
+    use pretty_assertions::assert_eq;
+
+    fn at(month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, month, day, hour, 0, 0).unwrap()
+    }
+
+    fn planned(uid: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> CalendarEvent {
+        CalendarEvent {
+            summary: "F: Planned - Stops Skipped".to_string(),
+            description: format!("Work {}\nSee the map", uid),
+            start,
+            end: Some(end),
+            alert_type: "Planned - Stops Skipped".to_string(),
+            severity: Severity::Warning,
+            kind: AlertKind::Planned,
+            ..CalendarEvent::for_test(uid, &["F"])
+        }
+    }
+
+    #[test]
+    fn test_weekly_digest() {
+        // Fri Dec 19 9pm to Mon Dec 22 5am in New York, which spans two ISO weeks
+        let weekend = planned("weekend", at(12, 20, 2), at(12, 22, 10));
+        let tuesday = planned("tuesday", at(12, 16, 15), at(12, 16, 20));
+        let delays = CalendarEvent::for_test("delays", &["F"]);
+
+        let events = weekly_digest(vec![weekend, tuesday, delays], "subway-f");
+        let summaries: Vec<(&str, &str)> = events
+            .iter()
+            .map(|event| (event.uid.as_str(), event.summary.as_str()))
+            .collect();
+
+        assert_eq!(
+            summaries,
+            vec![
+                ("delays", ""),
+                ("digest-subway-f-2025W51", "F: 2 Planned Service Changes"),
+                ("digest-subway-f-2025W52", "F: 1 Planned Service Change"),
+            ]
+        );
+
+        let week = &events[1];
+        assert!(week.all_day);
+        assert_eq!(week.start, at(12, 15, 5));
+        assert_eq!(week.end, Some(at(12, 22, 5)));
+        assert_eq!(week.severity, Severity::Warning);
+        assert_eq!(
+            week.description,
+            concat!(
+                "• F: Planned - Stops Skipped (Tue, Dec 16, 10:00 AM – 3:00 PM)\n",
+                "  Work tuesday\n",
+                "  See the map\n",
+                "\n",
+                "• F: Planned - Stops Skipped (Fri, Dec 19, 9:00 PM – Mon, Dec 22, 5:00 AM)\n",
+                "  Work weekend\n",
+                "  See the map",
+            )
+        );
+    }
+
+    #[test]
+    fn test_weeks_touched() {
+        let monday = |day| NaiveDate::from_ymd_opt(2025, 12, day).unwrap();
+
+        // Ends at midnight on Monday in New York
+        let sunday = planned("sunday", at(12, 21, 5), at(12, 22, 5));
+        assert_eq!(weeks_touched(&sunday), vec![monday(15)]);
+
+        let open_ended = CalendarEvent {
+            end: None,
+            ..planned("open-ended", at(12, 20, 5), at(12, 21, 5))
+        };
+        assert_eq!(weeks_touched(&open_ended), vec![monday(15)]);
+
+        let long = planned("long", at(12, 1, 12), at(12, 31, 12));
+        assert_eq!(weeks_touched(&long).len(), 5);
+    }
+
+    #[test]
+    fn test_digest_query_names_round_trip() {
+        for digest in Digest::ALL {
+            assert_eq!(Digest::from_query_name(digest.query_name()), Some(digest));
+        }
+        assert_eq!(Digest::from_query_name("daily"), None);
+    }
+}
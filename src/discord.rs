@@ -0,0 +1,135 @@
+use crate::alert::AlertDetail;
+use crate::notify::{self, Notifier};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// The longest embed title Discord accepts, in characters
+const MAX_TITLE_LEN: usize = 256;
+
+/// Discord webhooks, which take an embed colored like the first line the alert affects, with
+/// the alert's header as its title and its lines, type and next active period as fields.
+#[derive(Debug, Clone, Copy)]
+pub struct Discord;
+
+impl Notifier for Discord {
+    fn service(&self) -> &'static str {
+        "Discord"
+    }
+
+    fn payload(&self, alert: &AlertDetail, now: DateTime<Utc>) -> serde_json::Value {
+        let routes = notify::alert_routes(alert);
+
+        let mut fields = vec![serde_json::json!({
+            "name": "Type",
+            "value": alert.alert_type,
+            "inline": true,
+        })];
+        if !routes.is_empty() {
+            let routes: Vec<String> = routes.iter().map(|route| route.to_uppercase()).collect();
+            fields.insert(
+                0,
+                serde_json::json!({
+                    "name": if routes.len() == 1 { "Line" } else { "Lines" },
+                    "value": routes.join(", "),
+                    "inline": true,
+                }),
+            );
+        }
+        if let Some(period) = notify::next_period(alert, now) {
+            fields.push(serde_json::json!({ "name": "When", "value": period }));
+        }
+
+        let mut embed = serde_json::json!({
+            "title": truncate(&alert.header, MAX_TITLE_LEN),
+            "fields": fields,
+            "timestamp": alert.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        });
+        if let Some(color) = routes
+            .iter()
+            .find_map(|route| notify::route_line(route))
+            .and_then(|line| color(line.color))
+        {
+            embed["color"] = color.into();
+        }
+        if let Some(url) = &alert.url {
+            embed["url"] = url.as_str().into();
+        }
+
+        serde_json::json!({ "embeds": [embed] })
+    }
+}
+
+/// A hex color such as `#0039A6` as the integer Discord expects.
+fn color(hex: &str) -> Option<u32> {
+    u32::from_str_radix(hex.strip_prefix('#')?, 16).ok()
+}
+
+/// The first `max_len` characters of `text`, ending in an ellipsis if any were cut.
+fn truncate(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_len - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::tests::{alert, time};
+    use crate::severity::Severity;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_payload() {
+        let mut alert = alert("1", &["l", "G", "B44"], Severity::Severe);
+        alert.url = Some("https://new.mta.info/alerts".to_string());
+
+        assert_eq!(
+            Discord.payload(&alert, time(13)),
+            serde_json::json!({
+                "embeds": [{
+                    "title": "Trains are delayed while we address a signal problem",
+                    "color": 0xA7A9AC,
+                    "url": "https://new.mta.info/alerts",
+                    "timestamp": "2025-12-15T12:00:00Z",
+                    "fields": [
+                        { "name": "Lines", "value": "L, G, B44", "inline": true },
+                        { "name": "Type", "value": "Delays", "inline": true },
+                        { "name": "When", "value": "Mon, Dec 15, 7:00 AM – 10:00 AM" },
+                    ],
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_without_known_lines() {
+        let alert = alert("1", &["B44"], Severity::Severe);
+
+        let payload = Discord.payload(&alert, time(16));
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed.get("color"), None);
+        assert_eq!(embed.get("url"), None);
+        assert_eq!(
+            embed["fields"],
+            serde_json::json!([
+                { "name": "Line", "value": "B44", "inline": true },
+                { "name": "Type", "value": "Delays", "inline": true },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color("#0039A6"), Some(0x0039A6));
+        assert_eq!(color("0039A6"), None);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Delays", 6), "Delays");
+        assert_eq!(truncate("Delays", 5), "Dela…");
+    }
+}
@@ -0,0 +1,1668 @@
+use crate::calendar::{AlertKind, CalendarEvent, Language};
+use crate::digest::Digest;
+use crate::severity::Severity;
+use crate::stops::{self, Borough, Direction, Stations};
+use crate::summary;
+use chrono::{
+    DateTime, Datelike, Days, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How many days ahead calendars cover when the client doesn't ask for a window, which keeps
+/// planned work announced months out from making them huge.
+pub const DEFAULT_DAYS: u32 = 45;
+/// The longest window a client can ask for with `?days=`.
+pub const MAX_DAYS: u32 = 90;
+/// The nights `?exclude_overnight=1` drops events within, unless the server configures others.
+pub const DEFAULT_OVERNIGHT: HourRange = HourRange {
+    start: NaiveTime::MIN,
+    end: NaiveTime::from_hms_opt(5, 0, 0).unwrap(),
+};
+
+/// How many full days an event has to cover before calendars show it as an all-day event,
+/// unless the server configures otherwise.
+pub const DEFAULT_ALL_DAY_DAYS: u32 = 3;
+
+/// The longest description, in characters, calendars show before cutting it short with a link
+/// to the full details, unless the server configures otherwise.
+pub const DEFAULT_MAX_DESCRIPTION_LENGTH: usize = 4000;
+
+/// How often calendars ask the apps subscribed to them to check for changes, unless the server
+/// configures otherwise.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::minutes(30);
+/// The most often the server can have calendars ask to be checked.
+pub const MIN_REFRESH_INTERVAL: Duration = Duration::minutes(5);
+/// The least often the server can have calendars ask to be checked.
+pub const MAX_REFRESH_INTERVAL: Duration = Duration::weeks(1);
+
+/// When calendars end events the MTA hasn't given an end, unless the server configures another
+/// time: the end of the service day, in New York.
+pub const DEFAULT_SERVICE_DAY_END: NaiveTime = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+
+/// How long calendars keep an alert after its last active period ends, so one that has just
+/// cleared doesn't vanish the moment it's over.
+pub const EXPIRED_GRACE: Duration = Duration::hours(1);
+
+/// Where `?tz=` errors point for the time zone names it accepts.
+pub const TIME_ZONE_LIST_URL: &str = "https://en.wikipedia.org/wiki/List_of_tz_database_time_zones";
+
+/// The period a calendar covers, starting at `start` and lasting `days` days.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub start: DateTime<Utc>,
+    pub days: u32,
+}
+
+impl Window {
+    /// Midnight in New York at the end of the window's last day, counting the day it starts on
+    /// as the first, so it always covers whole local days.
+    pub fn end(&self) -> DateTime<Utc> {
+        let last_day =
+            self.start.with_timezone(&New_York).date_naive() + Days::new(self.days.into());
+        New_York
+            .from_local_datetime(&last_day.and_time(NaiveTime::MIN))
+            .earliest()
+            .map_or(self.start + Duration::days(self.days.into()), |end| {
+                end.with_timezone(&Utc)
+            })
+    }
+
+    /// A sentence saying how far ahead the window covers, for calendar descriptions.
+    pub fn describe(&self, language: Language) -> String {
+        match (language, self.days) {
+            (Language::English, 1) => "Shows today's alerts.".to_string(),
+            (Language::English, days) => {
+                format!("Shows alerts for the next {} days, including today.", days)
+            }
+            (Language::Spanish, 1) => "Muestra las alertas de hoy.".to_string(),
+            (Language::Spanish, days) => {
+                format!(
+                    "Muestra las alertas de los próximos {} días, incluido hoy.",
+                    days
+                )
+            }
+        }
+    }
+
+    /// Returns the event if it's active at some point in the window, with an open-ended
+    /// event's end clamped to the end of the window. With `keep_past`, events that ended before
+    /// the window starts are kept too, for calendars that have already decided which past
+    /// events to show.
+    fn clamp(&self, mut event: CalendarEvent, keep_past: bool) -> Option<CalendarEvent> {
+        let end = self.end();
+        let past = event.end.is_some_and(|event_end| event_end <= self.start);
+
+        if event.start >= end || (past && !keep_past) {
+            return None;
+        }
+
+        event.end.get_or_insert(end);
+        Some(event)
+    }
+}
+
+/// A daily range of New York local time, such as `07:00-10:00`. A range that ends before it
+/// starts runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HourRange {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl HourRange {
+    /// Parses a range such as `07:00-10:00`.
+    pub fn parse(value: &str) -> Option<HourRange> {
+        let (start, end) = value.trim().split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        (start != end).then_some(HourRange { start, end })
+    }
+
+    /// Whether local times `start` to `end` fall within a single day's range.
+    fn contains(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        // Only the ranges starting on `start`'s day or, past midnight, the day before can
+        // contain it
+        let first_day = start.date().pred_opt().unwrap_or(start.date());
+        first_day.iter_days().take(2).any(|day| {
+            let range_start = day.and_time(self.start);
+            let mut range_end = day.and_time(self.end);
+            if self.end <= self.start {
+                range_end += Duration::days(1);
+            }
+
+            range_start <= start && end <= range_end
+        })
+    }
+
+    /// Whether the range falls within local times `start` to `end` on any day.
+    fn overlaps(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        if end - start >= Duration::days(1) {
+            return true;
+        }
+
+        // Start a day early to catch a range that runs past midnight into `start`'s day
+        let first_day = start.date().pred_opt().unwrap_or(start.date());
+        first_day
+            .iter_days()
+            .take_while(|day| *day <= end.date())
+            .any(|day| {
+                let range_start = day.and_time(self.start);
+                let mut range_end = day.and_time(self.end);
+                if self.end <= self.start {
+                    range_end += Duration::days(1);
+                }
+
+                range_start < end && start < range_end
+            })
+    }
+}
+
+impl fmt::Display for HourRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// The shortest reminder `?alarm=` can ask for.
+pub const MIN_ALARM: Duration = Duration::minutes(5);
+/// The longest reminder `?alarm=` can ask for.
+pub const MAX_ALARM: Duration = Duration::hours(48);
+
+/// A reminder calendars attach to every event, this long before it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alarm {
+    pub before: Duration,
+}
+
+impl Alarm {
+    /// Parses an ISO 8601 duration, as [`parse_duration`] does.
+    pub fn parse(value: &str) -> Option<Alarm> {
+        parse_duration(value).map(|before| Alarm { before })
+    }
+}
+
+/// The canonical ISO 8601 form in hours, minutes and seconds, e.g. `PT1H30M` for both
+/// `PT90M` and `PT1H30M`.
+impl fmt::Display for Alarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_duration(self.before))
+    }
+}
+
+/// Parses an ISO 8601 duration such as `PT2H`, `PT1H30M` or `P1D`. Weeks, days, hours, minutes
+/// and seconds are supported, but not years or months, which vary in length.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().to_ascii_uppercase();
+    let rest = value.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (rest, None),
+    };
+
+    let mut total = Duration::zero();
+    let mut parts = 0;
+    for (part, units) in [
+        (date, &['W', 'D'][..]),
+        (time.unwrap_or(""), &['H', 'M', 'S']),
+    ] {
+        let mut part = part;
+        let mut units = units.iter();
+        while !part.is_empty() {
+            let digits = part.find(|c: char| !c.is_ascii_digit())?;
+            let number: i64 = part[..digits].parse().ok()?;
+            let unit = part[digits..].chars().next()?;
+            // Each unit at most once, largest first
+            units.find(|u| **u == unit)?;
+            let duration = match unit {
+                'W' => Duration::try_weeks(number),
+                'D' => Duration::try_days(number),
+                'H' => Duration::try_hours(number),
+                'M' => Duration::try_minutes(number),
+                _ => Duration::try_seconds(number),
+            };
+            total = total.checked_add(&duration?)?;
+            parts += 1;
+            part = &part[digits + 1..];
+        }
+    }
+
+    // `P` and `PT` on their own aren't durations
+    if parts == 0 || time == Some("") {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// Formats `duration` in its canonical ISO 8601 form in hours, minutes and seconds, e.g.
+/// `PT1H30M`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    let mut formatted = "PT".to_string();
+    if hours > 0 {
+        formatted.push_str(&format!("{}H", hours));
+    }
+    if minutes > 0 {
+        formatted.push_str(&format!("{}M", minutes));
+    }
+    if seconds > 0 || (hours == 0 && minutes == 0) {
+        formatted.push_str(&format!("{}S", seconds));
+    }
+    formatted
+}
+
+/// Whether `?when=` keeps events on weekdays or weekends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayType {
+    Weekdays,
+    Weekends,
+}
+
+impl DayType {
+    pub const ALL: [DayType; 2] = [DayType::Weekdays, DayType::Weekends];
+
+    /// The name used for this day type in query parameters, e.g. `?when=weekends`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            DayType::Weekdays => "weekdays",
+            DayType::Weekends => "weekends",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<DayType> {
+        DayType::ALL.into_iter().find(|d| d.query_name() == name)
+    }
+
+    fn includes(&self, weekday: Weekday) -> bool {
+        let weekend = matches!(weekday, Weekday::Sat | Weekday::Sun);
+        match self {
+            DayType::Weekdays => !weekend,
+            DayType::Weekends => weekend,
+        }
+    }
+}
+
+/// Optional restrictions applied to a calendar's events after they've been selected by line or
+/// station.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    /// Only keep events with at least one affected stop in one of these boroughs.
+    pub boroughs: Vec<Borough>,
+    /// Only keep events informing one of these stations or platforms, and events that aren't
+    /// scoped to stops at all.
+    pub stops: Vec<String>,
+    /// Only keep events active during this window.
+    pub window: Option<Window>,
+    /// Only keep events active during at least one of these daily ranges.
+    pub hours: Vec<HourRange>,
+    /// Only keep events active on at least one day of this type.
+    pub when: Option<DayType>,
+    /// Only keep events at least this severe.
+    pub min_severity: Option<Severity>,
+    /// Drop events that only affect the other direction of travel.
+    pub direction: Option<Direction>,
+    /// Only keep planned work or only real-time alerts.
+    pub kind: Option<AlertKind>,
+    /// The language event text is rendered in. Not a restriction, but it's chosen by the same
+    /// query string and so shares the cache key.
+    pub language: Language,
+    /// Show a placeholder event when no events are left, so calendar apps don't make an empty
+    /// calendar look like a broken subscription. Only calendars render it.
+    pub placeholder: bool,
+    /// Roll planned work up into one event per period instead of one per alert. Only
+    /// calendars render it.
+    pub digest: Option<Digest>,
+    /// Leave out the alerts of a line's express variant, e.g. `6X` alerts in the 6 calendar.
+    pub exclude_express: bool,
+    /// Drop events that are over within a single night of this range, such as late-night work
+    /// from 01:00 to 04:00. Events running into the night from the evening are kept.
+    pub exclude_overnight: Option<HourRange>,
+    /// Show events covering at least this many full New York days as all-day events, or never
+    /// with 0. `None` is [`DEFAULT_ALL_DAY_DAYS`]. Only calendars render it.
+    pub all_day_days: Option<u32>,
+    /// The New York time calendars end events without an end at, after they start or, if
+    /// they've started, after now. `None` is [`DEFAULT_SERVICE_DAY_END`]. Only calendars render
+    /// it.
+    pub open_ended_until: Option<NaiveTime>,
+    /// The longest description calendars show in full, or no limit with 0. `None` is
+    /// [`DEFAULT_MAX_DESCRIPTION_LENGTH`]. Set by the server rather than parsed from the query
+    /// string.
+    pub max_description_length: Option<usize>,
+    /// How often calendars ask apps to check them for changes. `None` is
+    /// [`DEFAULT_REFRESH_INTERVAL`]. Set by the server rather than parsed from the query string.
+    pub refresh_interval: Option<Duration>,
+    /// Keep only this many of the soonest-starting events, followed by an event saying how many
+    /// were left out. Only calendars render it.
+    pub limit: Option<usize>,
+    /// The calendar without `limit`, linked from the event about left out events. Set by the
+    /// server, which knows the URL, rather than parsed from the query string.
+    pub unlimited_url: Option<String>,
+    /// Remind subscribers of each event this long before it starts. Only calendars render it.
+    pub alarm: Option<Alarm>,
+    /// Leave out the indicators calendars put before event summaries, such as ⛔ for no
+    /// service. Only calendars render it.
+    pub plain: bool,
+    /// Replace the MTA's alert types in summaries with short ones naming the effect, the
+    /// stretch of stations and the direction, as [`summary::compact_summary`] does. Only
+    /// calendars render it.
+    pub compact: bool,
+    /// The time zone calendars write event times in. `None` is New York, the MTA's own. Only
+    /// calendars render it.
+    pub timezone: Option<Tz>,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        self.boroughs.is_empty()
+            && self.stops.is_empty()
+            && self.window.is_none()
+            && self.hours.is_empty()
+            && self.when.is_none()
+            && self.min_severity.is_none()
+            && self.direction.is_none()
+            && self.kind.is_none()
+            && self.language == Language::English
+            && !self.placeholder
+            && self.digest.is_none()
+            && !self.exclude_express
+            && self.exclude_overnight.is_none()
+            && self.all_day_days() == DEFAULT_ALL_DAY_DAYS
+            && self.limit.is_none()
+            && self.alarm.is_none()
+            && !self.plain
+            && !self.compact
+            && self.timezone().is_none()
+    }
+
+    /// The time zone calendars write event times in, if it isn't New York.
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone.filter(|timezone| *timezone != New_York)
+    }
+
+    /// How many full days an event has to cover to be shown as an all-day event.
+    pub fn all_day_days(&self) -> u32 {
+        self.all_day_days.unwrap_or(DEFAULT_ALL_DAY_DAYS)
+    }
+
+    pub fn apply(&self, events: Vec<CalendarEvent>, stations: &Stations) -> Vec<CalendarEvent> {
+        self.apply_keeping_past(events, stations, false)
+    }
+
+    /// [`EventFilter::apply`], but with `keep_past`, events that ended before the window starts
+    /// aren't dropped for it.
+    fn apply_keeping_past(
+        &self,
+        events: Vec<CalendarEvent>,
+        stations: &Stations,
+        keep_past: bool,
+    ) -> Vec<CalendarEvent> {
+        events
+            .into_iter()
+            .filter(|event| self.matches_boroughs(event, stations))
+            .filter(|event| self.matches_stops(event))
+            .filter(|event| self.min_severity.is_none_or(|min| event.severity >= min))
+            .filter(|event| self.matches_direction(event))
+            .filter(|event| self.kind.is_none_or(|kind| event.kind == kind))
+            .map(|mut event| {
+                event.localize(self.language);
+                event
+            })
+            .filter_map(|event| match &self.window {
+                Some(window) => window.clamp(event, keep_past),
+                None => Some(event),
+            })
+            .filter(|event| self.matches_hours(event))
+            .filter(|event| self.matches_when(event))
+            .filter(|event| !self.is_overnight(event))
+            .collect()
+    }
+
+    /// Like [`EventFilter::apply`], and then shapes events for a calendar: summaries are made
+    /// compact if asked, events without an end last until further notice, long ones become
+    /// all-day events, and long descriptions are cut short.
+    ///
+    /// Which past events calendars keep is up to [`drop_expired`] rather than the window: an
+    /// alert that ended within [`EXPIRED_GRACE`], and the past periods of an alert with one still
+    /// to come, stay.
+    pub fn apply_to_calendar(
+        &self,
+        events: Vec<CalendarEvent>,
+        stations: &Stations,
+    ) -> Vec<CalendarEvent> {
+        let now = self.window.map_or_else(Utc::now, |window| window.start);
+        let until = self.open_ended_until.unwrap_or(DEFAULT_SERVICE_DAY_END);
+        let events = drop_expired(events, now)
+            .into_iter()
+            .map(|mut event| {
+                if self.compact {
+                    event.summary = summary::compact_summary(&event, stations, self.language);
+                }
+                event.until_further_notice(now, until, self.language);
+                event
+            })
+            .collect();
+
+        let mut events = self.apply_keeping_past(events, stations, true);
+        let max_description_length = self
+            .max_description_length
+            .unwrap_or(DEFAULT_MAX_DESCRIPTION_LENGTH);
+        for event in &mut events {
+            event.spread_over_days(self.all_day_days());
+            event.truncate_description(max_description_length, self.language);
+        }
+
+        events
+    }
+
+    /// Canonical query-string form of the filter, suitable for use in a cache key.
+    pub fn cache_key(&self) -> String {
+        let mut params = Vec::new();
+
+        if !self.boroughs.is_empty() {
+            let boroughs: Vec<&str> = self.boroughs.iter().map(|b| b.query_name()).collect();
+            params.push(format!("borough={}", boroughs.join(",")));
+        }
+
+        if !self.stops.is_empty() {
+            params.push(format!("stations={}", self.stops.join(",")));
+        }
+
+        // The default window is left out so that `?days=45` shares the unparameterized entry
+        if let Some(window) = &self.window
+            && window.days != DEFAULT_DAYS
+        {
+            params.push(format!("days={}", window.days));
+        }
+
+        if !self.hours.is_empty() {
+            let hours: Vec<String> = self.hours.iter().map(|h| h.to_string()).collect();
+            params.push(format!("hours={}", hours.join(",")));
+        }
+
+        if let Some(when) = &self.when {
+            params.push(format!("when={}", when.query_name()));
+        }
+
+        if let Some(min_severity) = &self.min_severity {
+            params.push(format!("min_severity={}", min_severity.query_name()));
+        }
+
+        if let Some(direction) = &self.direction {
+            params.push(format!("direction={}", direction.query_name()));
+        }
+
+        if let Some(kind) = &self.kind {
+            params.push(format!("type={}", kind.query_name()));
+        }
+
+        if self.language != Language::English {
+            params.push(format!("lang={}", self.language.code()));
+        }
+
+        if self.placeholder {
+            params.push("placeholder=1".to_string());
+        }
+
+        if let Some(digest) = &self.digest {
+            params.push(format!("digest={}", digest.query_name()));
+        }
+
+        if self.exclude_express {
+            params.push("include_express=0".to_string());
+        }
+
+        // The range is the server's, so it's the same for every request
+        if self.exclude_overnight.is_some() {
+            params.push("exclude_overnight=1".to_string());
+        }
+
+        // Left out at the default, but not at a threshold the server configures, which a client
+        // could also ask for
+        if self.all_day_days() != DEFAULT_ALL_DAY_DAYS {
+            params.push(format!("all_day_days={}", self.all_day_days()));
+        }
+
+        if let Some(limit) = self.limit {
+            params.push(format!("limit={}", limit));
+        }
+
+        if let Some(alarm) = &self.alarm {
+            params.push(format!("alarm={}", alarm));
+        }
+
+        if self.plain {
+            params.push("plain=1".to_string());
+        }
+
+        if self.compact {
+            params.push("compact=1".to_string());
+        }
+
+        // `?tz=America/New_York` shares the unparameterized entry
+        if let Some(timezone) = self.timezone() {
+            params.push(format!("tz={}", timezone.name()));
+        }
+
+        params.join("&")
+    }
+
+    /// Events whose direction can't be determined from their stops are always kept.
+    fn matches_direction(&self, event: &CalendarEvent) -> bool {
+        match (self.direction, stops::alert_direction(&event.stop_ids)) {
+            (Some(wanted), Some(direction)) => wanted == direction,
+            _ => true,
+        }
+    }
+
+    /// Events are kept whole, however little of them overlaps. Events without an end are
+    /// always kept, since they overlap every range eventually.
+    fn matches_hours(&self, event: &CalendarEvent) -> bool {
+        if self.hours.is_empty() {
+            return true;
+        }
+        let Some(end) = event.end else {
+            return true;
+        };
+
+        let start = event.start.with_timezone(&New_York).naive_local();
+        let end = end.with_timezone(&New_York).naive_local();
+        self.hours.iter().any(|hours| hours.overlaps(start, end))
+    }
+
+    /// Checks every local day an event touches, so work from Friday night into Monday morning
+    /// counts for both weekdays and weekends. Events without an end are always kept.
+    fn matches_when(&self, event: &CalendarEvent) -> bool {
+        let Some(when) = self.when else {
+            return true;
+        };
+        let Some(end) = event.end else {
+            return true;
+        };
+
+        let start = event.start.with_timezone(&New_York).naive_local();
+        let end = end.with_timezone(&New_York).naive_local();
+        start
+            .date()
+            .iter_days()
+            .take(7)
+            // An event ending at midnight doesn't touch the day that starts then
+            .take_while(|day| day.and_time(NaiveTime::MIN) < end)
+            .any(|day| when.includes(day.weekday()))
+    }
+
+    /// Events without an end are never overnight.
+    fn is_overnight(&self, event: &CalendarEvent) -> bool {
+        let (Some(overnight), Some(end)) = (self.exclude_overnight, event.end) else {
+            return false;
+        };
+
+        let start = event.start.with_timezone(&New_York).naive_local();
+        let end = end.with_timezone(&New_York).naive_local();
+        overnight.contains(start, end)
+    }
+
+    fn matches_stops(&self, event: &CalendarEvent) -> bool {
+        if self.stops.is_empty() || event.stop_ids.is_empty() {
+            return true;
+        }
+
+        self.stops.iter().any(|stop_id| {
+            event
+                .stop_ids
+                .iter()
+                .any(|informed| stops::stop_matches(stop_id, informed))
+        })
+    }
+
+    fn matches_boroughs(&self, event: &CalendarEvent, stations: &Stations) -> bool {
+        if self.boroughs.is_empty() {
+            return true;
+        }
+
+        event.stop_ids.iter().any(|stop_id| {
+            stations
+                .get(stop_id)
+                .is_some_and(|station| self.boroughs.contains(&station.borough))
+        })
+    }
+}
+
+/// Parses a comma-separated `?borough=` value into a sorted, deduplicated list.
+pub fn parse_boroughs(value: &str) -> Result<Vec<Borough>, String> {
+    let mut boroughs = value
+        .split(',')
+        .map(|name| {
+            Borough::from_query_name(name.trim()).ok_or_else(|| {
+                let expected: Vec<&str> = Borough::ALL.iter().map(|b| b.query_name()).collect();
+                format!(
+                    "Unknown borough: {}. Expected one of: {}",
+                    name,
+                    expected.join(", ")
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    boroughs.sort_unstable();
+    boroughs.dedup();
+
+    Ok(boroughs)
+}
+
+/// Parses a comma-separated `?stations=` value into a sorted, deduplicated list of uppercase
+/// stop IDs. Whether they're real stops is up to the caller.
+pub fn parse_stations(value: &str) -> Vec<String> {
+    let mut stops: Vec<String> = value
+        .split(',')
+        .map(|stop_id| stop_id.trim().to_uppercase())
+        .filter(|stop_id| !stop_id.is_empty())
+        .collect();
+
+    stops.sort_unstable();
+    stops.dedup();
+
+    stops
+}
+
+/// Parses a comma-separated `?hours=` value such as `07:00-10:00,17:00-19:30` into a sorted,
+/// deduplicated list.
+pub fn parse_hours(value: &str) -> Result<Vec<HourRange>, String> {
+    let mut hours = value
+        .split(',')
+        .map(|range| {
+            HourRange::parse(range).ok_or_else(|| {
+                    format!(
+                        "Invalid hours: {}. Expected comma-separated 24-hour ranges such as 07:00-10:00,17:00-19:30",
+                        range
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    hours.sort_unstable();
+    hours.dedup();
+
+    Ok(hours)
+}
+
+/// Parses a `?when=` value.
+pub fn parse_when(value: &str) -> Result<DayType, String> {
+    DayType::from_query_name(value.trim()).ok_or_else(|| {
+        let expected: Vec<&str> = DayType::ALL.iter().map(|d| d.query_name()).collect();
+        format!(
+            "Unknown day type: {}. Expected one of: {}",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses a `?digest=` value.
+pub fn parse_digest(value: &str) -> Result<Digest, String> {
+    Digest::from_query_name(value.trim()).ok_or_else(|| {
+        let expected: Vec<&str> = Digest::ALL.iter().map(|d| d.query_name()).collect();
+        format!(
+            "Unknown digest: {}. Expected one of: {}",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses a `?min_severity=` value.
+pub fn parse_min_severity(value: &str) -> Result<Severity, String> {
+    Severity::from_query_name(value.trim()).ok_or_else(|| {
+        let expected: Vec<&str> = Severity::ALL.iter().map(|s| s.query_name()).collect();
+        format!(
+            "Unknown severity: {}. Expected one of: {}",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses a `?direction=` value.
+pub fn parse_direction(value: &str) -> Result<Direction, String> {
+    Direction::from_query_name(value.trim()).ok_or_else(|| {
+        let expected: Vec<&str> = Direction::ALL.iter().map(|d| d.query_name()).collect();
+        format!(
+            "Unknown direction: {}. Expected one of: {}",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses a `?type=` value, where `all` means no restriction.
+pub fn parse_kind(value: &str) -> Result<Option<AlertKind>, String> {
+    let value = value.trim();
+    if value == "all" {
+        return Ok(None);
+    }
+
+    AlertKind::from_query_name(value).map(Some).ok_or_else(|| {
+        let expected: Vec<&str> = AlertKind::ALL.iter().map(|k| k.query_name()).collect();
+        format!(
+            "Unknown alert type: {}. Expected one of: {}, all",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses a `?lang=` value.
+pub fn parse_language(value: &str) -> Result<Language, String> {
+    Language::from_code(value.trim()).ok_or_else(|| {
+        let expected: Vec<&str> = Language::ALL.iter().map(|l| l.code()).collect();
+        format!(
+            "Unsupported language: {}. Expected one of: {}",
+            value,
+            expected.join(", ")
+        )
+    })
+}
+
+/// Parses an on/off query parameter such as `?placeholder=1`: `1` or `true` turns it on, `0` or
+/// `false` leaves it off.
+pub fn parse_flag(name: &str, value: &str) -> Result<bool, String> {
+    match value.trim() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(format!("Invalid {}: {}. Expected 1 or 0", name, value)),
+    }
+}
+
+/// Parses a `?limit=` value, which must be at least 1.
+pub fn parse_limit(value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|limit| *limit >= 1)
+        .ok_or_else(|| {
+            format!(
+                "Invalid limit: {}. Expected a whole number of events, at least 1",
+                value
+            )
+        })
+}
+
+/// Parses a `?days=` value, which must be between 1 and [`MAX_DAYS`].
+pub fn parse_days(value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|days| (1..=MAX_DAYS).contains(days))
+        .ok_or_else(|| {
+            format!(
+                "Invalid days: {}. Expected a whole number of days from 1 to {}",
+                value, MAX_DAYS
+            )
+        })
+}
+
+/// Parses an `?all_day_days=` value, which must be between 0, for never, and [`MAX_DAYS`].
+pub fn parse_all_day_days(value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|days| *days <= MAX_DAYS)
+        .ok_or_else(|| {
+            format!(
+                "Invalid all_day_days: {}. Expected a whole number of days from 0 to {}",
+                value, MAX_DAYS
+            )
+        })
+}
+
+/// Drops the events of alerts whose every active period ended more than [`EXPIRED_GRACE`]
+/// before `now`. An alert with a period still to come keeps its past periods too, and events
+/// without an end never expire.
+pub fn drop_expired(events: Vec<CalendarEvent>, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+    let alert = |event: &CalendarEvent| {
+        if event.mta_alert_id.is_empty() {
+            event.uid.clone()
+        } else {
+            event.mta_alert_id.clone()
+        }
+    };
+
+    let mut latest_ends: HashMap<String, Option<DateTime<Utc>>> = HashMap::new();
+    for event in &events {
+        latest_ends
+            .entry(alert(event))
+            .and_modify(|latest| *latest = latest.zip(event.end).map(|(a, b)| a.max(b)))
+            .or_insert(event.end);
+    }
+
+    events
+        .into_iter()
+        .filter(|event| latest_ends[&alert(event)].is_none_or(|end| end > now - EXPIRED_GRACE))
+        .collect()
+}
+
+/// Parses an `?alarm=` value, an ISO 8601 duration from [`MIN_ALARM`] to [`MAX_ALARM`].
+pub fn parse_alarm(value: &str) -> Result<Alarm, String> {
+    Alarm::parse(value)
+        .filter(|alarm| (MIN_ALARM..=MAX_ALARM).contains(&alarm.before))
+        .ok_or_else(|| {
+            format!(
+                "Invalid alarm: {}. Expected an ISO 8601 duration from {} to {}, such as PT2H",
+                value,
+                Alarm { before: MIN_ALARM },
+                Alarm { before: MAX_ALARM }
+            )
+        })
+}
+
+/// Parses a `?tz=` value, an IANA time zone name such as `UTC` or `Europe/London`.
+pub fn parse_timezone(value: &str) -> Result<Tz, String> {
+    value.trim().parse().map_err(|_| {
+        format!(
+            "Unknown time zone: {}. Expected an IANA time zone name such as UTC or \
+             Europe/London: {}",
+            value, TIME_ZONE_LIST_URL
+        )
+    })
+}
+
+/// Parses a refresh interval for the server's configuration, an ISO 8601 duration from
+/// [`MIN_REFRESH_INTERVAL`] to [`MAX_REFRESH_INTERVAL`].
+pub fn parse_refresh_interval(value: &str) -> Result<Duration, String> {
+    parse_duration(value)
+        .filter(|interval| (MIN_REFRESH_INTERVAL..=MAX_REFRESH_INTERVAL).contains(interval))
+        .ok_or_else(|| {
+            format!(
+                "Invalid refresh interval: {}. Expected an ISO 8601 duration from {} to {}, such \
+                 as PT30M",
+                value,
+                format_duration(MIN_REFRESH_INTERVAL),
+                format_duration(MAX_REFRESH_INTERVAL)
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    fn event(uid: &str, stop_ids: &[&str]) -> CalendarEvent {
+        CalendarEvent {
+            stop_ids: stop_ids.iter().map(|s| s.to_string()).collect(),
+            ..CalendarEvent::for_test(uid, &["L"])
+        }
+    }
+
+    #[test]
+    fn test_parse_boroughs() {
+        assert_eq!(
+            parse_boroughs("queens,manhattan,queens"),
+            Ok(vec![Borough::Manhattan, Borough::Queens])
+        );
+        assert_eq!(parse_boroughs("si"), Ok(vec![Borough::StatenIsland]));
+        assert!(parse_boroughs("jersey").is_err());
+        assert!(parse_boroughs("").is_err());
+    }
+
+    #[test]
+    fn test_borough_filter() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let stations = Stations::from_csv(csv.as_slice()).unwrap();
+        let events = vec![
+            event("brooklyn", &["L08N"]),
+            event("manhattan", &["L06"]),
+            event("both", &["L06", "L08"]),
+            event("no-stops", &[]),
+        ];
+
+        let filter = EventFilter {
+            boroughs: vec![Borough::Brooklyn],
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &stations)
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(uids, vec!["brooklyn", "both"]);
+        assert_eq!(filter.cache_key(), "borough=brooklyn");
+    }
+
+    #[test]
+    fn test_stops_filter() {
+        let events = vec![
+            event("bedford", &["L08"]),
+            event("bedford-north", &["L08N"]),
+            event("canarsie", &["L29"]),
+            event("whole-line", &[]),
+        ];
+
+        assert_eq!(parse_stations(" l08, L06,,L08 "), vec!["L06", "L08"]);
+
+        let filter = EventFilter {
+            stops: parse_stations("L08S,L06"),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        // The southbound platform doesn't match an alert for the northbound one
+        assert_eq!(uids, vec!["bedford", "whole-line"]);
+        assert_eq!(filter.cache_key(), "stations=L06,L08S");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hours() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        assert_eq!(
+            parse_hours("17:00-19:30, 07:00-10:00"),
+            Ok(vec![
+                HourRange {
+                    start: time(7, 0),
+                    end: time(10, 0)
+                },
+                HourRange {
+                    start: time(17, 0),
+                    end: time(19, 30)
+                },
+            ])
+        );
+        assert_eq!(
+            parse_hours("22:00-01:00").unwrap()[0].to_string(),
+            "22:00-01:00"
+        );
+        assert!(parse_hours("7-10").is_err());
+        assert!(parse_hours("07:00").is_err());
+        assert!(parse_hours("07:00-25:00").is_err());
+        assert!(parse_hours("07:00-07:00").is_err());
+        assert!(parse_hours("").is_err());
+    }
+
+    #[test]
+    fn test_hours_filter() {
+        use chrono::TimeZone;
+
+        // New York is UTC-5 in December
+        let at = |day, hour, minute| {
+            Utc.with_ymd_and_hms(2025, 12, day, hour, minute, 0)
+                .unwrap()
+        };
+        let between = |uid, start, end| CalendarEvent {
+            start,
+            end: Some(end),
+            ..event(uid, &[])
+        };
+        let events = vec![
+            // 06:00-07:30 local
+            between("early-overlap", at(15, 11, 0), at(15, 12, 30)),
+            // 11:00-16:00 local
+            between("midday", at(15, 16, 0), at(15, 21, 0)),
+            // 19:00 local to 08:00 the next morning
+            between("overnight", at(16, 0, 0), at(16, 13, 0)),
+            // 23:00-05:00 local
+            between("late-night", at(16, 4, 0), at(16, 10, 0)),
+            CalendarEvent {
+                end: None,
+                ..event("open-ended", &[])
+            },
+        ];
+
+        let filter = EventFilter {
+            hours: parse_hours("07:00-10:00,17:00-19:30").unwrap(),
+            ..EventFilter::default()
+        };
+        let kept = filter.apply(events.clone(), &Stations::default());
+        let uids: Vec<&str> = kept.iter().map(|e| e.uid.as_str()).collect();
+
+        assert_eq!(uids, vec!["early-overlap", "overnight", "open-ended"]);
+        // Partial overlaps are kept intact
+        assert_eq!(kept[0].start, at(15, 11, 0));
+        assert_eq!(kept[0].end, Some(at(15, 12, 30)));
+        assert_eq!(filter.cache_key(), "hours=07:00-10:00,17:00-19:30");
+
+        // A range past midnight
+        let night = EventFilter {
+            hours: parse_hours("22:00-02:00").unwrap(),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = night
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+        assert_eq!(uids, vec!["overnight", "late-night", "open-ended"]);
+    }
+
+    #[test]
+    fn test_when_filter() {
+        use chrono::TimeZone;
+
+        // New York is UTC-5 in December, and December 12, 2025 is a Friday
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let between = |uid, start, end| CalendarEvent {
+            start,
+            end: Some(end),
+            ..event(uid, &[])
+        };
+        let events = vec![
+            // Friday 9pm to Monday 5am
+            between("weekend-work", at(13, 2), at(15, 10)),
+            // Friday 9pm to Saturday 5am
+            between("friday-night", at(13, 2), at(13, 10)),
+            // Saturday 6am to Sunday 10pm
+            between("saturday-sunday", at(13, 11), at(15, 3)),
+            // Tuesday 10pm to Wednesday 5am
+            between("weeknight", at(17, 3), at(17, 10)),
+            // Friday 7pm to midnight, ending as Saturday starts
+            between("friday-evening", at(13, 0), at(13, 5)),
+            CalendarEvent {
+                end: None,
+                ..event("open-ended", &[])
+            },
+        ];
+
+        let uids = |when| -> Vec<String> {
+            EventFilter {
+                when: Some(when),
+                ..EventFilter::default()
+            }
+            .apply(events.clone(), &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect()
+        };
+
+        assert_eq!(
+            uids(DayType::Weekends),
+            vec![
+                "weekend-work",
+                "friday-night",
+                "saturday-sunday",
+                "open-ended"
+            ]
+        );
+        assert_eq!(
+            uids(DayType::Weekdays),
+            vec![
+                "weekend-work",
+                "friday-night",
+                "weeknight",
+                "friday-evening",
+                "open-ended"
+            ]
+        );
+
+        let filter = EventFilter {
+            when: Some(parse_when("weekends").unwrap()),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "when=weekends");
+        assert!(parse_when("saturdays").is_err());
+    }
+
+    #[test]
+    fn test_min_severity_filter() {
+        let events = vec![
+            CalendarEvent {
+                severity: Severity::Info,
+                ..event("notice", &[])
+            },
+            CalendarEvent {
+                severity: Severity::Warning,
+                ..event("stops-skipped", &[])
+            },
+            CalendarEvent {
+                severity: Severity::Severe,
+                ..event("suspended", &[])
+            },
+        ];
+
+        let filter = EventFilter {
+            min_severity: Some(parse_min_severity("warning").unwrap()),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(uids, vec!["stops-skipped", "suspended"]);
+        assert_eq!(filter.cache_key(), "min_severity=warning");
+        assert!(parse_min_severity("critical").is_err());
+    }
+
+    #[test]
+    fn test_direction_filter() {
+        let events = vec![
+            event("manhattan-bound", &["F20N", "F21N"]),
+            event("coney-island-bound", &["F20S"]),
+            event("both-platforms", &["F20N", "F20S"]),
+            event("whole-station", &["F20"]),
+            event("no-stops", &[]),
+        ];
+
+        let filter = EventFilter {
+            direction: Some(parse_direction("north").unwrap()),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(
+            uids,
+            vec![
+                "manhattan-bound",
+                "both-platforms",
+                "whole-station",
+                "no-stops"
+            ]
+        );
+        assert_eq!(filter.cache_key(), "direction=north");
+        assert!(parse_direction("uptown").is_err());
+    }
+
+    #[test]
+    fn test_kind_filter() {
+        let events = vec![
+            CalendarEvent {
+                kind: AlertKind::Planned,
+                ..event("weekend-work", &[])
+            },
+            event("signal-problems", &[]),
+        ];
+
+        let filter = EventFilter {
+            kind: parse_kind("planned").unwrap(),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(uids, vec!["weekend-work"]);
+        assert_eq!(filter.cache_key(), "type=planned");
+        assert_eq!(parse_kind("all"), Ok(None));
+        assert!(parse_kind("emergency").is_err());
+    }
+
+    #[test]
+    fn test_language_localizes_descriptions() {
+        let events = vec![CalendarEvent {
+            description: "Trains are delayed".to_string(),
+            translations: [(Language::Spanish, "Los trenes tienen demoras".to_string())].into(),
+            ..event("translated", &[])
+        }];
+
+        let filter = EventFilter {
+            language: parse_language("es").unwrap(),
+            ..EventFilter::default()
+        };
+        let localized = filter.apply(events, &Stations::default());
+
+        assert_eq!(localized[0].description, "Los trenes tienen demoras");
+        assert_eq!(filter.cache_key(), "lang=es");
+        assert_eq!(EventFilter::default().cache_key(), "");
+        assert!(parse_language("fr").is_err());
+    }
+
+    #[test]
+    fn test_parse_placeholder() {
+        assert_eq!(parse_flag("placeholder", "1"), Ok(true));
+        assert_eq!(parse_flag("placeholder", "false"), Ok(false));
+        assert_eq!(
+            parse_flag("placeholder", "yes"),
+            Err("Invalid placeholder: yes. Expected 1 or 0".to_string())
+        );
+
+        let filter = EventFilter {
+            placeholder: true,
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "placeholder=1");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_exclude_express_cache_key() {
+        let filter = EventFilter {
+            exclude_express: true,
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "include_express=0");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_digest() {
+        assert_eq!(parse_digest(" weekly"), Ok(Digest::Weekly));
+        assert_eq!(
+            parse_digest("daily"),
+            Err("Unknown digest: daily. Expected one of: weekly".to_string())
+        );
+
+        let filter = EventFilter {
+            digest: Some(Digest::Weekly),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "digest=weekly");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(parse_days("7"), Ok(7));
+        assert_eq!(parse_days("90"), Ok(90));
+        assert!(parse_days("0").is_err());
+        assert!(parse_days("91").is_err());
+        assert!(parse_days("-1").is_err());
+        assert!(parse_days("week").is_err());
+    }
+
+    #[test]
+    fn test_overnight_range() {
+        let local = |day, hour, minute| {
+            NaiveDate::from_ymd_opt(2025, 12, day)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+
+        let night = DEFAULT_OVERNIGHT;
+        assert_eq!(night.to_string(), "00:00-05:00");
+        assert!(night.contains(local(16, 1, 0), local(16, 4, 0)));
+        assert!(night.contains(local(16, 0, 0), local(16, 5, 0)));
+        assert!(!night.contains(local(15, 23, 0), local(16, 4, 0)));
+        assert!(!night.contains(local(16, 4, 0), local(16, 6, 0)));
+        assert!(!night.contains(local(16, 1, 0), local(17, 4, 0)));
+
+        // A range past midnight contains the nights that start the evening before
+        let late = HourRange::parse("22:00-05:00").unwrap();
+        assert!(late.contains(local(15, 23, 0), local(16, 4, 0)));
+        assert!(late.contains(local(16, 1, 0), local(16, 4, 0)));
+        assert!(late.contains(local(15, 22, 0), local(15, 23, 30)));
+        assert!(!late.contains(local(15, 21, 0), local(16, 4, 0)));
+        assert!(!late.contains(local(15, 23, 0), local(16, 6, 0)));
+        assert!(!late.contains(local(15, 23, 0), local(16, 23, 30)));
+    }
+
+    #[test]
+    fn test_exclude_overnight() {
+        use chrono::TimeZone;
+
+        // New York is UTC-5 in December
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let between = |uid, start, end| CalendarEvent {
+            start,
+            end: Some(end),
+            ..event(uid, &[])
+        };
+        let events = vec![
+            // 01:00-04:00 local
+            between("overnight", at(16, 6), at(16, 9)),
+            // 22:00-04:00 local
+            between("evening", at(16, 3), at(16, 9)),
+            // 02:00-07:00 local
+            between("into-morning", at(16, 7), at(16, 12)),
+            CalendarEvent {
+                start: at(16, 6),
+                end: None,
+                ..event("open-ended", &[])
+            },
+        ];
+
+        let filter = EventFilter {
+            exclude_overnight: Some(DEFAULT_OVERNIGHT),
+            ..EventFilter::default()
+        };
+        let uids: Vec<String> = filter
+            .apply(events, &Stations::default())
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+        assert_eq!(uids, vec!["evening", "into-morning", "open-ended"]);
+        assert_eq!(filter.cache_key(), "exclude_overnight=1");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_apply_to_calendar() {
+        use chrono::TimeZone;
+
+        // New York is UTC-5 in December
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let open_ended = |uid, start| CalendarEvent {
+            start,
+            end: None,
+            summary: "L: Delays".to_string(),
+            ..event(uid, &[])
+        };
+        let events = vec![
+            open_ended("today", at(15, 13)),
+            open_ended("since-friday", at(12, 13)),
+            CalendarEvent {
+                start: at(15, 13),
+                end: Some(at(15, 15)),
+                ..event("ending", &[])
+            },
+        ];
+
+        let filter = EventFilter {
+            window: Some(Window {
+                start: at(15, 14),
+                days: DEFAULT_DAYS,
+            }),
+            ..EventFilter::default()
+        };
+        let calendar = filter.apply_to_calendar(events.clone(), &Stations::default());
+
+        assert_eq!(calendar[0].end, Some(at(16, 9)));
+        assert_eq!(calendar[0].summary, "L: Delays (until further notice)");
+        assert!(!calendar[0].all_day);
+        // Running from Friday to the end of Monday's service, it covers three full days
+        assert!(calendar[1].all_day);
+        assert_eq!(calendar[1].start, at(12, 5));
+        assert_eq!(calendar[1].end, Some(at(17, 5)));
+        assert_eq!(calendar[2], events[2]);
+
+        // Plain `apply` leaves open-ended events to the window
+        let applied = filter.apply(events, &Stations::default());
+        assert_eq!(applied[0].end, Some(filter.window.unwrap().end()));
+        assert_eq!(applied[0].summary, "L: Delays");
+    }
+
+    #[test]
+    fn test_parse_all_day_days() {
+        assert_eq!(parse_all_day_days("5"), Ok(5));
+        assert_eq!(parse_all_day_days(" 0 "), Ok(0));
+        assert_eq!(parse_all_day_days("90"), Ok(90));
+        assert!(parse_all_day_days("91").is_err());
+        assert!(parse_all_day_days("-1").is_err());
+        assert!(parse_all_day_days("never").is_err());
+
+        assert_eq!(EventFilter::default().all_day_days(), DEFAULT_ALL_DAY_DAYS);
+        let filter = EventFilter {
+            all_day_days: Some(DEFAULT_ALL_DAY_DAYS),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "");
+        assert!(filter.is_empty());
+
+        let filter = EventFilter {
+            all_day_days: Some(0),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "all_day_days=0");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("50"), Ok(50));
+        assert_eq!(parse_limit(" 1 "), Ok(1));
+        assert!(parse_limit("0").is_err());
+        assert!(parse_limit("-5").is_err());
+        assert!(parse_limit("all").is_err());
+
+        let filter = EventFilter {
+            limit: Some(50),
+            // The URL is the same calendar's, so it doesn't need a cache entry of its own
+            unlimited_url: Some("https://example.com/api/calendars/train/A.ics".to_string()),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "limit=50");
+        assert!(!filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("UTC"), Ok(Tz::UTC));
+        assert_eq!(parse_timezone(" Europe/London "), Ok(Tz::Europe__London));
+        assert_eq!(parse_timezone("America/New_York"), Ok(New_York));
+
+        for value in ["", "EST5EDT/Nowhere", "Mars/Base", "europe/london"] {
+            let error = parse_timezone(value).unwrap_err();
+            assert!(error.starts_with("Unknown time zone"), "{}", error);
+            assert!(error.ends_with(TIME_ZONE_LIST_URL), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_timezone_cache_key() {
+        let filter = EventFilter {
+            timezone: Some(Tz::Europe__London),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "tz=Europe/London");
+        assert!(!filter.is_empty());
+
+        // New York is the default, so it shares the default entry
+        let filter = EventFilter {
+            timezone: Some(New_York),
+            ..EventFilter::default()
+        };
+        assert_eq!(filter.cache_key(), "");
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_parse_alarm() {
+        let alarm = |minutes| {
+            Ok(Alarm {
+                before: Duration::minutes(minutes),
+            })
+        };
+        assert_eq!(parse_alarm("PT2H"), alarm(120));
+        assert_eq!(parse_alarm(" pt5m "), alarm(5));
+        assert_eq!(parse_alarm("PT1H30M"), alarm(90));
+        assert_eq!(parse_alarm("PT600S"), alarm(10));
+        assert_eq!(parse_alarm("P1D"), alarm(24 * 60));
+        assert_eq!(parse_alarm("P1DT12H"), alarm(36 * 60));
+        assert_eq!(parse_alarm("PT48H"), alarm(48 * 60));
+
+        // Out of bounds
+        assert!(parse_alarm("PT4M").is_err());
+        assert!(parse_alarm("PT48H1S").is_err());
+        assert!(parse_alarm("P1W").is_err());
+        // Not durations
+        for value in [
+            "",
+            "P",
+            "PT",
+            "2H",
+            "P99999999999999W9999999999999D",
+            "PT2",
+            "PTH",
+            "PT2H2H",
+            "PT30M1H",
+            "P1M",
+            "-PT2H",
+        ] {
+            assert!(parse_alarm(value).is_err(), "{:?}", value);
+        }
+        assert_eq!(
+            parse_alarm("2h"),
+            Err(
+                "Invalid alarm: 2h. Expected an ISO 8601 duration from PT5M to PT48H, such as PT2H"
+                    .to_string()
+            )
+        );
+
+        // Equivalent durations share a cache entry
+        for value in ["PT90M", "PT1H30M", "PT5400S"] {
+            let filter = EventFilter {
+                alarm: Some(parse_alarm(value).unwrap()),
+                ..EventFilter::default()
+            };
+            assert_eq!(filter.cache_key(), "alarm=PT1H30M");
+            assert!(!filter.is_empty());
+        }
+        let alarm = |before| Alarm { before }.to_string();
+        assert_eq!(alarm(Duration::days(2)), "PT48H");
+        assert_eq!(alarm(Duration::seconds(3601)), "PT1H1S");
+        assert_eq!(alarm(Duration::zero()), "PT0S");
+    }
+
+    #[test]
+    fn test_parse_refresh_interval() {
+        assert_eq!(parse_refresh_interval("PT30M"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_refresh_interval(" pt5m "), Ok(Duration::minutes(5)));
+        assert_eq!(parse_refresh_interval("P1D"), Ok(Duration::days(1)));
+        assert_eq!(parse_refresh_interval("P1W"), Ok(Duration::weeks(1)));
+
+        for value in ["", "30", "PT4M", "P1WT1S", "P1M"] {
+            assert!(parse_refresh_interval(value).is_err(), "{:?}", value);
+        }
+        assert_eq!(
+            parse_refresh_interval("30m"),
+            Err(
+                "Invalid refresh interval: 30m. Expected an ISO 8601 duration from PT5M to \
+                 PT168H, such as PT30M"
+                    .to_string()
+            )
+        );
+        assert_eq!(format_duration(DEFAULT_REFRESH_INTERVAL), "PT30M");
+    }
+
+    #[test]
+    fn test_drop_expired() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::proto::gtfs_realtime::FeedMessage;
+        use chrono::TimeZone;
+
+        let text = std::fs::read_to_string("tests/fixtures/expired_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = proto_feed_to_events(&feed);
+        assert_eq!(events.len(), 7);
+
+        let now = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let uids = |events: &[CalendarEvent]| -> Vec<String> {
+            events.iter().map(|event| event.uid.clone()).collect()
+        };
+        assert_eq!(
+            uids(&drop_expired(events.clone(), now)),
+            vec![
+                "mta-alert-lmm:alert:80002",
+                "mta-alert-lmm:alert:80003",
+                "mta-alert-lmm:alert:80004",
+                "mta-alert-lmm:alert:80005-0",
+                "mta-alert-lmm:alert:80005-1",
+                "mta-alert-lmm:alert:80006",
+            ]
+        );
+
+        // Past the grace period, the alert that just ended goes too
+        let later = now + Duration::minutes(31);
+        assert!(
+            !uids(&drop_expired(events.clone(), later))
+                .contains(&"mta-alert-lmm:alert:80002".to_string())
+        );
+
+        // Without a window, as for `generate_train_ics`, calendars expire alerts as of the real
+        // time, long after the fixture's
+        let kept = EventFilter::default().apply_to_calendar(events, &Stations::default());
+        assert_eq!(uids(&kept), vec!["mta-alert-lmm:alert:80006"]);
+    }
+
+    #[test]
+    fn test_window_end() {
+        use chrono::TimeZone;
+
+        let window = |start, days| Window { start, days };
+        let noon = Utc.with_ymd_and_hms(2025, 12, 15, 17, 0, 0).unwrap();
+        assert_eq!(
+            window(noon, 1).end(),
+            Utc.with_ymd_and_hms(2025, 12, 16, 5, 0, 0).unwrap()
+        );
+        assert_eq!(
+            window(noon, DEFAULT_DAYS).end(),
+            Utc.with_ymd_and_hms(2026, 1, 29, 5, 0, 0).unwrap()
+        );
+
+        // Local midnight moves an hour in UTC when the clocks change in between
+        let before_spring_forward = Utc.with_ymd_and_hms(2026, 3, 2, 17, 0, 0).unwrap();
+        assert_eq!(
+            window(before_spring_forward, 14).end(),
+            Utc.with_ymd_and_hms(2026, 3, 16, 4, 0, 0).unwrap()
+        );
+
+        assert_eq!(
+            window(noon, DEFAULT_DAYS).describe(Language::English),
+            "Shows alerts for the next 45 days, including today."
+        );
+        assert_eq!(
+            window(noon, 1).describe(Language::Spanish),
+            "Muestra las alertas de hoy."
+        );
+    }
+
+    #[test]
+    fn test_window_filter() {
+        use chrono::TimeZone;
+
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 12, day, hour, 0, 0).unwrap();
+        let timed = |uid: &str, start, end| CalendarEvent {
+            start,
+            end,
+            ..CalendarEvent::for_test(uid, &["L"])
+        };
+        let events = vec![
+            timed("ended", at(1, 0), Some(at(14, 0))),
+            timed("ongoing", at(1, 0), Some(at(16, 0))),
+            timed("open-ended", at(14, 0), None),
+            timed("upcoming", at(20, 0), Some(at(21, 0))),
+            timed("too-late", at(23, 0), Some(at(24, 0))),
+        ];
+
+        let filter = EventFilter {
+            window: Some(Window {
+                start: at(15, 0),
+                days: 7,
+            }),
+            ..EventFilter::default()
+        };
+        let kept = filter.apply(events, &Stations::default());
+
+        let uids: Vec<&str> = kept.iter().map(|e| e.uid.as_str()).collect();
+        assert_eq!(uids, vec!["ongoing", "open-ended", "upcoming"]);
+        // The window starts at 19:00 on the 14th in New York, and ends at midnight after its
+        // seventh day there
+        let end = Utc.with_ymd_and_hms(2025, 12, 21, 5, 0, 0).unwrap();
+        assert_eq!(kept[1].end, Some(end));
+        assert_eq!(filter.cache_key(), "days=7");
+
+        let default_window = EventFilter {
+            window: Some(Window {
+                start: at(15, 0),
+                days: DEFAULT_DAYS,
+            }),
+            ..EventFilter::default()
+        };
+        assert_eq!(default_window.cache_key(), "");
+    }
+}
@@ -0,0 +1,1617 @@
+use crate::alert::AlertDetail;
+use crate::changes;
+use crate::lines::{self, LINES, Line};
+use crate::notify;
+use crate::severity::Severity;
+use crate::stops::{self, Station, Stations};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as Json};
+use std::cell::{Cell, RefCell};
+
+/// The longest query accepted, in bytes
+pub const MAX_QUERY_LEN: usize = 10_000;
+/// How deeply selection sets can nest
+const MAX_DEPTH: usize = 20;
+/// The most fields, counting each list item, a query can resolve. Aliases let a short query
+/// select the same fields many times over, so the length and depth limits alone don't bound
+/// the work.
+pub const MAX_FIELDS: usize = 50_000;
+/// The largest response a query can build, in bytes of names and scalar values
+pub const MAX_RESPONSE_LEN: usize = 5_000_000;
+
+/// A GraphQL request as clients POST it, e.g. `{"query": "{ line(id: \"A\") { name } }"}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub query: String,
+    #[serde(default)]
+    pub variables: Option<Map<String, Json>>,
+    #[serde(default)]
+    pub operation_name: Option<String>,
+}
+
+/// What queries are answered from, all of it already loaded so a query never fetches anything
+/// itself.
+#[derive(Debug)]
+pub struct Data<'a> {
+    /// The subway feed's alerts, or why they couldn't be fetched
+    pub alerts: Result<&'a [AlertDetail], String>,
+    pub stations: &'a Stations,
+}
+
+/// Runs a query against `data`, returning the response body: `data` with the results, and
+/// `errors` if any fields failed. A field that fails is `null` in `data`.
+///
+/// Only the subset of GraphQL needed to select from the schema in [`SCHEMA`] is supported:
+///
+/// - one query per request, written `{ ... }` or `query Name($var: Type = default) { ... }`
+/// - fields, with aliases and arguments, and `__typename`
+/// - string, enum and `null` argument values, and variables of the types in [`INPUT_TYPES`]
+///
+/// Everything else is rejected with an error naming it, rather than ignored or half-supported:
+/// fragments, directives, mutations, subscriptions, introspection besides `__typename`, number,
+/// boolean, list and object values, block strings, list types, several operations in one
+/// request, and selecting two fields under the same response key.
+///
+/// A query that would resolve more than [`MAX_FIELDS`] fields or build a response over
+/// [`MAX_RESPONSE_LEN`] is stopped as soon as it does, and answered with only an error.
+pub fn execute(request: &Request, data: &Data) -> Json {
+    match prepare(request) {
+        Ok((operation, variables)) => {
+            let executor = Executor {
+                data,
+                variables,
+                errors: RefCell::new(Vec::new()),
+                fields_left: Cell::new(MAX_FIELDS),
+                bytes_left: Cell::new(MAX_RESPONSE_LEN),
+                exceeded: Cell::new(None),
+            };
+            let result = executor.object(&Query, &operation.selections, &[]);
+            if let Some(message) = executor.exceeded.get() {
+                return serde_json::json!({ "data": null, "errors": [{ "message": message }] });
+            }
+
+            let mut response = serde_json::json!({ "data": result });
+            let errors = executor.errors.into_inner();
+            if !errors.is_empty() {
+                response["errors"] = Json::Array(errors);
+            }
+            response
+        }
+        Err(messages) => {
+            let errors: Vec<Json> = messages
+                .into_iter()
+                .map(|message| serde_json::json!({ "message": message }))
+                .collect();
+            serde_json::json!({ "errors": errors })
+        }
+    }
+}
+
+/// Parses and validates the request's query, returning it along with its variables.
+fn prepare(request: &Request) -> Result<(Operation, Map<String, Json>), Vec<String>> {
+    if request.query.len() > MAX_QUERY_LEN {
+        return Err(vec![format!(
+            "Query is too long: {} bytes. The limit is {}.",
+            request.query.len(),
+            MAX_QUERY_LEN
+        )]);
+    }
+
+    let operation = Parser::new(&request.query)
+        .and_then(|mut parser| parser.document())
+        .map_err(|message| vec![message])?;
+    if let Some(name) = &request.operation_name
+        && operation.name.as_deref() != Some(name)
+    {
+        return Err(vec![format!("Unknown operation named \"{}\".", name)]);
+    }
+
+    let mut errors = Vec::new();
+    let provided = request.variables.clone().unwrap_or_default();
+    let mut variables = Map::new();
+    for variable in &operation.variables {
+        let value = match provided.get(&variable.name) {
+            Some(value) => value.clone(),
+            None => variable
+                .default
+                .as_ref()
+                .map_or(Json::Null, |default| default.to_json(&Map::new())),
+        };
+        if variable.required && value.is_null() {
+            errors.push(format!(
+                "Variable \"${}\" of required type \"{}\" was not provided.",
+                variable.name,
+                variable.type_name()
+            ));
+        }
+        variables.insert(variable.name.clone(), value);
+    }
+
+    validate(&operation, &mut errors);
+    if errors.is_empty() {
+        Ok((operation, variables))
+    } else {
+        Err(errors)
+    }
+}
+
+// Parsing
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punctuator(char),
+    Name(String),
+    String(String),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            // Commas are as insignificant as whitespace
+            ' ' | '\t' | '\n' | '\r' | ',' | '\u{feff}' => i += 1,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' && chars[i] != '\r' {
+                    i += 1;
+                }
+            }
+            '!' | '$' | '(' | ')' | ':' | '=' | '{' | '}' => {
+                tokens.push(Token::Punctuator(c));
+                i += 1;
+            }
+            '.' => return Err("Fragments aren't supported.".to_string()),
+            '@' => return Err("Directives aren't supported.".to_string()),
+            '[' | ']' => return Err("Lists aren't supported.".to_string()),
+            '-' | '0'..='9' => {
+                return Err(
+                    "Numbers aren't supported. Every argument is a string or an enum value."
+                        .to_string(),
+                );
+            }
+            '"' => {
+                if chars.get(i..i + 3) == Some(&['"', '"', '"']) {
+                    return Err("Block strings aren't supported.".to_string());
+                }
+                let (string, end) = string(&chars, i + 1)?;
+                tokens.push(Token::String(string));
+                i = end;
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < chars.len() && (chars[i] == '_' || chars[i].is_ascii_alphanumeric()) {
+                    i += 1;
+                }
+                tokens.push(Token::Name(chars[start..i].iter().collect()));
+            }
+            c => return Err(format!("Unexpected character: {:?}.", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a quoted string starting after its opening quote, returning it and the position after
+/// its closing quote.
+fn string(chars: &[char], mut i: usize) -> Result<(String, usize), String> {
+    let mut string = String::new();
+    loop {
+        match chars.get(i) {
+            None | Some('\n' | '\r') => return Err("Unterminated string.".to_string()),
+            Some('"') => return Ok((string, i + 1)),
+            Some('\\') => {
+                let escaped = match chars.get(i + 1) {
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some('/') => '/',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('u') => {
+                        let hex: String =
+                            chars.get(i + 2..i + 6).unwrap_or_default().iter().collect();
+                        i += 4;
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid unicode escape: \\u{}.", hex))?
+                    }
+                    _ => return Err("Invalid escape in string.".to_string()),
+                };
+                string.push(escaped);
+                i += 2;
+            }
+            Some(c) => {
+                string.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Variable(String),
+    String(String),
+    Enum(String),
+    Null,
+}
+
+impl Value {
+    /// The value as JSON, with variables substituted. Enum values become strings.
+    fn to_json(&self, variables: &Map<String, Json>) -> Json {
+        match self {
+            Value::Variable(name) => variables.get(name).cloned().unwrap_or(Json::Null),
+            Value::String(string) | Value::Enum(string) => string.as_str().into(),
+            Value::Null => Json::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    alias: Option<String>,
+    name: String,
+    arguments: Vec<(String, Value)>,
+    selections: Vec<Field>,
+}
+
+impl Field {
+    /// The key the field's result is returned under
+    fn response_key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VariableDef {
+    name: String,
+    /// The named type, e.g. `String` for `String!`
+    ty: String,
+    required: bool,
+    default: Option<Value>,
+}
+
+impl VariableDef {
+    /// The type as written in the query, e.g. `String!`
+    fn type_name(&self) -> String {
+        format!("{}{}", self.ty, if self.required { "!" } else { "" })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Operation {
+    name: Option<String>,
+    variables: Vec<VariableDef>,
+    selections: Vec<Field>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, String> {
+        Ok(Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            depth: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| "Unexpected end of query.".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn is(&self, punctuator: char) -> bool {
+        self.peek() == Some(&Token::Punctuator(punctuator))
+    }
+
+    /// Consumes `punctuator` if it's next.
+    fn skip(&mut self, punctuator: char) -> bool {
+        let is = self.is(punctuator);
+        if is {
+            self.position += 1;
+        }
+        is
+    }
+
+    fn expect(&mut self, punctuator: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Punctuator(c) if c == punctuator => Ok(()),
+            token => Err(format!(
+                "Expected \"{}\", found {}.",
+                punctuator,
+                describe(&token)
+            )),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => Err(format!("Expected a name, found {}.", describe(&token))),
+        }
+    }
+
+    /// Tracks nesting, so deeply nested queries fail instead of overflowing the stack.
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("Query is nested more than {} deep.", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    /// Parses the whole query, which must be a single query operation.
+    fn document(&mut self) -> Result<Operation, String> {
+        let operation = match self.peek() {
+            None => return Err("No operation to run.".to_string()),
+            Some(Token::Punctuator('{')) => Operation {
+                name: None,
+                variables: Vec::new(),
+                selections: self.selection_set()?,
+            },
+            Some(Token::Name(keyword)) if keyword == "query" => {
+                self.position += 1;
+                self.operation()?
+            }
+            Some(token) => return Err(unsupported_definition(token)),
+        };
+
+        match self.peek() {
+            None => Ok(operation),
+            Some(Token::Punctuator('{')) => Err(ONE_OPERATION.to_string()),
+            Some(Token::Name(keyword)) if keyword == "query" => Err(ONE_OPERATION.to_string()),
+            Some(token) => Err(unsupported_definition(token)),
+        }
+    }
+
+    fn operation(&mut self) -> Result<Operation, String> {
+        let name = match self.peek() {
+            Some(Token::Name(_)) => Some(self.name()?),
+            _ => None,
+        };
+
+        let mut variables = Vec::new();
+        if self.skip('(') {
+            while !self.skip(')') {
+                self.expect('$')?;
+                let name = self.name()?;
+                self.expect(':')?;
+                let ty = self.name()?;
+                let required = self.skip('!');
+                let default = if self.skip('=') {
+                    match self.value()? {
+                        Value::Variable(_) => {
+                            return Err("Default values can't be variables.".to_string());
+                        }
+                        value => Some(value),
+                    }
+                } else {
+                    None
+                };
+                variables.push(VariableDef {
+                    name,
+                    ty,
+                    required,
+                    default,
+                });
+            }
+        }
+
+        Ok(Operation {
+            name,
+            variables,
+            selections: self.selection_set()?,
+        })
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Field>, String> {
+        self.expect('{')?;
+        self.enter()?;
+
+        let mut fields = Vec::new();
+        while !self.skip('}') {
+            fields.push(self.field()?);
+        }
+        if fields.is_empty() {
+            return Err("Selection sets can't be empty.".to_string());
+        }
+
+        self.depth -= 1;
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<Field, String> {
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.skip(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+
+        Ok(Field {
+            alias,
+            name,
+            arguments: self.arguments()?,
+            selections: if self.is('{') {
+                self.selection_set()?
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    fn arguments(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let mut arguments = Vec::new();
+        if self.skip('(') {
+            while !self.skip(')') {
+                let name = self.name()?;
+                self.expect(':')?;
+                arguments.push((name, self.value()?));
+            }
+        }
+        Ok(arguments)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.next()? {
+            Token::Punctuator('$') => Ok(Value::Variable(self.name()?)),
+            Token::String(string) => Ok(Value::String(string)),
+            Token::Name(name) => match name.as_str() {
+                "null" => Ok(Value::Null),
+                "true" | "false" => Err(
+                    "Booleans aren't supported. Every argument is a string or an enum value."
+                        .to_string(),
+                ),
+                _ => Ok(Value::Enum(name)),
+            },
+            Token::Punctuator('{') => Err("Input objects aren't supported.".to_string()),
+            token => Err(format!("Expected a value, found {}.", describe(&token))),
+        }
+    }
+}
+
+const ONE_OPERATION: &str = "Only one operation per request is supported.";
+
+/// Why a definition starting with `token` can't be run.
+fn unsupported_definition(token: &Token) -> String {
+    match token {
+        Token::Name(keyword) if keyword == "mutation" || keyword == "subscription" => {
+            "Only queries are supported.".to_string()
+        }
+        Token::Name(keyword) if keyword == "fragment" => "Fragments aren't supported.".to_string(),
+        token => format!("Unexpected {}.", describe(token)),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Punctuator(c) => format!("\"{}\"", c),
+        Token::Name(name) => format!("\"{}\"", name),
+        Token::String(string) => format!("{:?}", string),
+    }
+}
+
+// Schema
+
+/// An object type. Field and argument types are written as in GraphQL, e.g. `[Alert!]!`.
+#[derive(Debug)]
+struct TypeDef {
+    name: &'static str,
+    fields: &'static [FieldDef],
+}
+
+#[derive(Debug)]
+struct FieldDef {
+    name: &'static str,
+    ty: &'static str,
+    args: &'static [ArgDef],
+}
+
+#[derive(Debug)]
+struct ArgDef {
+    name: &'static str,
+    ty: &'static str,
+}
+
+const fn field(name: &'static str, ty: &'static str) -> FieldDef {
+    FieldDef {
+        name,
+        ty,
+        args: &[],
+    }
+}
+
+const fn arg(name: &'static str, ty: &'static str) -> ArgDef {
+    ArgDef { name, ty }
+}
+
+/// The types arguments and variables can have, and whether each is an enum
+const INPUT_TYPES: &[(&str, bool)] = &[("String", false), ("DateTime", false), ("Severity", true)];
+
+/// The arguments every list of alerts can be narrowed with.
+const ALERT_FILTERS: &[ArgDef] = &[arg("activeAt", "DateTime"), arg("severity", "Severity")];
+
+/// Every object type, starting with the query root. The README lists each one's fields.
+const SCHEMA: &[TypeDef] = &[
+    TypeDef {
+        name: "Query",
+        fields: &[
+            field("lines", "[Line!]!"),
+            FieldDef {
+                name: "line",
+                ty: "Line",
+                args: &[arg("id", "String!")],
+            },
+            FieldDef {
+                name: "alerts",
+                ty: "[Alert!]",
+                args: &[
+                    arg("line", "String"),
+                    arg("activeAt", "DateTime"),
+                    arg("severity", "Severity"),
+                ],
+            },
+            FieldDef {
+                name: "station",
+                ty: "Station",
+                args: &[arg("stopId", "String!")],
+            },
+        ],
+    },
+    TypeDef {
+        name: "Line",
+        fields: &[
+            field("id", "String!"),
+            field("name", "String!"),
+            field("color", "String!"),
+            field("textColor", "String!"),
+            FieldDef {
+                name: "alerts",
+                ty: "[Alert!]",
+                args: ALERT_FILTERS,
+            },
+        ],
+    },
+    TypeDef {
+        name: "Alert",
+        fields: &[
+            field("id", "String!"),
+            field("type", "String!"),
+            field("header", "String!"),
+            field("description", "String!"),
+            field("severity", "Severity!"),
+            field("kind", "AlertKind!"),
+            field("createdAt", "DateTime!"),
+            field("updatedAt", "DateTime!"),
+            field("activePeriods", "[ActivePeriod!]!"),
+            field("routes", "[String!]!"),
+            field("stopIds", "[String!]!"),
+            field("url", "String"),
+            field("affectsAccessibility", "Boolean!"),
+        ],
+    },
+    TypeDef {
+        name: "ActivePeriod",
+        fields: &[field("start", "DateTime"), field("end", "DateTime")],
+    },
+    TypeDef {
+        name: "Station",
+        fields: &[
+            field("stopId", "String!"),
+            field("name", "String!"),
+            field("borough", "Borough!"),
+            field("routes", "[String!]!"),
+            field("lines", "[Line!]!"),
+            field("latitude", "Float!"),
+            field("longitude", "Float!"),
+            FieldDef {
+                name: "alerts",
+                ty: "[Alert!]",
+                args: ALERT_FILTERS,
+            },
+        ],
+    },
+];
+
+fn find_type(name: &str) -> Option<&'static TypeDef> {
+    SCHEMA.iter().find(|ty| ty.name == name)
+}
+
+/// The named type at the bottom of a type reference, e.g. `Alert` for `[Alert!]!`.
+fn named_type(ty: &str) -> &str {
+    ty.trim_matches(|c| matches!(c, '[' | ']' | '!'))
+}
+
+// Validation
+
+/// Checks the operation's variables, fields and arguments against the schema, so a query that
+/// can't run fails as a whole rather than once for every alert it would select from.
+fn validate(operation: &Operation, errors: &mut Vec<String>) {
+    for (index, variable) in operation.variables.iter().enumerate() {
+        if operation.variables[..index]
+            .iter()
+            .any(|other| other.name == variable.name)
+        {
+            errors.push(format!(
+                "There can be only one variable named \"${}\".",
+                variable.name
+            ));
+        }
+        if !INPUT_TYPES.iter().any(|(name, _)| *name == variable.ty) {
+            errors.push(format!(
+                "Variable \"${}\" can't have type \"{}\". Variables are String, DateTime or \
+                 Severity.",
+                variable.name, variable.ty
+            ));
+        } else if let Some(default) = &variable.default
+            && let Some(error) = literal_error(default, &variable.ty)
+        {
+            errors.push(format!(
+                "Variable \"${}\" has an invalid default: {}",
+                variable.name, error
+            ));
+        }
+    }
+
+    validate_selections(operation, &operation.selections, &SCHEMA[0], errors);
+}
+
+fn validate_selections(
+    operation: &Operation,
+    fields: &[Field],
+    ty: &TypeDef,
+    errors: &mut Vec<String>,
+) {
+    for (index, field) in fields.iter().enumerate() {
+        let key = field.response_key();
+        if fields[..index]
+            .iter()
+            .any(|other| other.response_key() == key)
+        {
+            errors.push(format!(
+                "\"{}\" is selected more than once on type \"{}\". Give each selection its own \
+                 alias.",
+                key, ty.name
+            ));
+        }
+
+        let (field_type, args) = match ty.fields.iter().find(|def| def.name == field.name) {
+            Some(def) => (def.ty, def.args),
+            None if field.name == "__typename" => ("String!", &[][..]),
+            None if field.name.starts_with("__") => {
+                errors.push(format!(
+                    "Introspection isn't supported, so \"{}\" can't be queried. Only \
+                     \"__typename\" is.",
+                    field.name
+                ));
+                continue;
+            }
+            None => {
+                errors.push(format!(
+                    "Cannot query field \"{}\" on type \"{}\".",
+                    field.name, ty.name
+                ));
+                continue;
+            }
+        };
+
+        for (index, (name, value)) in field.arguments.iter().enumerate() {
+            if field.arguments[..index]
+                .iter()
+                .any(|(other, _)| other == name)
+            {
+                errors.push(format!(
+                    "There can be only one argument named \"{}\".",
+                    name
+                ));
+            }
+            match args.iter().find(|arg| arg.name == name) {
+                Some(arg) => validate_argument(operation, value, arg, errors),
+                None => errors.push(format!(
+                    "Unknown argument \"{}\" on field \"{}.{}\".",
+                    name, ty.name, field.name
+                )),
+            }
+        }
+        for arg in args.iter().filter(|arg| arg.ty.ends_with('!')) {
+            let value = field.arguments.iter().find(|(name, _)| name == arg.name);
+            if value.is_none_or(|(_, value)| *value == Value::Null) {
+                errors.push(format!(
+                    "Field \"{}.{}\" argument \"{}\" of type \"{}\" is required.",
+                    ty.name, field.name, arg.name, arg.ty
+                ));
+            }
+        }
+
+        match find_type(named_type(field_type)) {
+            Some(_) if field.selections.is_empty() => errors.push(format!(
+                "Field \"{}\" of type \"{}\" must have a selection of subfields.",
+                field.name, field_type
+            )),
+            Some(object) => validate_selections(operation, &field.selections, object, errors),
+            None if !field.selections.is_empty() => errors.push(format!(
+                "Field \"{}\" must not have a selection since type \"{}\" has no subfields.",
+                field.name, field_type
+            )),
+            None => {}
+        }
+    }
+}
+
+fn validate_argument(operation: &Operation, value: &Value, arg: &ArgDef, errors: &mut Vec<String>) {
+    let Value::Variable(name) = value else {
+        if let Some(error) = literal_error(value, named_type(arg.ty)) {
+            errors.push(format!("Argument \"{}\" is invalid: {}", arg.name, error));
+        }
+        return;
+    };
+
+    match operation
+        .variables
+        .iter()
+        .find(|variable| variable.name == *name)
+    {
+        None => errors.push(format!("Variable \"${}\" is not defined.", name)),
+        Some(variable)
+            if variable.ty != named_type(arg.ty)
+                || (arg.ty.ends_with('!') && !variable.required && variable.default.is_none()) =>
+        {
+            errors.push(format!(
+                "Variable \"${}\" of type \"{}\" can't be used for argument \"{}\" of type \
+                 \"{}\".",
+                name,
+                variable.type_name(),
+                arg.name,
+                arg.ty
+            ))
+        }
+        Some(_) => {}
+    }
+}
+
+/// Why a literal `value` can't be used as the input type `ty`, if it can't. Enums take enum
+/// values and other types take strings, as GraphQL requires.
+fn literal_error(value: &Value, ty: &str) -> Option<String> {
+    let is_enum = INPUT_TYPES
+        .iter()
+        .any(|(name, is_enum)| *name == ty && *is_enum);
+    match value {
+        Value::String(string) if is_enum => Some(format!(
+            "{} is an enum, so it takes a value such as SEVERE, not the string {:?}.",
+            ty, string
+        )),
+        Value::Enum(name) if !is_enum => Some(format!(
+            "{} takes a string, such as \"{}\", not an enum value.",
+            ty, name
+        )),
+        _ => None,
+    }
+}
+
+// Execution
+
+/// A field to resolve, with its arguments' values.
+struct FieldRef<'q> {
+    name: &'q str,
+    arguments: Map<String, Json>,
+    selections: &'q [Field],
+}
+
+impl FieldRef<'_> {
+    fn argument(&self, name: &str) -> Option<&Json> {
+        self.arguments.get(name).filter(|value| !value.is_null())
+    }
+
+    fn string_argument(&self, name: &str) -> Result<Option<&str>, String> {
+        match self.argument(name) {
+            None => Ok(None),
+            Some(Json::String(value)) => Ok(Some(value)),
+            Some(value) => Err(format!(
+                "Argument \"{}\" must be a string, not {}.",
+                name, value
+            )),
+        }
+    }
+}
+
+/// A type whose fields queries can select.
+trait Object {
+    const TYPE: &'static str;
+
+    /// The value of `field`, which is known to be one of the type's fields with valid
+    /// arguments.
+    fn resolve(&self, executor: &Executor, field: &FieldRef, path: &[Json])
+    -> Result<Json, String>;
+}
+
+struct Executor<'a> {
+    data: &'a Data<'a>,
+    variables: Map<String, Json>,
+    errors: RefCell<Vec<Json>>,
+    /// How many more fields the query can resolve
+    fields_left: Cell<usize>,
+    /// How many more bytes the response can take
+    bytes_left: Cell<usize>,
+    /// Why the query was stopped, once it's used up either
+    exceeded: Cell<Option<&'static str>>,
+}
+
+impl Executor<'_> {
+    /// Selects `fields` from `object`, recording any that fail as errors.
+    fn object<T: Object>(&self, object: &T, fields: &[Field], path: &[Json]) -> Json {
+        let mut result = Map::new();
+
+        for field in fields {
+            let key = field.response_key();
+            if !self.charge(1, key.len()) {
+                break;
+            }
+            let mut field_path = path.to_vec();
+            field_path.push(key.into());
+
+            let value = if field.name == "__typename" {
+                Ok(T::TYPE.into())
+            } else {
+                object.resolve(self, &self.field(field), &field_path)
+            }
+            .unwrap_or_else(|message| {
+                self.error(message, &field_path);
+                Json::Null
+            });
+            if !self.charge(0, scalar_len(&value)) {
+                break;
+            }
+            result.insert(key.to_string(), value);
+        }
+
+        result.into()
+    }
+
+    fn list<'o, T: Object + 'o>(
+        &self,
+        objects: impl IntoIterator<Item = &'o T>,
+        field: &FieldRef,
+        path: &[Json],
+    ) -> Json {
+        objects
+            .into_iter()
+            .enumerate()
+            .take_while(|_| self.charge(1, 0))
+            .map(|(index, object)| {
+                let mut path = path.to_vec();
+                path.push(index.into());
+                self.object(object, field.selections, &path)
+            })
+            .collect()
+    }
+
+    /// Takes `fields` and `bytes` out of the query's budget, returning whether there was enough.
+    /// Once there isn't, nothing more is resolved.
+    fn charge(&self, fields: usize, bytes: usize) -> bool {
+        if self.exceeded.get().is_some() {
+            return false;
+        }
+
+        match (
+            self.fields_left.get().checked_sub(fields),
+            self.bytes_left.get().checked_sub(bytes),
+        ) {
+            (Some(fields_left), Some(bytes_left)) => {
+                self.fields_left.set(fields_left);
+                self.bytes_left.set(bytes_left);
+                true
+            }
+            (None, _) => {
+                self.exceeded.set(Some(
+                    "Query is too expensive: it resolves too many fields. Select fewer fields, \
+                     or use fewer aliases.",
+                ));
+                false
+            }
+            (_, None) => {
+                self.exceeded.set(Some(
+                    "Query is too expensive: its response is too large. Select fewer fields, \
+                     or use fewer aliases.",
+                ));
+                false
+            }
+        }
+    }
+
+    fn error(&self, message: String, path: &[Json]) {
+        self.errors
+            .borrow_mut()
+            .push(serde_json::json!({ "message": message, "path": path }));
+    }
+
+    /// The field with its arguments' variables substituted.
+    fn field<'q>(&self, field: &'q Field) -> FieldRef<'q> {
+        FieldRef {
+            name: &field.name,
+            arguments: field
+                .arguments
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_json(&self.variables)))
+                .collect(),
+            selections: &field.selections,
+        }
+    }
+
+    fn alerts(&self) -> Result<&[AlertDetail], String> {
+        self.data
+            .alerts
+            .as_ref()
+            .copied()
+            .map_err(|e| format!("Error fetching alerts: {}", e))
+    }
+
+    /// The alerts that `matches` picks out, narrowed by the field's `activeAt` and `severity`.
+    fn filtered_alerts(
+        &self,
+        field: &FieldRef,
+        path: &[Json],
+        matches: impl Fn(&AlertDetail) -> bool,
+    ) -> Result<Json, String> {
+        let active_at = match field.string_argument("activeAt")? {
+            Some(time) => Some(
+                DateTime::parse_from_rfc3339(time)
+                    .map_err(|_| {
+                        format!(
+                            "Invalid activeAt: {}. Expected an RFC 3339 timestamp such as 2025-12-15T12:00:00Z.",
+                            time
+                        )
+                    })?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+        let min_severity = match field.string_argument("severity")? {
+            Some(name) => Some(
+                Severity::from_query_name(&name.to_lowercase())
+                    .filter(|_| name.chars().all(|c| c.is_ascii_uppercase()))
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid severity: {}. Expected one of: INFO, WARNING, SEVERE.",
+                            name
+                        )
+                    })?,
+            ),
+            None => None,
+        };
+
+        let alerts: Vec<&AlertDetail> = self
+            .alerts()?
+            .iter()
+            .filter(|alert| matches(alert))
+            .filter(|alert| active_at.is_none_or(|time| alert.is_active_at(time)))
+            .filter(|alert| min_severity.is_none_or(|min| alert.severity >= min))
+            .collect();
+
+        Ok(self.list(alerts, field, path))
+    }
+}
+
+/// The bytes a scalar value takes in the response. Objects and lists are counted by their
+/// fields and items instead.
+fn scalar_len(value: &Json) -> usize {
+    match value {
+        Json::Object(_) | Json::Array(_) => 0,
+        Json::String(value) => value.len() + 2,
+        value => value.to_string().len(),
+    }
+}
+
+/// The GraphQL name of an enum value, e.g. `STATEN_ISLAND` for [`Borough::StatenIsland`].
+///
+/// [`Borough::StatenIsland`]: crate::stops::Borough::StatenIsland
+fn enum_value(value: impl Serialize) -> Json {
+    match serde_json::to_value(value) {
+        Ok(Json::String(name)) => name.to_uppercase().into(),
+        _ => Json::Null,
+    }
+}
+
+fn time(time: DateTime<Utc>) -> Json {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        .into()
+}
+
+struct Query;
+
+impl Object for Query {
+    const TYPE: &'static str = "Query";
+
+    fn resolve(
+        &self,
+        executor: &Executor,
+        field: &FieldRef,
+        path: &[Json],
+    ) -> Result<Json, String> {
+        match field.name {
+            "lines" => Ok(executor.list(LINES, field, path)),
+            "line" => {
+                let id = lines::normalize(field.string_argument("id")?.unwrap_or_default());
+                Ok(lines::find_line(&id).map_or(Json::Null, |line| {
+                    executor.object(line, field.selections, path)
+                }))
+            }
+            "alerts" => {
+                let trains = match field.string_argument("line")? {
+                    Some(name) => {
+                        let name = lines::normalize(name);
+                        Some(
+                            lines::resolve(&name)
+                                .ok_or_else(|| format!("Unknown train line: {}.", name))?,
+                        )
+                    }
+                    None => None,
+                };
+                executor.filtered_alerts(field, path, |alert| {
+                    trains.is_none_or(|trains| changes::alert_affects_any(alert, trains))
+                })
+            }
+            "station" => {
+                if executor.data.stations.is_empty() {
+                    return Err("Station data isn't available.".to_string());
+                }
+                let stop_id = field.string_argument("stopId")?.unwrap_or_default();
+                Ok(executor
+                    .data
+                    .stations
+                    .get(stop_id.trim())
+                    .map_or(Json::Null, |station| {
+                        executor.object(station, field.selections, path)
+                    }))
+            }
+            _ => unreachable!("Query has no field {}", field.name),
+        }
+    }
+}
+
+impl Object for Line {
+    const TYPE: &'static str = "Line";
+
+    fn resolve(
+        &self,
+        executor: &Executor,
+        field: &FieldRef,
+        path: &[Json],
+    ) -> Result<Json, String> {
+        match field.name {
+            "id" => Ok(self.id.into()),
+            "name" => Ok(self.name.into()),
+            "color" => Ok(self.color.into()),
+            "textColor" => Ok(self.text_color.into()),
+            "alerts" => executor.filtered_alerts(field, path, |alert| {
+                changes::alert_affects_any(alert, &[self.id])
+            }),
+            _ => unreachable!("Line has no field {}", field.name),
+        }
+    }
+}
+
+impl Object for AlertDetail {
+    const TYPE: &'static str = "Alert";
+
+    fn resolve(
+        &self,
+        executor: &Executor,
+        field: &FieldRef,
+        path: &[Json],
+    ) -> Result<Json, String> {
+        match field.name {
+            "id" => Ok(self.id.as_str().into()),
+            "type" => Ok(self.alert_type.as_str().into()),
+            "header" => Ok(self.header.as_str().into()),
+            "description" => Ok(self.description.as_str().into()),
+            "severity" => Ok(enum_value(self.severity)),
+            "kind" => Ok(enum_value(self.kind)),
+            "createdAt" => Ok(time(self.created_at)),
+            "updatedAt" => Ok(time(self.updated_at)),
+            "activePeriods" => Ok(executor.list(&self.active_periods, field, path)),
+            "routes" => Ok(notify::alert_routes(self).into()),
+            "stopIds" => {
+                let mut stop_ids: Vec<&str> = Vec::new();
+                for stop_id in self
+                    .informed_entities
+                    .iter()
+                    .filter_map(|entity| entity.stop_id.as_deref())
+                {
+                    if !stop_ids.contains(&stop_id) {
+                        stop_ids.push(stop_id);
+                    }
+                }
+                Ok(stop_ids.into())
+            }
+            "url" => Ok(self.url.as_deref().into()),
+            "affectsAccessibility" => Ok(self.affects_accessibility().into()),
+            _ => unreachable!("Alert has no field {}", field.name),
+        }
+    }
+}
+
+impl Object for crate::alert::ActivePeriod {
+    const TYPE: &'static str = "ActivePeriod";
+
+    fn resolve(&self, _: &Executor, field: &FieldRef, _: &[Json]) -> Result<Json, String> {
+        match field.name {
+            "start" => Ok(self.start.map_or(Json::Null, time)),
+            "end" => Ok(self.end.map_or(Json::Null, time)),
+            _ => unreachable!("ActivePeriod has no field {}", field.name),
+        }
+    }
+}
+
+impl Object for Station {
+    const TYPE: &'static str = "Station";
+
+    fn resolve(
+        &self,
+        executor: &Executor,
+        field: &FieldRef,
+        path: &[Json],
+    ) -> Result<Json, String> {
+        match field.name {
+            "stopId" => Ok(self.stop_id.as_str().into()),
+            "name" => Ok(self.name.as_str().into()),
+            "borough" => Ok(enum_value(self.borough)),
+            "routes" => Ok(self.routes.clone().into()),
+            "lines" => {
+                let lines = self
+                    .routes
+                    .iter()
+                    .filter_map(|route| lines::find_line(&lines::normalize(route)));
+                Ok(executor.list(lines, field, path))
+            }
+            "latitude" => Ok(self.latitude.into()),
+            "longitude" => Ok(self.longitude.into()),
+            "alerts" => executor.filtered_alerts(field, path, |alert| {
+                alert.informed_entities.iter().any(|entity| {
+                    entity
+                        .stop_id
+                        .as_deref()
+                        .is_some_and(|stop_id| stops::stop_matches(&self.stop_id, stop_id))
+                })
+            }),
+            _ => unreachable!("Station has no field {}", field.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::InformedEntity;
+    use crate::notify::tests::alert;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn alerts() -> Vec<AlertDetail> {
+        let mut station_alert = alert("3", &[], Severity::Warning);
+        station_alert.informed_entities.push(InformedEntity {
+            agency_id: None,
+            route_id: None,
+            stop_id: Some("A15S".to_string()),
+            sort_order: None,
+        });
+        station_alert.active_periods.clear();
+
+        vec![
+            alert("1", &["A", "C"], Severity::Severe),
+            alert("2", &["L"], Severity::Info),
+            station_alert,
+        ]
+    }
+
+    fn stations() -> Stations {
+        Stations::from_csv(&include_bytes!("../tests/fixtures/stations.csv")[..]).unwrap()
+    }
+
+    fn run(query: &str, variables: Json) -> Json {
+        let alerts = alerts();
+        let stations = stations();
+        let request = Request {
+            query: query.to_string(),
+            variables: variables.as_object().cloned(),
+            operation_name: None,
+        };
+        execute(
+            &request,
+            &Data {
+                alerts: Ok(&alerts),
+                stations: &stations,
+            },
+        )
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(
+            run(
+                r#"{ line(id: "a") { id name textColor alerts { id severity kind routes } } }"#,
+                Json::Null
+            ),
+            json!({ "data": { "line": {
+                "id": "A",
+                "name": "8 Avenue Express",
+                "textColor": "#FFFFFF",
+                "alerts": [{ "id": "1", "severity": "SEVERE", "kind": "REALTIME", "routes": ["A", "C"] }],
+            } } })
+        );
+
+        assert_eq!(
+            run(r#"{ line(id: "X") { id } }"#, Json::Null),
+            json!({ "data": { "line": null } })
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let response = run("{ lines { id } }", Json::Null);
+        let lines = response["data"]["lines"].as_array().unwrap();
+        assert_eq!(lines.len(), LINES.len());
+        assert_eq!(lines[0], json!({ "id": "A" }));
+    }
+
+    #[test]
+    fn test_alert_filters() {
+        let query = r#"query Alerts($line: String, $at: DateTime, $severity: Severity = INFO) {
+            alerts(line: $line, activeAt: $at, severity: $severity) { id }
+        }"#;
+        let ids = |variables: Json| {
+            let response = run(query, variables);
+            response["data"]["alerts"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|alert| alert["id"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(json!({})), ["1", "2", "3"]);
+        assert_eq!(ids(json!({ "line": "ace" })), ["1"]);
+        assert_eq!(ids(json!({ "severity": "WARNING" })), ["1", "3"]);
+        // Alerts without active periods are always active
+        assert_eq!(ids(json!({ "at": "2025-12-15T16:00:00Z" })), ["3"]);
+        assert_eq!(
+            ids(json!({ "at": "2025-12-15T13:00:00+01:00" })),
+            ["1", "2", "3"]
+        );
+
+        assert_eq!(
+            run(query, json!({ "severity": "severe" }))["errors"][0]["message"],
+            "Invalid severity: severe. Expected one of: INFO, WARNING, SEVERE."
+        );
+        assert_eq!(
+            run(query, json!({ "line": "X" })),
+            json!({
+                "data": { "alerts": null },
+                "errors": [{ "message": "Unknown train line: X.", "path": ["alerts"] }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_station() {
+        assert_eq!(
+            run(
+                r#"{ station(stopId: "A15N") { stopId name borough routes lines { id } alerts { id } } }"#,
+                Json::Null
+            ),
+            json!({ "data": { "station": {
+                "stopId": "A15",
+                "name": "125 St",
+                "borough": "MANHATTAN",
+                "routes": ["A", "B", "C", "D"],
+                "lines": [{ "id": "A" }, { "id": "B" }, { "id": "C" }, { "id": "D" }],
+                "alerts": [{ "id": "3" }],
+            } } })
+        );
+    }
+
+    #[test]
+    fn test_aliases_and_typename() {
+        assert_eq!(
+            run(
+                r#"{ a: line(id: "A") { id name } l: line(id: "L") { color } __typename }"#,
+                Json::Null
+            ),
+            json!({ "data": {
+                "a": { "id": "A", "name": "8 Avenue Express" },
+                "l": { "color": "#A7A9AC" },
+                "__typename": "Query",
+            } })
+        );
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        assert_eq!(
+            run(
+                r#"{ lines { id nope alerts(activeAt: $at) } station { name } line(id: A) { id id } }"#,
+                Json::Null
+            ),
+            json!({
+                "errors": [
+                    { "message": "Cannot query field \"nope\" on type \"Line\"." },
+                    { "message": "Variable \"$at\" is not defined." },
+                    {
+                        "message": "Field \"alerts\" of type \"[Alert!]\" must have a selection of subfields."
+                    },
+                    {
+                        "message": "Field \"Query.station\" argument \"stopId\" of type \"String!\" is required."
+                    },
+                    {
+                        "message": "Argument \"id\" is invalid: String takes a string, such as \"A\", not an enum value."
+                    },
+                    {
+                        "message": "\"id\" is selected more than once on type \"Line\". Give each selection its own alias."
+                    },
+                ],
+            })
+        );
+
+        assert_eq!(
+            run(
+                r#"query ($id: String, $s: Severity = "SEVERE") { line(id: $id) { alerts(severity: $s) { id } } }"#,
+                Json::Null
+            ),
+            json!({
+                "errors": [
+                    {
+                        "message": "Variable \"$s\" has an invalid default: Severity is an enum, so it takes a value such as SEVERE, not the string \"SEVERE\"."
+                    },
+                    {
+                        "message": "Variable \"$id\" of type \"String\" can't be used for argument \"id\" of type \"String!\"."
+                    },
+                ],
+            })
+        );
+
+        assert_eq!(
+            run("query ($id: String!) { line(id: $id) { id } }", json!({})),
+            json!({
+                "errors": [{ "message": "Variable \"$id\" of required type \"String!\" was not provided." }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_unsupported_features() {
+        for (query, message) in [
+            (
+                "{ lines { ...F } } fragment F on Line { id }",
+                "Fragments aren't supported.",
+            ),
+            (
+                "{ lines { ... on Line { id } } }",
+                "Fragments aren't supported.",
+            ),
+            (
+                "{ lines { id @skip(if: true) } }",
+                "Directives aren't supported.",
+            ),
+            ("mutation { lines { id } }", "Only queries are supported."),
+            (
+                "subscription { lines { id } }",
+                "Only queries are supported.",
+            ),
+            (
+                "query A { lines { id } } query B { lines { id } }",
+                "Only one operation per request is supported.",
+            ),
+            (
+                "{ __schema { types { name } } }",
+                "Introspection isn't supported, so \"__schema\" can't be queried. Only \
+                 \"__typename\" is.",
+            ),
+            (
+                "{ line(id: 1) { id } }",
+                "Numbers aren't supported. Every argument is a string or an enum value.",
+            ),
+            (
+                "{ line(id: true) { id } }",
+                "Booleans aren't supported. Every argument is a string or an enum value.",
+            ),
+            (
+                "query ($ids: [String!]) { lines { id } }",
+                "Lists aren't supported.",
+            ),
+            (
+                "{ line(id: { a: \"A\" }) { id } }",
+                "Input objects aren't supported.",
+            ),
+            (
+                r#"{ line(id: """A""") { id } }"#,
+                "Block strings aren't supported.",
+            ),
+            (
+                "query ($at: Time) { lines { id } }",
+                "Variable \"$at\" can't have type \"Time\". Variables are String, DateTime or \
+                 Severity.",
+            ),
+        ] {
+            assert_eq!(
+                run(query, Json::Null),
+                json!({ "errors": [{ "message": message }] }),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_feed_error() {
+        let stations = stations();
+        let request = Request {
+            query: r#"{ line(id: "A") { name alerts { id } } }"#.to_string(),
+            ..Request::default()
+        };
+        let data = Data {
+            alerts: Err("timed out".to_string()),
+            stations: &stations,
+        };
+
+        assert_eq!(
+            execute(&request, &data),
+            json!({
+                "data": { "line": { "name": "8 Avenue Express", "alerts": null } },
+                "errors": [{
+                    "message": "Error fetching alerts: timed out",
+                    "path": ["line", "alerts"],
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_cost_limits() {
+        // A short query that aliases the same long list over and over
+        let alerts: Vec<AlertDetail> = (0..1000)
+            .map(|i| alert(&i.to_string(), &["A"], Severity::Info))
+            .collect();
+        let stations = stations();
+        let data = Data {
+            alerts: Ok(&alerts),
+            stations: &stations,
+        };
+        let aliases: Vec<String> = (0..100)
+            .map(|i| format!("a{}: alerts {{ id }}", i))
+            .collect();
+        let request = Request {
+            query: format!("{{ {} }}", aliases.join(" ")),
+            ..Request::default()
+        };
+        assert!(request.query.len() < MAX_QUERY_LEN);
+        assert_eq!(
+            execute(&request, &data),
+            json!({
+                "data": null,
+                "errors": [{
+                    "message": "Query is too expensive: it resolves too many fields. Select \
+                        fewer fields, or use fewer aliases.",
+                }],
+            })
+        );
+
+        // Few fields, but long ones
+        let mut long = alert("4", &["A"], Severity::Severe);
+        long.header = "x".repeat(MAX_RESPONSE_LEN / 2);
+        let alerts = vec![long];
+        let request = Request {
+            query:
+                r#"{ a: line(id: "A") { alerts { header } } b: line(id: "A") { alerts { header } }
+                c: line(id: "A") { alerts { header } } }"#
+                    .to_string(),
+            ..Request::default()
+        };
+        let data = Data {
+            alerts: Ok(&alerts),
+            stations: &stations,
+        };
+        let response = execute(&request, &data);
+        assert_eq!(response["data"], Json::Null);
+        assert!(
+            response["errors"][0]["message"]
+                .as_str()
+                .unwrap()
+                .contains("its response is too large")
+        );
+
+        // Ordinary queries are well within both
+        let response = run(
+            "{ lines { id name alerts { id header activePeriods { start end } } } }",
+            Json::Null,
+        );
+        assert!(response.get("errors").is_none(), "{}", response);
+    }
+
+    #[test]
+    fn test_request_errors() {
+        for (query, message) in [
+            ("", "No operation to run."),
+            ("{ lines { id }", "Unexpected end of query."),
+            ("{ line(id: \"A) { id } }", "Unterminated string."),
+            ("{ lines {} }", "Selection sets can't be empty."),
+            ("{ lines { id } } }", "Unexpected \"}\"."),
+        ] {
+            assert_eq!(
+                run(query, Json::Null),
+                json!({ "errors": [{ "message": message }] }),
+                "{}",
+                query
+            );
+        }
+
+        let nested = format!("{}{}", "{ lines ".repeat(30), "}".repeat(30));
+        assert_eq!(
+            run(&nested, Json::Null)["errors"][0]["message"],
+            "Query is nested more than 20 deep."
+        );
+    }
+
+    #[test]
+    fn test_operation_name() {
+        let alerts = alerts();
+        let stations = stations();
+        let data = Data {
+            alerts: Ok(&alerts),
+            stations: &stations,
+        };
+        let request = |operation_name: &str| Request {
+            query: r#"query L { l: line(id: "L") { id } }"#.to_string(),
+            variables: None,
+            operation_name: Some(operation_name.to_string()),
+        };
+
+        assert_eq!(
+            execute(&request("L"), &data),
+            json!({ "data": { "l": { "id": "L" } } })
+        );
+        assert_eq!(
+            execute(&request("A"), &data),
+            json!({ "errors": [{ "message": "Unknown operation named \"A\"." }] })
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(r#"a(b: $c, d: "A\né") # comment"#).unwrap(),
+            [
+                Token::Name("a".to_string()),
+                Token::Punctuator('('),
+                Token::Name("b".to_string()),
+                Token::Punctuator(':'),
+                Token::Punctuator('$'),
+                Token::Name("c".to_string()),
+                Token::Name("d".to_string()),
+                Token::Punctuator(':'),
+                Token::String("A\né".to_string()),
+                Token::Punctuator(')'),
+            ]
+        );
+    }
+}
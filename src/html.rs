@@ -0,0 +1,252 @@
+use crate::calendar::CalendarEvent;
+use crate::lines::Line;
+use crate::rss::escape_xml;
+use crate::status::LineStatus;
+use chrono::{DateTime, Utc};
+use chrono_tz::America::New_York;
+
+/// Links to a line's calendar in its different forms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscribeLinks {
+    pub calendar_url: String,
+    pub webcal_url: String,
+    pub google_url: String,
+    pub outlook_url: String,
+}
+
+/// Renders a status page for a line or trunk group: its alerts in effect now and those coming
+/// up, with times in New York time, and links to subscribe to its calendar.
+///
+/// `line` is the single line the page is for, which colors its bullet.
+pub fn generate_status_page(
+    name: &str,
+    line: Option<&Line>,
+    events: &[CalendarEvent],
+    links: &SubscribeLinks,
+    now: DateTime<Utc>,
+) -> String {
+    let (current, upcoming): (Vec<&CalendarEvent>, Vec<&CalendarEvent>) = events
+        .iter()
+        .filter(|event| event.end.is_none_or(|end| end > now))
+        .partition(|event| event.start <= now);
+    let mut upcoming = upcoming;
+    upcoming.sort_by_key(|event| event.start);
+
+    let status = LineStatus::from_events(events, now);
+    let name = escape_xml(name);
+    let bullet = match line {
+        Some(line) => format!(
+            r#"<span class="bullet" style="background-color: {}; color: {}">{}</span> "#,
+            line.color, line.text_color, line.id
+        ),
+        None => String::new(),
+    };
+    let subtitle = line.map_or_else(String::new, |line| {
+        format!("<p class=\"subtitle\">{}</p>\n", escape_xml(line.name))
+    });
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{name} Train Status</title>
+    <link rel="icon" type="image/svg+xml" href="/favicon.svg">
+    <style>
+        body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; max-width: 800px; margin: 0 auto; padding: 20px; line-height: 1.6; }}
+        .bullet {{ display: inline-block; width: 1.4em; height: 1.4em; line-height: 1.4em; border-radius: 50%; text-align: center; font-weight: bold; }}
+        .subtitle {{ color: #666; margin-top: -10px; }}
+        .status {{ font-size: 20px; font-weight: bold; }}
+        .alert {{ border-left: 4px solid #0039a6; padding: 4px 16px; margin: 16px 0; background-color: #f5f5f5; border-radius: 4px; }}
+        .alert h3 {{ margin: 8px 0 0; }}
+        .when {{ color: #555; font-size: 14px; }}
+        .subscribe {{ display: inline-block; padding: 12px 24px; background-color: #0039a6; color: white; border-radius: 4px; font-weight: bold; text-decoration: none; }}
+        code {{ display: block; margin: 10px 0; padding: 12px; background-color: #f5f5f5; border-radius: 4px; word-break: break-all; }}
+    </style>
+</head>
+<body>
+    <h1>{bullet}{name} Train</h1>
+    {subtitle}<p class="status">{status}</p>
+    <h2>In Effect Now</h2>
+{current}
+    <h2>Coming Up</h2>
+{upcoming}
+    <h2>Subscribe</h2>
+    <p><a class="subscribe" href="{webcal_url}">Subscribe in Calendar</a></p>
+    <p>Or add it to <a href="{google_url}">Google Calendar</a> or <a href="{outlook_url}">Outlook</a>, or copy this URL into your calendar app:</p>
+    <code>{calendar_url}</code>
+    <p><a href="/">All lines</a></p>
+</body>
+</html>"#,
+        name = name,
+        bullet = bullet,
+        subtitle = subtitle,
+        status = status.label(),
+        current = generate_alerts(&current, "No alerts right now."),
+        upcoming = generate_alerts(&upcoming, "No upcoming alerts."),
+        webcal_url = escape_xml(&links.webcal_url),
+        google_url = escape_xml(&links.google_url),
+        outlook_url = escape_xml(&links.outlook_url),
+        calendar_url = escape_xml(&links.calendar_url),
+    )
+}
+
+fn generate_alerts(events: &[&CalendarEvent], empty: &str) -> String {
+    if events.is_empty() {
+        return format!("    <p>{}</p>", empty);
+    }
+
+    events
+        .iter()
+        .map(|event| generate_alert(event))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn generate_alert(event: &CalendarEvent) -> String {
+    let description = escape_xml(&event.description).replace('\n', "<br>\n");
+    let link = match &event.url {
+        Some(url) => format!(
+            "\n        <p><a href=\"{}\">Full alert</a></p>",
+            escape_xml(url)
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"    <div class="alert">
+        <h3>{summary}</h3>
+        <p class="when">{when}</p>
+        <p>{description}</p>{link}
+    </div>"#,
+        summary = escape_xml(&event.summary),
+        when = format_period(event.start, event.end),
+        description = description,
+        link = link,
+    )
+}
+
+/// Formats an active period in New York time, e.g. `Mon, Dec 15, 9:45 PM – 5:00 AM`. The end
+/// date is only repeated when the period doesn't end within a day of its start. A period the
+/// clocks change during gives both times' zones, e.g. `Sat, Mar 8, 11:45 PM EST – 5:00 AM EDT`,
+/// so it doesn't look an hour longer or shorter than it is.
+pub(crate) fn format_period(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> String {
+    const DATE_TIME: &str = "%a, %b %-d, %-I:%M %p";
+
+    let start = start.with_timezone(&New_York);
+    let Some(end) = end else {
+        return format!("{} until further notice", start.format(DATE_TIME));
+    };
+
+    let end = end.with_timezone(&New_York);
+    let mut end_format = if end - start < chrono::Duration::days(1) {
+        "%-I:%M %p".to_string()
+    } else {
+        DATE_TIME.to_string()
+    };
+    let mut start_format = DATE_TIME.to_string();
+    if start.offset() != end.offset() {
+        start_format.push_str(" %Z");
+        end_format.push_str(" %Z");
+    }
+
+    format!(
+        "{} – {}",
+        start.format(&start_format),
+        end.format(&end_format)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn links() -> SubscribeLinks {
+        SubscribeLinks {
+            calendar_url: "http://localhost:3000/api/calendars/train/F.ics".to_string(),
+            webcal_url: "webcal://localhost:3000/api/calendars/train/F.ics".to_string(),
+            google_url: "/subscribe/google/F".to_string(),
+            outlook_url: "/subscribe/outlook/F".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_period() {
+        let at = |day, hour, minute| {
+            Utc.with_ymd_and_hms(2025, 12, day, hour, minute, 0)
+                .unwrap()
+        };
+
+        assert_eq!(
+            format_period(at(16, 2, 45), Some(at(16, 10, 0))),
+            "Mon, Dec 15, 9:45 PM – 5:00 AM"
+        );
+        assert_eq!(
+            format_period(at(16, 2, 0), Some(at(22, 5, 0))),
+            "Mon, Dec 15, 9:00 PM – Mon, Dec 22, 12:00 AM"
+        );
+        assert_eq!(
+            format_period(at(15, 17, 0), None),
+            "Mon, Dec 15, 12:00 PM until further notice"
+        );
+
+        // Saturday 11:45 PM to Sunday 5 AM is 4h15m over the spring-forward weekend, and 6h15m
+        // over the fall-back weekend
+        let spring_start = Utc.with_ymd_and_hms(2025, 3, 9, 4, 45, 0).unwrap();
+        assert_eq!(
+            format_period(
+                spring_start,
+                Some(spring_start + chrono::Duration::minutes(255))
+            ),
+            "Sat, Mar 8, 11:45 PM EST – 5:00 AM EDT"
+        );
+        let fall_start = Utc.with_ymd_and_hms(2025, 11, 2, 3, 45, 0).unwrap();
+        assert_eq!(
+            format_period(
+                fall_start,
+                Some(fall_start + chrono::Duration::minutes(375))
+            ),
+            "Sat, Nov 1, 11:45 PM EDT – 5:00 AM EST"
+        );
+    }
+
+    #[test]
+    fn test_status_page() {
+        let now = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let events = vec![
+            CalendarEvent {
+                summary: "F: Delays".to_string(),
+                description: "Trains are delayed <after> a sick customer\nExpect delays"
+                    .to_string(),
+                ..CalendarEvent::for_test("delays", &["F"])
+            },
+            CalendarEvent {
+                summary: "F: Planned - Stops Skipped".to_string(),
+                start: now + chrono::Duration::days(1),
+                ..CalendarEvent::for_test("work", &["F"])
+            },
+            CalendarEvent {
+                end: Some(now - chrono::Duration::hours(1)),
+                ..CalendarEvent::for_test("over", &["F"])
+            },
+        ];
+
+        let html = generate_status_page("F", crate::lines::find_line("F"), &events, &links(), now);
+
+        assert!(html.contains("<title>F Train Status</title>"));
+        assert!(html.contains("background-color: #FF6319"));
+        assert!(html.contains("<p class=\"status\">Delays</p>"));
+        assert!(
+            html.contains("Trains are delayed &lt;after&gt; a sick customer<br>\nExpect delays")
+        );
+        assert_eq!(html.matches("<div class=\"alert\">").count(), 2);
+        assert!(
+            html.find("F: Delays").unwrap() < html.find("Coming Up").unwrap()
+                && html.find("Coming Up").unwrap() < html.find("F: Planned").unwrap()
+        );
+        assert!(html.contains("href=\"webcal://localhost:3000/api/calendars/train/F.ics\""));
+    }
+}
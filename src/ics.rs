@@ -1,122 +1,559 @@
-use crate::calendar::CalendarEvent;
-use chrono::{DateTime, Utc};
+use crate::calendar::{CalendarEvent, Language};
+use crate::filter::{self, Alarm, EventFilter};
+use crate::network::Network;
+use crate::notify;
+use crate::recurrence::{self, Recurrence};
+use chrono::{DateTime, Duration, FixedOffset, LocalResult, Offset, TimeZone, Utc};
+use chrono_tz::America::New_York;
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+
+/// Identifies this crate and its version as the calendars' producer.
+const PRODID: &str = concat!(
+    "-//NYC TRAIN CAL//",
+    env!("CARGO_PKG_NAME"),
+    " ",
+    env!("CARGO_PKG_VERSION"),
+    "//EN"
+);
+
+/// The CSS color name closest to each line color, for the RFC 7986 `COLOR` property.
+const COLOR_NAMES: &[(&str, &str)] = &[
+    ("#0039A6", "darkblue"),
+    ("#FF6319", "orangered"),
+    ("#6CBE45", "yellowgreen"),
+    ("#00933C", "forestgreen"),
+    ("#996633", "sienna"),
+    ("#A7A9AC", "darkgray"),
+    ("#FCCC0A", "gold"),
+    ("#EE352E", "orangered"),
+    ("#B933AD", "darkorchid"),
+    ("#808183", "gray"),
+];
+
+/// New York's time zone, for the `TZID` that event times are written in. Only the rules in
+/// effect since 2007 are included, which is enough for alerts.
+const NEW_YORK_VTIMEZONE: &str = "BEGIN:VTIMEZONE\r\n\
+TZID:America/New_York\r\n\
+BEGIN:DAYLIGHT\r\n\
+TZOFFSETFROM:-0500\r\n\
+TZOFFSETTO:-0400\r\n\
+TZNAME:EDT\r\n\
+DTSTART:20070311T020000\r\n\
+RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r\n\
+END:DAYLIGHT\r\n\
+BEGIN:STANDARD\r\n\
+TZOFFSETFROM:-0400\r\n\
+TZOFFSETTO:-0500\r\n\
+TZNAME:EST\r\n\
+DTSTART:20071104T020000\r\n\
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=1SU\r\n\
+END:STANDARD\r\n\
+END:VTIMEZONE\r\n";
 
 pub fn generate_ics(events: &[CalendarEvent]) -> String {
     generate_ics_with_name(events, None)
 }
 
 pub fn generate_ics_with_name(events: &[CalendarEvent], train_name: Option<&str>) -> String {
-    let mut ics = String::new();
+    generate_localized_ics(events, train_name, Language::English)
+}
 
-    let (cal_name, cal_desc) = if let Some(train) = train_name {
-        (
-            format!("MTA {} Train Alerts", train.to_uppercase()),
-            format!(
-                "Real-time alerts and planned service changes for MTA {} train",
-                train.to_uppercase()
-            ),
-        )
-    } else {
-        (
-            "MTA Subway Alerts".to_string(),
-            "Real-time alerts and planned service changes for MTA Subway".to_string(),
-        )
-    };
+/// Generates a calendar whose name and description are in `language`.
+///
+/// Events are written as they are, so they should already be localized.
+pub fn generate_localized_ics(
+    events: &[CalendarEvent],
+    train_name: Option<&str>,
+    language: Language,
+) -> String {
+    let metadata = Network::Subway.calendar_metadata(train_name, language);
+
+    generate_ics_with_metadata(events, &metadata)
+}
+
+/// A calendar's name and description. They're written as the RFC 7986 `NAME` and
+/// `DESCRIPTION` properties, and as `X-WR-CALNAME` and `X-WR-CALDESC` for clients that predate
+/// it, so subscriptions don't show up as "Untitled".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarMetadata {
+    pub name: String,
+    pub description: String,
+    /// The line color as a hex string, which Apple Calendar and clients that support the
+    /// RFC 7986 `COLOR` property show the calendar in
+    pub color: Option<&'static str>,
+    /// The reminder every event gets, if any
+    pub alarm: Option<Alarm>,
+    /// Leaves out the [`Category::indicator`](crate::calendar::Category::indicator) before
+    /// event summaries
+    pub plain: bool,
+    /// The time zone event times are written in
+    pub timezone: Tz,
+    /// How often apps are asked to check the calendar for changes, as the RFC 7986
+    /// `REFRESH-INTERVAL` property and the `X-PUBLISHED-TTL` that Outlook and older clients read
+    pub refresh_interval: Duration,
+    /// The title of the event shown when the calendar has no alerts and `?placeholder=1` asks
+    /// for one, e.g. "No service alerts for the F train"
+    pub placeholder: String,
+}
+
+impl CalendarMetadata {
+    /// Metadata for a calendar of `network`'s alerts, whose one-line `summary` is followed by
+    /// where the alerts come from.
+    pub fn new(
+        network: Network,
+        name: String,
+        summary: &str,
+        placeholder: String,
+        language: Language,
+    ) -> Self {
+        CalendarMetadata {
+            name,
+            description: format!("{}. {}", summary, network.attribution(language)),
+            placeholder,
+            color: None,
+            alarm: None,
+            plain: false,
+            timezone: New_York,
+            refresh_interval: filter::DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
+    /// Colors the calendar after the first of `train_names` that's a subway line or express
+    /// variant, e.g. orange for `F`.
+    pub fn with_line_color(self, train_names: &[&str]) -> Self {
+        let line = train_names
+            .iter()
+            .find_map(|train_name| notify::route_line(train_name));
+
+        CalendarMetadata {
+            color: line.map(|line| line.color),
+            ..self
+        }
+    }
+
+    /// Reminds subscribers of every event `alarm` before it starts.
+    pub fn with_alarm(self, alarm: Option<Alarm>) -> Self {
+        CalendarMetadata { alarm, ..self }
+    }
+
+    /// Leaves out the indicators before event summaries when `plain`.
+    pub fn with_plain(self, plain: bool) -> Self {
+        CalendarMetadata { plain, ..self }
+    }
+
+    /// Writes event times in `timezone`, or in New York without one.
+    pub fn with_timezone(self, timezone: Option<Tz>) -> Self {
+        CalendarMetadata {
+            timezone: timezone.unwrap_or(New_York),
+            ..self
+        }
+    }
+
+    /// Asks apps to check the calendar for changes every `refresh_interval`, or every
+    /// [`DEFAULT_REFRESH_INTERVAL`](filter::DEFAULT_REFRESH_INTERVAL) without one.
+    pub fn with_refresh_interval(self, refresh_interval: Option<Duration>) -> Self {
+        CalendarMetadata {
+            refresh_interval: refresh_interval.unwrap_or(filter::DEFAULT_REFRESH_INTERVAL),
+            ..self
+        }
+    }
+
+    /// Applies `filter`'s reminder, plain summaries, time zone and refresh interval, and says in
+    /// the description how far ahead its window covers.
+    pub fn with_filter(self, filter: &EventFilter) -> Self {
+        let description = match &filter.window {
+            Some(window) => format!("{} {}", self.description, window.describe(filter.language)),
+            None => self.description,
+        };
+
+        CalendarMetadata {
+            description,
+            ..self
+        }
+        .with_alarm(filter.alarm)
+        .with_plain(filter.plain)
+        .with_timezone(filter.timezone)
+        .with_refresh_interval(filter.refresh_interval)
+    }
+}
+
+pub fn generate_ics_with_metadata(events: &[CalendarEvent], metadata: &CalendarMetadata) -> String {
+    let mut ics = String::new();
 
     // ICS header
-    ics.push_str("BEGIN:VCALENDAR\r\n");
-    ics.push_str("VERSION:2.0\r\n");
-    ics.push_str("PRODID:-//NYC TRAIN CAL//MTA Subway Alerts//EN\r\n");
-    ics.push_str("CALSCALE:GREGORIAN\r\n");
-    ics.push_str("METHOD:PUBLISH\r\n");
-    ics.push_str(&format!("X-WR-CALNAME:{}\r\n", cal_name));
-    ics.push_str("X-WR-TIMEZONE:America/New_York\r\n");
-    ics.push_str(&format!("X-WR-CALDESC:{}\r\n", cal_desc));
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{}", PRODID));
+    push_line(&mut ics, "METHOD:PUBLISH");
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    let name = escape_text(&metadata.name);
+    let description = escape_text(&metadata.description);
+    push_line(&mut ics, &format!("NAME:{}", name));
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", name));
+    push_line(&mut ics, &format!("DESCRIPTION:{}", description));
+    push_line(&mut ics, &format!("X-WR-CALDESC:{}", description));
+    push_line(
+        &mut ics,
+        &format!("X-WR-TIMEZONE:{}", metadata.timezone.name()),
+    );
+    let refresh_interval = filter::format_duration(metadata.refresh_interval);
+    push_line(
+        &mut ics,
+        &format!("REFRESH-INTERVAL;VALUE=DURATION:{}", refresh_interval),
+    );
+    push_line(&mut ics, &format!("X-PUBLISHED-TTL:{}", refresh_interval));
+    if let Some(color) = metadata.color {
+        push_line(&mut ics, &format!("X-APPLE-CALENDAR-COLOR:{}", color));
+        if let Some((_, name)) = COLOR_NAMES.iter().find(|(hex, _)| *hex == color) {
+            push_line(&mut ics, &format!("COLOR:{}", name));
+        }
+    }
+
+    // Sorted by start and then UID, so the calendar doesn't change when the feed lists the same
+    // alerts in another order
+    let mut events = events.to_vec();
+    events.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.uid.cmp(&b.uid)));
+    let events = recurrence::collapse(&events, metadata.timezone);
 
-    for event in events {
-        ics.push_str(&generate_event(event));
+    if let Some(vtimezone) = generate_vtimezone(metadata.timezone, &events) {
+        ics.push_str(&vtimezone);
     }
 
-    ics.push_str("END:VCALENDAR\r\n");
+    for (event, recurrence) in &events {
+        ics.push_str(&generate_event(event, recurrence.as_ref(), metadata));
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
 
     ics
 }
 
-fn generate_event(event: &CalendarEvent) -> String {
+fn generate_event(
+    event: &CalendarEvent,
+    recurrence: Option<&Recurrence>,
+    metadata: &CalendarMetadata,
+) -> String {
     let mut vevent = String::new();
 
-    vevent.push_str("BEGIN:VEVENT\r\n");
-
-    vevent.push_str(&fold_line(&format!("UID:{}@nyctraincal", event.uid)));
-    vevent.push_str("\r\n");
+    push_line(&mut vevent, "BEGIN:VEVENT");
+    push_line(&mut vevent, &format!("UID:{}@nyctraincal", event.uid));
 
     let created = format_datetime(&event.created_at);
     let updated = format_datetime(&event.updated_at);
-    vevent.push_str(&fold_line(&format!("CREATED:{}", created)));
-    vevent.push_str("\r\n");
-    vevent.push_str(&fold_line(&format!("LAST-MODIFIED:{}", updated)));
-    vevent.push_str("\r\n");
-    vevent.push_str(&fold_line(&format!("DTSTAMP:{}", updated)));
-    vevent.push_str("\r\n");
-
-    let start = format_datetime(&event.start);
-    vevent.push_str(&fold_line(&format!("DTSTART:{}", start)));
-    vevent.push_str("\r\n");
-
-    if let Some(end) = &event.end {
-        let end_str = format_datetime(end);
-        vevent.push_str(&fold_line(&format!("DTEND:{}", end_str)));
-        vevent.push_str("\r\n");
+    push_line(&mut vevent, &format!("CREATED:{}", created));
+    push_line(&mut vevent, &format!("LAST-MODIFIED:{}", updated));
+    push_line(
+        &mut vevent,
+        &format!("DTSTAMP:{}", format_datetime(&event.feed_time)),
+    );
+    push_line(&mut vevent, &format!("SEQUENCE:{}", event.sequence));
+
+    if event.all_day {
+        let end = event.end.unwrap_or(event.start + chrono::Duration::days(1));
+        push_line(
+            &mut vevent,
+            &format!("DTSTART;VALUE=DATE:{}", format_date(&event.start)),
+        );
+        push_line(
+            &mut vevent,
+            &format!("DTEND;VALUE=DATE:{}", format_date(&end)),
+        );
+        // Outlook ignores TRANSP for all-day events and shows them as free only with this
+        push_line(&mut vevent, "X-MICROSOFT-CDO-BUSYSTATUS:FREE");
     } else {
-        let end = event.start + chrono::Duration::hours(1);
-        let end_str = format_datetime(&end);
-        vevent.push_str(&fold_line(&format!("DTEND:{}", end_str)));
-        vevent.push_str("\r\n");
+        let end = event
+            .end
+            .unwrap_or(event.start + chrono::Duration::hours(1));
+        push_line(
+            &mut vevent,
+            &format!(
+                "DTSTART{}",
+                format_local_datetime(&event.start, metadata.timezone)
+            ),
+        );
+        push_line(
+            &mut vevent,
+            &format!("DTEND{}", format_local_datetime(&end, metadata.timezone)),
+        );
     }
 
-    vevent.push_str(&fold_line(&format!(
-        "SUMMARY:{}",
-        escape_text(&event.summary)
-    )));
-    vevent.push_str("\r\n");
+    // Alerts are informational, so they never make subscribers look busy
+    push_line(&mut vevent, "TRANSP:TRANSPARENT");
+    push_line(&mut vevent, "STATUS:CONFIRMED");
+
+    if let Some(recurrence) = recurrence {
+        push_line(&mut vevent, &format!("RRULE:{}", recurrence.rule()));
+        for exdate in &recurrence.exdates {
+            push_line(
+                &mut vevent,
+                &format!("EXDATE{}", format_local_datetime(exdate, metadata.timezone)),
+            );
+        }
+    }
+
+    let summary = match event.category.indicator() {
+        Some(indicator) if !metadata.plain => {
+            escape_text(&format!("{} {}", indicator, event.summary))
+        }
+        _ => escape_text(&event.summary),
+    };
+    push_line(&mut vevent, &format!("SUMMARY:{}", summary));
 
     if !event.description.is_empty() {
-        vevent.push_str(&fold_line(&format!(
-            "DESCRIPTION:{}",
-            escape_text(&event.description)
-        )));
-        vevent.push_str("\r\n");
+        push_line(
+            &mut vevent,
+            &format!("DESCRIPTION:{}", escape_text(&event.description)),
+        );
     }
 
-    vevent.push_str(&fold_line(&format!(
-        "CATEGORIES:{}",
-        escape_text(&event.alert_type)
-    )));
-    vevent.push_str("\r\n");
+    if let Some(html) = &event.html_description {
+        push_line(
+            &mut vevent,
+            &format!("X-ALT-DESC;FMTTYPE=text/html:{}", escape_text(html)),
+        );
+    }
+
+    if let Some(url) = &event.url {
+        push_line(&mut vevent, &format!("URL:{}", escape_uri(url)));
+    }
+
+    // The category, then each line, so apps can color and filter by either
+    let categories: Vec<String> = std::iter::once(event.category.name())
+        .chain(event.line_names())
+        .map(escape_text)
+        .collect();
+    push_line(&mut vevent, &format!("CATEGORIES:{}", categories.join(",")));
 
-    vevent.push_str(&fold_line(&format!(
-        "X-MTA-ALERT-ID:{}",
-        event.mta_alert_id
-    )));
-    vevent.push_str("\r\n");
+    if !event.mta_alert_id.is_empty() {
+        push_line(
+            &mut vevent,
+            &format!("X-MTA-ALERT-ID:{}", event.mta_alert_id),
+        );
+    }
+
+    if let Some(alarm) = metadata.alarm {
+        push_line(&mut vevent, "BEGIN:VALARM");
+        push_line(&mut vevent, "ACTION:DISPLAY");
+        push_line(&mut vevent, &format!("DESCRIPTION:{}", summary));
+        push_line(&mut vevent, &format!("TRIGGER:-{}", alarm));
+        push_line(&mut vevent, "END:VALARM");
+    }
 
-    vevent.push_str("END:VEVENT\r\n");
+    push_line(&mut vevent, "END:VEVENT");
 
     vevent
 }
 
+/// Appends a content line, folded to fit the 75-octet limit, and its CRLF. Every line goes
+/// through here so none can slip past folding.
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(&fold_line(line));
+    out.push_str("\r\n");
+}
+
 fn format_datetime(dt: &DateTime<Utc>) -> String {
     dt.format("%Y%m%dT%H%M%SZ").to_string()
 }
 
+/// Formats `dt` in `timezone` along with its `TZID` parameter, e.g.
+/// `;TZID=America/New_York:20251215T053000`, for the property name it follows. Times in UTC
+/// are written as UTC date-times instead, e.g. `:20251215T103000Z`, which need no `VTIMEZONE`.
+///
+/// A local time repeated when the clocks go back refers to its first occurrence, so times in
+/// the repeated hour after the change are written in UTC too.
+fn format_local_datetime(dt: &DateTime<Utc>, timezone: Tz) -> String {
+    if is_utc(timezone) {
+        return format!(":{}", format_datetime(dt));
+    }
+
+    let local = dt.with_timezone(&timezone).naive_local();
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Ambiguous(first, _) if first != *dt => format!(":{}", format_datetime(dt)),
+        _ => format!(
+            ";TZID={}:{}",
+            timezone.name(),
+            local.format("%Y%m%dT%H%M%S")
+        ),
+    }
+}
+
+/// Whether `timezone` is one of UTC's names, whose times are written with a `Z`.
+fn is_utc(timezone: Tz) -> bool {
+    matches!(
+        timezone.name(),
+        "UTC" | "Etc/UTC" | "UCT" | "Etc/UCT" | "Universal" | "Etc/Universal" | "Zulu" | "Etc/Zulu"
+    )
+}
+
+/// The `VTIMEZONE` defining `timezone` for the event times written in it, or `None` for UTC,
+/// which doesn't need one.
+///
+/// New York's is written with its rules. Other zones' rules can't be read out of the time zone
+/// database, so theirs lists the offset the first event starts in and each change of offset
+/// until the last event ends, which is all calendar apps need for these events.
+fn generate_vtimezone(
+    timezone: Tz,
+    events: &[(CalendarEvent, Option<Recurrence>)],
+) -> Option<String> {
+    if timezone == New_York {
+        return Some(NEW_YORK_VTIMEZONE.to_string());
+    }
+    if is_utc(timezone) {
+        return None;
+    }
+
+    let first = events
+        .iter()
+        .filter(|(event, _)| !event.all_day)
+        .map(|(event, _)| event.start)
+        .min()?;
+    let last = events
+        .iter()
+        .filter(|(event, _)| !event.all_day)
+        .map(|(event, recurrence)| {
+            let length = event
+                .end
+                .map_or(Duration::hours(1), |end| end - event.start);
+            recurrence.as_ref().map_or(event.start, |r| r.until) + length
+        })
+        .max()?;
+
+    let mut vtimezone = String::new();
+    push_line(&mut vtimezone, "BEGIN:VTIMEZONE");
+    push_line(&mut vtimezone, &format!("TZID:{}", timezone.name()));
+
+    // The offset in effect at the first event is taken to have applied since 1970
+    let offset = |time: DateTime<Utc>| time.with_timezone(&timezone).offset().to_owned();
+    let mut current = offset(first);
+    push_observance(
+        &mut vtimezone,
+        &current,
+        current.fix(),
+        "19700101T000000".to_string(),
+    );
+
+    // Offsets change at most a few times a year, so a day at a time finds every change, which
+    // is then narrowed down to the second
+    let mut before = first;
+    while before < last {
+        let after = (before + Duration::days(1)).min(last);
+        if offset(after) == current {
+            before = after;
+            continue;
+        }
+
+        let (mut unchanged, mut changed) = (before, after);
+        while changed - unchanged > Duration::seconds(1) {
+            let middle = unchanged + (changed - unchanged) / 2;
+            if offset(middle) == current {
+                unchanged = middle;
+            } else {
+                changed = middle;
+            }
+        }
+        let next = offset(changed);
+        let start = (changed.naive_utc() + current.fix())
+            .format("%Y%m%dT%H%M%S")
+            .to_string();
+        push_observance(&mut vtimezone, &next, current.fix(), start);
+
+        current = next;
+        before = changed;
+    }
+
+    push_line(&mut vtimezone, "END:VTIMEZONE");
+    Some(vtimezone)
+}
+
+/// Appends a `STANDARD` or `DAYLIGHT` observance switching from offset `from` to `offset` at
+/// local time `start`.
+fn push_observance(
+    out: &mut String,
+    offset: &<Tz as TimeZone>::Offset,
+    from: FixedOffset,
+    start: String,
+) {
+    let component = if offset.dst_offset().is_zero() {
+        "STANDARD"
+    } else {
+        "DAYLIGHT"
+    };
+    push_line(out, &format!("BEGIN:{}", component));
+    push_line(out, &format!("TZOFFSETFROM:{}", format_offset(from)));
+    push_line(out, &format!("TZOFFSETTO:{}", format_offset(offset.fix())));
+    if let Some(name) = offset.abbreviation() {
+        push_line(out, &format!("TZNAME:{}", escape_text(name)));
+    }
+    push_line(out, &format!("DTSTART:{}", start));
+    push_line(out, &format!("END:{}", component));
+}
+
+/// Formats a UTC offset as `-0500`, or `+053000` with seconds.
+fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.unsigned_abs();
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    match seconds % 60 {
+        0 => format!("{}{:02}{:02}", sign, hours, minutes),
+        seconds => format!("{}{:02}{:02}{:02}", sign, hours, minutes, seconds),
+    }
+}
+
+/// Formats the New York date of `dt`, for all-day events. All-day events cover New York days
+/// whatever zone the calendar's times are written in, since those are the days service runs.
+fn format_date(dt: &DateTime<Utc>) -> String {
+    dt.with_timezone(&New_York).format("%Y%m%d").to_string()
+}
+
+/// Escapes a URI value (RFC 5545 section 3.3.13), which isn't TEXT and so has no backslash
+/// escapes: spaces, quotes, non-ASCII characters and anything else a URI can't contain are
+/// percent-encoded, while existing `%XX` escapes and reserved characters such as `#` and `,`
+/// are kept.
+fn escape_uri(uri: &str) -> String {
+    let mut escaped = String::with_capacity(uri.len());
+    for byte in uri.trim().bytes() {
+        let allowed = byte.is_ascii_alphanumeric() || b"-._~:/?#[]@!$&'()*+,;=%".contains(&byte);
+        if allowed {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// Escapes a TEXT value (RFC 5545 section 3.3.11). Backslashes, semicolons and commas get a
+/// backslash, and each line break (`\n`, `\r\n` or a lone `\r`) becomes `\n`. Other control
+/// characters aren't allowed in TEXT and are dropped, except tabs.
+///
+/// The text is read in one pass, so a backslash the alert already contains is escaped exactly
+/// once and comes back unchanged when a calendar app unescapes it.
 fn escape_text(text: &str) -> String {
-    text.replace('\\', "\\\\")
-        .replace('\n', "\\n")
-        .replace('\r', "")
-        .replace(',', "\\,")
-        .replace(';', "\\;")
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                escaped.push_str("\\n");
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
+/// Folds a content line as RFC 5545 requires: lines are at most 75 octets, and longer ones
+/// continue on the next line after a CRLF and a single space. Lines only break between UTF-8
+/// characters, so an accented letter or emoji is never split, which can leave a line shorter.
 fn fold_line(line: &str) -> String {
     const MAX_LEN: usize = 75;
 
@@ -149,8 +586,9 @@ fn fold_line(line: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calendar::Category;
     use chrono::TimeZone;
-    use pretty_assertions::assert_eq;
+    use pretty_assertions::{assert_eq, assert_ne};
     use rstest::rstest;
     use std::path::PathBuf;
 
@@ -160,6 +598,27 @@ mod tests {
         assert_eq!(format_datetime(&dt), "20251215T103000Z");
     }
 
+    #[test]
+    fn test_format_local_datetime() {
+        let dt = Utc.with_ymd_and_hms(2025, 12, 15, 10, 30, 0).unwrap();
+        assert_eq!(
+            format_local_datetime(&dt, New_York),
+            ";TZID=America/New_York:20251215T053000"
+        );
+
+        // 1:30 AM happens twice on 2 Nov 2025
+        let first = Utc.with_ymd_and_hms(2025, 11, 2, 5, 30, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2025, 11, 2, 6, 30, 0).unwrap();
+        assert_eq!(
+            format_local_datetime(&first, New_York),
+            ";TZID=America/New_York:20251102T013000"
+        );
+        assert_eq!(
+            format_local_datetime(&second, New_York),
+            ":20251102T063000Z"
+        );
+    }
+
     #[test]
     fn test_escape_text() {
         assert_eq!(escape_text("Hello, World!"), "Hello\\, World!");
@@ -167,6 +626,126 @@ mod tests {
         assert_eq!(escape_text("Semi;colon"), "Semi\\;colon");
     }
 
+    #[rstest]
+    #[case::mta_alert(
+        "No trains between Jay St, York St; use the Q",
+        "No trains between Jay St\\, York St\\; use the Q"
+    )]
+    #[case::backslash("Track 1\\2", "Track 1\\\\2")]
+    #[case::already_escaped("a\\,b\\;c\\n", "a\\\\\\,b\\\\\\;c\\\\n")]
+    #[case::crlf("Line 1\r\nLine 2", "Line 1\\nLine 2")]
+    #[case::lone_cr("Line 1\rLine 2", "Line 1\\nLine 2")]
+    #[case::blank_lines("\n\nA\n\n", "\\n\\nA\\n\\n")]
+    #[case::control("Bell\u{7}\tTab\u{0}", "Bell\tTab")]
+    #[case::colon_and_quotes("Note: \"Express\" trains", "Note: \"Express\" trains")]
+    #[case::unicode("Délais 🚇, ñ; 中文", "Délais 🚇\\, ñ\\; 中文")]
+    #[case::empty("", "")]
+    fn test_escape_nasty_text(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(escape_text(text), expected);
+    }
+
+    /// A reader for the escaped TEXT values in a generated calendar, following RFC 5545 rather
+    /// than the writer above: unfolds the lines, then unescapes the value of each `name`
+    /// property.
+    fn parse_text_values(ics: &str, name: &str) -> Vec<String> {
+        parse_text_lists(ics, name)
+            .into_iter()
+            .map(|mut values| {
+                assert_eq!(values.len(), 1, "Unescaped ',' in {:?}", values);
+                values.remove(0)
+            })
+            .collect()
+    }
+
+    /// Like [`parse_text_values`], for properties such as `CATEGORIES` whose value is a list of
+    /// TEXT values separated by unescaped commas.
+    fn parse_text_lists(ics: &str, name: &str) -> Vec<Vec<String>> {
+        let prefix = format!("{}:", name);
+        unfold(ics)
+            .split("\r\n")
+            .filter_map(|line| line.strip_prefix(&prefix))
+            .map(|value| {
+                let mut values = vec![String::new()];
+                let mut chars = value.chars();
+                while let Some(c) = chars.next() {
+                    let text = values.last_mut().unwrap();
+                    match c {
+                        '\\' => match chars.next() {
+                            Some('n') | Some('N') => text.push('\n'),
+                            Some(escaped @ ('\\' | ';' | ',')) => text.push(escaped),
+                            other => panic!("Invalid escape \\{:?} in {:?}", other, value),
+                        },
+                        ',' => values.push(String::new()),
+                        ';' => panic!("Unescaped ';' in {:?}", value),
+                        c => text.push(c),
+                    }
+                }
+                values
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_text_values_round_trip() {
+        let nasty = [
+            "No trains between Jay St, York St; use the Q",
+            "Track 1\\2 and a\\,b\\;c\\n",
+            "Line 1\nLine 2\n\nSee mta.info",
+            "Délais 🚇, ñ; 中文 with a long tail to make sure the value is folded, more than once, \
+             across several lines; commas, semicolons and \\ backslashes included",
+        ];
+
+        let events: Vec<CalendarEvent> = nasty
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let mut event = CalendarEvent::for_test(&format!("mta-alert-{}-0", i), &["F"]);
+                event.summary = text.to_string();
+                event.description = text.to_string();
+                event.routes = vec![text.to_string()];
+                event
+            })
+            .collect();
+        let metadata = CalendarMetadata {
+            name: nasty[0].to_string(),
+            description: nasty[1].to_string(),
+            color: None,
+            alarm: None,
+            plain: false,
+            timezone: New_York,
+            refresh_interval: filter::DEFAULT_REFRESH_INTERVAL,
+            placeholder: String::new(),
+        };
+        let ics = generate_ics_with_metadata(&events, &metadata);
+
+        assert_eq!(parse_text_values(&ics, "SUMMARY"), nasty);
+        assert_eq!(
+            parse_text_lists(&ics, "CATEGORIES"),
+            nasty.map(|text| vec!["Alert".to_string(), text.to_string()])
+        );
+        let mut descriptions = vec![nasty[1]];
+        descriptions.extend(nasty);
+        assert_eq!(parse_text_values(&ics, "DESCRIPTION"), descriptions);
+        assert_eq!(parse_text_values(&ics, "NAME"), [nasty[0]]);
+        assert_eq!(parse_text_values(&ics, "X-WR-CALDESC"), [nasty[1]]);
+    }
+
+    #[test]
+    fn test_html_description() {
+        let html = "<p>[F] trains skip <b>Smith-9 Sts</b>; see <a href=\"https://mta.info\">mta.info</a>, \
+                    or the B61 bus</p>";
+        let mut event = CalendarEvent::for_test("mta-alert-1-0", &["F"]);
+        event.description = "[F] trains skip Smith-9 Sts".to_string();
+        event.html_description = Some(html.to_string());
+        let plain = CalendarEvent::for_test("mta-alert-2-0", &["F"]);
+
+        let ics = generate_ics(&[event, plain]);
+        assert_eq!(
+            parse_text_values(&ics, "X-ALT-DESC;FMTTYPE=text/html"),
+            [html]
+        );
+    }
+
     #[test]
     fn test_fold_line() {
         let short = "SUMMARY:Short";
@@ -179,8 +758,104 @@ mod tests {
             assert!(line.len() <= 75, "Line too long: {} chars", line.len());
         }
 
-        let unfolded = folded.replace("\r\n ", "");
-        assert_eq!(unfolded, long);
+        assert_eq!(unfold(&folded), long);
+    }
+
+    /// Joins folded lines back together, as a calendar app reading the file would
+    fn unfold(ics: &str) -> String {
+        ics.replace("\r\n ", "")
+    }
+
+    #[rstest]
+    #[case::accent("é")]
+    #[case::emoji("🚇")]
+    fn test_fold_line_multibyte(#[case] character: &str) {
+        // Move the character across the fold so it starts on each byte around the limit
+        for padding in 70..=76 {
+            let line = format!(
+                "SUMMARY:{}{}{}",
+                "a".repeat(padding - 8),
+                character,
+                "b".repeat(90)
+            );
+            let folded = fold_line(&line);
+
+            for physical in folded.split("\r\n") {
+                assert!(
+                    physical.len() <= 75,
+                    "Line too long: {} octets",
+                    physical.len()
+                );
+            }
+            assert!(
+                folded
+                    .split("\r\n")
+                    .skip(1)
+                    .all(|physical| physical.starts_with(' '))
+            );
+            assert!(folded.contains(character), "{} was split", character);
+            assert_eq!(unfold(&folded), line);
+        }
+
+        // A line made only of multi-byte characters still folds between them
+        let line = format!("DESCRIPTION:{}", character.repeat(60));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|physical| physical.len() <= 75));
+        assert_eq!(unfold(&folded), line);
+    }
+
+    #[rstest]
+    #[case::plain("https://new.mta.info/alerts", "https://new.mta.info/alerts")]
+    #[case::reserved(
+        "https://bustime.mta.info/#%7B%22label%22%3A%22Bx4%22%7D?a=1,2;b=3",
+        "https://bustime.mta.info/#%7B%22label%22%3A%22Bx4%22%7D?a=1,2;b=3"
+    )]
+    #[case::unsafe_characters(
+        " https://mta.info/alerts/F train\"<x>\\{|}^`\n ",
+        "https://mta.info/alerts/F%20train%22%3Cx%3E%5C%7B%7C%7D%5E%60"
+    )]
+    #[case::non_ascii("https://mta.info/Délais", "https://mta.info/D%C3%A9lais")]
+    fn test_escape_uri(#[case] uri: &str, #[case] expected: &str) {
+        assert_eq!(escape_uri(uri), expected);
+    }
+
+    #[test]
+    fn test_every_line_is_folded() {
+        let mut event = CalendarEvent::for_test("mta-alert-1-0", &["F"]);
+        event.summary = format!("Délais sur la ligne F 🚇 {}", "é".repeat(40));
+        event.description = "🚇 Trains run with delays. ".repeat(10);
+        event.routes = (1..=30).map(|route| route.to_string()).collect();
+        event.mta_alert_id = "x".repeat(100);
+        event.url = Some(format!("https://new.mta.info/alerts/{}", "f".repeat(80)));
+        let metadata = CalendarMetadata {
+            name: "É".repeat(50),
+            description: "🚇".repeat(30),
+            color: None,
+            alarm: None,
+            plain: false,
+            timezone: New_York,
+            refresh_interval: filter::DEFAULT_REFRESH_INTERVAL,
+            placeholder: String::new(),
+        };
+        let ics = generate_ics_with_metadata(&[event.clone()], &metadata);
+
+        let golden = std::fs::read_to_string("tests/golden-2025-12-15.ics").unwrap();
+        for ics in [&ics, &golden] {
+            assert!(ics.ends_with("\r\n"));
+            for line in ics.split_terminator("\r\n") {
+                assert!(line.len() <= 75, "Line too long: {:?}", line);
+            }
+        }
+
+        let unfolded = unfold(&ics);
+        assert!(unfolded.contains(&format!("\r\nSUMMARY:{}\r\n", escape_text(&event.summary))));
+        assert!(unfolded.contains(&format!(
+            "\r\nCATEGORIES:Alert,{}\r\n",
+            event.routes.join(",")
+        )));
+        assert!(unfolded.contains(&format!("\r\nX-MTA-ALERT-ID:{}\r\n", event.mta_alert_id)));
+        assert!(unfolded.contains(&format!("\r\nURL:{}\r\n", event.url.unwrap())));
+        assert!(unfolded.contains(&format!("\r\nNAME:{}\r\n", metadata.name)));
     }
 
     #[test]
@@ -191,13 +866,25 @@ mod tests {
             uid: "test-event-1".to_string(),
             summary: "Test Event".to_string(),
             description: "Test Description".to_string(),
+            html_description: None,
             start: Utc.with_ymd_and_hms(2025, 12, 15, 10, 0, 0).unwrap(),
             end: Some(Utc.with_ymd_and_hms(2025, 12, 15, 11, 0, 0).unwrap()),
             created_at: Utc.with_ymd_and_hms(2025, 12, 14, 9, 0, 0).unwrap(),
             updated_at: Utc.with_ymd_and_hms(2025, 12, 15, 8, 0, 0).unwrap(),
+            feed_time: Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap(),
             mta_alert_id: "123".to_string(),
             routes: vec!["L".to_string()],
+            stop_ids: vec![],
+            affected_stations: vec![],
             alert_type: "Delays".to_string(),
+            severity: crate::severity::Severity::Severe,
+            kind: crate::calendar::AlertKind::Realtime,
+            category: crate::calendar::Category::Delays,
+            translations: Default::default(),
+            all_day: false,
+            url: None,
+            sequence: 0,
+            series_sequence: 0,
         }];
 
         let ics = generate_ics(&events);
@@ -205,8 +892,360 @@ mod tests {
         assert!(ics.contains("BEGIN:VCALENDAR"));
         assert!(ics.contains("END:VCALENDAR"));
         assert!(ics.contains("VERSION:2.0"));
-        assert!(ics.contains("PRODID:-//NYC TRAIN CAL//MTA Subway Alerts//EN"));
-        assert!(ics.contains("SUMMARY:Test Event"));
+        assert!(ics.starts_with(&format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//NYC TRAIN CAL//nyc-train-time {}//EN\r\n\
+             METHOD:PUBLISH\r\nCALSCALE:GREGORIAN\r\n",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(ics.contains("SUMMARY:⚠️ Test Event"));
+    }
+
+    #[test]
+    fn test_summary_indicators() {
+        let event = |category| CalendarEvent {
+            summary: "F: Alert".to_string(),
+            category,
+            ..CalendarEvent::for_test("mta-alert-1-0", &["F"])
+        };
+        let summaries = |events: &[CalendarEvent], plain| {
+            let metadata = Network::Subway
+                .calendar_metadata(Some("F"), Language::English)
+                .with_plain(plain);
+            parse_text_values(&generate_ics_with_metadata(events, &metadata), "SUMMARY")
+        };
+
+        let events = [
+            event(Category::NoService),
+            event(Category::PlannedWork),
+            event(Category::Other),
+        ];
+        assert_eq!(
+            summaries(&events, false),
+            vec!["⛔ F: Alert", "🔧 F: Alert", "F: Alert"]
+        );
+        assert_eq!(summaries(&events, true), vec!["F: Alert"; 3]);
+
+        // Reminders read the same as the event
+        let metadata = Network::Subway
+            .calendar_metadata(Some("F"), Language::English)
+            .with_alarm(Some(Alarm {
+                before: chrono::Duration::hours(1),
+            }));
+        let ics = generate_ics_with_metadata(&events[..1], &metadata);
+        assert!(ics.contains("\r\nACTION:DISPLAY\r\nDESCRIPTION:⛔ F: Alert\r\n"));
+
+        for category in Category::ALL {
+            assert_eq!(category.indicator().is_none(), category == Category::Other);
+        }
+    }
+
+    #[test]
+    fn test_calendar_metadata_properties() {
+        let metadata = CalendarMetadata::new(
+            Network::Subway,
+            "MTA F, M Train Alerts".to_string(),
+            "Real-time alerts and planned service changes for MTA F, M train",
+            "No service alerts for the F, M train".to_string(),
+            Language::English,
+        );
+        let ics = generate_ics_with_metadata(&[], &metadata);
+
+        assert!(ics.contains("\r\nNAME:MTA F\\, M Train Alerts\r\n"));
+        assert!(ics.contains("\r\nX-WR-CALNAME:MTA F\\, M Train Alerts\r\n"));
+        let unfolded = unfold(&ics);
+        let description = "Real-time alerts and planned service changes for MTA F\\, M train. \
+            Data from the MTA (mta.info).";
+        assert!(unfolded.contains(&format!("\r\nDESCRIPTION:{}\r\n", description)));
+        assert!(unfolded.contains(&format!("\r\nX-WR-CALDESC:{}\r\n", description)));
+        assert!(ics.lines().all(|line| line.len() <= 75));
+    }
+
+    #[test]
+    fn test_line_colors() {
+        let header = |train_names: &[&str]| {
+            let metadata = Network::Subway
+                .calendar_metadata(Some(&train_names.join(", ")), Language::English)
+                .with_line_color(train_names);
+            generate_ics_with_metadata(&[], &metadata)
+        };
+
+        let f = header(&["F"]);
+        assert!(f.contains("\r\nX-APPLE-CALENDAR-COLOR:#FF6319\r\nCOLOR:orangered\r\n"));
+        let l = header(&["L"]);
+        assert!(l.contains("\r\nX-APPLE-CALENDAR-COLOR:#A7A9AC\r\nCOLOR:darkgray\r\n"));
+        // Several lines take the first one's color, and express variants their local line's
+        assert!(header(&["l", "F"]).contains("X-APPLE-CALENDAR-COLOR:#A7A9AC\r\n"));
+        assert!(header(&["7X"]).contains("X-APPLE-CALENDAR-COLOR:#B933AD\r\n"));
+        assert!(!header(&["LIRR"]).contains("COLOR"));
+
+        for line in crate::lines::LINES {
+            assert!(
+                COLOR_NAMES.iter().any(|(hex, _)| *hex == line.color),
+                "{}",
+                line.id
+            );
+        }
+    }
+
+    #[test]
+    fn test_alarms() {
+        let events = [
+            CalendarEvent::for_test("mta-alert-1-0", &["F"]),
+            CalendarEvent::for_test("mta-alert-2-0", &["G"]),
+        ];
+        let metadata = Network::Subway.calendar_metadata(Some("F"), Language::English);
+        assert!(!generate_ics_with_metadata(&events, &metadata).contains("VALARM"));
+
+        let alarm = Alarm {
+            before: chrono::Duration::minutes(90),
+        };
+        let ics = generate_ics_with_metadata(&events, &metadata.with_alarm(Some(alarm)));
+        let valarm = |summary: &str| {
+            format!(
+                "\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:{}\r\n\
+                 TRIGGER:-PT1H30M\r\nEND:VALARM\r\nEND:VEVENT\r\n",
+                summary
+            )
+        };
+        assert!(ics.contains(&valarm(&escape_text(&events[0].summary))));
+        assert!(ics.contains(&valarm(&escape_text(&events[1].summary))));
+        assert_eq!(ics.matches("BEGIN:VALARM").count(), 2);
+    }
+
+    fn event(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> CalendarEvent {
+        CalendarEvent {
+            uid: "test-event-1".to_string(),
+            summary: "Planned - Part Suspended".to_string(),
+            description: String::new(),
+            html_description: None,
+            start,
+            end,
+            created_at: Utc.with_ymd_and_hms(2025, 10, 20, 9, 0, 0).unwrap(),
+            updated_at: Utc.with_ymd_and_hms(2025, 10, 20, 9, 0, 0).unwrap(),
+            feed_time: Utc.with_ymd_and_hms(2025, 10, 20, 9, 0, 0).unwrap(),
+            mta_alert_id: "lmm:planned_work:1".to_string(),
+            routes: vec!["L".to_string()],
+            stop_ids: vec![],
+            affected_stations: vec![],
+            alert_type: "Planned - Part Suspended".to_string(),
+            severity: crate::severity::Severity::Severe,
+            kind: crate::calendar::AlertKind::Planned,
+            category: crate::calendar::Category::PlannedWork,
+            translations: Default::default(),
+            all_day: false,
+            url: None,
+            sequence: 0,
+            series_sequence: 0,
+        }
+    }
+
+    #[test]
+    fn test_times_across_fall_back() {
+        // Friday 9:45 PM EDT to Monday 5:00 AM EST, over the weekend the clocks go back
+        let start = Utc.with_ymd_and_hms(2025, 11, 1, 1, 45, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 11, 3, 10, 0, 0).unwrap();
+        let ics = generate_ics(&[event(start, Some(end))]);
+
+        assert_eq!(ics.matches("BEGIN:VTIMEZONE\r\n").count(), 1);
+        assert!(ics.contains("TZID:America/New_York\r\n"));
+        assert!(ics.contains("DTSTART;TZID=America/New_York:20251031T214500\r\n"));
+        assert!(ics.contains("DTEND;TZID=America/New_York:20251103T050000\r\n"));
+
+        // Saturday 11 PM EDT, with no end, lasts an hour
+        let start = Utc.with_ymd_and_hms(2025, 11, 2, 3, 0, 0).unwrap();
+        let ics = generate_ics(&[event(start, None)]);
+        assert!(ics.contains("DTSTART;TZID=America/New_York:20251101T230000\r\n"));
+        assert!(ics.contains("DTEND;TZID=America/New_York:20251102T000000\r\n"));
+
+        // The timezone comes before any events
+        let timezone = ics.find("BEGIN:VTIMEZONE").unwrap();
+        assert!(timezone < ics.find("BEGIN:VEVENT").unwrap());
+    }
+
+    #[test]
+    fn test_times_across_clock_changes() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::proto::gtfs_realtime::FeedMessage;
+
+        let text = std::fs::read_to_string("tests/fixtures/dst_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = proto_feed_to_events(&feed);
+        let ics = generate_ics(&events);
+        validate(&ics);
+        let vevent = |uid: &str| {
+            let start = ics.find(&format!("UID:{}", uid)).unwrap();
+            let end = start + ics[start..].find("END:VEVENT").unwrap();
+            ics[start..end].to_string()
+        };
+
+        // Saturday 11:45 PM EST to Sunday 5 AM EDT is 4h15m over the spring-forward weekend...
+        let spring = &events[0];
+        assert_eq!(
+            spring.end.unwrap() - spring.start,
+            chrono::Duration::minutes(255)
+        );
+        let spring = vevent("mta-alert-lmm:planned_work:95001");
+        assert!(spring.contains("DTSTART;TZID=America/New_York:20250308T234500\r\n"));
+        assert!(spring.contains("DTEND;TZID=America/New_York:20250309T050000\r\n"));
+
+        // ...and the same hours are 6h15m over the fall-back weekend
+        let fall = &events[1];
+        assert_eq!(
+            fall.end.unwrap() - fall.start,
+            chrono::Duration::minutes(375)
+        );
+        let fall = vevent("mta-alert-lmm:planned_work:95002");
+        assert!(fall.contains("DTSTART;TZID=America/New_York:20251101T234500\r\n"));
+        assert!(fall.contains("DTEND;TZID=America/New_York:20251102T050000\r\n"));
+
+        // Nightly work keeps its New York hours on both sides of the change. The shorter night
+        // can't be an occurrence, since they all last as long as the first, so the series skips
+        // Saturday and the night is written on its own
+        let series = vevent("mta-alert-lmm:planned_work:95003-T234500/PT5H15M");
+        assert!(series.contains("DTSTART;TZID=America/New_York:20250306T234500\r\n"));
+        assert!(series.contains("DTEND;TZID=America/New_York:20250307T050000\r\n"));
+        assert!(
+            series.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TU,TH,FR,SU;UNTIL=20250312T034500Z\r\n")
+        );
+        assert!(!series.contains("EXDATE"));
+        let night = vevent("mta-alert-lmm:planned_work:95003-2");
+        assert!(night.contains("DTSTART;TZID=America/New_York:20250308T234500\r\n"));
+        assert!(night.contains("DTEND;TZID=America/New_York:20250309T050000\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 4);
+    }
+
+    #[test]
+    fn test_other_time_zones() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::proto::gtfs_realtime::FeedMessage;
+
+        let text = std::fs::read_to_string("tests/fixtures/dst_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = proto_feed_to_events(&feed);
+        let metadata = Network::Subway.calendar_metadata(Some("G"), Language::English);
+        let vevent = |ics: &str, uid: &str| {
+            let start = ics.find(&format!("UID:{}", uid)).unwrap();
+            let end = start + ics[start..].find("END:VEVENT").unwrap();
+            ics[start..end].to_string()
+        };
+
+        // UTC times are written with a Z and need no VTIMEZONE
+        let ics =
+            generate_ics_with_metadata(&events, &metadata.clone().with_timezone(Some(Tz::UTC)));
+        assert_eq!(validate(&ics), Vec::<String>::new());
+        assert!(ics.contains("X-WR-TIMEZONE:UTC\r\n"));
+        assert!(!ics.contains("VTIMEZONE"));
+        assert!(!ics.contains("TZID"));
+        let spring = vevent(&ics, "mta-alert-lmm:planned_work:95001");
+        assert!(spring.contains("DTSTART:20250309T044500Z\r\n"));
+        assert!(spring.contains("DTEND:20250309T090000Z\r\n"));
+
+        // Nightly work moves an hour in UTC when New York's clocks change, so only the nights
+        // after the change repeat at the same UTC time
+        let series = vevent(&ics, "mta-alert-lmm:planned_work:95003-T034500/PT5H15M");
+        assert!(series.contains("DTSTART:20250310T034500Z\r\n"));
+        assert!(series.contains("RRULE:FREQ=WEEKLY;BYDAY=MO,TU,WE;UNTIL=20250312T034500Z\r\n"));
+        assert_eq!(ics.matches("RRULE").count(), 1);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 6);
+
+        // Other zones get a VTIMEZONE of their own, covering their clock changes between the
+        // first event and the last
+        let ics = generate_ics_with_metadata(
+            &events,
+            &metadata.clone().with_timezone(Some(Tz::Europe__London)),
+        );
+        assert_eq!(validate(&ics), Vec::<String>::new());
+        assert!(ics.contains("X-WR-TIMEZONE:Europe/London\r\n"));
+        assert!(!ics.contains("America/New_York"));
+        assert!(ics.contains(
+            "BEGIN:VTIMEZONE\r\n\
+             TZID:Europe/London\r\n\
+             BEGIN:STANDARD\r\n\
+             TZOFFSETFROM:+0000\r\n\
+             TZOFFSETTO:+0000\r\n\
+             TZNAME:GMT\r\n\
+             DTSTART:19700101T000000\r\n\
+             END:STANDARD\r\n\
+             BEGIN:DAYLIGHT\r\n\
+             TZOFFSETFROM:+0000\r\n\
+             TZOFFSETTO:+0100\r\n\
+             TZNAME:BST\r\n\
+             DTSTART:20250330T010000\r\n\
+             END:DAYLIGHT\r\n\
+             BEGIN:STANDARD\r\n\
+             TZOFFSETFROM:+0100\r\n\
+             TZOFFSETTO:+0000\r\n\
+             TZNAME:GMT\r\n\
+             DTSTART:20251026T020000\r\n\
+             END:STANDARD\r\n\
+             END:VTIMEZONE\r\n"
+        ));
+        let fall = vevent(&ics, "mta-alert-lmm:planned_work:95002");
+        assert!(fall.contains("DTSTART;TZID=Europe/London:20251102T034500\r\n"));
+        assert!(fall.contains("DTEND;TZID=Europe/London:20251102T100000\r\n"));
+
+        // A zone without clock changes has a single observance
+        let ics =
+            generate_ics_with_metadata(&events, &metadata.with_timezone(Some(Tz::Asia__Kolkata)));
+        assert_eq!(validate(&ics), Vec::<String>::new());
+        assert_eq!(ics.matches("TZOFFSETTO:+0530\r\n").count(), 1);
+        assert!(ics.contains("DTSTART;TZID=Asia/Kolkata:20250309T101500\r\n"));
+    }
+
+    #[test]
+    fn test_refresh_interval() {
+        let events = [CalendarEvent::for_test("mta-alert-1-0", &["F"])];
+        let metadata = Network::Subway.calendar_metadata(Some("F"), Language::English);
+        let header = |ics: &str| ics[..ics.find("BEGIN:VEVENT").unwrap()].to_string();
+
+        let ics = generate_ics_with_metadata(&events, &metadata);
+        assert_eq!(validate(&ics), Vec::<String>::new());
+        assert_eq!(ics.matches("REFRESH-INTERVAL").count(), 1);
+        assert_eq!(ics.matches("X-PUBLISHED-TTL").count(), 1);
+        assert!(header(&ics).contains("\r\nREFRESH-INTERVAL;VALUE=DURATION:PT30M\r\n"));
+        assert!(header(&ics).contains("\r\nX-PUBLISHED-TTL:PT30M\r\n"));
+
+        // The server can ask for another interval
+        let filter = EventFilter {
+            refresh_interval: Some(chrono::Duration::minutes(90)),
+            ..EventFilter::default()
+        };
+        let ics = generate_ics_with_metadata(&events, &metadata.with_filter(&filter));
+        assert_eq!(validate(&ics), Vec::<String>::new());
+        assert_eq!(ics.matches("REFRESH-INTERVAL").count(), 1);
+        assert!(header(&ics).contains("\r\nREFRESH-INTERVAL;VALUE=DURATION:PT1H30M\r\n"));
+        assert!(header(&ics).contains("\r\nX-PUBLISHED-TTL:PT1H30M\r\n"));
+
+        // Whatever the server is configured with is written as a valid duration
+        for interval in [
+            filter::MIN_REFRESH_INTERVAL,
+            chrono::Duration::seconds(3601),
+            chrono::Duration::days(2),
+            filter::MAX_REFRESH_INTERVAL,
+        ] {
+            assert!(
+                is_duration(&filter::format_duration(interval)),
+                "{:?}",
+                interval
+            );
+        }
+        for value in ["PT30M", "P1D", "P1DT12H", "PT1H1S", "P2W", "PT0S"] {
+            assert!(is_duration(value), "{}", value);
+        }
+        for value in [
+            "", "30M", "P", "PT", "PT30", "PTM", "PT1M1H", "P1W1D", "P1M", "-PT30M",
+        ] {
+            assert!(!is_duration(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_format_offset() {
+        let offset = |seconds| FixedOffset::east_opt(seconds).unwrap();
+        assert_eq!(format_offset(offset(0)), "+0000");
+        assert_eq!(format_offset(offset(-5 * 3600)), "-0500");
+        assert_eq!(format_offset(offset(5 * 3600 + 45 * 60)), "+0545");
+        assert_eq!(format_offset(offset(-(3600 + 30))), "-010030");
     }
 
     #[rstest]
@@ -243,5 +1282,362 @@ mod tests {
             "Generated ICS does not match golden file for {:?}",
             path
         );
+        assert_events_are_free(&golden_ics);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::proto::gtfs_realtime::FeedMessage;
+        use protobuf::Message;
+
+        let generate = || {
+            let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+            let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+            generate_ics(&proto_feed_to_events(&feed))
+        };
+        let ics = generate();
+        // Nothing depends on when the calendar is generated, so clients and HTTP caches only
+        // see changes when the feed changes
+        assert_eq!(generate(), ics);
+
+        // Every event is stamped with the feed's header timestamp
+        let vevents: Vec<&str> = ics.split("BEGIN:VEVENT\r\n").skip(1).collect();
+        assert!(!vevents.is_empty());
+        for vevent in &vevents {
+            assert!(
+                vevent.contains("\r\nDTSTAMP:20251216T040048Z\r\n"),
+                "{}",
+                vevent
+            );
+        }
+        // And last modified when the MTA last edited its alert
+        let last_modified: std::collections::BTreeSet<&str> = ics
+            .lines()
+            .filter_map(|line| line.strip_prefix("LAST-MODIFIED:"))
+            .collect();
+        assert!(last_modified.len() > 1);
+        assert!(
+            last_modified
+                .iter()
+                .all(|time| time.len() == 16 && time.ends_with('Z'))
+        );
+
+        // Alerts without the MTA's timestamps were last modified when the feed was produced
+        let text = std::fs::read_to_string("tests/fixtures/duplicate_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let ics = generate_ics(&proto_feed_to_events(&feed));
+        assert!(ics.contains("\r\nLAST-MODIFIED:20251215T120000Z\r\nDTSTAMP:20251215T120000Z\r\n"));
+    }
+
+    #[test]
+    fn test_event_order_is_deterministic() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::proto::gtfs_realtime::FeedMessage;
+        use protobuf::Message;
+
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let events = proto_feed_to_events(&FeedMessage::parse_from_bytes(&pb_bytes).unwrap());
+        let ics = generate_ics(&events);
+
+        // Shuffle with a fixed linear congruential generator, a few times over
+        let mut state: u64 = 2025;
+        for _ in 0..5 {
+            let mut shuffled = events.clone();
+            for i in (1..shuffled.len()).rev() {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                shuffled.swap(i, (state >> 33) as usize % (i + 1));
+            }
+            assert_ne!(shuffled, events);
+            assert_eq!(generate_ics(&shuffled), ics);
+        }
+
+        // Events are in order of start and then UID, a repeating event where its first period is
+        let uids: Vec<String> = unfold(&ics)
+            .lines()
+            .filter_map(|line| line.strip_prefix("UID:"))
+            .map(|uid| uid.split('@').next().unwrap().to_string())
+            .filter(|uid| events.iter().any(|event| event.uid == *uid))
+            .collect();
+        let mut sorted = events.clone();
+        sorted.sort_by_key(|event| (event.start, event.uid.clone()));
+        let expected: Vec<String> = sorted
+            .into_iter()
+            .map(|event| event.uid)
+            .filter(|uid| uids.contains(uid))
+            .collect();
+        assert_eq!(uids, expected);
+    }
+
+    /// Checks `ics` against the parts of RFC 5545 calendar apps and validators are strict about,
+    /// returning a message for each problem: CRLF line endings and folding, well-formed content
+    /// lines, the calendar properties in order, balanced components, and the properties each
+    /// component needs exactly once.
+    fn validate(ics: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !ics.ends_with("\r\n") {
+            errors.push("Doesn't end with CRLF".to_string());
+        }
+        let bytes = ics.as_bytes();
+        for (i, byte) in bytes.iter().enumerate() {
+            let bare_lf = *byte == b'\n' && (i == 0 || bytes[i - 1] != b'\r');
+            let bare_cr = *byte == b'\r' && bytes.get(i + 1) != Some(&b'\n');
+            if bare_lf || bare_cr {
+                errors.push(format!("Bare line ending at byte {}", i));
+            }
+        }
+        for line in ics.split_terminator("\r\n") {
+            if line.len() > 75 {
+                errors.push(format!("Line longer than 75 octets: {:?}", line));
+            }
+        }
+
+        let lines: Vec<String> = unfold(ics)
+            .split_terminator("\r\n")
+            .map(str::to_string)
+            .collect();
+        let header = [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            &format!("PRODID:{}", PRODID),
+            "METHOD:PUBLISH",
+            "CALSCALE:GREGORIAN",
+        ];
+        if lines.iter().take(header.len()).ne(header.iter()) {
+            errors.push(format!("Calendar doesn't start with {:?}", header));
+        }
+
+        // Each open component, with the names of the properties it has so far
+        let mut components: Vec<(String, Vec<String>)> = Vec::new();
+        for line in &lines {
+            let Some((name_and_params, value)) = line.split_once(':') else {
+                errors.push(format!("Content line without a value: {:?}", line));
+                continue;
+            };
+            let name = name_and_params.split(';').next().unwrap_or_default();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                errors.push(format!("Invalid property name: {:?}", line));
+                continue;
+            }
+
+            match name {
+                "BEGIN" => components.push((value.to_string(), Vec::new())),
+                "END" => match components.pop() {
+                    Some((component, properties)) if component == value => {
+                        errors.extend(validate_component(&component, &properties));
+                    }
+                    _ => errors.push(format!("Unbalanced END:{}", value)),
+                },
+                _ => match components.last_mut() {
+                    Some((_, properties)) => properties.push(name.to_string()),
+                    None => errors.push(format!("Property outside VCALENDAR: {:?}", line)),
+                },
+            }
+
+            let utc = ["DTSTAMP", "CREATED", "LAST-MODIFIED"];
+            let is_utc = value.len() == 16
+                && value.ends_with('Z')
+                && value.chars().enumerate().all(|(i, c)| match i {
+                    8 => c == 'T',
+                    15 => true,
+                    _ => c.is_ascii_digit(),
+                });
+            if utc.contains(&name) && !is_utc {
+                errors.push(format!("Not a UTC date-time: {:?}", line));
+            }
+
+            let durations = ["REFRESH-INTERVAL", "X-PUBLISHED-TTL", "TRIGGER"];
+            let duration = value.strip_prefix('-').unwrap_or(value);
+            if durations.contains(&name) && !is_duration(duration) {
+                errors.push(format!("Not a duration: {:?}", line));
+            }
+        }
+        if !components.is_empty() {
+            errors.push(format!("Unclosed components: {:?}", components));
+        }
+
+        errors
+    }
+
+    /// Whether `value` is an RFC 5545 duration (section 3.3.6) without a sign, such as `PT30M`,
+    /// `P1DT12H` or `P2W`.
+    fn is_duration(value: &str) -> bool {
+        let Some(rest) = value.strip_prefix('P') else {
+            return false;
+        };
+        let (date, time) = match rest.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (rest, None),
+        };
+        // Each part is digits and a unit, with units in order and at most once
+        let parts = |part: &str, units: &str| -> Option<usize> {
+            let mut remaining = units;
+            let mut count = 0;
+            let mut digits = 0;
+            for c in part.chars() {
+                if c.is_ascii_digit() {
+                    digits += 1;
+                    continue;
+                }
+                let position = remaining.find(c)?;
+                if digits == 0 {
+                    return None;
+                }
+                remaining = &remaining[position + 1..];
+                digits = 0;
+                count += 1;
+            }
+            (digits == 0).then_some(count)
+        };
+
+        // Weeks can't be combined with anything else
+        if let Some(weeks) = date.strip_suffix('W') {
+            return time.is_none()
+                && !weeks.is_empty()
+                && weeks.chars().all(|c| c.is_ascii_digit());
+        }
+        match (parts(date, "D"), time.map(|time| parts(time, "HMS"))) {
+            (Some(days), None) => days > 0,
+            (Some(_), Some(Some(times))) => times > 0,
+            _ => false,
+        }
+    }
+
+    /// The problems with a component's `properties`: ones it needs exactly once that are
+    /// missing or repeated, and ones that may appear at most once but are repeated.
+    fn validate_component(component: &str, properties: &[String]) -> Vec<String> {
+        let (required, optional): (&[&str], &[&str]) = match component {
+            "VCALENDAR" => (
+                &["VERSION", "PRODID"],
+                &[
+                    "METHOD",
+                    "CALSCALE",
+                    "NAME",
+                    "COLOR",
+                    "REFRESH-INTERVAL",
+                    "X-PUBLISHED-TTL",
+                ],
+            ),
+            "VEVENT" => (
+                &["UID", "DTSTAMP", "DTSTART"],
+                &[
+                    "DTEND",
+                    "SUMMARY",
+                    "DESCRIPTION",
+                    "CREATED",
+                    "LAST-MODIFIED",
+                    "SEQUENCE",
+                    "TRANSP",
+                    "STATUS",
+                    "URL",
+                    "RRULE",
+                ],
+            ),
+            "VALARM" => (&["ACTION", "TRIGGER", "DESCRIPTION"], &[]),
+            "VTIMEZONE" => (&["TZID"], &[]),
+            "STANDARD" | "DAYLIGHT" => (&["DTSTART", "TZOFFSETFROM", "TZOFFSETTO"], &[]),
+            _ => return vec![format!("Unknown component {}", component)],
+        };
+
+        let count = |name: &str| properties.iter().filter(|p| *p == name).count();
+        let mut errors = Vec::new();
+        for name in required {
+            if count(name) != 1 {
+                errors.push(format!(
+                    "{} has {} {} properties",
+                    component,
+                    count(name),
+                    name
+                ));
+            }
+        }
+        for name in optional {
+            if count(name) > 1 {
+                errors.push(format!(
+                    "{} has {} {} properties",
+                    component,
+                    count(name),
+                    name
+                ));
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn test_every_calendar_is_valid() {
+        use crate::calendar::proto_feed_to_events;
+        use crate::filter::{DEFAULT_DAYS, Window};
+        use crate::proto::gtfs_realtime::FeedMessage;
+        use crate::stops::Stations;
+        use protobuf::Message;
+
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+        let events = proto_feed_to_events(&feed);
+        let filter = EventFilter {
+            window: Some(Window {
+                start: crate::calendar::feed_time(&feed),
+                days: DEFAULT_DAYS,
+            }),
+            // Lines without alerts still get an event to check
+            placeholder: true,
+            alarm: Some(Alarm {
+                before: chrono::Duration::hours(2),
+            }),
+            ..EventFilter::default()
+        };
+
+        assert_eq!(validate(&generate_ics(&events)), Vec::<String>::new());
+        for line in crate::lines::LINES {
+            let ics = crate::render_trains_ics(
+                &events,
+                line.id,
+                &[line.id],
+                &filter,
+                &Stations::default(),
+            );
+            assert!(ics.contains("BEGIN:VEVENT"));
+            assert_eq!(validate(&ics), Vec::<String>::new(), "{}", line.id);
+        }
+
+        // And the validator catches what it's meant to
+        let broken = generate_ics(&[CalendarEvent::for_test("mta-alert-1-0", &["F"])])
+            .replacen("DTSTAMP", "X-DTSTAMP", 1)
+            .replacen("END:VEVENT\r\n", "END:VEVENT\n", 1);
+        assert_eq!(validate(&broken).len(), 3, "{:?}", validate(&broken));
+    }
+
+    /// Asserts that every event in `ics` is marked as free time and confirmed, and that all-day
+    /// events are free in Outlook too.
+    fn assert_events_are_free(ics: &str) {
+        let vevents: Vec<&str> = ics.split("BEGIN:VEVENT\r\n").skip(1).collect();
+        assert!(!vevents.is_empty());
+        for vevent in vevents {
+            let vevent = &vevent[..vevent.find("END:VEVENT").unwrap()];
+            assert!(vevent.contains("\r\nTRANSP:TRANSPARENT\r\n"), "{}", vevent);
+            assert!(vevent.contains("\r\nSTATUS:CONFIRMED\r\n"), "{}", vevent);
+            assert_eq!(
+                vevent.contains("\r\nX-MICROSOFT-CDO-BUSYSTATUS:FREE\r\n"),
+                vevent.contains("\r\nDTSTART;VALUE=DATE:"),
+                "{}",
+                vevent
+            );
+        }
+    }
+
+    #[test]
+    fn test_events_are_free() {
+        let timed = CalendarEvent::for_test("mta-alert-1-0", &["F"]);
+        let all_day = CalendarEvent {
+            all_day: true,
+            ..CalendarEvent::for_test("mta-alert-2-0", &["F"])
+        };
+        let placeholder = CalendarEvent::placeholder("No current alerts".to_string(), timed.start);
+
+        assert_events_are_free(&generate_ics(&[timed, all_day, placeholder]));
     }
 }
@@ -1,41 +1,929 @@
+// `openapi_spec` describes every endpoint in a single `json!`
+#![recursion_limit = "256"]
+
+pub mod accessibility;
+pub mod alert;
+pub mod atom;
+pub mod badge;
+pub mod bus;
 pub mod calendar;
+pub mod changes;
+pub mod dedup;
+pub mod departures;
+pub mod digest;
+pub mod discord;
+pub mod filter;
+pub mod graphql;
+pub mod html;
 pub mod ics;
+pub mod lines;
+pub mod network;
+pub mod notify;
+pub mod ntfy;
+pub mod openapi;
+pub mod opml;
 pub mod proto;
+pub mod railroad;
+pub mod recurrence;
+pub mod revision;
+pub mod rss;
+pub mod sanitize;
+pub mod saved;
+pub mod sequences;
+pub mod severity;
+pub mod sitemap;
+pub mod slack;
+pub mod stats;
+pub mod status;
+pub mod stops;
+pub mod summary;
+pub mod timing;
+pub mod widget;
+pub mod zip;
 
+use crate::alert::AlertDetail;
+use crate::calendar::CalendarEvent;
+use crate::digest::Digest;
+use crate::filter::EventFilter;
+use crate::ics::CalendarMetadata;
+use crate::network::Network;
 use crate::proto::gtfs_realtime::FeedMessage;
+use crate::stops::Stations;
 use protobuf::Message;
+use std::collections::HashSet;
+
+pub const SUBWAY_ALERTS_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/camsys%2Fsubway-alerts";
+pub const LIRR_ALERTS_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/camsys%2Flirr-alerts";
+pub const MNR_ALERTS_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/camsys%2Fmnr-alerts";
+pub const BUS_ALERTS_URL: &str =
+    "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/camsys%2Fbus-alerts";
+/// The Port Authority's PATH alerts, which don't carry the MTA's Mercury extensions
+pub const PATH_ALERTS_URL: &str = "https://www.panynj.gov/bin/portauthority/path/gtfs-rt/alerts";
+
+/// An alert feed parsed both into calendar events and into whole alerts.
+#[derive(Debug, Clone, Default)]
+pub struct Feed {
+    pub events: Vec<CalendarEvent>,
+    pub alerts: Vec<AlertDetail>,
+}
+
+impl Feed {
+    pub fn parse(feed_message: &FeedMessage) -> Feed {
+        Feed {
+            events: dedup::merge_duplicates(calendar::proto_feed_to_events(feed_message)),
+            alerts: alert::proto_feed_to_alerts(feed_message),
+        }
+    }
+
+    pub fn find_alert(&self, alert_id: &str) -> Option<&AlertDetail> {
+        self.alerts.iter().find(|alert| alert.id == alert_id)
+    }
+}
+
+pub async fn fetch_mta_feed() -> Result<Feed, Box<dyn std::error::Error + Send + Sync>> {
+    fetch_feed(Network::Subway).await
+}
+
+/// Fetches and parses the alert feed for `network`.
+pub async fn fetch_feed(
+    network: Network,
+) -> Result<Feed, Box<dyn std::error::Error + Send + Sync>> {
+    let response = reqwest::get(network.alerts_url()).await?;
+    let bytes = response.bytes().await?;
+
+    let feed_message = FeedMessage::parse_from_bytes(&bytes)?;
+
+    Ok(Feed::parse(&feed_message))
+}
 
 pub async fn fetch_mta_events()
 -> Result<Vec<calendar::CalendarEvent>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = "https://api-endpoint.mta.info/Dataservice/mtagtfsfeeds/camsys%2Fsubway-alerts";
+    Ok(fetch_mta_feed().await?.events)
+}
 
-    let response = reqwest::get(url).await?;
-    let bytes = response.bytes().await?;
+/// Fetches current elevator and escalator outages as calendar events.
+pub async fn fetch_accessibility_events()
+-> Result<Vec<CalendarEvent>, Box<dyn std::error::Error + Send + Sync>> {
+    let (outages, equipment) = tokio::try_join!(
+        fetch_bytes(accessibility::OUTAGES_URL),
+        fetch_bytes(accessibility::EQUIPMENT_URL),
+    )?;
+
+    Ok(accessibility::outages_to_events(&outages, &equipment)?)
+}
 
+/// Fetches and parses a subway trip-updates feed, such as one of [`departures::feed_urls`].
+pub async fn fetch_trips(
+    url: &str,
+) -> Result<Vec<departures::Trip>, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = fetch_bytes(url).await?;
     let feed_message = FeedMessage::parse_from_bytes(&bytes)?;
-    let events = calendar::proto_feed_to_events(&feed_message);
 
-    Ok(events)
+    Ok(departures::proto_feed_to_trips(&feed_message))
+}
+
+async fn fetch_bytes(url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// The path of the alert detail endpoint for an MTA alert ID.
+pub fn alert_path(alert_id: &str) -> String {
+    format!(
+        "/api/alerts/{}",
+        percent_encode(alert_id, |byte| byte == b':')
+    )
+}
+
+/// Percent-encodes `value` for use as a URL query parameter value.
+pub fn encode_query_value(value: &str) -> String {
+    percent_encode(value, |_| false)
+}
+
+/// Percent-encodes every byte of `value` except unreserved characters and those `keep` allows.
+fn percent_encode(value: &str, keep: fn(u8) -> bool) -> String {
+    let mut encoded = String::new();
+
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') || keep(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+/// Copies `events`, pointing the URL of each one without a link from the MTA at its alert
+/// detail page under `base_url`.
+///
+/// The events usually end up in documents that are cached and served to everyone, so
+/// `base_url` should be the server's configured public URL, never one taken from a request.
+pub fn with_alert_urls(events: &[CalendarEvent], base_url: &str) -> Vec<CalendarEvent> {
+    events
+        .iter()
+        .map(|event| CalendarEvent {
+            url: event
+                .url
+                .clone()
+                .or_else(|| Some(format!("{}{}", base_url, alert_path(&event.mta_alert_id)))),
+            ..event.clone()
+        })
+        .collect()
 }
 
 pub async fn generate_train_ics(
     train_name: &str,
 ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let events = fetch_mta_events().await?;
+    generate_trains_ics(&[train_name]).await
+}
+
+/// Generates a single calendar covering every line in `train_names`.
+///
+/// Alerts that affect several of the requested lines are only emitted once.
+pub async fn generate_trains_ics(
+    train_names: &[&str],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    generate_routes_ics(Network::Subway, Some(&train_names.join(", ")), train_names).await
+}
+
+/// Generates a single calendar covering every route in `route_ids` on `network`.
+///
+/// `name` is the line or branch that the calendar is titled after, or `None` for the whole
+/// network.
+pub async fn generate_routes_ics(
+    network: Network,
+    name: Option<&str>,
+    route_ids: &[&str],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let feed = fetch_feed(network).await?;
 
-    let train_upper = train_name.to_uppercase();
-    let filtered_events: Vec<_> = events
+    Ok(render_routes_ics(
+        &feed.events,
+        network,
+        name,
+        route_ids,
+        &EventFilter {
+            window: Some(filter::Window {
+                start: chrono::Utc::now(),
+                days: filter::DEFAULT_DAYS,
+            }),
+            ..EventFilter::default()
+        },
+        &Stations::default(),
+    ))
+}
+
+/// Renders the calendar for `route_ids` on `network` from an already fetched set of events.
+pub fn render_routes_ics(
+    events: &[CalendarEvent],
+    network: Network,
+    name: Option<&str>,
+    route_ids: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    let calendar = format!("{:?} {}", network, route_ids.join(" "));
+    let mut metadata = network.calendar_metadata(name, filter.language);
+    if network == Network::Subway {
+        metadata = metadata.with_line_color(route_ids);
+    }
+
+    render_calendar(
+        narrow_to_trains(
+            filter_events_for_trains(events, route_ids, !filter.exclude_express),
+            network,
+            route_ids,
+            !filter.exclude_express,
+        ),
+        &calendar,
+        metadata,
+        filter,
+        stations,
+    )
+}
+
+/// Renders the calendar for `train_names` from an already fetched set of events.
+///
+/// `name` is the line, group or list of lines that the calendar is titled after.
+pub fn render_trains_ics(
+    events: &[CalendarEvent],
+    name: &str,
+    train_names: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    render_routes_ics(
+        events,
+        Network::Subway,
+        Some(name),
+        train_names,
+        filter,
+        stations,
+    )
+}
+
+/// Renders the events for `train_names` as JSON, in the same shape as the alerts endpoint.
+pub fn render_trains_json(
+    events: &[CalendarEvent],
+    train_names: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    let filtered_events = filter.apply(
+        filter_events_for_trains(events, train_names, !filter.exclude_express),
+        stations,
+    );
+
+    serde_json::to_string(&filtered_events).expect("calendar events serialize to JSON")
+}
+
+/// Keeps the events that affect at least one of `train_names` (case-insensitive). With
+/// `include_express`, a local line's events include its express variant's, e.g. `6X` for `6`.
+pub fn filter_events_for_trains(
+    events: &[CalendarEvent],
+    train_names: &[&str],
+    include_express: bool,
+) -> Vec<CalendarEvent> {
+    events
+        .iter()
+        .filter(|event| affects_any_train(event, train_names, include_express))
+        .cloned()
+        .collect()
+}
+
+fn affects_any_train(event: &CalendarEvent, train_names: &[&str], include_express: bool) -> bool {
+    event.routes.iter().any(|route| {
+        train_names
+            .iter()
+            .any(|train| lines::route_matches(train, route, include_express))
+    })
+}
+
+/// Narrows each of `events` to the lines in `train_names`, with [`CalendarEvent::narrow_to_routes`],
+/// so a subway calendar's summaries only list its own lines. A single line's calendar also
+/// drops the other lines' bracketed names from the alert's headline. `include_express` is as
+/// for [`filter_events_for_trains`].
+///
+/// Other networks' events are left as they are: their alerts name branches and routes in
+/// full rather than in brackets, and a branch's riders want to know about the main line.
+pub fn narrow_to_trains(
+    mut events: Vec<CalendarEvent>,
+    network: Network,
+    train_names: &[&str],
+    include_express: bool,
+) -> Vec<CalendarEvent> {
+    if network != Network::Subway {
+        return events;
+    }
+
+    let requested = |route: &str| {
+        train_names
+            .iter()
+            .any(|train| lines::route_matches(train, route, include_express))
+    };
+    for event in &mut events {
+        event.narrow_to_routes(requested, train_names.len() == 1);
+    }
+    events
+}
+
+/// Keeps the events that affect step-free access on any of `train_names`: alerts for those
+/// lines that the MTA flags as affecting accessible stations, and elevator outages at stations
+/// they serve according to `stations`. `include_express` is as for [`filter_events_for_trains`].
+pub fn filter_accessibility_events_for_trains(
+    events: &[CalendarEvent],
+    train_names: &[&str],
+    stations: &Stations,
+    include_express: bool,
+) -> Vec<CalendarEvent> {
+    let serves_line = |route: &str| {
+        train_names
+            .iter()
+            .any(|train| lines::route_matches(train, route, include_express))
+    };
+
+    events
+        .iter()
+        .filter(|event| event.affects_accessibility())
+        .filter(|event| {
+            // Elevator outages only inform the station
+            event.routes.iter().any(|route| serves_line(route))
+                || event.stop_ids.iter().any(|stop_id| {
+                    stations.get(stop_id).is_some_and(|station| {
+                        station.routes.iter().any(|route| serves_line(route))
+                    })
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Renders the accessibility calendar for `train_names` from an already fetched set of events.
+pub fn render_trains_accessibility_ics(
+    events: &[CalendarEvent],
+    name: &str,
+    train_names: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    render_calendar(
+        filter_accessibility_events_for_trains(
+            events,
+            train_names,
+            stations,
+            !filter.exclude_express,
+        ),
+        &format!("subway {} accessibility", train_names.join(" ")),
+        CalendarMetadata::trains_accessibility(name, train_names, filter.language),
+        filter,
+        stations,
+    )
+}
+
+/// Renders the RSS feed for `train_names`, linking back to `link`.
+pub fn render_trains_rss(
+    events: &[CalendarEvent],
+    name: &str,
+    train_names: &[&str],
+    link: &str,
+) -> String {
+    let filtered_events = filter_events_for_trains(events, train_names, true);
+    let name = name.to_uppercase();
+
+    rss::generate_rss(
+        &filtered_events,
+        &format!("MTA {} Train Alerts", name),
+        &format!(
+            "Real-time alerts and planned service changes for MTA {} train",
+            name
+        ),
+        link,
+    )
+}
+
+/// Renders the Atom feed for `train_names`, linking back to `link`.
+pub fn render_trains_atom(
+    events: &[CalendarEvent],
+    name: &str,
+    train_names: &[&str],
+    link: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let filtered_events = filter_events_for_trains(events, train_names, true);
+    let name = name.to_uppercase();
+
+    atom::generate_atom(
+        &filtered_events,
+        &format!("MTA {} Train Alerts", name),
+        &format!("urn:nyctraincal:feed:train:{}", name),
+        link,
+        now,
+    )
+}
+
+/// Renders the status page for `train_names`.
+///
+/// `name` is the line or group the page is for, which is already normalized.
+pub fn render_trains_page(
+    events: &[CalendarEvent],
+    name: &str,
+    train_names: &[&str],
+    links: &html::SubscribeLinks,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let filtered_events = filter_events_for_trains(events, train_names, true);
+
+    html::generate_status_page(name, lines::find_line(name), &filtered_events, links, now)
+}
+
+/// A line's row in the status summary.
+#[derive(serde::Serialize)]
+struct LineStatusRow {
+    #[serde(flatten)]
+    line: &'static lines::Line,
+    /// The status as it's shown to people, e.g. `Part Suspended`
+    label: &'static str,
+    #[serde(flatten)]
+    summary: status::LineSummary,
+}
+
+/// Renders every line's status and alert counts at `now` as JSON, in display order.
+pub fn render_status_json(events: &[CalendarEvent], now: chrono::DateTime<chrono::Utc>) -> String {
+    let lines: Vec<LineStatusRow> = lines::LINES
+        .iter()
+        .map(|line| {
+            let summary = status::LineSummary::from_events(
+                &filter_events_for_trains(events, &[line.id], true),
+                now,
+            );
+            LineStatusRow {
+                line,
+                label: summary.status.label(),
+                summary,
+            }
+        })
+        .collect();
+
+    serde_json::to_string(&serde_json::json!({ "lines": lines }))
+        .expect("line statuses serialize to JSON")
+}
+
+/// Renders the calendar covering every line in `train_names` under the generic subway name.
+pub fn render_all_trains_ics(
+    events: &[CalendarEvent],
+    train_names: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    render_routes_ics(events, Network::Subway, None, train_names, filter, stations)
+}
+
+/// Renders the calendar for a single station from an already fetched set of events.
+pub fn render_station_ics(
+    events: &[CalendarEvent],
+    stop_id: &str,
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    render_calendar(
+        filter_events_for_stop(events, stop_id),
+        &format!("station {}", stop_id),
+        CalendarMetadata::station(&station_label(stop_id, stations), filter.language),
+        filter,
+        stations,
+    )
+}
+
+/// Keeps the events for `train_names` that affect a station in `segment`, or the whole line.
+/// `include_express` is as for [`filter_events_for_trains`].
+pub fn filter_events_for_segment(
+    events: &[CalendarEvent],
+    train_names: &[&str],
+    segment: &HashSet<String>,
+    include_express: bool,
+) -> Vec<CalendarEvent> {
+    filter_events_for_trains(events, train_names, include_express)
         .into_iter()
         .filter(|event| {
-            event
-                .routes
+            event.stop_ids.is_empty()
+                || event
+                    .stop_ids
+                    .iter()
+                    .any(|stop_id| segment.contains(stops::parent_station(stop_id)))
+        })
+        .collect()
+}
+
+/// Renders the calendar for a trip from `from` to `to` on `train_names`, where `segment` holds
+/// the stations between them.
+pub fn render_commute_ics(
+    events: &[CalendarEvent],
+    from: &str,
+    to: &str,
+    train_names: &[&str],
+    segment: &HashSet<String>,
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    let calendar = format!("commute {} {} {}", from, to, train_names.join(" "));
+    let metadata = CalendarMetadata::commute(
+        &station_name(from, stations),
+        &station_name(to, stations),
+        train_names,
+        filter.language,
+    );
+
+    render_calendar(
+        filter_events_for_segment(events, train_names, segment, !filter.exclude_express),
+        &calendar,
+        metadata,
+        filter,
+        stations,
+    )
+}
+
+/// Renders a saved calendar covering `train_names` and `stop_ids`, titled `name` or else after
+/// its lines and stations.
+pub fn render_custom_ics(
+    events: &[CalendarEvent],
+    name: Option<&str>,
+    train_names: &[&str],
+    stop_ids: &[&str],
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    let name = name.map_or_else(
+        || {
+            let stop_names = stop_ids
                 .iter()
-                .any(|route| route.to_uppercase() == train_upper)
+                .map(|stop_id| station_name(stop_id, stations));
+            train_names
+                .iter()
+                .map(|train_name| train_name.to_string())
+                .chain(stop_names)
+                .collect::<Vec<_>>()
+                .join(", ")
+        },
+        str::to_string,
+    );
+
+    let selected: Vec<CalendarEvent> = events
+        .iter()
+        .filter(|event| {
+            affects_any_train(event, train_names, !filter.exclude_express)
+                || stop_ids.iter().any(|stop_id| {
+                    event
+                        .stop_ids
+                        .iter()
+                        .any(|informed| stops::stop_matches(stop_id, informed))
+                })
         })
+        .cloned()
         .collect();
 
-    Ok(ics::generate_ics_with_name(
-        &filtered_events,
-        Some(train_name),
-    ))
+    render_calendar(
+        selected,
+        &format!("custom {} {}", train_names.join(" "), stop_ids.join(" ")),
+        CalendarMetadata::custom(&name, train_names, filter.language),
+        filter,
+        stations,
+    )
+}
+
+/// Renders the elevator and escalator outage calendar for a single station.
+pub fn render_accessibility_ics(
+    events: &[CalendarEvent],
+    stop_id: &str,
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    render_calendar(
+        filter_events_for_stop(events, stop_id),
+        &format!("accessibility {}", stop_id),
+        CalendarMetadata::station_accessibility(&station_label(stop_id, stations), filter.language),
+        filter,
+        stations,
+    )
+}
+
+/// Shapes the events a calendar covers with `filter`, rolls them up into digests and cuts them
+/// short as it asks, adds `metadata`'s placeholder if that leaves none, and renders the result.
+/// `calendar` identifies the calendar as for [`with_digest`].
+fn render_calendar(
+    events: Vec<CalendarEvent>,
+    calendar: &str,
+    metadata: CalendarMetadata,
+    filter: &EventFilter,
+    stations: &Stations,
+) -> String {
+    let events = with_placeholder(
+        with_limit(
+            with_digest(filter.apply_to_calendar(events, stations), filter, calendar),
+            filter,
+        ),
+        filter,
+        &metadata.placeholder,
+    );
+
+    ics::generate_ics_with_metadata(&events, &metadata.with_filter(filter))
+}
+
+/// The name of the station `stop_id`, or the stop ID when the server has no station data.
+fn station_name(stop_id: &str, stations: &Stations) -> String {
+    stations
+        .get(stop_id)
+        .map_or_else(|| stop_id.to_string(), |station| station.name.clone())
+}
+
+/// How a station's calendars name it: its name and lines, e.g. `125 St (A, B, C, D)`, or the
+/// stop ID when the server has no station data.
+fn station_label(stop_id: &str, stations: &Stations) -> String {
+    match stations.get(stop_id) {
+        Some(station) if !station.routes.is_empty() => {
+            format!("{} ({})", station.name, station.routes.join(", "))
+        }
+        Some(station) => station.name.clone(),
+        None => stop_id.to_string(),
+    }
+}
+
+/// Rolls planned work up into digests when `filter` asks for them. `calendar` identifies the
+/// calendar, e.g. `subway-f`, and keeps each digest's UID stable.
+fn with_digest(
+    events: Vec<CalendarEvent>,
+    filter: &EventFilter,
+    calendar: &str,
+) -> Vec<CalendarEvent> {
+    match filter.digest {
+        Some(Digest::Weekly) => digest::weekly_digest(events, &calendar_slug(calendar)),
+        None => events,
+    }
+}
+
+/// Keeps the `filter.limit` soonest-starting events, followed by an event saying how many later
+/// ones were left out.
+fn with_limit(mut events: Vec<CalendarEvent>, filter: &EventFilter) -> Vec<CalendarEvent> {
+    let Some(limit) = filter.limit.filter(|limit| events.len() > *limit) else {
+        return events;
+    };
+
+    events.sort_by_key(|event| event.start);
+    let omitted = events.split_off(limit).len();
+    if let Some(last) = events.last() {
+        events.push(CalendarEvent::omitted_notice(
+            omitted,
+            last.start,
+            filter.language,
+            filter.unlimited_url.clone(),
+        ));
+    }
+
+    events
+}
+
+/// Lowercases `text` and joins its words with dashes, for use in UIDs.
+fn calendar_slug(text: &str) -> String {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Adds a placeholder event titled `summary` when `filter` asks for one and `events` is empty.
+fn with_placeholder(
+    mut events: Vec<CalendarEvent>,
+    filter: &EventFilter,
+    summary: &str,
+) -> Vec<CalendarEvent> {
+    if filter.placeholder && events.is_empty() {
+        let now = filter
+            .window
+            .map_or_else(chrono::Utc::now, |window| window.start);
+        events.push(CalendarEvent::placeholder(summary.to_string(), now));
+    }
+
+    events
+}
+
+/// Keeps the events whose informed entities include `stop_id`, its parent station or one of
+/// its platforms.
+pub fn filter_events_for_stop(events: &[CalendarEvent], stop_id: &str) -> Vec<CalendarEvent> {
+    events
+        .iter()
+        .filter(|event| {
+            event
+                .stop_ids
+                .iter()
+                .any(|informed| stops::stop_matches(stop_id, informed))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn event(uid: &str, routes: &[&str]) -> CalendarEvent {
+        CalendarEvent::for_test(uid, routes)
+    }
+
+    #[test]
+    fn test_filter_events_for_trains_dedupes_shared_alerts() {
+        let events = vec![
+            event("f-only", &["F"]),
+            event("f-and-l", &["F", "L"]),
+            event("a-only", &["A"]),
+            event("l-only", &["L"]),
+        ];
+
+        let uids: Vec<String> = filter_events_for_trains(&events, &["F", "l"], true)
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(uids, vec!["f-only", "f-and-l", "l-only"]);
+    }
+
+    #[test]
+    fn test_filter_events_for_trains_includes_express_variants() {
+        let events = vec![
+            event("local", &["6"]),
+            event("express", &["6X"]),
+            event("both", &["6", "6X"]),
+        ];
+        let uids = |train_names: &[&str], include_express| -> Vec<String> {
+            filter_events_for_trains(&events, train_names, include_express)
+                .into_iter()
+                .map(|e| e.uid)
+                .collect()
+        };
+
+        assert_eq!(uids(&["6"], true), vec!["local", "express", "both"]);
+        assert_eq!(uids(&["6"], false), vec!["local", "both"]);
+        assert_eq!(uids(&["6x"], true), vec!["express", "both"]);
+    }
+
+    #[test]
+    fn test_filter_events_for_stop_matches_parent_and_platforms() {
+        let with_stops = |uid: &str, stop_ids: &[&str]| CalendarEvent {
+            stop_ids: stop_ids.iter().map(|s| s.to_string()).collect(),
+            ..event(uid, &["A"])
+        };
+        let events = vec![
+            with_stops("station", &["A15"]),
+            with_stops("northbound", &["A15N"]),
+            with_stops("southbound", &["A15S"]),
+            with_stops("elsewhere", &["A16"]),
+        ];
+
+        let uids = |stop_id: &str| -> Vec<String> {
+            filter_events_for_stop(&events, stop_id)
+                .into_iter()
+                .map(|e| e.uid)
+                .collect()
+        };
+
+        assert_eq!(uids("A15"), vec!["station", "northbound", "southbound"]);
+        assert_eq!(uids("A15N"), vec!["station", "northbound"]);
+    }
+
+    #[test]
+    fn test_filter_events_for_segment() {
+        let with_stops = |uid: &str, routes: &[&str], stop_ids: &[&str]| CalendarEvent {
+            stop_ids: stop_ids.iter().map(|s| s.to_string()).collect(),
+            ..event(uid, routes)
+        };
+        let events = vec![
+            with_stops("bergen-st", &["F"], &["F20S"]),
+            with_stops("both-ends", &["F"], &["D20", "D21"]),
+            with_stops("whole-line", &["F"], &[]),
+            with_stops("queens", &["F"], &["F01", "F02"]),
+            with_stops("g-at-bergen-st", &["G"], &["F20"]),
+        ];
+        let segment: HashSet<String> = ["D21", "F14", "F20"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let uids: Vec<String> = filter_events_for_segment(&events, &["F"], &segment, true)
+            .into_iter()
+            .map(|e| e.uid)
+            .collect();
+
+        assert_eq!(uids, vec!["bergen-st", "both-ends", "whole-line"]);
+    }
+
+    #[test]
+    fn test_filter_accessibility_events_for_trains() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let stations = Stations::from_csv(csv.as_slice()).unwrap();
+        let elevator = |uid: &str, stop_id: &str| CalendarEvent {
+            mta_alert_id: format!("{}#EL100", stop_id),
+            stop_ids: vec![stop_id.to_string()],
+            ..event(uid, &[])
+        };
+        let events = vec![
+            CalendarEvent {
+                description: "This service change affects one or more ADA accessible stations"
+                    .to_string(),
+                ..event("ada-work", &["L"])
+            },
+            event("delays", &["L"]),
+            elevator("bedford-elevator", "L08N"),
+            elevator("125-st-elevator", "A15S"),
+        ];
+
+        let uids: Vec<String> =
+            filter_accessibility_events_for_trains(&events, &["L"], &stations, true)
+                .into_iter()
+                .map(|e| e.uid)
+                .collect();
+
+        assert_eq!(uids, vec!["ada-work", "bedford-elevator"]);
+    }
+
+    #[test]
+    fn test_alert_urls_are_percent_encoded() {
+        let events = vec![event("mta-alert-A27N#EL290X", &[])];
+        let linked = with_alert_urls(&events, "https://example.com");
+
+        assert_eq!(
+            linked[0].url.as_deref(),
+            Some("https://example.com/api/alerts/mta-alert-A27N%23EL290X")
+        );
+
+        // The MTA's own link is kept
+        let mut with_link = event("lmm:alert:1", &[]);
+        with_link.url = Some("https://new.mta.info/alerts".to_string());
+        let linked = with_alert_urls(&[with_link], "https://example.com");
+        assert_eq!(
+            linked[0].url.as_deref(),
+            Some("https://new.mta.info/alerts")
+        );
+        assert_eq!(
+            alert_path("lmm:planned_work:1"),
+            "/api/alerts/lmm:planned_work:1"
+        );
+    }
+
+    #[test]
+    fn test_encode_query_value() {
+        assert_eq!(
+            encode_query_value("https://example.com/api/calendars/train/A.ics?days=7"),
+            "https%3A%2F%2Fexample.com%2Fapi%2Fcalendars%2Ftrain%2FA.ics%3Fdays%3D7"
+        );
+        assert_eq!(encode_query_value("MTA A Train"), "MTA%20A%20Train");
+    }
+
+    #[test]
+    fn test_with_limit() {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let day = |day| Utc.with_ymd_and_hms(2025, 12, day, 15, 0, 0).unwrap();
+        let starting = |uid: &str, start| CalendarEvent {
+            start,
+            ..event(uid, &["A"])
+        };
+        let events = vec![
+            starting("third", day(18)),
+            starting("first", day(15)),
+            starting("fourth", day(19)),
+            starting("second", day(16)),
+        ];
+
+        let filter = EventFilter {
+            limit: Some(2),
+            unlimited_url: Some("https://example.com/api/calendars/train/A.ics".to_string()),
+            ..EventFilter::default()
+        };
+        let limited = with_limit(events.clone(), &filter);
+        let uids: Vec<&str> = limited.iter().map(|e| e.uid.as_str()).collect();
+        assert_eq!(uids, vec!["first", "second", "omitted-events"]);
+
+        let notice = &limited[2];
+        assert_eq!(notice.summary, "2 more alerts not shown");
+        assert_eq!(
+            notice.description,
+            "2 later alerts were left out to keep this calendar short. See every alert at https://example.com/api/calendars/train/A.ics"
+        );
+        assert_eq!(notice.url, filter.unlimited_url);
+        assert!(notice.all_day);
+        // On the New York day of the last event kept
+        assert_eq!(notice.start, day(16) - Duration::hours(10));
+
+        // Nothing to leave out
+        let filter = EventFilter {
+            limit: Some(4),
+            ..EventFilter::default()
+        };
+        assert_eq!(with_limit(events.clone(), &filter), events);
+    }
 }
@@ -0,0 +1,363 @@
+use serde::Serialize;
+
+/// A subway line with the metadata needed to present it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Line {
+    /// The GTFS route ID, e.g. `A` or `SI`
+    pub id: &'static str,
+    pub name: &'static str,
+    /// The official MTA line color as a hex string
+    pub color: &'static str,
+    /// The text color used on top of `color`
+    pub text_color: &'static str,
+}
+
+const fn line(
+    id: &'static str,
+    name: &'static str,
+    color: &'static str,
+    text_color: &'static str,
+) -> Line {
+    Line {
+        id,
+        name,
+        color,
+        text_color,
+    }
+}
+
+const WHITE: &str = "#FFFFFF";
+const BLACK: &str = "#000000";
+
+pub const LINES: &[Line] = &[
+    line("A", "8 Avenue Express", "#0039A6", WHITE),
+    line("C", "8 Avenue Local", "#0039A6", WHITE),
+    line("E", "8 Avenue Local", "#0039A6", WHITE),
+    line("B", "6 Avenue Express", "#FF6319", WHITE),
+    line("D", "6 Avenue Express", "#FF6319", WHITE),
+    line("F", "Queens Blvd Express/6 Av Local", "#FF6319", WHITE),
+    line("M", "Queens Blvd Local/6 Av Local", "#FF6319", WHITE),
+    line("G", "Brooklyn-Queens Crosstown", "#6CBE45", WHITE),
+    line("J", "Nassau St Local", "#996633", WHITE),
+    line("Z", "Nassau St Express", "#996633", WHITE),
+    line("L", "14 St-Canarsie Local", "#A7A9AC", WHITE),
+    line("N", "Broadway Express", "#FCCC0A", BLACK),
+    line("Q", "Broadway Express", "#FCCC0A", BLACK),
+    line("R", "Broadway Local", "#FCCC0A", BLACK),
+    line("W", "Broadway Local", "#FCCC0A", BLACK),
+    line("1", "Broadway - 7 Avenue Local", "#EE352E", WHITE),
+    line("2", "7 Avenue Express", "#EE352E", WHITE),
+    line("3", "7 Avenue Express", "#EE352E", WHITE),
+    line("4", "Lexington Avenue Express", "#00933C", WHITE),
+    line("5", "Lexington Avenue Express", "#00933C", WHITE),
+    line("6", "Lexington Avenue Local", "#00933C", WHITE),
+    line("7", "Flushing Local", "#B933AD", WHITE),
+    line("S", "Shuttle", "#808183", WHITE),
+    line("GS", "42 St Shuttle", "#808183", WHITE),
+    line("FS", "Franklin Av Shuttle", "#808183", WHITE),
+    line("H", "Rockaway Park Shuttle", "#808183", WHITE),
+    line("SI", "Staten Island Railway", "#808183", WHITE),
+];
+
+pub fn find_line(id: &str) -> Option<&'static Line> {
+    LINES.iter().find(|line| line.id == id)
+}
+
+/// An express pattern that the feed gives its own route ID, e.g. `6X` for the <6> train.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ExpressVariant {
+    pub id: &'static str,
+    /// The local line the express pattern runs on
+    pub local: &'static str,
+}
+
+pub const EXPRESS_VARIANTS: &[ExpressVariant] = &[
+    ExpressVariant {
+        id: "6X",
+        local: "6",
+    },
+    ExpressVariant {
+        id: "7X",
+        local: "7",
+    },
+    ExpressVariant {
+        id: "FX",
+        local: "F",
+    },
+];
+
+pub fn find_express_variant(id: &str) -> Option<&'static ExpressVariant> {
+    EXPRESS_VARIANTS.iter().find(|variant| variant.id == id)
+}
+
+/// The line that covers every shuttle
+pub const SHUTTLE_LINE: &str = "S";
+
+/// The shuttles, which the feeds tell apart by route ID and which each have their own line as
+/// well as being covered together by `S`.
+pub const SHUTTLES: &[&str] = &["GS", "FS", "H"];
+
+pub fn find_shuttle(id: &str) -> Option<&'static Line> {
+    SHUTTLES.contains(&id).then(|| find_line(id)).flatten()
+}
+
+/// Route IDs that some feeds and tools use in place of a line's own ID. The subway feed tags
+/// Staten Island Railway alerts `SI`, but `SIR` turns up elsewhere, including in the alert text.
+const ROUTE_ALIASES: &[(&str, &str)] = &[("SIR", "SI")];
+
+/// The line's own ID for `route` if it's an alias from [`ROUTE_ALIASES`], or else `route`
+/// unchanged (case-insensitive).
+pub fn canonical_route(route: &str) -> &str {
+    ROUTE_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(route))
+        .map_or(route, |(_, id)| id)
+}
+
+/// Whether an alert informing `route` belongs in the calendar for `train` (case-insensitive).
+///
+/// The `S` calendar takes every shuttle's alerts, while each shuttle's own calendar, e.g. `GS`,
+/// only matches that shuttle. With `include_express`, a local line's calendar also takes its
+/// express variant's alerts, so `6` matches `6X`. An express variant's own calendar only
+/// matches the variant. Aliases such as `SIR` match the line they stand for.
+pub fn route_matches(train: &str, route: &str, include_express: bool) -> bool {
+    let route = canonical_route(route);
+
+    canonical_route(train).eq_ignore_ascii_case(route)
+        || (train.eq_ignore_ascii_case(SHUTTLE_LINE)
+            && SHUTTLES
+                .iter()
+                .any(|shuttle| shuttle.eq_ignore_ascii_case(route)))
+        || (include_express
+            && EXPRESS_VARIANTS.iter().any(|variant| {
+                variant.local.eq_ignore_ascii_case(train) && variant.id.eq_ignore_ascii_case(route)
+            }))
+}
+
+/// The IDs of every supported line, in display order.
+pub fn train_ids() -> Vec<&'static str> {
+    LINES.iter().map(|line| line.id).collect()
+}
+
+/// A trunk line whose routes share track and, usually, planned work.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TrainGroup {
+    pub id: &'static str,
+    pub trains: &'static [&'static str],
+}
+
+pub const TRAIN_GROUPS: &[TrainGroup] = &[
+    TrainGroup {
+        id: "ACE",
+        trains: &["A", "C", "E"],
+    },
+    TrainGroup {
+        id: "BDFM",
+        trains: &["B", "D", "F", "M"],
+    },
+    TrainGroup {
+        id: "NQRW",
+        trains: &["N", "Q", "R", "W"],
+    },
+    TrainGroup {
+        id: "123",
+        trains: &["1", "2", "3"],
+    },
+    TrainGroup {
+        id: "456",
+        trains: &["4", "5", "6"],
+    },
+];
+
+/// Normalizes user input for a line or group name, so that `a` and ` A ` both become `A` and
+/// route ID aliases such as `sir` become the line's own ID.
+pub fn normalize(name: &str) -> String {
+    canonical_route(&name.trim().to_uppercase()).to_string()
+}
+
+/// Resolves a single train line, express variant or trunk group to the train lines it covers.
+///
+/// `name` must already be normalized with [`normalize`].
+pub fn resolve(name: &str) -> Option<&'static [&'static str]> {
+    if let Some(line) = find_line(name) {
+        return Some(std::slice::from_ref(&line.id));
+    }
+
+    if let Some(variant) = find_express_variant(name) {
+        return Some(std::slice::from_ref(&variant.id));
+    }
+
+    TRAIN_GROUPS
+        .iter()
+        .find(|group| group.id == name)
+        .map(|group| group.trains)
+}
+
+/// Names people use for lines that aren't their route IDs, and the line each one means.
+const ALIASES: &[(&str, &str)] = &[
+    ("ONE", "1"),
+    ("TWO", "2"),
+    ("THREE", "3"),
+    ("FOUR", "4"),
+    ("FIVE", "5"),
+    ("SIX", "6"),
+    ("SEVEN", "7"),
+];
+
+/// The most suggestions [`suggest`] returns
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Suggests the lines and trunk groups someone who asked for the unknown `name` probably meant,
+/// best first, e.g. `A` for `AA` or `6` for `SIX`.
+///
+/// Suggestions are names that are one edit away from `name` (two for names of four or more
+/// characters), or that `name` starts with or is the start of. `name` must already be
+/// normalized with [`normalize`].
+pub fn suggest(name: &str) -> Vec<&'static str> {
+    let length = name.chars().count();
+    if length == 0 {
+        return Vec::new();
+    }
+    let max_distance = if length >= 4 { 2 } else { 1 };
+
+    let mut candidates: Vec<(usize, &'static str)> = LINES
+        .iter()
+        .map(|line| line.id)
+        .chain(EXPRESS_VARIANTS.iter().map(|variant| variant.id))
+        .chain(TRAIN_GROUPS.iter().map(|group| group.id))
+        .filter_map(|id| {
+            let distance = levenshtein(name, id);
+            let is_prefix = id.starts_with(name) || name.starts_with(id);
+            (distance <= max_distance || is_prefix).then_some((distance, id))
+        })
+        .collect();
+    // Stable, so equally close names stay in display order
+    candidates.sort_by_key(|(distance, _)| *distance);
+
+    let mut suggestions: Vec<&'static str> = ALIASES
+        .iter()
+        .filter(|(alias, _)| *alias == name)
+        .map(|(_, id)| *id)
+        .collect();
+    for (_, id) in candidates {
+        if !suggestions.contains(&id) {
+            suggestions.push(id);
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+
+    suggestions
+}
+
+/// How many single-character insertions, deletions and substitutions turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("AA"), vec!["A"]);
+        assert_eq!(suggest("SIX"), vec!["6", "SI", "S"]);
+        assert_eq!(suggest("SEVEN"), vec!["7", "S"]);
+        assert_eq!(suggest("GSS"), vec!["GS", "G"]);
+        assert_eq!(suggest("7XX"), vec!["7X", "7"]);
+        assert_eq!(suggest("ACEE"), vec!["ACE", "A"]);
+        assert_eq!(suggest("BDMF"), vec!["BDFM", "B"]);
+        assert_eq!(suggest("L TRAIN"), vec!["L"]);
+        assert_eq!(suggest("Q1"), vec!["Q", "1"]);
+        assert_eq!(suggest("PURPLE"), Vec::<&str>::new());
+        assert_eq!(suggest(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("NQRW", "NQRW"), 0);
+        assert_eq!(levenshtein("AA", "A"), 1);
+        assert_eq!(levenshtein("BDMF", "BDFM"), 2);
+        assert_eq!(levenshtein("", "ACE"), 3);
+    }
+
+    #[test]
+    fn test_route_matches() {
+        assert!(route_matches("f", "F", false));
+        assert!(route_matches("6", "6X", true));
+        assert!(route_matches("F", "fx", true));
+        assert!(!route_matches("6", "6X", false));
+        assert!(!route_matches("6X", "6", true));
+        assert!(!route_matches("7", "6X", true));
+        assert!(route_matches("S", "GS", false));
+        assert!(route_matches("s", "h", false));
+        assert!(!route_matches("GS", "FS", true));
+        assert!(!route_matches("GS", "S", true));
+        assert!(route_matches("SI", "SIR", false));
+        assert!(route_matches("sir", "SI", false));
+        assert!(!route_matches("S", "SIR", true));
+
+        for shuttle in SHUTTLES {
+            assert!(find_shuttle(shuttle).is_some(), "{}", shuttle);
+        }
+        for variant in EXPRESS_VARIANTS {
+            assert!(find_line(variant.local).is_some(), "{}", variant.id);
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("A"), Some(&["A"][..]));
+        assert_eq!(resolve("SI"), Some(&["SI"][..]));
+        assert_eq!(resolve("6X"), Some(&["6X"][..]));
+        assert_eq!(resolve("H"), Some(&["H"][..]));
+        assert_eq!(resolve("ACE"), Some(&["A", "C", "E"][..]));
+        assert_eq!(resolve("456"), Some(&["4", "5", "6"][..]));
+        assert_eq!(resolve("AC"), None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        for name in ["a", "A", " a "] {
+            assert_eq!(normalize(name), "A");
+        }
+        for name in ["si", "Si", "SI", "SIR", " sir "] {
+            assert_eq!(resolve(&normalize(name)), Some(&["SI"][..]));
+        }
+        assert_eq!(normalize("ace"), "ACE");
+    }
+
+    #[test]
+    fn test_groups_only_contain_valid_trains() {
+        for group in TRAIN_GROUPS {
+            assert_eq!(find_line(group.id), None);
+            for train in group.trains {
+                assert!(find_line(train).is_some(), "{} in {}", train, group.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_line_colors_are_hex() {
+        for line in LINES {
+            for color in [line.color, line.text_color] {
+                assert_eq!(color.len(), 7, "{}", line.id);
+                assert!(color.starts_with('#'));
+                assert!(color[1..].chars().all(|c| c.is_ascii_hexdigit()));
+            }
+        }
+    }
+}
@@ -1,51 +1,646 @@
 use axum::{
-    Router,
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::get,
+    Json, Router, async_trait,
+    body::Body,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{FromRequestParts, MatchedPath, Path, Query, RawQuery, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{get, post},
 };
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use chrono_tz::America::New_York;
 use moka::future::Cache;
+use nyc_train_time::Feed;
+use nyc_train_time::alert::AlertDetail;
+use nyc_train_time::badge;
+use nyc_train_time::bus::BusRoutes;
+use nyc_train_time::calendar::CalendarEvent;
+use nyc_train_time::changes::{self, AlertChange};
+use nyc_train_time::departures::{self, Trip};
+use nyc_train_time::filter::{self, EventFilter, HourRange, Window};
+use nyc_train_time::graphql;
+use nyc_train_time::html::SubscribeLinks;
+use nyc_train_time::lines::{self, LINES, TRAIN_GROUPS};
+use nyc_train_time::network::Network;
+use nyc_train_time::notify::{self, Webhook};
+use nyc_train_time::opml::{self, FeedFormat};
+use nyc_train_time::railroad::{self, Railroad};
+use nyc_train_time::revision::Revisions;
+use nyc_train_time::saved::{CalendarConfig, SavedCalendars};
+use nyc_train_time::sequences::StopSequences;
+use nyc_train_time::severity::{ALERT_PRIORITIES, AlertPriority, Severity};
+use nyc_train_time::sitemap::{self, SitemapUrl};
+use nyc_train_time::slack::Slack;
+use nyc_train_time::stats::Stats;
+use nyc_train_time::status::LineStatus;
+use nyc_train_time::stops::{Station, Stations};
+use nyc_train_time::timing::Timing;
+use nyc_train_time::widget::StationWidget;
+use nyc_train_time::zip::ZipWriter;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
 use tower::ServiceBuilder;
 use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
+
+const ICS_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+const SVG_CONTENT_TYPE: &str = "image/svg+xml";
+const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+const OPML_CONTENT_TYPE: &str = "text/x-opml; charset=utf-8";
+const ZIP_CONTENT_TYPE: &str = "application/zip";
+/// Whether a document came from the cache: `HIT` or `MISS`
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+const CACHE_TTL: Duration = Duration::from_secs(30);
+/// Arrival predictions go stale much faster than alerts
+const TRIPS_CACHE_TTL: Duration = Duration::from_secs(15);
+/// How many trains in each direction station widgets show by default
+const WIDGET_ARRIVALS: usize = 3;
+/// How many saved calendar configs are kept before the least recently used are dropped
+const SAVED_CALENDARS_CAPACITY: usize = 10_000;
+/// The most stations a station search returns
+const MAX_STATION_RESULTS: usize = 20;
+/// The most stations a saved calendar can cover
+const MAX_SAVED_STATIONS: usize = 20;
+/// The longest title a saved calendar can have, in characters
+const MAX_SAVED_NAME_LEN: usize = 100;
+/// How long posting an alert to a webhook can take before it's given up on
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+type FeedError = Arc<Box<dyn std::error::Error + Send + Sync>>;
+
+/// A rendered document along with its strong ETag, computed once when it's cached.
+struct CachedDocument {
+    content: String,
+    etag: String,
+    /// When the content last changed, truncated to the second precision of HTTP dates
+    last_modified: DateTime<Utc>,
+    /// When the document was generated and cached, which `Cache-Control` counts down from
+    cached_at: Instant,
+}
+
+impl CachedDocument {
+    fn new(content: String) -> Self {
+        let digest = Sha256::digest(content.as_bytes());
+        let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        let now = Utc::now();
+
+        CachedDocument {
+            content,
+            etag: format!("\"{}\"", hex),
+            last_modified: now.with_nanosecond(0).unwrap_or(now),
+            cached_at: Instant::now(),
+        }
+    }
+
+    /// Wraps freshly rendered `content`, keeping the ETag and `Last-Modified` time of
+    /// `previous` when regenerating produced exactly the same document.
+    fn refresh(previous: Option<Arc<CachedDocument>>, content: String) -> Arc<CachedDocument> {
+        match previous {
+            Some(previous) if previous.content == content => Arc::new(CachedDocument {
+                content,
+                etag: previous.etag.clone(),
+                last_modified: previous.last_modified,
+                cached_at: Instant::now(),
+            }),
+            _ => Arc::new(CachedDocument::new(content)),
+        }
+    }
+
+    /// How many whole seconds the document has been cached for, for the `Age` header.
+    fn age(&self) -> u64 {
+        self.cached_at.elapsed().as_secs()
+    }
+
+    /// How many more seconds the document stays cached, for `Cache-Control: max-age`.
+    fn max_age(&self) -> u64 {
+        CACHE_TTL.as_secs().saturating_sub(self.age())
+    }
+}
+
+/// The parts of a request that affect how a cached document is served.
+struct DocumentRequest {
+    method: Method,
+    headers: HeaderMap,
+    /// How long serving the document took, sent back in `Server-Timing`
+    timing: Timing,
+    /// Whether an admin asked for the document to be regenerated with `?refresh=1`
+    refresh: bool,
+}
+
+impl DocumentRequest {
+    /// Whether the request's `If-None-Match` header matches `etag`.
+    fn if_none_match(&self, etag: &str) -> bool {
+        self.headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+    }
+
+    /// Whether the document is unchanged according to the request's conditional headers.
+    ///
+    /// `If-Modified-Since` is only consulted when there's no `If-None-Match`, as RFC 9110
+    /// requires.
+    fn is_not_modified(&self, document: &CachedDocument) -> bool {
+        if self.headers.contains_key(header::IF_NONE_MATCH) {
+            return self.if_none_match(&document.etag);
+        }
+
+        self.headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .is_some_and(|since| document.last_modified <= since)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for DocumentRequest {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // Without the admin token `?refresh=1` is ignored, so it can't be used to get around
+        // the cache that keeps load off the MTA's feeds
+        let refresh = parts
+            .uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .any(|param| param == "refresh=1")
+            && state.is_admin(&parts.headers);
+
+        Ok(DocumentRequest {
+            method: parts.method.clone(),
+            headers: parts.headers.clone(),
+            timing: Timing::default(),
+            refresh,
+        })
+    }
+}
 
 #[derive(Clone)]
 struct AppState {
-    cache: Cache<String, String>,
+    cache: Cache<String, Arc<CachedDocument>>,
+    /// The last document generated for each cache key, kept after `cache` expires it so
+    /// regenerating identical content doesn't move `Last-Modified`
+    history: Cache<String, Arc<CachedDocument>>,
+    /// Parsed alert feeds keyed by feed URL, shared by every output format
+    feed_cache: Cache<&'static str, Arc<Feed>>,
+    /// How many times each alert event has been edited, for its `SEQUENCE`
+    revisions: Arc<Revisions>,
+    /// Elevator and escalator outages, which come from a separate pair of feeds
+    accessibility_cache: Cache<&'static str, Arc<Vec<CalendarEvent>>>,
+    /// Parsed trip-updates feeds keyed by feed URL, for departures
+    trips_cache: Cache<String, Arc<Vec<Trip>>>,
+    stations: Arc<Stations>,
+    /// Changes to the subway feed's alerts, for the live streams
+    alert_changes: broadcast::Sender<Arc<AlertChange>>,
+    /// Known bus routes, loaded from static GTFS. Bus calendars are unavailable when empty.
+    bus_routes: Arc<BusRoutes>,
+    /// Each subway route's stopping order, loaded from static GTFS. Commute calendars are
+    /// unavailable when empty.
+    stop_sequences: Arc<StopSequences>,
+    /// Calendar configs saved with `POST /api/calendars`
+    saved_calendars: Arc<SavedCalendars>,
+    /// The URL the server is reachable at, e.g. behind a reverse proxy. When unset, links are
+    /// built from the request's `Host` header.
+    public_url: Option<String>,
+    /// The bearer token for the admin endpoints, which are disabled when it's unset
+    admin_token: Option<String>,
+    /// Whether `GET /api/graphql` serves GraphiQL for trying out queries
+    graphql_playground: bool,
+    /// The nightly range `?exclude_overnight=1` drops events within
+    overnight: HourRange,
+    /// How many full days an event has to cover for calendars to show it as an all-day event,
+    /// unless `?all_day_days=` asks otherwise
+    all_day_days: u32,
+    /// The New York time calendars end events the MTA hasn't given an end at
+    open_ended_until: NaiveTime,
+    /// The longest description calendars show in full, or no limit with 0
+    max_description_length: usize,
+    /// How often calendars ask the apps subscribed to them to check for changes
+    refresh_interval: chrono::Duration,
+    /// Request, cache and fetch counters for `GET /api/stats`
+    stats: Arc<Stats>,
+    started_at: Instant,
+    /// When the server started, which is when pages that haven't been generated since last
+    /// changed as far as the sitemap knows
+    started: DateTime<Utc>,
+    /// The current time, which tests pin to match their fixtures
+    clock: fn() -> DateTime<Utc>,
+}
+
+impl AppState {
+    fn new(
+        stations: Stations,
+        bus_routes: BusRoutes,
+        stop_sequences: StopSequences,
+        saved_calendars: SavedCalendars,
+        public_url: Option<String>,
+        admin_token: Option<String>,
+    ) -> Self {
+        // Cache for 30 seconds - reduces MTA API calls significantly. Every bus route, station
+        // and filter combination gets its own entry, so there can be thousands.
+        let cache = Cache::builder()
+            .max_capacity(2000)
+            .time_to_live(CACHE_TTL)
+            .build();
+
+        let feed_cache = Cache::builder()
+            .max_capacity(10)
+            .time_to_live(CACHE_TTL)
+            .build();
+
+        let accessibility_cache = Cache::builder()
+            .max_capacity(1)
+            .time_to_live(CACHE_TTL)
+            .build();
+
+        let trips_cache = Cache::builder()
+            .max_capacity(10)
+            .time_to_live(TRIPS_CACHE_TTL)
+            .build();
+
+        let history = Cache::builder()
+            .max_capacity(5000)
+            .time_to_idle(Duration::from_secs(24 * 60 * 60))
+            .build();
+
+        AppState {
+            cache,
+            history,
+            feed_cache,
+            revisions: Arc::default(),
+            accessibility_cache,
+            trips_cache,
+            stations: Arc::new(stations),
+            bus_routes: Arc::new(bus_routes),
+            stop_sequences: Arc::new(stop_sequences),
+            saved_calendars: Arc::new(saved_calendars),
+            alert_changes: broadcast::channel(256).0,
+            public_url,
+            admin_token,
+            graphql_playground: false,
+            overnight: filter::DEFAULT_OVERNIGHT,
+            all_day_days: filter::DEFAULT_ALL_DAY_DAYS,
+            open_ended_until: filter::DEFAULT_SERVICE_DAY_END,
+            max_description_length: filter::DEFAULT_MAX_DESCRIPTION_LENGTH,
+            refresh_interval: filter::DEFAULT_REFRESH_INTERVAL,
+            stats: Arc::default(),
+            started_at: Instant::now(),
+            started: Utc::now(),
+            clock: Utc::now,
+        }
+    }
+
+    async fn subway_feed(&self) -> Result<Arc<Feed>, FeedError> {
+        self.feed(Network::Subway).await
+    }
+
+    async fn feed(&self, network: Network) -> Result<Arc<Feed>, FeedError> {
+        let url = network.alerts_url();
+        self.feed_cache
+            .try_get_with(url, async {
+                let result = nyc_train_time::fetch_feed(network).await;
+                self.stats.record_fetch(url, &result, (self.clock)());
+                result.map(|mut feed| {
+                    for event in &mut feed.events {
+                        event.list_affected_stations(&self.stations, &self.stop_sequences);
+                    }
+                    self.revisions.apply(url, &mut feed.events, (self.clock)());
+                    Arc::new(feed)
+                })
+            })
+            .await
+    }
+
+    async fn accessibility_events(&self) -> Result<Arc<Vec<CalendarEvent>>, FeedError> {
+        let url = nyc_train_time::accessibility::OUTAGES_URL;
+        self.accessibility_cache
+            .try_get_with(url, async {
+                let result = nyc_train_time::fetch_accessibility_events().await;
+                self.stats.record_fetch(url, &result, (self.clock)());
+                result.map(Arc::new)
+            })
+            .await
+    }
+
+    async fn trips(&self, url: String) -> Result<Arc<Vec<Trip>>, FeedError> {
+        self.trips_cache
+            .try_get_with(url.clone(), async move {
+                let result = nyc_train_time::fetch_trips(&url).await;
+                self.stats.record_fetch(&url, &result, (self.clock)());
+                result.map(Arc::new)
+            })
+            .await
+    }
+
+    /// The trips in the trip-updates feeds of every line serving `station`.
+    async fn station_trips(&self, station: &Station) -> Result<Vec<Trip>, FeedError> {
+        let mut trips = Vec::new();
+        for url in departures::feed_urls(&station.routes) {
+            trips.extend(self.trips(url).await?.iter().cloned());
+        }
+        Ok(trips)
+    }
+
+    /// The URL clients reach this server at: the configured public URL, or else one derived
+    /// from the `Host` header.
+    ///
+    /// Any client can send any `Host`, so cached documents, which are served to everyone, link
+    /// with [`Self::public_url`] instead.
+    fn base_url(&self, headers: &HeaderMap) -> String {
+        if let Some(public_url) = self.public_url() {
+            return public_url.to_string();
+        }
+
+        let host = headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or("localhost:3000");
+
+        format!("http://{}", host)
+    }
+
+    /// The configured public URL, without a trailing slash.
+    fn public_url(&self) -> Option<&str> {
+        self.public_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+    }
+
+    /// Whether the request carries the admin bearer token.
+    fn is_admin(&self, headers: &HeaderMap) -> bool {
+        let Some(admin_token) = &self.admin_token else {
+            return false;
+        };
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        // Comparing digests keeps the time taken independent of how much of the token matches
+        token.is_some_and(|token| Sha256::digest(token) == Sha256::digest(admin_token))
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Cache for 30 seconds - reduces MTA API calls significantly
-    let cache = Cache::builder()
-        .max_capacity(100)
-        .time_to_live(Duration::from_secs(30))
-        .build();
+    let stations_path =
+        std::env::var("STATIONS_CSV").unwrap_or_else(|_| "data/Stations.csv".to_string());
+    let stations = match Stations::load(&stations_path) {
+        Ok(stations) => {
+            println!("Loaded {} stations from {}", stations.len(), stations_path);
+            stations
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not load stations from {}: {}. Borough filters are disabled.",
+                stations_path, e
+            );
+            Stations::default()
+        }
+    };
 
-    let state = AppState { cache };
+    let bus_routes_paths =
+        std::env::var("BUS_ROUTES_TXT").unwrap_or_else(|_| "data/bus_routes.txt".to_string());
+    let bus_routes = match BusRoutes::load(bus_routes_paths.split(',')) {
+        Ok(bus_routes) => {
+            println!(
+                "Loaded {} bus routes from {}",
+                bus_routes.len(),
+                bus_routes_paths
+            );
+            bus_routes
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not load bus routes from {}: {}. Bus calendars are disabled.",
+                bus_routes_paths, e
+            );
+            BusRoutes::default()
+        }
+    };
 
-    // Rate limiting: 10 requests per IP per second
-    let governor_conf = GovernorConfigBuilder::default()
-        .per_second(10)
-        .burst_size(20)
-        .finish()
-        .ok_or("Failed to build governor config")?;
+    let gtfs_dir =
+        std::env::var("SUBWAY_GTFS_DIR").unwrap_or_else(|_| "data/gtfs_subway".to_string());
+    let stop_sequences = match StopSequences::load(&gtfs_dir) {
+        Ok(stop_sequences) => {
+            println!(
+                "Loaded stop sequences for {} routes from {}",
+                stop_sequences.len(),
+                gtfs_dir
+            );
+            stop_sequences
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not load stop sequences from {}: {}. Commute calendars are disabled.",
+                gtfs_dir, e
+            );
+            StopSequences::default()
+        }
+    };
 
-    let app = Router::new()
-        .route("/", get(handle_index))
-        .route(
-            "/api/calendars/train/:train_name",
-            get(handle_train_calendar),
+    let saved_calendars_path = std::env::var("SAVED_CALENDARS_JSON")
+        .unwrap_or_else(|_| "data/saved_calendars.json".to_string());
+    let saved_calendars = match SavedCalendars::load(
+        &saved_calendars_path,
+        SAVED_CALENDARS_CAPACITY,
+    ) {
+        Ok(saved_calendars) => {
+            println!(
+                "Loaded {} saved calendars from {}",
+                saved_calendars.len().await,
+                saved_calendars_path
+            );
+            saved_calendars
+        }
+        Err(e) => {
+            // Not persisting, so a file that fails to parse isn't overwritten
+            eprintln!(
+                "Could not load saved calendars from {}: {}. Saved calendars won't outlast the server.",
+                saved_calendars_path, e
+            );
+            SavedCalendars::in_memory(SAVED_CALENDARS_CAPACITY)
+        }
+    };
+
+    let public_url = std::env::var("PUBLIC_BASE_URL").ok();
+    let admin_token = std::env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty());
+    let graphql_playground = std::env::var("GRAPHQL_PLAYGROUND")
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"));
+    let overnight = match std::env::var("OVERNIGHT_HOURS") {
+        Ok(value) => HourRange::parse(&value).unwrap_or_else(|| {
+            eprintln!(
+                "Ignoring invalid OVERNIGHT_HOURS: {:?}. Expected a 24-hour range such as 00:00-05:00.",
+                value
+            );
+            filter::DEFAULT_OVERNIGHT
+        }),
+        Err(_) => filter::DEFAULT_OVERNIGHT,
+    };
+    let all_day_days = match std::env::var("ALL_DAY_DAYS") {
+        Ok(value) => filter::parse_all_day_days(&value).unwrap_or_else(|message| {
+            eprintln!("Ignoring invalid ALL_DAY_DAYS. {}.", message);
+            filter::DEFAULT_ALL_DAY_DAYS
+        }),
+        Err(_) => filter::DEFAULT_ALL_DAY_DAYS,
+    };
+    let open_ended_until = match std::env::var("OPEN_ENDED_UNTIL") {
+        Ok(value) => NaiveTime::parse_from_str(value.trim(), "%H:%M").unwrap_or_else(|_| {
+            eprintln!(
+                "Ignoring invalid OPEN_ENDED_UNTIL: {:?}. Expected a 24-hour time such as 04:00.",
+                value
+            );
+            filter::DEFAULT_SERVICE_DAY_END
+        }),
+        Err(_) => filter::DEFAULT_SERVICE_DAY_END,
+    };
+    let max_description_length = match std::env::var("MAX_DESCRIPTION_LENGTH") {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Ignoring invalid MAX_DESCRIPTION_LENGTH: {:?}. Expected a whole number of characters, or 0 for no limit.",
+                value
+            );
+            filter::DEFAULT_MAX_DESCRIPTION_LENGTH
+        }),
+        Err(_) => filter::DEFAULT_MAX_DESCRIPTION_LENGTH,
+    };
+    let refresh_interval = match std::env::var("REFRESH_INTERVAL") {
+        Ok(value) => filter::parse_refresh_interval(&value).unwrap_or_else(|message| {
+            eprintln!("Ignoring invalid REFRESH_INTERVAL. {}.", message);
+            filter::DEFAULT_REFRESH_INTERVAL
+        }),
+        Err(_) => filter::DEFAULT_REFRESH_INTERVAL,
+    };
+    let state = AppState {
+        graphql_playground,
+        overnight,
+        all_day_days,
+        open_ended_until,
+        max_description_length,
+        refresh_interval,
+        ..AppState::new(
+            stations,
+            bus_routes,
+            stop_sequences,
+            saved_calendars,
+            public_url,
+            admin_token,
         )
+    };
+
+    // Rate limiting: 10 requests per IP per second
+    let governor_conf = Arc::new(
+        GovernorConfigBuilder::default()
+            .per_second(10)
+            .burst_size(20)
+            .finish()
+            .ok_or("Failed to build governor config")?,
+    );
+
+    // Exports generate every line's calendar: 1 request per IP every 30 seconds, after a
+    // burst of 2
+    let export_governor_conf = Arc::new(
+        GovernorConfigBuilder::default()
+            .per_second(30)
+            .burst_size(2)
+            .finish()
+            .ok_or("Failed to build export governor config")?,
+    );
+
+    let cors = cors_layer(std::env::var("CORS_ALLOWED_ORIGINS").ok().as_deref());
+
+    let notifiers_path =
+        std::env::var("NOTIFIERS_JSON").unwrap_or_else(|_| "data/notifiers.json".to_string());
+    let mut webhooks = notify::load_webhooks(&notifiers_path, state.public_url.as_deref())
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "Could not load notifiers from {}: {}. Only SLACK_WEBHOOK_URL is posted to.",
+                notifiers_path, e
+            );
+            Vec::new()
+        });
+    webhooks.extend(slack_webhook_from_env());
+
+    let webhook_client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()?;
+    for webhook in webhooks {
+        println!(
+            "Posting new {} alerts for {} to {}",
+            webhook.min_severity().query_name(),
+            webhook.trains().join(", "),
+            webhook.service()
+        );
+        tokio::spawn(post_alerts(
+            state.alert_changes.subscribe(),
+            state.clock,
+            webhook_client.clone(),
+            webhook,
+        ));
+    }
+    tokio::spawn(broadcast_alert_changes(state.clone()));
+
+    let app = limited_routes(cors.clone())
         .layer(
             ServiceBuilder::new()
                 .layer(GovernorLayer {
-                    config: governor_conf.into(),
+                    config: governor_conf.clone(),
                 })
                 .layer(tower::limit::ConcurrencyLimitLayer::new(50)), // Max 50 concurrent requests
         )
+        // Streams stay open, so they'd soon use up the concurrency limit
+        .merge(streaming_routes(cors).layer(GovernorLayer {
+            config: governor_conf,
+        }))
+        .merge(
+            export_routes().layer(
+                ServiceBuilder::new()
+                    .layer(GovernorLayer {
+                        config: export_governor_conf,
+                    })
+                    .layer(tower::limit::ConcurrencyLimitLayer::new(5)), // Max 5 concurrent exports
+            ),
+        )
+        .merge(unlimited_routes())
+        .layer(middleware::from_fn(problem_json))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            count_requests,
+        ))
+        .layer(version_header_layer())
         .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
@@ -63,300 +658,6315 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn handle_train_calendar(
-    State(state): State<AppState>,
-    Path(train_name): Path<String>,
-) -> Response {
-    let train_name = train_name.strip_suffix(".ics").unwrap_or(&train_name);
+/// Routes subject to per-IP rate limiting and the concurrency limit. `cors` only applies to the
+/// JSON API routes.
+fn limited_routes(cors: CorsLayer) -> Router<AppState> {
+    Router::new()
+        .route("/", get(handle_index))
+        .route(
+            "/api/calendars/train/:train_name",
+            get(handle_train_calendar),
+        )
+        .route(
+            "/api/calendars/train/:train_name/accessibility",
+            get(handle_train_accessibility_calendar),
+        )
+        .route(
+            "/api/calendars/train/:train_name/accessibility.ics",
+            get(handle_train_accessibility_calendar),
+        )
+        .route(
+            "/api/calendars/trains/:train_names",
+            get(handle_trains_calendar),
+        )
+        .route("/api/calendars/commute", get(handle_commute_calendar))
+        .route("/api/calendars/commute.ics", get(handle_commute_calendar))
+        .route("/api/calendars", post(handle_save_calendar))
+        .route("/api/calendars/custom/:id", get(handle_custom_calendar))
+        .route("/api/calendars/all", get(handle_all_calendar))
+        .route("/api/calendars/all.ics", get(handle_all_calendar))
+        .route(
+            "/api/calendars/station/:stop_id",
+            get(handle_station_calendar),
+        )
+        .route("/api/calendars/lirr/:line", get(handle_lirr_calendar))
+        .route("/api/calendars/mnr/:line", get(handle_mnr_calendar))
+        .route("/api/calendars/path/:route", get(handle_path_calendar))
+        .route("/api/calendars/bus/:route", get(handle_bus_calendar))
+        .route(
+            "/api/calendars/accessibility/:stop_id",
+            get(handle_accessibility_calendar),
+        )
+        .route("/api/badge/:train_name", get(handle_badge))
+        .route("/api/feeds/train/:train_name", get(handle_train_feed))
+        .route("/api/feeds.opml", get(handle_feeds_opml))
+        .route("/api/docs", get(handle_docs))
+        .route(
+            "/api/graphql",
+            get(handle_graphql_playground).post(handle_graphql),
+        )
+        .route("/admin/cache/purge", post(handle_cache_purge))
+        .route("/train/:train_name", get(handle_train_page))
+        .route("/webcal/:train_name", get(handle_webcal))
+        .route(
+            "/subscribe/google/:train_name",
+            get(handle_subscribe_google),
+        )
+        .route(
+            "/subscribe/outlook/:train_name",
+            get(handle_subscribe_outlook),
+        )
+        .merge(json_api_routes().layer(cors))
+}
 
-    const VALID_TRAINS: &[&str] = &[
-        "A", "C", "E", "B", "D", "F", "M", "G", "J", "Z", "L", "N", "Q", "R", "W", "1", "2", "3",
-        "4", "5", "6", "7", "S", "SI",
-    ];
+/// The JSON API routes, which browsers on other sites may call.
+fn json_api_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/alerts/train/:train_name", get(handle_train_alerts))
+        .route("/api/alerts/:alert_id", get(handle_alert))
+        .route("/api/departures/:stop_id", get(handle_departures))
+        .route("/api/widget/station/:stop_id", get(handle_station_widget))
+        .route("/api/stations", get(handle_stations))
+        .route("/api/status.json", get(handle_status))
+        .route("/api/trains", get(handle_trains))
+        .route("/api/groups", get(handle_groups))
+        .route("/api/openapi.json", get(handle_openapi))
+        .route("/api/stats", get(handle_stats))
+}
 
-    if !VALID_TRAINS.contains(&train_name) {
-        return (
-            StatusCode::BAD_REQUEST,
-            format!(
-                "Invalid train line: {}. Train lines are case-sensitive.",
-                train_name
-            ),
+/// Allows cross-origin `GET`s from `allowed_origins`, a comma-separated list of origins such as
+/// `https://example.com`, or from any origin when it's unset or `*`.
+fn cors_layer(allowed_origins: Option<&str>) -> CorsLayer {
+    let allow_origin = match allowed_origins.map(str::trim) {
+        None | Some("") | Some("*") => AllowOrigin::any(),
+        Some(origins) => AllowOrigin::list(origins.split(',').filter_map(|origin| {
+            let origin = origin.trim();
+            match HeaderValue::from_str(origin) {
+                Ok(origin) => Some(origin),
+                Err(_) => {
+                    eprintln!("Ignoring invalid CORS origin: {:?}", origin);
+                    None
+                }
+            }
+        })),
+    };
+
+    // Responses also get `Vary: Origin` and the preflight request headers
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::HEAD])
+}
+
+/// Long-lived streaming routes, which are rate limited but exempt from the concurrency limit.
+fn streaming_routes(cors: CorsLayer) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/alerts/train/:train_name/stream",
+            get(handle_train_alert_stream),
         )
-            .into_response();
+        .route("/ws/alerts", get(handle_alerts_ws))
+        .layer(cors)
+}
+
+/// Routes that fan out to every line, which get a stricter rate limit than `limited_routes`.
+fn export_routes() -> Router<AppState> {
+    Router::new().route("/api/calendars/export.zip", get(handle_calendar_export))
+}
+
+/// Routes that bypass rate and concurrency limiting.
+fn unlimited_routes() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+        .route("/version", get(handle_version))
+        .route("/robots.txt", get(handle_robots))
+        .route("/sitemap.xml", get(handle_sitemap))
+        .route("/favicon.ico", get(handle_favicon))
+        .route("/favicon.svg", get(handle_favicon))
+}
+
+/// Adds `X-App-Version` to every response, so it shows up in bug reports.
+fn version_header_layer() -> SetResponseHeaderLayer<HeaderValue> {
+    SetResponseHeaderLayer::overriding(
+        HeaderName::from_static("x-app-version"),
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    )
+}
+
+/// Why a request failed. Each kind has one status code and problem type, so handlers only
+/// pick the kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProblemKind {
+    /// A query parameter or request body is invalid
+    InvalidParameter,
+    InvalidStopId,
+    UnknownTrainLine,
+    UnknownStop,
+    UnknownAlert,
+    UnknownCalendar,
+    /// An unknown bus, railroad or PATH route
+    UnknownRoute,
+    UnsupportedFormat,
+    NotAcceptable,
+    Unauthorized,
+    NotFound,
+    /// Station, bus route or stop sequence data the request needs isn't loaded
+    DataUnavailable,
+    /// An MTA feed couldn't be fetched
+    UpstreamUnavailable,
+    Internal,
+}
+
+impl ProblemKind {
+    fn status(self) -> StatusCode {
+        match self {
+            ProblemKind::InvalidParameter | ProblemKind::InvalidStopId => StatusCode::BAD_REQUEST,
+            ProblemKind::UnknownTrainLine
+            | ProblemKind::UnknownStop
+            | ProblemKind::UnknownAlert
+            | ProblemKind::UnknownCalendar
+            | ProblemKind::UnknownRoute
+            | ProblemKind::UnsupportedFormat
+            | ProblemKind::NotFound => StatusCode::NOT_FOUND,
+            ProblemKind::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            ProblemKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            ProblemKind::DataUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ProblemKind::UpstreamUnavailable => StatusCode::BAD_GATEWAY,
+            ProblemKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
-    // Check cache first
-    if let Some(cached_content) = state.cache.get(train_name).await {
-        println!("Cache hit for train: {}", train_name);
-        return (
-            StatusCode::OK,
-            [("Content-Type", "text/calendar; charset=utf-8")],
-            cached_content,
-        )
-            .into_response();
+    /// The problem document's `type`, e.g. `unknown-train-line`.
+    fn problem_type(self) -> &'static str {
+        match self {
+            ProblemKind::InvalidParameter => "invalid-parameter",
+            ProblemKind::InvalidStopId => "invalid-stop-id",
+            ProblemKind::UnknownTrainLine => "unknown-train-line",
+            ProblemKind::UnknownStop => "unknown-stop",
+            ProblemKind::UnknownAlert => "unknown-alert",
+            ProblemKind::UnknownCalendar => "unknown-calendar",
+            ProblemKind::UnknownRoute => "unknown-route",
+            ProblemKind::UnsupportedFormat => "unsupported-format",
+            ProblemKind::NotAcceptable => "not-acceptable",
+            ProblemKind::Unauthorized => "unauthorized",
+            ProblemKind::NotFound => "not-found",
+            ProblemKind::DataUnavailable => "data-unavailable",
+            ProblemKind::UpstreamUnavailable => "upstream-unavailable",
+            ProblemKind::Internal => "internal-error",
+        }
     }
 
-    println!("Cache miss - fetching calendar for train: {}", train_name);
+    fn title(self) -> &'static str {
+        match self {
+            ProblemKind::InvalidParameter => "Invalid parameter",
+            ProblemKind::InvalidStopId => "Invalid stop ID",
+            ProblemKind::UnknownTrainLine => "Unknown train line",
+            ProblemKind::UnknownStop => "Unknown stop",
+            ProblemKind::UnknownAlert => "Unknown alert",
+            ProblemKind::UnknownCalendar => "Unknown calendar",
+            ProblemKind::UnknownRoute => "Unknown route",
+            ProblemKind::UnsupportedFormat => "Unsupported format",
+            ProblemKind::NotAcceptable => "Not acceptable",
+            ProblemKind::Unauthorized => "Unauthorized",
+            ProblemKind::NotFound => "Not found",
+            ProblemKind::DataUnavailable => "Data unavailable",
+            ProblemKind::UpstreamUnavailable => "MTA feed unavailable",
+            ProblemKind::Internal => "Internal error",
+        }
+    }
+}
 
-    match nyc_train_time::generate_train_ics(train_name).await {
-        Ok(ics_content) => {
-            // Cache the result
-            state
-                .cache
-                .insert(train_name.to_string(), ics_content.clone())
-                .await;
+/// An error response. The body is `detail` as plain text, which [`problem_json`] swaps for an
+/// RFC 7807 `application/problem+json` document when the client asked for JSON.
+#[derive(Debug, Clone, PartialEq)]
+struct ApiError {
+    kind: ProblemKind,
+    detail: String,
+    /// Extra members of the problem document, such as an unknown line's `suggestions`
+    extensions: serde_json::Map<String, serde_json::Value>,
+}
 
-            (
-                StatusCode::OK,
-                [("Content-Type", "text/calendar; charset=utf-8")],
-                ics_content,
-            )
-                .into_response()
+impl ApiError {
+    fn new(kind: ProblemKind, detail: impl Into<String>) -> Self {
+        ApiError {
+            kind,
+            detail: detail.into(),
+            extensions: serde_json::Map::new(),
         }
-        Err(e) => {
-            eprintln!("Error generating calendar: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error generating calendar: {}", e),
-            )
-                .into_response()
+    }
+
+    fn invalid_parameter(detail: impl Into<String>) -> Self {
+        ApiError::new(ProblemKind::InvalidParameter, detail)
+    }
+
+    /// An unknown line, suggesting up to three close matches.
+    fn unknown_train_line(train_name: &str) -> Self {
+        let suggestions = lines::suggest(train_name);
+
+        let mut detail = format!("Unknown train line: {}.", train_name);
+        if let Some((last, rest)) = suggestions.split_last() {
+            let options = match rest {
+                [] => last.to_string(),
+                rest => format!("{} or {}", rest.join(", "), last),
+            };
+            detail.push_str(&format!(" Did you mean {}?", options));
+        }
+
+        let mut error = ApiError::new(ProblemKind::UnknownTrainLine, detail);
+        error
+            .extensions
+            .insert("suggestions".to_string(), suggestions.into());
+        error
+    }
+
+    fn invalid_stop_id(stop_id: &str) -> Self {
+        ApiError::new(
+            ProblemKind::InvalidStopId,
+            format!(
+                "Invalid stop ID: {}. Expected a GTFS stop ID such as A15, or A15N/A15S for a single platform.",
+                stop_id
+            ),
+        )
+    }
+
+    /// The RFC 7807 problem document.
+    fn problem(&self) -> serde_json::Value {
+        let mut problem = serde_json::json!({
+            "type": self.kind.problem_type(),
+            "title": self.kind.title(),
+            "status": self.kind.status().as_u16(),
+            "detail": self.detail,
+        });
+        for (name, value) in &self.extensions {
+            problem[name] = value.clone();
         }
+        problem
     }
 }
 
-async fn handle_index() -> Response {
-    let html = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>NYC Train Cal</title>
-    <style>
-        body {
-            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
-            max-width: 800px;
-            margin: 0 auto;
-            padding: 20px;
-            line-height: 1.6;
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.kind.status(), self.detail.clone()).into_response();
+        if self.kind == ProblemKind::Unauthorized {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
         }
-        h1 {
-            color: #333;
-            margin-top: 20px;
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// Whether errors for a request should be problem documents: when it accepts JSON, or asked
+/// for a `.json` path.
+fn wants_problem_json(path: &str, headers: &HeaderMap) -> bool {
+    path.ends_with(".json")
+        || accept_ranges(headers).iter().any(|(range, q)| {
+            (range == "application/json" || range == PROBLEM_JSON_CONTENT_TYPE) && *q > 0.0
+        })
+}
+
+/// Counts each request under the route it matched, for `GET /api/stats`.
+async fn count_requests(
+    State(state): State<AppState>,
+    request: axum::extract::Request,
+    next: Next,
+) -> Response {
+    if let Some(route) = request.extensions().get::<MatchedPath>() {
+        state.stats.record_request(route.as_str());
+    }
+    next.run(request).await
+}
+
+/// Renders [`ApiError`]s as problem documents for clients that asked for JSON, leaving the
+/// plain-text body for everyone else.
+async fn problem_json(request: axum::extract::Request, next: Next) -> Response {
+    let json = wants_problem_json(request.uri().path(), request.headers());
+    let mut response = next.run(request).await;
+
+    let Some(error) = response.extensions_mut().remove::<ApiError>() else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("Accept"));
+    if !json {
+        return Response::from_parts(parts, body);
+    }
+
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(error.problem().to_string()))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CalendarParams {
+    borough: Option<String>,
+    days: Option<String>,
+    hours: Option<String>,
+    when: Option<String>,
+    min_severity: Option<String>,
+    direction: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    lang: Option<String>,
+    placeholder: Option<String>,
+    digest: Option<String>,
+    include_express: Option<String>,
+    exclude_overnight: Option<String>,
+    all_day_days: Option<String>,
+    limit: Option<String>,
+    alarm: Option<String>,
+    plain: Option<String>,
+    compact: Option<String>,
+    tz: Option<String>,
+}
+
+impl CalendarParams {
+    /// The query parameter names, which saved calendars also use for their filters.
+    const NAMES: &[&str] = &[
+        "borough",
+        "days",
+        "hours",
+        "when",
+        "min_severity",
+        "direction",
+        "type",
+        "lang",
+        "placeholder",
+        "digest",
+        "include_express",
+        "exclude_overnight",
+        "all_day_days",
+        "limit",
+        "alarm",
+        "plain",
+        "compact",
+        "tz",
+    ];
+
+    /// The parameters of a saved calendar's filters.
+    fn from_filters(filters: &BTreeMap<String, String>) -> Result<Self, ApiError> {
+        if let Some(name) = filters
+            .keys()
+            .find(|name| !Self::NAMES.contains(&name.as_str()))
+        {
+            return Err(ApiError::new(
+                ProblemKind::InvalidParameter,
+                format!(
+                    "Unknown filter: {}. Expected one of: {}",
+                    name,
+                    Self::NAMES.join(", ")
+                ),
+            ));
         }
-        .line-group {
-            margin: 30px 0;
+
+        let filters = serde_json::to_value(filters).expect("filters serialize to JSON");
+        serde_json::from_value(filters).map_err(|e| ApiError::invalid_parameter(e.to_string()))
+    }
+
+    /// The filter for the calendar at `path`, e.g. `/api/calendars/train/A.ics`, which the
+    /// event about left out events links to without `?limit=`.
+    fn to_calendar_filter(&self, state: &AppState, path: &str) -> Result<EventFilter, ApiError> {
+        let mut event_filter = self.to_filter(state)?;
+        link_unlimited(state, &mut event_filter, path);
+        Ok(event_filter)
+    }
+
+    fn to_filter(&self, state: &AppState) -> Result<EventFilter, ApiError> {
+        let days = match &self.days {
+            Some(days) => filter::parse_days(days).map_err(ApiError::invalid_parameter)?,
+            None => filter::DEFAULT_DAYS,
+        };
+
+        let mut event_filter = EventFilter {
+            window: Some(Window {
+                start: (state.clock)(),
+                days,
+            }),
+            open_ended_until: Some(state.open_ended_until),
+            max_description_length: Some(state.max_description_length),
+            refresh_interval: Some(state.refresh_interval),
+            ..EventFilter::default()
+        };
+
+        if let Some(hours) = &self.hours {
+            event_filter.hours = filter::parse_hours(hours).map_err(ApiError::invalid_parameter)?;
         }
-        .train-grid {
-            display: grid;
-            grid-template-columns: repeat(auto-fill, minmax(80px, 1fr));
-            gap: 12px;
-            margin-bottom: 20px;
+
+        if let Some(when) = &self.when {
+            event_filter.when =
+                Some(filter::parse_when(when).map_err(ApiError::invalid_parameter)?);
         }
-        .train-link {
-            display: block;
-            padding: 20px;
-            text-align: center;
-            font-weight: bold;
-            font-size: 24px;
-            border-radius: 8px;
-            transition: transform 0.2s;
-            cursor: pointer;
-            border: none;
-            text-decoration: none;
+
+        if let Some(min_severity) = &self.min_severity {
+            event_filter.min_severity = Some(
+                filter::parse_min_severity(min_severity).map_err(ApiError::invalid_parameter)?,
+            );
         }
-        .train-link:hover {
-            transform: scale(1.05);
+
+        if let Some(direction) = &self.direction {
+            event_filter.direction =
+                Some(filter::parse_direction(direction).map_err(ApiError::invalid_parameter)?);
         }
-        .train-link.selected {
-            box-shadow: 0 0 0 3px #333;
+
+        if let Some(kind) = &self.kind {
+            event_filter.kind = filter::parse_kind(kind).map_err(ApiError::invalid_parameter)?;
         }
-        /* NYC Subway line colors */
-        .train-1, .train-2, .train-3 { background-color: #ee352e; color: white; }
-        .train-4, .train-5, .train-6 { background-color: #00933c; color: white; }
-        .train-7 { background-color: #b933ad; color: white; }
-        .train-A, .train-C, .train-E { background-color: #0039a6; color: white; }
-        .train-B, .train-D, .train-F, .train-M { background-color: #ff6319; color: white; }
-        .train-G { background-color: #6cbe45; color: white; }
-        .train-J, .train-Z { background-color: #996633; color: white; }
-        .train-L { background-color: #a7a9ac; color: white; }
-        .train-N, .train-Q, .train-R, .train-W { background-color: #fccc0a; color: black; }
-        .train-S, .train-SI { background-color: #808183; color: white; }
-        .url-section {
-            background-color: #f5f5f5;
-            padding: 20px;
-            border-radius: 8px;
-            margin: 30px 0;
-            display: none;
+
+        if let Some(lang) = &self.lang {
+            event_filter.language =
+                filter::parse_language(lang).map_err(ApiError::invalid_parameter)?;
         }
-        .url-section.visible {
-            display: block;
+
+        if let Some(placeholder) = &self.placeholder {
+            event_filter.placeholder = filter::parse_flag("placeholder", placeholder)
+                .map_err(ApiError::invalid_parameter)?;
         }
-        .url-container {
-            display: flex;
-            gap: 10px;
-            margin-top: 10px;
+
+        if let Some(digest) = &self.digest {
+            event_filter.digest =
+                Some(filter::parse_digest(digest).map_err(ApiError::invalid_parameter)?);
         }
-        .url-box {
-            flex: 1;
-            padding: 12px;
-            font-family: monospace;
-            font-size: 14px;
-            background-color: white;
-            border: 2px solid #ddd;
-            border-radius: 4px;
-            word-break: break-all;
+
+        if let Some(include_express) = &self.include_express {
+            event_filter.exclude_express = !filter::parse_flag("include_express", include_express)
+                .map_err(ApiError::invalid_parameter)?;
         }
-        .copy-btn {
-            padding: 12px 24px;
-            background-color: #0039a6;
-            color: white;
-            border: none;
-            border-radius: 4px;
-            cursor: pointer;
-            font-weight: bold;
-            transition: background-color 0.2s;
+
+        if let Some(exclude_overnight) = &self.exclude_overnight
+            && filter::parse_flag("exclude_overnight", exclude_overnight)
+                .map_err(ApiError::invalid_parameter)?
+        {
+            event_filter.exclude_overnight = Some(state.overnight);
         }
-        .copy-btn:hover {
-            background-color: #002d7a;
+
+        event_filter.all_day_days = Some(match &self.all_day_days {
+            Some(days) => filter::parse_all_day_days(days).map_err(ApiError::invalid_parameter)?,
+            None => state.all_day_days,
+        });
+
+        if let Some(limit) = &self.limit {
+            event_filter.limit =
+                Some(filter::parse_limit(limit).map_err(ApiError::invalid_parameter)?);
         }
-        .copy-btn.copied {
-            background-color: #00933c;
+
+        if let Some(alarm) = &self.alarm {
+            event_filter.alarm =
+                Some(filter::parse_alarm(alarm).map_err(ApiError::invalid_parameter)?);
         }
-        .instructions {
-            background-color: #f5f5f5;
-            padding: 20px;
-            border-radius: 8px;
-            margin-top: 30px;
+
+        if let Some(plain) = &self.plain {
+            event_filter.plain =
+                filter::parse_flag("plain", plain).map_err(ApiError::invalid_parameter)?;
         }
-        .instructions code {
-            background-color: #e0e0e0;
-            padding: 2px 6px;
-            border-radius: 3px;
-            font-family: monospace;
+
+        if let Some(compact) = &self.compact {
+            event_filter.compact =
+                filter::parse_flag("compact", compact).map_err(ApiError::invalid_parameter)?;
         }
-        @media (max-width: 600px) {
-            body {
-                padding: 15px;
+
+        if let Some(tz) = &self.tz {
+            event_filter.timezone =
+                Some(filter::parse_timezone(tz).map_err(ApiError::invalid_parameter)?);
+        }
+
+        if let Some(borough) = &self.borough {
+            event_filter.boroughs =
+                filter::parse_boroughs(borough).map_err(ApiError::invalid_parameter)?;
+
+            if state.stations.is_empty() {
+                return Err(ApiError::new(
+                    ProblemKind::DataUnavailable,
+                    "Borough filtering is unavailable because station data is not loaded."
+                        .to_string(),
+                ));
             }
-            h1 {
-                font-size: 24px;
-                margin-top: 10px;
+        }
+
+        Ok(event_filter)
+    }
+}
+
+/// Splits a path segment such as `A.ics` into its name and lowercased extension.
+fn split_extension(segment: &str) -> (&str, Option<String>) {
+    match segment.rsplit_once('.') {
+        Some((name, extension)) => (name, Some(extension.to_lowercase())),
+        None => (segment, None),
+    }
+}
+
+/// The representations the train calendar endpoint can serve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CalendarFormat {
+    Ics,
+    Json,
+}
+
+impl CalendarFormat {
+    /// Every format, in order of preference when a client accepts several equally.
+    const ALL: [CalendarFormat; 2] = [CalendarFormat::Ics, CalendarFormat::Json];
+
+    fn extension(self) -> &'static str {
+        match self {
+            CalendarFormat::Ics => "ics",
+            CalendarFormat::Json => "json",
+        }
+    }
+
+    fn media_type(self) -> &'static str {
+        match self {
+            CalendarFormat::Ics => "text/calendar",
+            CalendarFormat::Json => "application/json",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<CalendarFormat> {
+        CalendarFormat::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    fn list(to_str: fn(CalendarFormat) -> &'static str) -> String {
+        let names: Vec<&str> = CalendarFormat::ALL.into_iter().map(to_str).collect();
+        names.join(", ")
+    }
+}
+
+/// Picks the calendar format a client prefers according to its `Accept` header.
+///
+/// Falls back to ICS when there's no `Accept` header. Returns `None` when the client only
+/// accepts formats we can't serve.
+fn negotiate_format(headers: &HeaderMap) -> Option<CalendarFormat> {
+    let ranges = accept_ranges(headers);
+
+    if ranges.is_empty() {
+        return Some(CalendarFormat::Ics);
+    }
+
+    let mut best: Option<(CalendarFormat, f32)> = None;
+    for format in CalendarFormat::ALL {
+        let media_type = format.media_type();
+        let main_type = media_type.split('/').next().unwrap_or_default();
+
+        // The most specific matching range decides the quality of each format
+        let q = ranges
+            .iter()
+            .filter_map(|(range, q)| match range.as_str() {
+                "*/*" => Some((0, *q)),
+                range if range == media_type => Some((2, *q)),
+                range if range.strip_suffix("/*") == Some(main_type) => Some((1, *q)),
+                _ => None,
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, q)| q);
+
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((format, q));
+        }
+    }
+
+    best.map(|(format, _)| format)
+}
+
+/// The `Accept` header's (media range, q) pairs, e.g. ("text/*", 0.5).
+fn accept_ranges(headers: &HeaderMap) -> Vec<(String, f32)> {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media_range = parts.next().filter(|r| !r.is_empty())?.to_lowercase();
+            let q = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse().ok())?;
+            Some((media_range, q))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TrainStationsParams {
+    stations: Option<String>,
+}
+
+async fn handle_train_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_name): Path<String>,
+    Query(params): Query<CalendarParams>,
+    Query(stations_params): Query<TrainStationsParams>,
+) -> Response {
+    let (train_name, extension) = split_extension(&train_name);
+    let train_name = lines::normalize(train_name);
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    // An explicit extension wins over the Accept header
+    let format = match extension {
+        Some(extension) => match CalendarFormat::from_extension(&extension) {
+            Some(format) => format,
+            None => {
+                return ApiError::new(
+                    ProblemKind::UnsupportedFormat,
+                    format!(
+                        "Unsupported format: {}. Supported formats: {}",
+                        extension,
+                        CalendarFormat::list(CalendarFormat::extension)
+                    ),
+                )
+                .into_response();
             }
-            .train-grid {
-                grid-template-columns: repeat(auto-fill, minmax(60px, 1fr));
-                gap: 8px;
+        },
+        None => match negotiate_format(&request.headers) {
+            Some(format) => format,
+            None => {
+                return ApiError::new(
+                    ProblemKind::NotAcceptable,
+                    format!(
+                        "None of the accepted media types can be served. Supported types: {}",
+                        CalendarFormat::list(CalendarFormat::media_type)
+                    ),
+                )
+                .into_response();
             }
-            .train-link {
-                padding: 15px;
-                font-size: 20px;
+        },
+    };
+
+    let mut event_filter = match params.to_filter(&state) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    if let Some(stations) = &stations_params.stations {
+        match line_stops(&state, &train_name, trains, stations) {
+            Ok(stops) => event_filter.stops = stops,
+            Err(e) => return e.into_response(),
+        }
+    }
+    let path = format!("/api/calendars/train/{}.ics", train_name);
+    link_unlimited(&state, &mut event_filter, &path);
+
+    let mut response = match format {
+        CalendarFormat::Ics => {
+            serve_calendar(&state, &request, &train_name, trains, &event_filter).await
+        }
+        CalendarFormat::Json => {
+            let cache_key = format!("json:{}", calendar_cache_key(&train_name, &event_filter));
+            serve_cached(&state, &request, cache_key, JSON_CONTENT_TYPE, |events| {
+                nyc_train_time::render_trains_json(events, trains, &event_filter, &state.stations)
+            })
+            .await
+        }
+    };
+
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("Accept"));
+    response
+}
+
+/// The stations in a `?stations=` list for `train_name`, which must all be on the line.
+fn line_stops(
+    state: &AppState,
+    train_name: &str,
+    trains: &[&str],
+    stations: &str,
+) -> Result<Vec<String>, ApiError> {
+    if state.stations.is_empty() {
+        return Err(ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Station filtering is unavailable because station data is not loaded.",
+        ));
+    }
+
+    let stops = filter::parse_stations(stations);
+    if stops.is_empty() {
+        return Err(ApiError::invalid_parameter(
+            "Missing stations. Expected comma-separated stop IDs such as ?stations=L08,L06.",
+        ));
+    }
+
+    let unknown: Vec<&str> = stops
+        .iter()
+        .filter(|stop_id| !state.stations.serves(stop_id, trains))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        return Err(ApiError::invalid_parameter(format!(
+            "Unknown stations for the {} line: {}.",
+            train_name,
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(stops)
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AlertsParams {
+    accessibility: Option<String>,
+}
+
+async fn handle_train_alerts(
+    State(state): State<AppState>,
+    Path(train_name): Path<String>,
+    Query(params): Query<AlertsParams>,
+) -> Response {
+    let train_name = lines::normalize(train_name.strip_suffix(".json").unwrap_or(&train_name));
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    let accessibility = match &params.accessibility {
+        Some(value) => match filter::parse_flag("accessibility", value) {
+            Ok(accessibility) => accessibility,
+            Err(e) => return ApiError::invalid_parameter(e).into_response(),
+        },
+        None => false,
+    };
+
+    if accessibility && state.stations.is_empty() {
+        return stations_unavailable_response();
+    }
+
+    match state.subway_feed().await {
+        Ok(feed) => {
+            let train_events = if accessibility {
+                nyc_train_time::filter_accessibility_events_for_trains(
+                    &feed.events,
+                    trains,
+                    &state.stations,
+                    true,
+                )
+            } else {
+                nyc_train_time::filter_events_for_trains(&feed.events, trains, true)
+            };
+            Json(train_events).into_response()
+        }
+        Err(e) => {
+            eprintln!("Error fetching alerts: {}", e);
+            ApiError::new(
+                ProblemKind::UpstreamUnavailable,
+                format!("Error fetching alerts: {}", e),
+            )
+            .into_response()
+        }
+    }
+}
+
+/// Streams a line's alerts as server-sent events: a `snapshot` of its current alerts, then
+/// `alert_added`, `alert_updated` and `alert_removed` as the feed changes. A client that falls
+/// too far behind to be sent every change gets a fresh `snapshot` instead.
+async fn handle_train_alert_stream(
+    State(state): State<AppState>,
+    Path(train_name): Path<String>,
+) -> Response {
+    let train_name = lines::normalize(&train_name);
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    // Subscribe before taking the snapshot so no change falls between the two
+    let changes = state.alert_changes.subscribe();
+
+    let feed = match state.subway_feed().await {
+        Ok(feed) => feed,
+        Err(e) => {
+            eprintln!("Error fetching alerts: {}", e);
+            return ApiError::new(
+                ProblemKind::UpstreamUnavailable,
+                format!("Error fetching alerts: {}", e),
+            )
+            .into_response();
+        }
+    };
+
+    let snapshot = Event::default()
+        .event("snapshot")
+        .json_data(alert_snapshot(&feed, trains));
+
+    // The stream, and with it the subscription, is dropped when the client disconnects
+    let updates = BroadcastStream::new(changes)
+        .then(move |change| {
+            let state = state.clone();
+            async move {
+                match change {
+                    Ok(change) => Some(change.affects_any(trains).then(|| {
+                        Event::default()
+                            .event(change.kind.event_name())
+                            .json_data(&change.alert)
+                    })),
+                    // A client that fell behind gets a fresh snapshot in place of the changes
+                    // it missed. Without a feed to take one from, the stream ends, and the
+                    // browser reconnects for a snapshot once there is one.
+                    Err(BroadcastStreamRecvError::Lagged(_)) => {
+                        let feed = state.subway_feed().await.ok()?;
+                        let snapshot = alert_snapshot(&feed, trains);
+                        Some(Some(Event::default().event("snapshot").json_data(snapshot)))
+                    }
+                }
             }
-            .url-container {
-                flex-direction: column;
+        })
+        .map_while(|event| event)
+        .filter_map(|event| event);
+
+    Sse::new(tokio_stream::once(snapshot).chain(updates))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// The most lines a WebSocket connection can subscribe to, counting each line of a group.
+const MAX_WS_SUBSCRIPTIONS: usize = 10;
+/// How often idle WebSocket connections are pinged, so proxies don't drop them
+const WS_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A WebSocket client's subscription, e.g. `{"subscribe": ["A", "NQRW"]}`.
+#[derive(Debug, Deserialize)]
+struct WsSubscription {
+    subscribe: Vec<String>,
+}
+
+/// Parses a subscription message into the train lines it covers.
+fn parse_ws_subscription(text: &str) -> Result<Vec<&'static str>, String> {
+    let subscription: WsSubscription = serde_json::from_str(text)
+        .map_err(|_| r#"Expected a subscription such as {"subscribe": ["A", "L"]}"#.to_string())?;
+
+    let mut trains: Vec<&'static str> = Vec::new();
+    for name in &subscription.subscribe {
+        let name = lines::normalize(name);
+        let resolved =
+            lines::resolve(&name).ok_or_else(|| format!("Unknown train line: {}.", name))?;
+        for train in resolved {
+            if !trains.contains(train) {
+                trains.push(train);
             }
-            .copy-btn {
-                width: 100%;
+        }
+    }
+
+    if trains.len() > MAX_WS_SUBSCRIPTIONS {
+        return Err(format!(
+            "Too many lines: {}. A connection can subscribe to at most {}.",
+            trains.len(),
+            MAX_WS_SUBSCRIPTIONS
+        ));
+    }
+
+    Ok(trains)
+}
+
+/// Live alert changes over a WebSocket. Clients send a subscription listing lines, get a
+/// `snapshot` of their alerts, then an `alert_added`, `alert_updated` or `alert_removed`
+/// message whenever one of them changes, or a fresh `snapshot` if they fell too far behind to
+/// be sent every change.
+async fn handle_alerts_ws(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| stream_alerts_ws(state, socket))
+}
+
+async fn stream_alerts_ws(state: AppState, mut socket: WebSocket) {
+    let mut changes = state.alert_changes.subscribe();
+    let mut trains: Vec<&'static str> = Vec::new();
+    let mut ping = tokio::time::interval_at(
+        tokio::time::Instant::now() + WS_PING_INTERVAL,
+        WS_PING_INTERVAL,
+    );
+    let mut awaiting_pong = false;
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match parse_ws_subscription(&text) {
+                    Ok(subscribed) => {
+                        trains = subscribed;
+                        ws_snapshot(&state, &trains).await
+                    }
+                    Err(message) => serde_json::json!({ "type": "error", "message": message }),
+                },
+                Some(Ok(Message::Pong(_))) => {
+                    awaiting_pong = false;
+                    continue;
+                }
+                // Pings are answered automatically
+                Some(Ok(Message::Ping(_) | Message::Binary(_))) => continue,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            change = changes.recv() => match change {
+                Ok(change) if change.affects_any(&trains) => serde_json::json!({
+                    "type": change.kind.event_name(),
+                    "alert": change.alert,
+                }),
+                // Changes were missed, so the client's alerts could be out of date
+                Err(broadcast::error::RecvError::Lagged(_)) if !trains.is_empty() => {
+                    ws_snapshot(&state, &trains).await
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = ping.tick() => {
+                // No pong since the last ping means the connection is gone
+                if awaiting_pong || socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                awaiting_pong = true;
+                continue;
             }
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
         }
-    </style>
-</head>
-<body>
-    <h1>🚇 NYC Train Cal</h1>
-    <p>Subscribe to service alerts for your train line and plan your days better. Click a line to get its calendar subscription URL:</p>
-    
-    <div class="train-grid">
-        <button class="train-link train-A" data-train="A">A</button>
-        <button class="train-link train-C" data-train="C">C</button>
-        <button class="train-link train-E" data-train="E">E</button>
-        <button class="train-link train-B" data-train="B">B</button>
-        <button class="train-link train-D" data-train="D">D</button>
-        <button class="train-link train-F" data-train="F">F</button>
-        <button class="train-link train-M" data-train="M">M</button>
-        <button class="train-link train-G" data-train="G">G</button>
-        <button class="train-link train-J" data-train="J">J</button>
-        <button class="train-link train-Z" data-train="Z">Z</button>
-        <button class="train-link train-L" data-train="L">L</button>
-        <button class="train-link train-N" data-train="N">N</button>
-        <button class="train-link train-Q" data-train="Q">Q</button>
-        <button class="train-link train-R" data-train="R">R</button>
-        <button class="train-link train-W" data-train="W">W</button>
-        <button class="train-link train-1" data-train="1">1</button>
-        <button class="train-link train-2" data-train="2">2</button>
-        <button class="train-link train-3" data-train="3">3</button>
-        <button class="train-link train-4" data-train="4">4</button>
-        <button class="train-link train-5" data-train="5">5</button>
-        <button class="train-link train-6" data-train="6">6</button>
-        <button class="train-link train-7" data-train="7">7</button>
-        <button class="train-link train-S" data-train="S">S</button>
-        <button class="train-link train-SI" data-train="SI">SI</button>
-    </div>
-    
-    <div class="url-section" id="urlSection">
-        <h3>Calendar Subscription URL</h3>
-        <p>Copy this URL and add it to your calendar app:</p>
-        <div class="url-container">
-            <div class="url-box" id="urlBox"></div>
-            <button class="copy-btn" id="copyBtn">Copy</button>
-        </div>
-    </div>
-    
-    <div class="instructions">
-        <h2>How to Subscribe</h2>
-        <ol>
-            <li>Click on a train line above to get its calendar URL</li>
-            <li>Click the "Copy" button to copy the URL</li>
-            <li>In your calendar app (Google Calendar, Apple Calendar, Outlook, etc.), look for "Subscribe to calendar" or "Add calendar by URL"</li>
-            <li>Paste the URL you copied</li>
-            <li>Your calendar will stay updated with MTA service alerts and planned service changes so you can plan ahead!</li>
-        </ol>
-    </div>
+    }
+}
 
-    <script>
-        const trainButtons = document.querySelectorAll('.train-link');
-        const urlSection = document.getElementById('urlSection');
-        const urlBox = document.getElementById('urlBox');
-        const copyBtn = document.getElementById('copyBtn');
-        
-        trainButtons.forEach(button => {
-            button.addEventListener('click', () => {
-                const train = button.dataset.train;
-                const url = window.location.origin + '/api/calendars/train/' + train + '.ics';
-                
-                // Update selected state
-                trainButtons.forEach(btn => btn.classList.remove('selected'));
-                button.classList.add('selected');
-                
-                // Show URL section
-                urlSection.classList.add('visible');
-                urlBox.textContent = url;
-                
-                // Reset copy button
-                copyBtn.textContent = 'Copy';
-                copyBtn.classList.remove('copied');
-                
-                // Scroll to URL section
-                urlSection.scrollIntoView({ behavior: 'smooth', block: 'nearest' });
-            });
-        });
-        
-        copyBtn.addEventListener('click', () => {
-            const url = urlBox.textContent;
-            navigator.clipboard.writeText(url).then(() => {
-                copyBtn.textContent = 'Copied!';
-                copyBtn.classList.add('copied');
-                setTimeout(() => {
-                    copyBtn.textContent = 'Copy';
-                    copyBtn.classList.remove('copied');
-                }, 2000);
-            });
+/// The alerts in `feed` that affect any of `trains`, for the live streams' snapshots.
+fn alert_snapshot<'a>(feed: &'a Feed, trains: &[&str]) -> Vec<&'a AlertDetail> {
+    feed.alerts
+        .iter()
+        .filter(|alert| changes::alert_affects_any(alert, trains))
+        .collect()
+}
+
+async fn ws_snapshot(state: &AppState, trains: &[&str]) -> serde_json::Value {
+    match state.subway_feed().await {
+        Ok(feed) => {
+            let alerts = alert_snapshot(&feed, trains);
+            serde_json::json!({ "type": "snapshot", "lines": trains, "alerts": alerts })
+        }
+        Err(e) => serde_json::json!({
+            "type": "error",
+            "message": format!("Error fetching alerts: {}", e),
+        }),
+    }
+}
+
+/// Polls the subway feed and broadcasts how its alerts change to the live streams.
+///
+/// Polling goes through the feed cache, so streams never add requests to the MTA API.
+async fn broadcast_alert_changes(state: AppState) {
+    let mut interval = tokio::time::interval(CACHE_TTL);
+    let mut previous: Option<Arc<Feed>> = None;
+
+    loop {
+        interval.tick().await;
+
+        let feed = match state.subway_feed().await {
+            Ok(feed) => feed,
+            Err(e) => {
+                eprintln!("Error polling alerts: {}", e);
+                continue;
+            }
+        };
+
+        if let Some(previous) = &previous
+            && !Arc::ptr_eq(previous, &feed)
+        {
+            publish_alert_changes(&state, previous, &feed);
+        }
+        previous = Some(feed);
+    }
+}
+
+fn publish_alert_changes(state: &AppState, previous: &Feed, current: &Feed) {
+    for change in changes::diff_alerts(&previous.alerts, &current.alerts) {
+        // Sending only fails when nobody is streaming
+        let _ = state.alert_changes.send(Arc::new(change));
+    }
+}
+
+/// The Slack webhook configured by `SLACK_WEBHOOK_URL`, `SLACK_LINES` and
+/// `SLACK_MIN_SEVERITY`, alongside those in the notifiers config. `None` when no webhook is set
+/// or the config is invalid.
+fn slack_webhook_from_env() -> Option<Webhook> {
+    let webhook_url = std::env::var("SLACK_WEBHOOK_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+
+    let trains = match std::env::var("SLACK_LINES") {
+        Ok(list) => match notify::parse_trains(list.split(',')) {
+            Ok(trains) => trains,
+            Err(e) => {
+                eprintln!(
+                    "Invalid SLACK_LINES: {} Slack notifications are disabled.",
+                    e
+                );
+                return None;
+            }
+        },
+        Err(_) => lines::train_ids(),
+    };
+
+    let min_severity = match std::env::var("SLACK_MIN_SEVERITY") {
+        Ok(name) => match Severity::from_query_name(&name.trim().to_lowercase()) {
+            Some(severity) => severity,
+            None => {
+                eprintln!(
+                    "Invalid SLACK_MIN_SEVERITY: {}. Expected one of: {}. Slack notifications are disabled.",
+                    name,
+                    Severity::ALL.map(|s| s.query_name()).join(", ")
+                );
+                return None;
+            }
+        },
+        Err(_) => Severity::Severe,
+    };
+
+    Some(Webhook::new(
+        webhook_url,
+        Box::new(Slack),
+        trains,
+        min_severity,
+    ))
+}
+
+/// Posts new alerts for the webhook's lines to it.
+///
+/// Alerts come from the same feed diffs as the live streams, which start from whatever is in
+/// the feed when the server starts, so a restart doesn't repost alerts that are already there.
+async fn post_alerts(
+    mut changes: broadcast::Receiver<Arc<AlertChange>>,
+    clock: fn() -> DateTime<Utc>,
+    client: reqwest::Client,
+    mut webhook: Webhook,
+) {
+    loop {
+        let change = match changes.recv().await {
+            Ok(change) => change,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!(
+                    "{} notifications skipped {} alert changes",
+                    webhook.service(),
+                    skipped
+                );
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        let Some(payload) = webhook.message(&change, clock()) else {
+            continue;
+        };
+
+        if let Err(e) = notify::post(&client, &webhook.url, &payload).await {
+            eprintln!(
+                "Error posting alert {} to {}: {}",
+                change.alert.id,
+                webhook.service(),
+                e
+            );
+        }
+    }
+}
+
+/// Elevator outages at stations a line serves, and its service changes that the MTA flags as
+/// affecting accessible stations.
+async fn handle_train_accessibility_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_name): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let train_name = lines::normalize(&train_name);
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    // Elevator outages only name the station, so which lines they affect comes from station data
+    if state.stations.is_empty() {
+        return stations_unavailable_response();
+    }
+
+    let path = format!("/api/calendars/train/{}/accessibility.ics", train_name);
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key(
+        &format!("train-accessibility:{}", train_name),
+        &event_filter,
+    );
+    serve_cached_ics(&state, &request, cache_key, |events| {
+        nyc_train_time::render_trains_accessibility_ics(
+            events,
+            &train_name,
+            trains,
+            &event_filter,
+            &state.stations,
+        )
+    })
+    .await
+}
+
+fn stations_unavailable_response() -> Response {
+    ApiError::new(
+        ProblemKind::DataUnavailable,
+        "Accessibility alerts for a line are unavailable because station data is not loaded.",
+    )
+    .into_response()
+}
+
+async fn handle_trains_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_names): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let train_names = train_names.strip_suffix(".ics").unwrap_or(&train_names);
+
+    let mut trains: Vec<&str> = Vec::new();
+    for name in train_names.split(',').map(lines::normalize) {
+        match lines::resolve(&name) {
+            Some(resolved) => trains.extend(resolved),
+            None => return ApiError::unknown_train_line(&name).into_response(),
+        }
+    }
+
+    // Sort and dedupe so `F,L` and `L,F` share a cache entry
+    trains.sort_unstable();
+    trains.dedup();
+
+    let path = format!("/api/calendars/trains/{}.ics", trains.join(","));
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    serve_calendar(&state, &request, &trains.join(", "), &trains, &event_filter).await
+}
+
+async fn handle_alert(State(state): State<AppState>, Path(alert_id): Path<String>) -> Response {
+    match state.subway_feed().await {
+        Ok(feed) => match feed.find_alert(&alert_id) {
+            Some(alert) => Json(alert).into_response(),
+            None => ApiError::new(
+                ProblemKind::UnknownAlert,
+                format!("Unknown alert: {}.", alert_id),
+            )
+            .into_response(),
+        },
+        Err(e) => {
+            eprintln!("Error fetching alerts: {}", e);
+            ApiError::new(
+                ProblemKind::UpstreamUnavailable,
+                format!("Error fetching alerts: {}", e),
+            )
+            .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DeparturesParams {
+    limit: Option<String>,
+}
+
+/// The next trains to leave a station or platform in each direction, from the trip-updates
+/// feeds of the lines that serve it.
+async fn handle_departures(
+    State(state): State<AppState>,
+    Path(stop_id): Path<String>,
+    Query(params): Query<DeparturesParams>,
+) -> Response {
+    let stop_id = stop_id.strip_suffix(".json").unwrap_or(&stop_id);
+
+    if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+        return ApiError::invalid_stop_id(stop_id).into_response();
+    }
+
+    let limit = match &params.limit {
+        Some(limit) => match departures::parse_limit(limit) {
+            Ok(limit) => limit,
+            Err(e) => return ApiError::invalid_parameter(e).into_response(),
+        },
+        None => departures::DEFAULT_LIMIT,
+    };
+
+    // Which feeds to fetch depends on the lines serving the station
+    if state.stations.is_empty() {
+        return ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Departures are unavailable because station data is not loaded.",
+        )
+        .into_response();
+    }
+
+    let Some(station) = state.stations.get(stop_id) else {
+        return ApiError::new(
+            ProblemKind::UnknownStop,
+            format!("Unknown stop: {}.", stop_id),
+        )
+        .into_response();
+    };
+
+    let trips = match state.station_trips(station).await {
+        Ok(trips) => trips,
+        Err(e) => {
+            eprintln!("Error fetching trip updates: {}", e);
+            return ApiError::new(
+                ProblemKind::UpstreamUnavailable,
+                format!("Error fetching departures: {}", e),
+            )
+            .into_response();
+        }
+    };
+
+    let departures =
+        departures::next_departures(&trips, stop_id, &state.stations, (state.clock)(), limit);
+
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", TRIPS_CACHE_TTL.as_secs()),
+        )],
+        Json(serde_json::json!({
+            "stop_id": stop_id,
+            "name": station.name,
+            "departures": departures,
+        })),
+    )
+        .into_response()
+}
+
+/// A station's alerts in effect and its next trains in each direction, in one response for
+/// kiosk pages. When trip updates can't be fetched, the alerts are still served, with
+/// `arrivals` null.
+async fn handle_station_widget(
+    State(state): State<AppState>,
+    Path(stop_id): Path<String>,
+    Query(params): Query<DeparturesParams>,
+) -> Response {
+    let stop_id = stop_id.strip_suffix(".json").unwrap_or(&stop_id);
+
+    if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+        return ApiError::invalid_stop_id(stop_id).into_response();
+    }
+
+    let limit = match &params.limit {
+        Some(limit) => match departures::parse_limit(limit) {
+            Ok(limit) => limit,
+            Err(e) => return ApiError::invalid_parameter(e).into_response(),
+        },
+        None => WIDGET_ARRIVALS,
+    };
+
+    if state.stations.is_empty() {
+        return ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Station widgets are unavailable because station data is not loaded.",
+        )
+        .into_response();
+    }
+
+    let Some(station) = state.stations.get(stop_id) else {
+        return ApiError::new(
+            ProblemKind::UnknownStop,
+            format!("Unknown stop: {}.", stop_id),
+        )
+        .into_response();
+    };
+
+    let feed = match state.subway_feed().await {
+        Ok(feed) => feed,
+        Err(e) => {
+            eprintln!("Error fetching alerts: {}", e);
+            return ApiError::new(
+                ProblemKind::UpstreamUnavailable,
+                format!("Error fetching alerts: {}", e),
+            )
+            .into_response();
+        }
+    };
+
+    let now = (state.clock)();
+    let arrivals = match state.station_trips(station).await {
+        Ok(trips) => Some(departures::next_departures(
+            &trips,
+            stop_id,
+            &state.stations,
+            now,
+            limit,
+        )),
+        Err(e) => {
+            eprintln!("Error fetching trip updates: {}", e);
+            None
+        }
+    };
+
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", TRIPS_CACHE_TTL.as_secs()),
+        )],
+        Json(StationWidget::new(station, &feed.alerts, arrivals, now)),
+    )
+        .into_response()
+}
+
+/// Every line's status and how many alerts it has in effect and coming up, like the status
+/// board on mta.info.
+async fn handle_status(State(state): State<AppState>, request: DocumentRequest) -> Response {
+    serve_cached(
+        &state,
+        &request,
+        "json:status".to_string(),
+        JSON_CONTENT_TYPE,
+        |events| nyc_train_time::render_status_json(events, (state.clock)()),
+    )
+    .await
+}
+
+/// An SVG badge showing a line's current status, for embedding in wikis and dashboards.
+async fn handle_badge(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_name): Path<String>,
+) -> Response {
+    let train_name = lines::normalize(train_name.strip_suffix(".svg").unwrap_or(&train_name));
+
+    let Some(line) = lines::find_line(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    let cache_key = format!("badge:{}", line.id);
+    serve_cached(&state, &request, cache_key, SVG_CONTENT_TYPE, |events| {
+        let events = nyc_train_time::filter_events_for_trains(events, &[line.id], true);
+        let status = LineStatus::from_events(&events, (state.clock)());
+        badge::render_badge(line, status)
+    })
+    .await
+}
+
+/// A line's alerts as a web page, for sharing during incidents with people who don't use
+/// the calendar.
+async fn handle_train_page(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_name): Path<String>,
+) -> Response {
+    let train_name = lines::normalize(&train_name);
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    // The page is cached for everyone, so its links can only be absolute with
+    // `PUBLIC_BASE_URL`. Without it, the subscribe button goes to the webcal page, which isn't
+    // cached and builds its links from the request.
+    let path = format!("/api/calendars/train/{}.ics", train_name);
+    let (webcal, calendar_url) = match state.public_url() {
+        Some(public_url) => {
+            let calendar_url = format!("{}{}", public_url, path);
+            (webcal_url(&calendar_url), calendar_url)
+        }
+        None => (format!("/webcal/{}", train_name), path),
+    };
+    let links = SubscribeLinks {
+        webcal_url: webcal,
+        google_url: format!("/subscribe/google/{}", train_name),
+        outlook_url: format!("/subscribe/outlook/{}", train_name),
+        calendar_url,
+    };
+
+    let cache_key = format!("page:{}", train_name);
+    serve_cached(&state, &request, cache_key, HTML_CONTENT_TYPE, |events| {
+        nyc_train_time::render_trains_page(events, &train_name, trains, &links, (state.clock)())
+    })
+    .await
+}
+
+async fn handle_train_feed(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(train_name): Path<String>,
+) -> Response {
+    let (train_name, is_atom) = match train_name.strip_suffix(".atom") {
+        Some(train_name) => (train_name, true),
+        None => (
+            train_name.strip_suffix(".rss").unwrap_or(&train_name),
+            false,
+        ),
+    };
+
+    let train_name = lines::normalize(train_name);
+
+    let Some(trains) = lines::resolve(&train_name) else {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    };
+
+    // Feeds are cached for everyone, so they only link absolutely under `PUBLIC_BASE_URL`
+    let link = format!("{}/", state.public_url().unwrap_or_default());
+
+    if is_atom {
+        serve_cached(
+            &state,
+            &request,
+            format!("atom:{}", train_name),
+            ATOM_CONTENT_TYPE,
+            |events| {
+                nyc_train_time::render_trains_atom(
+                    events,
+                    &train_name,
+                    trains,
+                    &link,
+                    (state.clock)(),
+                )
+            },
+        )
+        .await
+    } else {
+        serve_cached(
+            &state,
+            &request,
+            format!("rss:{}", train_name),
+            RSS_CONTENT_TYPE,
+            |events| nyc_train_time::render_trains_rss(events, &train_name, trains, &link),
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FeedsOpmlParams {
+    format: Option<String>,
+}
+
+/// Every line's feed as an OPML subscription list, for importing into a feed reader.
+async fn handle_feeds_opml(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<FeedsOpmlParams>,
+) -> Response {
+    let format = match params.format.as_deref().map(str::trim) {
+        Some(format) => match FeedFormat::from_query_name(format) {
+            Some(format) => format,
+            None => {
+                let expected: Vec<&str> = FeedFormat::ALL.iter().map(|f| f.query_name()).collect();
+                return ApiError::new(
+                    ProblemKind::InvalidParameter,
+                    format!(
+                        "Unknown feed format: {}. Expected one of: {}",
+                        format,
+                        expected.join(", ")
+                    ),
+                )
+                .into_response();
+            }
+        },
+        None => FeedFormat::default(),
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, OPML_CONTENT_TYPE),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        opml::generate_opml(&state.base_url(&headers), format),
+    )
+        .into_response()
+}
+
+/// Liveness check for load balancers; never touches the cache or the MTA API.
+async fn handle_healthz(State(state): State<AppState>) -> Response {
+    Json(serde_json::json!({
+        "status": "ok",
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+    }))
+    .into_response()
+}
+
+/// Counters for debugging a running server with `curl`, without setting up metrics scraping.
+async fn handle_stats(State(state): State<AppState>) -> Response {
+    let stats = state.stats.snapshot();
+    Json(serde_json::json!({
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+        "requests": {
+            "total": stats.total_requests,
+            "by_route": stats.requests,
+        },
+        "cache": {
+            "entries": state.cache.entry_count(),
+            "hits": stats.cache_hits,
+            "misses": stats.cache_misses,
+        },
+        "feeds": {
+            "last_success": stats.last_success,
+            "last_error": stats.last_error,
+        },
+    }))
+    .into_response()
+}
+
+async fn handle_robots(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!(
+            "User-agent: *\nAllow: /\nDisallow: /api/\nSitemap: {}/sitemap.xml\n",
+            state.base_url(&headers)
+        ),
+    )
+        .into_response()
+}
+
+/// The index and every line and group's status page. A page's `lastmod` is when its content
+/// last changed, or when the server started if it hasn't been generated since.
+async fn handle_sitemap(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let base_url = state.base_url(&headers);
+    let names = LINES
+        .iter()
+        .map(|line| line.id)
+        .chain(TRAIN_GROUPS.iter().map(|group| group.id));
+
+    let mut urls = vec![SitemapUrl {
+        loc: format!("{}/", base_url),
+        lastmod: state.started,
+    }];
+    for name in names {
+        let lastmod = match state.history.get(&format!("page:{}", name)).await {
+            Some(document) => document.last_modified,
+            None => state.started,
+        };
+        urls.push(SitemapUrl {
+            loc: format!("{}/train/{}", base_url, name),
+            lastmod,
         });
-    </script>
-</body>
-</html>"#;
+    }
 
     (
-        StatusCode::OK,
-        [("Content-Type", "text/html; charset=utf-8")],
-        html,
+        [
+            (header::CONTENT_TYPE, XML_CONTENT_TYPE),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        sitemap::generate_sitemap(&urls),
+    )
+        .into_response()
+}
+
+/// A subway bullet. Browsers accept SVG even from `/favicon.ico`.
+async fn handle_favicon() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "image/svg+xml"),
+            (header::CACHE_CONTROL, "public, max-age=604800"),
+        ],
+        include_str!("../assets/favicon.svg"),
+    )
+        .into_response()
+}
+
+/// Which build is running, embedded by `build.rs`.
+async fn handle_version() -> Response {
+    let build_timestamp = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|seconds| DateTime::<Utc>::from_timestamp(seconds, 0));
+
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": env!("GIT_COMMIT"),
+        "build_timestamp": build_timestamp,
+        "rustc_version": env!("RUSTC_VERSION"),
+    }))
+    .into_response()
+}
+
+/// Readiness check: ready when fresh alert data is cached or the MTA feed can be fetched.
+async fn handle_readyz(State(state): State<AppState>) -> Response {
+    const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+    if state
+        .feed_cache
+        .contains_key(nyc_train_time::SUBWAY_ALERTS_URL)
+    {
+        return Json(serde_json::json!({ "status": "ready", "source": "cache" })).into_response();
+    }
+
+    let reason = match tokio::time::timeout(FETCH_TIMEOUT, state.subway_feed()).await {
+        Ok(Ok(_)) => {
+            return Json(serde_json::json!({ "status": "ready", "source": "upstream" }))
+                .into_response();
+        }
+        Ok(Err(e)) => format!("Failed to fetch MTA alerts feed: {}", e),
+        Err(_) => format!(
+            "Timed out after {}s fetching MTA alerts feed",
+            FETCH_TIMEOUT.as_secs()
+        ),
+    };
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "status": "unavailable", "reason": reason })),
     )
         .into_response()
 }
+
+#[derive(Serialize)]
+struct TrainInfo {
+    #[serde(flatten)]
+    line: &'static lines::Line,
+    calendar_url: String,
+}
+
+#[derive(Serialize)]
+struct TrainsResponse {
+    trains: Vec<TrainInfo>,
+    /// How MTA alert priorities map to the levels accepted by `?min_severity=`
+    severity_levels: &'static [AlertPriority],
+}
+
+async fn handle_trains(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let base_url = state.base_url(&headers);
+    let trains: Vec<TrainInfo> = LINES
+        .iter()
+        .map(|line| TrainInfo {
+            line,
+            calendar_url: format!("{}/api/calendars/train/{}.ics", base_url, line.id),
+        })
+        .collect();
+
+    Json(TrainsResponse {
+        trains,
+        severity_levels: ALERT_PRIORITIES,
+    })
+    .into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StationSearchParams {
+    q: Option<String>,
+}
+
+#[derive(Serialize)]
+struct StationInfo<'a> {
+    stop_id: &'a str,
+    name: &'a str,
+    /// The borough as it's named in `?borough=`
+    borough: &'static str,
+    routes: &'a [String],
+    calendar_url: String,
+}
+
+/// Stations whose names match `?q=`, for looking up the stop IDs that station calendars take.
+async fn handle_stations(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<StationSearchParams>,
+) -> Response {
+    let Some(query) = params.q.filter(|q| !q.trim().is_empty()) else {
+        return ApiError::new(
+            ProblemKind::InvalidParameter,
+            "Missing q. Expected part of a station name such as ?q=bedford.",
+        )
+        .into_response();
+    };
+
+    if state.stations.is_empty() {
+        return ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Station search is unavailable because station data is not loaded.",
+        )
+        .into_response();
+    }
+
+    let base_url = state.base_url(&headers);
+    let stations: Vec<StationInfo> = state
+        .stations
+        .search(&query, MAX_STATION_RESULTS)
+        .into_iter()
+        .map(|station| StationInfo {
+            stop_id: &station.stop_id,
+            name: &station.name,
+            borough: station.borough.query_name(),
+            routes: &station.routes,
+            calendar_url: format!("{}/api/calendars/station/{}.ics", base_url, station.stop_id),
+        })
+        .collect();
+
+    Json(stations).into_response()
+}
+
+async fn handle_groups() -> Response {
+    Json(TRAIN_GROUPS).into_response()
+}
+
+async fn handle_all_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let event_filter = match params.to_calendar_filter(&state, "/api/calendars/all.ics") {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key("all", &event_filter);
+    serve_cached_ics(&state, &request, cache_key, |events| {
+        nyc_train_time::render_all_trains_ics(
+            events,
+            &lines::train_ids(),
+            &event_filter,
+            &state.stations,
+        )
+    })
+    .await
+}
+
+async fn handle_station_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(stop_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let stop_id = stop_id.strip_suffix(".ics").unwrap_or(&stop_id);
+
+    if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+        return ApiError::invalid_stop_id(stop_id).into_response();
+    }
+
+    let path = format!("/api/calendars/station/{}.ics", stop_id);
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key(&format!("station:{}", stop_id), &event_filter);
+    serve_cached_ics(&state, &request, cache_key, |events| {
+        nyc_train_time::render_station_ics(events, stop_id, &event_filter, &state.stations)
+    })
+    .await
+}
+
+/// Checks a calendar config and puts it in canonical form, so the same calendar always gets the
+/// same ID.
+fn canonical_config(state: &AppState, config: CalendarConfig) -> Result<CalendarConfig, ApiError> {
+    let mut lines = Vec::new();
+    for name in config.lines.iter().map(|name| lines::normalize(name)) {
+        if lines::resolve(&name).is_none() {
+            return Err(ApiError::unknown_train_line(&name));
+        }
+        lines.push(name);
+    }
+    lines.sort_unstable();
+    lines.dedup();
+
+    let mut stations = Vec::new();
+    for stop_id in config.stations.iter().map(|stop_id| stop_id.trim()) {
+        if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+            return Err(ApiError::invalid_stop_id(stop_id));
+        }
+        stations.push(stop_id.to_string());
+    }
+    stations.sort_unstable();
+    stations.dedup();
+
+    if lines.is_empty() && stations.is_empty() {
+        return Err(ApiError::new(
+            ProblemKind::InvalidParameter,
+            "A saved calendar needs at least one line or station.".to_string(),
+        ));
+    }
+    if stations.len() > MAX_SAVED_STATIONS {
+        return Err(ApiError::new(
+            ProblemKind::InvalidParameter,
+            format!(
+                "Too many stations: {}. A saved calendar can cover at most {}.",
+                stations.len(),
+                MAX_SAVED_STATIONS
+            ),
+        ));
+    }
+
+    let name = config
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if name
+        .as_ref()
+        .is_some_and(|name| name.chars().count() > MAX_SAVED_NAME_LEN)
+    {
+        return Err(ApiError::new(
+            ProblemKind::InvalidParameter,
+            format!(
+                "Calendar name is too long. Expected at most {} characters.",
+                MAX_SAVED_NAME_LEN
+            ),
+        ));
+    }
+
+    // Parsing the filters now means a saved calendar can't fail to render later
+    CalendarParams::from_filters(&config.filters)?.to_filter(state)?;
+
+    Ok(CalendarConfig {
+        name,
+        lines,
+        stations,
+        filters: config.filters,
+    })
+}
+
+/// Saves a calendar config and returns the short URL it can be subscribed to at.
+async fn handle_save_calendar(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(config): Json<CalendarConfig>,
+) -> Response {
+    let config = match canonical_config(&state, config) {
+        Ok(config) => config,
+        Err(response) => return response.into_response(),
+    };
+
+    match state.saved_calendars.save(config).await {
+        Ok((id, created)) => {
+            let path = format!("/api/calendars/custom/{}.ics", id);
+            let status = if created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+
+            (
+                status,
+                [(header::LOCATION, path.clone())],
+                Json(serde_json::json!({
+                    "id": id,
+                    "url": format!("{}{}", state.base_url(&headers), path),
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            eprintln!("Error saving calendar: {}", e);
+            ApiError::new(
+                ProblemKind::Internal,
+                format!("Error saving calendar: {}", e),
+            )
+            .into_response()
+        }
+    }
+}
+
+async fn handle_custom_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(id): Path<String>,
+) -> Response {
+    let id = id.strip_suffix(".ics").unwrap_or(&id);
+
+    let Some(config) = state.saved_calendars.get(id).await else {
+        return ApiError::new(
+            ProblemKind::UnknownCalendar,
+            format!("Unknown calendar: {}.", id),
+        )
+        .into_response();
+    };
+
+    let event_filter = match CalendarParams::from_filters(&config.filters)
+        .and_then(|params| params.to_filter(&state))
+    {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let trains: Vec<&str> = config
+        .lines
+        .iter()
+        .filter_map(|name| lines::resolve(name))
+        .flatten()
+        .copied()
+        .collect();
+    let stop_ids: Vec<&str> = config.stations.iter().map(String::as_str).collect();
+
+    let cache_key = calendar_cache_key(&format!("custom:{}", id), &event_filter);
+    serve_cached_ics(&state, &request, cache_key, |events| {
+        nyc_train_time::render_custom_ics(
+            events,
+            config.name.as_deref(),
+            &trains,
+            &stop_ids,
+            &event_filter,
+            &state.stations,
+        )
+    })
+    .await
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CommuteParams {
+    from: Option<String>,
+    to: Option<String>,
+    via: Option<String>,
+}
+
+/// A calendar of the alerts between two stations on one or more lines, e.g.
+/// `?from=F20&to=D21&via=F`.
+async fn handle_commute_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Query(commute): Query<CommuteParams>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let (Some(from), Some(to), Some(via)) = (&commute.from, &commute.to, &commute.via) else {
+        return ApiError::new(
+            ProblemKind::InvalidParameter,
+            "Missing from, to or via. Expected stations and lines such as ?from=F20&to=D21&via=F.",
+        )
+        .into_response();
+    };
+
+    for stop_id in [from, to] {
+        if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+            return ApiError::invalid_stop_id(stop_id).into_response();
+        }
+    }
+    let from = nyc_train_time::stops::parent_station(from);
+    let to = nyc_train_time::stops::parent_station(to);
+
+    let mut trains: Vec<&str> = Vec::new();
+    for name in via.split(',').map(lines::normalize) {
+        match lines::resolve(&name) {
+            Some(resolved) => trains.extend(resolved),
+            None => return ApiError::unknown_train_line(&name).into_response(),
+        }
+    }
+    trains.sort_unstable();
+    trains.dedup();
+
+    if state.stop_sequences.is_empty() {
+        return ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Commute calendars are unavailable because stop sequence data is not loaded.",
+        )
+        .into_response();
+    }
+
+    // Lines in `via` that don't run between the two stations, such as the E in `via=ACE`
+    // north of 50 St, are left out rather than rejected
+    let mut segment = HashSet::new();
+    let mut served: Vec<&str> = Vec::new();
+    for train in &trains {
+        if let Some(stations) = state.stop_sequences.segment(train, from, to) {
+            segment.extend(stations);
+            served.push(train);
+        }
+    }
+
+    if served.is_empty() {
+        let via = trains.join(", ");
+        let message = match [from, to].into_iter().find(|stop_id| {
+            !trains
+                .iter()
+                .any(|train| state.stop_sequences.serves(train, stop_id))
+        }) {
+            Some(stop_id) => format!("Station {} is not on the {} train.", stop_id, via),
+            None => format!(
+                "Stations {} and {} are not on the same {} train.",
+                from, to, via
+            ),
+        };
+        return ApiError::new(ProblemKind::InvalidParameter, message).into_response();
+    }
+
+    let path = format!(
+        "/api/calendars/commute.ics?from={}&to={}&via={}",
+        from,
+        to,
+        served.join(",")
+    );
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key(
+        &format!("commute:{}-{}:{}", from, to, served.join(",")),
+        &event_filter,
+    );
+    serve_cached_ics(&state, &request, cache_key, |events| {
+        nyc_train_time::render_commute_ics(
+            events,
+            from,
+            to,
+            &served,
+            &segment,
+            &event_filter,
+            &state.stations,
+        )
+    })
+    .await
+}
+
+async fn handle_lirr_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(line_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    serve_rail_calendar(&state, &request, &railroad::LIRR, &line_id, params).await
+}
+
+async fn handle_mnr_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(line_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    serve_rail_calendar(&state, &request, &railroad::MNR, &line_id, params).await
+}
+
+async fn handle_path_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(route_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    serve_rail_calendar(&state, &request, &railroad::PATH, &route_id, params).await
+}
+
+async fn serve_rail_calendar(
+    state: &AppState,
+    request: &DocumentRequest,
+    railroad: &Railroad,
+    line_id: &str,
+    params: CalendarParams,
+) -> Response {
+    let line_id = line_id.strip_suffix(".ics").unwrap_or(line_id);
+
+    let Some(line) = railroad.find_line(line_id) else {
+        return ApiError::new(
+            ProblemKind::UnknownRoute,
+            format!(
+                "Unknown {} {}: {}. Expected one of: {}",
+                railroad.name,
+                railroad.line_kind,
+                line_id,
+                railroad.line_ids().join(", ")
+            ),
+        )
+        .into_response();
+    };
+
+    let path = format!("/api/calendars/{}/{}.ics", railroad.id, line.id);
+    let event_filter = match params.to_calendar_filter(state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key(&format!("{}:{}", railroad.id, line.id), &event_filter);
+    serve_network_cached(
+        state,
+        request,
+        railroad.network,
+        cache_key,
+        ICS_CONTENT_TYPE,
+        |events| {
+            nyc_train_time::render_routes_ics(
+                &railroad.with_line_names(events),
+                railroad.network,
+                Some(line.name),
+                &[line.route_id],
+                &event_filter,
+                &state.stations,
+            )
+        },
+    )
+    .await
+}
+
+async fn handle_bus_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(route_name): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let route_name = route_name.strip_suffix(".ics").unwrap_or(&route_name);
+
+    if state.bus_routes.is_empty() {
+        return ApiError::new(
+            ProblemKind::DataUnavailable,
+            "Bus calendars are unavailable because bus route data is not loaded.".to_string(),
+        )
+        .into_response();
+    }
+
+    let Some(route) = state.bus_routes.find(route_name) else {
+        return ApiError::new(
+            ProblemKind::UnknownRoute,
+            format!("Unknown bus route: {}.", route_name),
+        )
+        .into_response();
+    };
+
+    let path = format!("/api/calendars/bus/{}.ics", route.route_id);
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    // Keyed by route ID, so `M15-SBS` and `M15+` share an entry
+    let cache_key = calendar_cache_key(&format!("bus:{}", route.route_id), &event_filter);
+    serve_network_cached(
+        &state,
+        &request,
+        Network::Bus,
+        cache_key,
+        ICS_CONTENT_TYPE,
+        |events| {
+            nyc_train_time::render_routes_ics(
+                &state.bus_routes.with_route_names(events),
+                Network::Bus,
+                Some(&route.short_name),
+                &[&route.route_id],
+                &event_filter,
+                &state.stations,
+            )
+        },
+    )
+    .await
+}
+
+async fn handle_accessibility_calendar(
+    State(state): State<AppState>,
+    request: DocumentRequest,
+    Path(stop_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Response {
+    let stop_id = stop_id.strip_suffix(".ics").unwrap_or(&stop_id);
+
+    if !nyc_train_time::stops::is_valid_stop_id(stop_id) {
+        return ApiError::invalid_stop_id(stop_id).into_response();
+    }
+
+    let path = format!("/api/calendars/accessibility/{}.ics", stop_id);
+    let event_filter = match params.to_calendar_filter(&state, &path) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    let cache_key = calendar_cache_key(&format!("accessibility:{}", stop_id), &event_filter);
+    serve_document(&state, &request, cache_key, ICS_CONTENT_TYPE, async {
+        let events = request
+            .timing
+            .time("mta_fetch", state.accessibility_events())
+            .await?;
+        Ok(request.timing.measure("ics_gen", || {
+            nyc_train_time::render_accessibility_ics(
+                &events,
+                stop_id,
+                &event_filter,
+                &state.stations,
+            )
+        }))
+    })
+    .await
+}
+
+async fn serve_calendar(
+    state: &AppState,
+    request: &DocumentRequest,
+    name: &str,
+    trains: &[&str],
+    event_filter: &EventFilter,
+) -> Response {
+    let cache_key = calendar_cache_key(name, event_filter);
+
+    serve_cached_ics(state, request, cache_key, |events| {
+        nyc_train_time::render_trains_ics(events, name, trains, event_filter, &state.stations)
+    })
+    .await
+}
+
+/// Streams a zip archive of every line's calendar, e.g. `A.ics`, with the same defaults as
+/// `/api/calendars/train/A.ics`. Calendars are generated (or taken from the cache) one at a
+/// time as the archive is sent, so they're never all held in memory at once.
+async fn handle_calendar_export(State(state): State<AppState>) -> Response {
+    let event_filter = match CalendarParams::default().to_filter(&state) {
+        Ok(event_filter) => event_filter,
+        Err(response) => return response.into_response(),
+    };
+
+    // Once the archive has started there's no way to report an error, so make sure the feed
+    // can be fetched first
+    if let Err(e) = state.feed(Network::Subway).await {
+        eprintln!("Error fetching feed for export: {}", e);
+        return ApiError::new(
+            ProblemKind::UpstreamUnavailable,
+            format!("Error generating calendar: {}", e),
+        )
+        .into_response();
+    }
+
+    let (sender, receiver) = mpsc::channel::<Result<Vec<u8>, std::io::Error>>(1);
+
+    tokio::spawn(async move {
+        let mut zip = ZipWriter::new();
+
+        for line in LINES {
+            let trains = std::slice::from_ref(&line.id);
+            let cache_key = calendar_cache_key(line.id, &event_filter);
+            let document = cached_document(&state, cache_key, false, async {
+                let feed = state.feed(Network::Subway).await?;
+                let events = alert_urls(&state, &feed.events);
+                Ok(nyc_train_time::render_trains_ics(
+                    &events,
+                    line.id,
+                    trains,
+                    &event_filter,
+                    &state.stations,
+                ))
+            })
+            .await;
+
+            let chunk = match document {
+                Ok(document) => Ok(zip.entry(
+                    &format!("{}.ics", line.id),
+                    document.content.as_bytes(),
+                    document
+                        .last_modified
+                        .with_timezone(&New_York)
+                        .naive_local(),
+                )),
+                // Aborts the response, so the client doesn't get a truncated archive
+                Err(e) => Err(std::io::Error::other(e.to_string())),
+            };
+            let failed = chunk.is_err();
+
+            // Sending fails once the client has gone away
+            if sender.send(chunk).await.is_err() || failed {
+                return;
+            }
+        }
+
+        let _ = sender.send(Ok(zip.finish())).await;
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, ZIP_CONTENT_TYPE),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"mta-subway-calendars.zip\"",
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct PurgeParams {
+    train: Option<String>,
+}
+
+/// Drops cached documents so the next request regenerates them from a fresh feed, e.g. after
+/// the MTA corrects an alert. `?train=A` only drops the documents that can include the A
+/// train's alerts.
+async fn handle_cache_purge(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<PurgeParams>,
+) -> Response {
+    if !state.is_admin(&headers) {
+        return ApiError::new(
+            ProblemKind::Unauthorized,
+            "A valid admin token is required.",
+        )
+        .into_response();
+    }
+
+    let purged = match params.train {
+        None => {
+            let purged = state.cache.iter().count();
+            state.cache.invalidate_all();
+            state.feed_cache.invalidate_all();
+            state.accessibility_cache.invalidate_all();
+            state.trips_cache.invalidate_all();
+            purged
+        }
+        Some(train_name) => {
+            let train_name = lines::normalize(&train_name);
+            let Some(trains) = lines::resolve(&train_name) else {
+                return ApiError::unknown_train_line(&train_name).into_response();
+            };
+
+            let cached: Vec<Arc<String>> = state.cache.iter().map(|(key, _)| key).collect();
+            let mut keys = Vec::new();
+            for key in cached {
+                if cached_document_covers(&state, &key, trains).await {
+                    keys.push(key);
+                }
+            }
+            for key in &keys {
+                state.cache.invalidate(key.as_str()).await;
+            }
+            // Otherwise the calendars would be regenerated from the same feed
+            state
+                .feed_cache
+                .invalidate(nyc_train_time::SUBWAY_ALERTS_URL)
+                .await;
+            keys.len()
+        }
+    };
+
+    println!("Purged {} cached documents", purged);
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "purged": purged })),
+    )
+        .into_response()
+}
+
+/// The calendar or feed a cache key belongs to, e.g. `A` for `json:A?days=7`.
+fn cache_key_name(cache_key: &str) -> &str {
+    let name = cache_key.split('?').next().unwrap_or(cache_key);
+    [
+        "json:",
+        "rss:",
+        "atom:",
+        "train-accessibility:",
+        "badge:",
+        "page:",
+    ]
+    .iter()
+    .find_map(|prefix| name.strip_prefix(prefix))
+    .unwrap_or(name)
+}
+
+/// Links the event about left out events to the calendar at `path` without `?limit=`.
+///
+/// Calendars are cached for everyone, so the link is built from the public URL and the
+/// filter's canonical parameters rather than from the request, and left out without a public
+/// URL.
+fn link_unlimited(state: &AppState, event_filter: &mut EventFilter, path: &str) {
+    let (Some(_), Some(public_url)) = (event_filter.limit, state.public_url()) else {
+        return;
+    };
+
+    let params = EventFilter {
+        limit: None,
+        ..event_filter.clone()
+    }
+    .cache_key();
+    let separator = if path.contains('?') { '&' } else { '?' };
+    event_filter.unlimited_url = Some(if params.is_empty() {
+        format!("{}{}", public_url, path)
+    } else {
+        format!("{}{}{}{}", public_url, path, separator, params)
+    });
+}
+
+/// Whether the document cached under `cache_key` can include alerts for any of `trains`: it's
+/// for one of them, a trunk group or list of lines with one of them, every line, or a station,
+/// commute or saved calendar on one of them.
+async fn cached_document_covers(state: &AppState, cache_key: &str, trains: &[&str]) -> bool {
+    let name = cache_key_name(cache_key);
+    if name == "all" || name == "status" {
+        return true;
+    }
+    if let Some(stop_id) = name.strip_prefix("station:") {
+        return state.stations.serves(stop_id, trains);
+    }
+    if let Some(id) = name.strip_prefix("custom:") {
+        return match state.saved_calendars.get(id).await {
+            Some(config) => {
+                lines_cover(config.lines.iter().map(String::as_str), trains)
+                    || config
+                        .stations
+                        .iter()
+                        .any(|stop_id| state.stations.serves(stop_id, trains))
+            }
+            None => false,
+        };
+    }
+
+    // A commute's key ends with the lines it's on, e.g. `commute:A27-A32:A,C`
+    let names = match name.strip_prefix("commute:") {
+        Some(commute) => commute.rsplit(':').next().unwrap_or_default(),
+        None => name,
+    };
+    lines_cover(names.split(',').map(str::trim), trains)
+}
+
+/// Whether any of the lines or trunk groups in `names` covers one of `trains`.
+fn lines_cover<'a>(mut names: impl Iterator<Item = &'a str>, trains: &[&str]) -> bool {
+    names.any(|name| {
+        lines::resolve(name).is_some_and(|resolved| {
+            resolved.iter().any(|line| {
+                trains.iter().any(|train| {
+                    lines::route_matches(line, train, true)
+                        || lines::route_matches(train, line, true)
+                })
+            })
+        })
+    })
+}
+
+/// The cache key for a calendar, e.g. `A` or `L?borough=brooklyn`.
+fn calendar_cache_key(name: &str, event_filter: &EventFilter) -> String {
+    let params = event_filter.cache_key();
+
+    if params.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}?{}", name, params)
+    }
+}
+
+async fn serve_cached_ics(
+    state: &AppState,
+    request: &DocumentRequest,
+    cache_key: String,
+    render: impl FnOnce(&[CalendarEvent]) -> String,
+) -> Response {
+    serve_cached(state, request, cache_key, ICS_CONTENT_TYPE, render).await
+}
+
+/// Serves a document rendered from the subway alert feed, caching it under `cache_key`.
+async fn serve_cached(
+    state: &AppState,
+    request: &DocumentRequest,
+    cache_key: String,
+    content_type: &'static str,
+    render: impl FnOnce(&[CalendarEvent]) -> String,
+) -> Response {
+    serve_network_cached(
+        state,
+        request,
+        Network::Subway,
+        cache_key,
+        content_type,
+        render,
+    )
+    .await
+}
+
+/// Serves a document rendered from `network`'s alert feed, caching it under `cache_key`.
+async fn serve_network_cached(
+    state: &AppState,
+    request: &DocumentRequest,
+    network: Network,
+    cache_key: String,
+    content_type: &'static str,
+    render: impl FnOnce(&[CalendarEvent]) -> String,
+) -> Response {
+    serve_document(state, request, cache_key, content_type, async {
+        let feed = request
+            .timing
+            .time("mta_fetch", state.feed(network))
+            .await?;
+        let timing_name = if content_type == ICS_CONTENT_TYPE {
+            "ics_gen"
+        } else {
+            "render"
+        };
+
+        // The alert detail endpoint only looks alerts up in the subway feed
+        if network == Network::Subway {
+            let events = alert_urls(state, &feed.events);
+            Ok(request.timing.measure(timing_name, || render(&events)))
+        } else {
+            Ok(request.timing.measure(timing_name, || render(&feed.events)))
+        }
+    })
+    .await
+}
+
+/// `events`, with alerts the MTA doesn't link to linking to their detail page instead. That
+/// link needs `PUBLIC_BASE_URL`: the documents these events go into are cached for everyone,
+/// so they can't use a URL that came from one client's `Host` header.
+fn alert_urls<'a>(state: &AppState, events: &'a [CalendarEvent]) -> Cow<'a, [CalendarEvent]> {
+    match state.public_url() {
+        Some(public_url) => Cow::Owned(nyc_train_time::with_alert_urls(events, public_url)),
+        None => Cow::Borrowed(events),
+    }
+}
+
+/// Serves a document, caching it under `cache_key`. `generate` only runs on a cache miss.
+///
+/// HEAD requests get the same headers as GET, including `Content-Length`, but no body. They
+/// use the cached document when there is one, and otherwise generate and cache it so the
+/// `GET` that usually follows is a cache hit.
+///
+/// Responses say whether the cache was hit in `X-Cache`, and how long the cache lookup and
+/// whatever `generate` timed with `request.timing` took in `Server-Timing`.
+async fn serve_document(
+    state: &AppState,
+    request: &DocumentRequest,
+    cache_key: String,
+    content_type: &'static str,
+    generate: impl Future<Output = Result<String, FeedError>>,
+) -> Response {
+    let started = Instant::now();
+    let mut generating = None;
+    let generate = async {
+        let started = Instant::now();
+        let result = generate.await;
+        generating = Some(started.elapsed());
+        result
+    };
+
+    let result = cached_document(state, cache_key, request.refresh, generate).await;
+    request.timing.record(
+        "cache",
+        started
+            .elapsed()
+            .saturating_sub(generating.unwrap_or_default()),
+    );
+
+    let mut response = match result {
+        Ok(document) => document_response(request, content_type, &document),
+        Err(e) => ApiError::new(
+            ProblemKind::UpstreamUnavailable,
+            format!("Error generating calendar: {}", e),
+        )
+        .into_response(),
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(
+        X_CACHE,
+        HeaderValue::from_static(if generating.is_some() { "MISS" } else { "HIT" }),
+    );
+    headers.insert(
+        SERVER_TIMING,
+        HeaderValue::from_str(&request.timing.header_value()).unwrap(),
+    );
+    response
+}
+
+/// Returns the document cached under `cache_key`, running `generate` and caching its output on
+/// a cache miss. With `refresh`, the cached document is skipped and overwritten.
+async fn cached_document(
+    state: &AppState,
+    cache_key: String,
+    refresh: bool,
+    generate: impl Future<Output = Result<String, FeedError>>,
+) -> Result<Arc<CachedDocument>, FeedError> {
+    if refresh {
+        println!("Refresh requested - generating: {}", cache_key);
+    } else if let Some(cached_content) = state.cache.get(&cache_key).await {
+        println!("Cache hit for: {}", cache_key);
+        state.stats.record_cache_lookup(true);
+        return Ok(cached_content);
+    } else {
+        println!("Cache miss - generating: {}", cache_key);
+    }
+    state.stats.record_cache_lookup(false);
+
+    match generate.await {
+        Ok(content) => {
+            let previous = state.history.get(&cache_key).await;
+            let document = CachedDocument::refresh(previous, content);
+
+            // Cache the result
+            state
+                .history
+                .insert(cache_key.clone(), document.clone())
+                .await;
+            state.cache.insert(cache_key, document.clone()).await;
+
+            Ok(document)
+        }
+        Err(e) => {
+            eprintln!("Error generating {}: {}", cache_key, e);
+            Err(e)
+        }
+    }
+}
+
+fn document_response(
+    request: &DocumentRequest,
+    content_type: &'static str,
+    document: &CachedDocument,
+) -> Response {
+    let headers = [
+        (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_str(&format!("public, max-age={}", document.max_age())).unwrap(),
+        ),
+        (header::AGE, HeaderValue::from(document.age())),
+        (header::ETAG, HeaderValue::from_str(&document.etag).unwrap()),
+        (
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&http_date(&document.last_modified)).unwrap(),
+        ),
+    ];
+
+    if request.is_not_modified(document) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    let content_length = [(
+        header::CONTENT_LENGTH,
+        HeaderValue::from(document.content.len()),
+    )];
+
+    if request.method == Method::HEAD {
+        (StatusCode::OK, headers, content_length).into_response()
+    } else {
+        (
+            StatusCode::OK,
+            headers,
+            content_length,
+            document.content.clone(),
+        )
+            .into_response()
+    }
+}
+
+/// Formats a timestamp as an HTTP date, e.g. `Mon, 15 Dec 2025 10:00:00 GMT`.
+fn http_date(dt: &DateTime<Utc>) -> String {
+    dt.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Sends calendar apps on to a train's calendar and shows browsers a page to subscribe from.
+///
+/// `webcal://<host>/webcal/A` makes Apple Calendar subscribe in one tap: it fetches this URL,
+/// which redirects it to the `.ics`. A browser that opens the same URL gets both links instead.
+async fn handle_webcal(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(train_name): Path<String>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    let train_name = lines::normalize(&train_name);
+
+    if lines::resolve(&train_name).is_none() {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    }
+
+    let calendar_url = train_calendar_url(&state, &headers, &train_name, query);
+    let vary = [(header::VARY, "Accept, User-Agent")];
+
+    if !is_browser(&headers) {
+        return (StatusCode::FOUND, vary, [(header::LOCATION, calendar_url)]).into_response();
+    }
+
+    let webcal_url = webcal_url(&calendar_url);
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Subscribe to {train} Train Alerts</title>
+    <style>
+        body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; max-width: 600px; margin: 0 auto; padding: 20px; line-height: 1.6; }}
+        .subscribe {{ display: inline-block; padding: 12px 24px; background-color: #0039a6; color: white; border-radius: 4px; font-weight: bold; text-decoration: none; }}
+        code {{ display: block; margin: 10px 0; padding: 12px; background-color: #f5f5f5; border-radius: 4px; word-break: break-all; }}
+    </style>
+</head>
+<body>
+    <h1>{train} Train Alerts</h1>
+    <p><a class="subscribe" href="{webcal_url}">Subscribe in Calendar</a></p>
+    <p>Or copy this URL into your calendar app's "Subscribe to calendar" or "Add calendar by URL" dialog:</p>
+    <code id="url">{calendar_url}</code>
+    <button id="copyBtn">Copy</button>
+    <script>
+        document.getElementById('copyBtn').addEventListener('click', () => {{
+            navigator.clipboard.writeText(document.getElementById('url').textContent).then(() => {{
+                document.getElementById('copyBtn').textContent = 'Copied!';
+            }});
+        }});
+    </script>
+</body>
+</html>"#,
+        train = train_name,
+        webcal_url = nyc_train_time::rss::escape_xml(&webcal_url),
+        calendar_url = nyc_train_time::rss::escape_xml(&calendar_url),
+    );
+
+    (
+        StatusCode::OK,
+        vary,
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response()
+}
+
+/// The absolute URL of a train's calendar, with the query parameters of the current request.
+fn train_calendar_url(
+    state: &AppState,
+    headers: &HeaderMap,
+    train_name: &str,
+    query: Option<String>,
+) -> String {
+    let mut calendar_url = format!(
+        "{}/api/calendars/train/{}.ics",
+        state.base_url(headers),
+        train_name
+    );
+    if let Some(query) = query.filter(|query| !query.is_empty()) {
+        calendar_url.push('?');
+        calendar_url.push_str(&query);
+    }
+
+    calendar_url
+}
+
+/// Calendar services that can subscribe to a calendar from a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubscribeService {
+    Google,
+    Outlook,
+}
+
+impl SubscribeService {
+    /// The link that opens the service's "add calendar" dialog for `calendar_url`.
+    fn subscribe_url(&self, calendar_url: &str, calendar_name: &str) -> String {
+        let calendar_url = nyc_train_time::encode_query_value(calendar_url);
+
+        match self {
+            SubscribeService::Google => {
+                format!(
+                    "https://calendar.google.com/calendar/r?cid={}",
+                    calendar_url
+                )
+            }
+            SubscribeService::Outlook => format!(
+                "https://outlook.live.com/owa?path=/calendar/action/compose&rru=addsubscription&url={}&name={}",
+                calendar_url,
+                nyc_train_time::encode_query_value(calendar_name)
+            ),
+        }
+    }
+}
+
+async fn handle_subscribe_google(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(train_name): Path<String>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    redirect_to_subscribe(
+        &state,
+        &headers,
+        SubscribeService::Google,
+        &train_name,
+        query,
+    )
+}
+
+async fn handle_subscribe_outlook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(train_name): Path<String>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    redirect_to_subscribe(
+        &state,
+        &headers,
+        SubscribeService::Outlook,
+        &train_name,
+        query,
+    )
+}
+
+/// Redirects to `service`'s subscribe dialog for a train's calendar. The calendar URL uses
+/// `PUBLIC_BASE_URL` when it's set, since the service fetches it from outside any proxy.
+fn redirect_to_subscribe(
+    state: &AppState,
+    headers: &HeaderMap,
+    service: SubscribeService,
+    train_name: &str,
+    query: Option<String>,
+) -> Response {
+    let train_name = lines::normalize(train_name);
+
+    if lines::resolve(&train_name).is_none() {
+        return ApiError::unknown_train_line(&train_name).into_response();
+    }
+
+    let calendar_url = train_calendar_url(state, headers, &train_name, query);
+    let calendar_name = format!("MTA {} Train Alerts", train_name);
+
+    (
+        StatusCode::FOUND,
+        [(
+            header::LOCATION,
+            service.subscribe_url(&calendar_url, &calendar_name),
+        )],
+    )
+        .into_response()
+}
+
+/// Whether a request comes from a web browser rather than a calendar app. Browsers ask for
+/// HTML by name; calendar apps send `*/*` or `text/calendar`, and don't claim to be Mozilla.
+fn is_browser(headers: &HeaderMap) -> bool {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+    };
+
+    header(header::ACCEPT).contains("text/html")
+        && header(header::USER_AGENT).starts_with("Mozilla/")
+}
+
+/// The `webcal://` form of an `http://` or `https://` URL.
+fn webcal_url(url: &str) -> String {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+
+    format!("webcal://{}", rest)
+}
+
+async fn handle_openapi() -> Response {
+    Json(nyc_train_time::openapi::openapi_spec()).into_response()
+}
+
+/// Swagger UI for `/api/openapi.json`, loaded from a CDN.
+async fn handle_docs() -> Response {
+    let html = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>NYC Train Cal API</title>
+    <link rel="icon" type="image/svg+xml" href="/favicon.svg">
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+    <script>
+        SwaggerUIBundle({ url: '/api/openapi.json', dom_id: '#swagger-ui' });
+    </script>
+</body>
+</html>"#;
+
+    (
+        StatusCode::OK,
+        [("Content-Type", "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response()
+}
+
+/// Runs a GraphQL query over the lines, the subway feed's alerts and the stations.
+async fn handle_graphql(
+    State(state): State<AppState>,
+    Json(request): Json<graphql::Request>,
+) -> Response {
+    let feed = state.subway_feed().await;
+    if let Err(e) = &feed {
+        eprintln!("Error fetching alerts: {}", e);
+    }
+
+    // Queries can take a while to run, so they don't hold up the async workers
+    let feed = feed.map_err(|e| e.to_string());
+    let stations = state.stations.clone();
+    let response = tokio::task::spawn_blocking(move || {
+        let data = graphql::Data {
+            alerts: feed
+                .as_ref()
+                .map(|feed| feed.alerts.as_slice())
+                .map_err(|e| e.clone()),
+            stations: &stations,
+        };
+        graphql::execute(&request, &data)
+    })
+    .await;
+
+    match response {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => {
+            eprintln!("Error running GraphQL query: {}", e);
+            ApiError::new(
+                ProblemKind::Internal,
+                "Error running GraphQL query.".to_string(),
+            )
+            .into_response()
+        }
+    }
+}
+
+/// GraphiQL for `POST /api/graphql`, loaded from a CDN, when `GRAPHQL_PLAYGROUND` is set.
+async fn handle_graphql_playground(State(state): State<AppState>) -> Response {
+    if !state.graphql_playground {
+        return ApiError::new(ProblemKind::NotFound, "The GraphQL playground is disabled.")
+            .into_response();
+    }
+
+    let html = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>NYC Train Cal GraphQL</title>
+    <link rel="icon" type="image/svg+xml" href="/favicon.svg">
+    <link rel="stylesheet" href="https://unpkg.com/graphiql@3/graphiql.min.css">
+    <style>
+        body { margin: 0; }
+        #graphiql { height: 100vh; }
+    </style>
+</head>
+<body>
+    <div id="graphiql"></div>
+    <script src="https://unpkg.com/react@18/umd/react.production.min.js"></script>
+    <script src="https://unpkg.com/react-dom@18/umd/react-dom.production.min.js"></script>
+    <script src="https://unpkg.com/graphiql@3/graphiql.min.js"></script>
+    <script>
+        const fetcher = GraphiQL.createFetcher({ url: '/api/graphql' });
+        ReactDOM.createRoot(document.getElementById('graphiql')).render(
+            React.createElement(GraphiQL, {
+                fetcher,
+                defaultQuery: '{\n  line(id: "A") {\n    name\n    alerts(severity: SEVERE) {\n      header\n    }\n  }\n}\n',
+            })
+        );
+    </script>
+</body>
+</html>"#;
+
+    (
+        StatusCode::OK,
+        [("Content-Type", "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response()
+}
+
+async fn handle_index() -> Response {
+    let html = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>NYC Train Cal</title>
+    <link rel="icon" type="image/svg+xml" href="/favicon.svg">
+    <style>
+        body {
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            max-width: 800px;
+            margin: 0 auto;
+            padding: 20px;
+            line-height: 1.6;
+        }
+        h1 {
+            color: #333;
+            margin-top: 20px;
+        }
+        .line-group {
+            margin: 30px 0;
+        }
+        .train-grid {
+            display: grid;
+            grid-template-columns: repeat(auto-fill, minmax(80px, 1fr));
+            gap: 12px;
+            margin-bottom: 20px;
+        }
+        .train-link {
+            display: block;
+            padding: 20px;
+            text-align: center;
+            font-weight: bold;
+            font-size: 24px;
+            border-radius: 8px;
+            transition: transform 0.2s;
+            cursor: pointer;
+            border: none;
+            text-decoration: none;
+        }
+        .train-link:hover {
+            transform: scale(1.05);
+        }
+        .train-link.selected {
+            box-shadow: 0 0 0 3px #333;
+        }
+        /* NYC Subway line colors */
+        .train-1, .train-2, .train-3 { background-color: #ee352e; color: white; }
+        .train-4, .train-5, .train-6 { background-color: #00933c; color: white; }
+        .train-7 { background-color: #b933ad; color: white; }
+        .train-A, .train-C, .train-E { background-color: #0039a6; color: white; }
+        .train-B, .train-D, .train-F, .train-M { background-color: #ff6319; color: white; }
+        .train-G { background-color: #6cbe45; color: white; }
+        .train-J, .train-Z { background-color: #996633; color: white; }
+        .train-L { background-color: #a7a9ac; color: white; }
+        .train-N, .train-Q, .train-R, .train-W { background-color: #fccc0a; color: black; }
+        .train-S, .train-GS, .train-FS, .train-H, .train-SI { background-color: #808183; color: white; }
+        .rail-grid {
+            grid-template-columns: repeat(auto-fill, minmax(150px, 1fr));
+        }
+        .rail-link {
+            padding: 14px;
+            font-size: 16px;
+        }
+        /* Railroad colors */
+        .rail-lirr { background-color: #0f61a9; color: white; }
+        .rail-mnr-hudson { background-color: #009b3a; color: white; }
+        .rail-mnr-harlem { background-color: #0039a6; color: white; }
+        .rail-mnr-new-haven { background-color: #ee0034; color: white; }
+        .rail-path-nwk-wtc { background-color: #d93a30; color: white; }
+        .rail-path-jsq-33 { background-color: #ff9900; color: black; }
+        .rail-path-hob-33 { background-color: #4d92fb; color: white; }
+        .rail-path-hob-wtc { background-color: #65c100; color: white; }
+        .url-section {
+            background-color: #f5f5f5;
+            padding: 20px;
+            border-radius: 8px;
+            margin: 30px 0;
+            display: none;
+        }
+        .url-section.visible {
+            display: block;
+        }
+        .url-container {
+            display: flex;
+            gap: 10px;
+            margin-top: 10px;
+        }
+        .url-box {
+            flex: 1;
+            padding: 12px;
+            font-family: monospace;
+            font-size: 14px;
+            background-color: white;
+            border: 2px solid #ddd;
+            border-radius: 4px;
+            word-break: break-all;
+        }
+        .copy-btn {
+            padding: 12px 24px;
+            background-color: #0039a6;
+            color: white;
+            border: none;
+            border-radius: 4px;
+            cursor: pointer;
+            font-weight: bold;
+            transition: background-color 0.2s;
+        }
+        .copy-btn:hover {
+            background-color: #002d7a;
+        }
+        .copy-btn.copied {
+            background-color: #00933c;
+        }
+        .subscribe-link {
+            display: inline-block;
+            margin-top: 15px;
+            font-weight: bold;
+            color: #0039a6;
+        }
+        .instructions {
+            background-color: #f5f5f5;
+            padding: 20px;
+            border-radius: 8px;
+            margin-top: 30px;
+        }
+        .instructions code {
+            background-color: #e0e0e0;
+            padding: 2px 6px;
+            border-radius: 3px;
+            font-family: monospace;
+        }
+        @media (max-width: 600px) {
+            body {
+                padding: 15px;
+            }
+            h1 {
+                font-size: 24px;
+                margin-top: 10px;
+            }
+            .train-grid {
+                grid-template-columns: repeat(auto-fill, minmax(60px, 1fr));
+                gap: 8px;
+            }
+            .train-link {
+                padding: 15px;
+                font-size: 20px;
+            }
+            .rail-grid {
+                grid-template-columns: repeat(auto-fill, minmax(120px, 1fr));
+            }
+            .rail-link {
+                font-size: 14px;
+            }
+            .url-container {
+                flex-direction: column;
+            }
+            .copy-btn {
+                width: 100%;
+            }
+        }
+    </style>
+</head>
+<body>
+    <h1>🚇 NYC Train Cal</h1>
+    <p>Subscribe to service alerts for your train line and plan your days better. Click a line to get its calendar subscription URL:</p>
+    
+    <div class="train-grid">
+        <button class="train-link train-A" data-train="A">A</button>
+        <button class="train-link train-C" data-train="C">C</button>
+        <button class="train-link train-E" data-train="E">E</button>
+        <button class="train-link train-B" data-train="B">B</button>
+        <button class="train-link train-D" data-train="D">D</button>
+        <button class="train-link train-F" data-train="F">F</button>
+        <button class="train-link train-M" data-train="M">M</button>
+        <button class="train-link train-G" data-train="G">G</button>
+        <button class="train-link train-J" data-train="J">J</button>
+        <button class="train-link train-Z" data-train="Z">Z</button>
+        <button class="train-link train-L" data-train="L">L</button>
+        <button class="train-link train-N" data-train="N">N</button>
+        <button class="train-link train-Q" data-train="Q">Q</button>
+        <button class="train-link train-R" data-train="R">R</button>
+        <button class="train-link train-W" data-train="W">W</button>
+        <button class="train-link train-1" data-train="1">1</button>
+        <button class="train-link train-2" data-train="2">2</button>
+        <button class="train-link train-3" data-train="3">3</button>
+        <button class="train-link train-4" data-train="4">4</button>
+        <button class="train-link train-5" data-train="5">5</button>
+        <button class="train-link train-6" data-train="6">6</button>
+        <button class="train-link train-7" data-train="7">7</button>
+        <button class="train-link train-S" data-train="S" title="All shuttles">S</button>
+        <button class="train-link train-GS" data-train="GS" title="42 St Shuttle">GS</button>
+        <button class="train-link train-FS" data-train="FS" title="Franklin Av Shuttle">FS</button>
+        <button class="train-link train-H" data-train="H" title="Rockaway Park Shuttle">H</button>
+        <button class="train-link train-SI" data-train="SI">SI</button>
+    </div>
+
+    <h2>Railroads</h2>
+    <h3>Long Island Rail Road</h3>
+    <div class="train-grid rail-grid">
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/babylon.ics">Babylon</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/hempstead.ics">Hempstead</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/oyster-bay.ics">Oyster Bay</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/ronkonkoma.ics">Ronkonkoma</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/montauk.ics">Montauk</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/long-beach.ics">Long Beach</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/far-rockaway.ics">Far Rockaway</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/west-hempstead.ics">West Hempstead</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/port-washington.ics">Port Washington</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/port-jefferson.ics">Port Jefferson</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/belmont-park.ics">Belmont Park</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/city-terminal-zone.ics">City Terminal Zone</button>
+        <button class="train-link rail-link rail-lirr" data-path="/api/calendars/lirr/greenport.ics">Greenport</button>
+    </div>
+    <h3>Metro-North Railroad</h3>
+    <div class="train-grid rail-grid">
+        <button class="train-link rail-link rail-mnr-hudson" data-path="/api/calendars/mnr/hudson.ics">Hudson</button>
+        <button class="train-link rail-link rail-mnr-harlem" data-path="/api/calendars/mnr/harlem.ics">Harlem</button>
+        <button class="train-link rail-link rail-mnr-new-haven" data-path="/api/calendars/mnr/new-haven.ics">New Haven</button>
+        <button class="train-link rail-link rail-mnr-new-haven" data-path="/api/calendars/mnr/new-canaan.ics">New Canaan</button>
+        <button class="train-link rail-link rail-mnr-new-haven" data-path="/api/calendars/mnr/danbury.ics">Danbury</button>
+        <button class="train-link rail-link rail-mnr-new-haven" data-path="/api/calendars/mnr/waterbury.ics">Waterbury</button>
+    </div>
+    <h3>PATH</h3>
+    <div class="train-grid rail-grid">
+        <button class="train-link rail-link rail-path-nwk-wtc" data-path="/api/calendars/path/nwk-wtc.ics">Newark - WTC</button>
+        <button class="train-link rail-link rail-path-jsq-33" data-path="/api/calendars/path/jsq-33.ics">Journal Square - 33 St</button>
+        <button class="train-link rail-link rail-path-hob-33" data-path="/api/calendars/path/hob-33.ics">Hoboken - 33 St</button>
+        <button class="train-link rail-link rail-path-hob-wtc" data-path="/api/calendars/path/hob-wtc.ics">Hoboken - WTC</button>
+    </div>
+    
+    <div class="url-section" id="urlSection">
+        <h3>Calendar Subscription URL</h3>
+        <p>Copy this URL and add it to your calendar app:</p>
+        <div class="url-container">
+            <div class="url-box" id="urlBox"></div>
+            <button class="copy-btn" id="copyBtn">Copy</button>
+        </div>
+        <a class="subscribe-link" id="subscribeLink">Subscribe in Apple Calendar</a>
+    </div>
+    
+    <div class="instructions">
+        <h2>How to Subscribe</h2>
+        <ol>
+            <li>Click on a train line above to get its calendar URL</li>
+            <li>Click the "Copy" button to copy the URL</li>
+            <li>In your calendar app (Google Calendar, Apple Calendar, Outlook, etc.), look for "Subscribe to calendar" or "Add calendar by URL"</li>
+            <li>Paste the URL you copied</li>
+            <li>Your calendar will stay updated with MTA service alerts and planned service changes so you can plan ahead!</li>
+        </ol>
+    </div>
+
+    <script>
+        const trainButtons = document.querySelectorAll('.train-link');
+        const urlSection = document.getElementById('urlSection');
+        const urlBox = document.getElementById('urlBox');
+        const copyBtn = document.getElementById('copyBtn');
+        const subscribeLink = document.getElementById('subscribeLink');
+        
+        trainButtons.forEach(button => {
+            button.addEventListener('click', () => {
+                const path = button.dataset.path || '/api/calendars/train/' + button.dataset.train + '.ics';
+                const url = window.location.origin + path;
+                
+                // Update selected state
+                trainButtons.forEach(btn => btn.classList.remove('selected'));
+                button.classList.add('selected');
+                
+                // Show URL section
+                urlSection.classList.add('visible');
+                urlBox.textContent = url;
+
+                // One tap subscribes on iOS and macOS; the webcal endpoint redirects Calendar to the .ics
+                if (button.dataset.train) {
+                    subscribeLink.href = 'webcal://' + window.location.host + '/webcal/' + button.dataset.train;
+                    subscribeLink.style.display = '';
+                } else {
+                    subscribeLink.style.display = 'none';
+                }
+                
+                // Reset copy button
+                copyBtn.textContent = 'Copy';
+                copyBtn.classList.remove('copied');
+                
+                // Scroll to URL section
+                urlSection.scrollIntoView({ behavior: 'smooth', block: 'nearest' });
+            });
+        });
+        
+        copyBtn.addEventListener('click', () => {
+            const url = urlBox.textContent;
+            navigator.clipboard.writeText(url).then(() => {
+                copyBtn.textContent = 'Copied!';
+                copyBtn.classList.add('copied');
+                setTimeout(() => {
+                    copyBtn.textContent = 'Copy';
+                    copyBtn.classList.remove('copied');
+                }, 2000);
+            });
+        });
+    </script>
+</body>
+</html>"#;
+
+    (
+        StatusCode::OK,
+        [("Content-Type", "text/html; charset=utf-8")],
+        html,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use chrono::TimeZone;
+    use nyc_train_time::calendar::{AlertKind, Category};
+    use nyc_train_time::proto::gtfs_realtime::FeedMessage;
+    use pretty_assertions::{assert_eq, assert_ne};
+    use protobuf::Message;
+    use tower::ServiceExt;
+
+    /// App state with the golden alert feed already cached, so requests never hit the MTA API.
+    async fn test_state() -> AppState {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let feed = FeedMessage::parse_from_bytes(&pb_bytes).unwrap();
+        let feed = Feed::parse(&feed);
+
+        let bus_routes = BusRoutes::load(["tests/fixtures/bus_routes.txt"]).unwrap();
+        let mut state = AppState::new(
+            Stations::default(),
+            bus_routes,
+            StopSequences::default(),
+            SavedCalendars::in_memory(SAVED_CALENDARS_CAPACITY),
+            None,
+            None,
+        );
+        state.clock = || Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        state.started = Utc.with_ymd_and_hms(2025, 12, 15, 11, 0, 0).unwrap();
+        state
+            .feed_cache
+            .insert(nyc_train_time::SUBWAY_ALERTS_URL, Arc::new(feed))
+            .await;
+        state
+    }
+
+    async fn send(state: &AppState, request: Request<Body>) -> Response {
+        limited_routes(cors_layer(None))
+            .merge(streaming_routes(cors_layer(None)))
+            .merge(export_routes())
+            .merge(unlimited_routes())
+            .layer(middleware::from_fn(problem_json))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                count_requests,
+            ))
+            .layer(version_header_layer())
+            .with_state(state.clone())
+            .oneshot(request)
+            .await
+            .unwrap()
+    }
+
+    async fn get_response(state: &AppState, uri: &str) -> Response {
+        send(state, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn head_response(state: &AppState, uri: &str) -> Response {
+        send(state, Request::head(uri).body(Body::empty()).unwrap()).await
+    }
+
+    async fn body_string(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_train_calendar_extensions() {
+        let state = test_state().await;
+
+        for uri in [
+            "/api/calendars/train/A",
+            "/api/calendars/train/A.ics",
+            "/api/calendars/train/A.ICS",
+        ] {
+            let response = get_response(&state, uri).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(response.headers()["Content-Type"], ICS_CONTENT_TYPE);
+            assert!(body_string(response).await.starts_with("BEGIN:VCALENDAR"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_train_calendar_unsupported_extension() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.xml").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unsupported format: xml. Supported formats: ics, json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_train_calendar_accept_header() {
+        let state = test_state().await;
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A",
+            header::ACCEPT,
+            "application/json",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], JSON_CONTENT_TYPE);
+        assert!(
+            response
+                .headers()
+                .get_all(header::VARY)
+                .iter()
+                .any(|v| v == "Accept")
+        );
+        let events: Vec<serde_json::Value> =
+            serde_json::from_str(&body_string(response).await).unwrap();
+        assert!(!events.is_empty());
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A",
+            header::ACCEPT,
+            "text/calendar",
+        )
+        .await;
+        assert_eq!(response.headers()["Content-Type"], ICS_CONTENT_TYPE);
+
+        // The extension takes precedence over the Accept header
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A.ics",
+            header::ACCEPT,
+            "application/json",
+        )
+        .await;
+        assert_eq!(response.headers()["Content-Type"], ICS_CONTENT_TYPE);
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A.json",
+            header::ACCEPT,
+            "text/calendar",
+        )
+        .await;
+        assert_eq!(response.headers()["Content-Type"], JSON_CONTENT_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_train_calendar_not_acceptable() {
+        let state = test_state().await;
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A",
+            header::ACCEPT,
+            "application/xml",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(
+            body_string(response).await,
+            "None of the accepted media types can be served. Supported types: text/calendar, application/json"
+        );
+    }
+
+    #[test]
+    fn test_negotiate_format() {
+        fn negotiate(accept: &str) -> Option<CalendarFormat> {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+            negotiate_format(&headers)
+        }
+
+        assert_eq!(
+            negotiate_format(&HeaderMap::new()),
+            Some(CalendarFormat::Ics)
+        );
+        assert_eq!(negotiate("*/*"), Some(CalendarFormat::Ics));
+        assert_eq!(
+            negotiate("text/html,application/xhtml+xml,*/*;q=0.8"),
+            Some(CalendarFormat::Ics)
+        );
+        assert_eq!(
+            negotiate("text/calendar;q=0.5, application/json"),
+            Some(CalendarFormat::Json)
+        );
+        assert_eq!(negotiate("application/*"), Some(CalendarFormat::Json));
+        assert_eq!(
+            negotiate("application/json;q=0, */*"),
+            Some(CalendarFormat::Ics)
+        );
+        assert_eq!(negotiate("application/xml, text/html"), None);
+        assert_eq!(negotiate("text/calendar;q=0"), None);
+    }
+
+    #[tokio::test]
+    async fn test_train_calendar_unknown_line() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/ZZ.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown train line: ZZ. Did you mean Z?"
+        );
+
+        let response = get_response(&state, "/api/calendars/train/PURPLE.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_string(response).await, "Unknown train line: PURPLE.");
+    }
+
+    #[tokio::test]
+    async fn test_express_variants() {
+        let state = test_state().await;
+        let uids = |body: String| -> Vec<(String, Vec<String>)> {
+            let events: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+            events
+                .iter()
+                .map(|event| {
+                    let routes = event["routes"].as_array().unwrap();
+                    (
+                        event["id"].as_str().unwrap().to_string(),
+                        routes
+                            .iter()
+                            .map(|route| route.as_str().unwrap().to_string())
+                            .collect(),
+                    )
+                })
+                .collect()
+        };
+
+        let response = get_response(&state, "/api/calendars/train/6x.json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let express = uids(body_string(response).await);
+        assert!(!express.is_empty());
+        assert!(
+            express
+                .iter()
+                .all(|(_, routes)| routes.contains(&"6X".to_string()))
+        );
+
+        let response = get_response(&state, "/api/calendars/train/6.json").await;
+        let local = uids(body_string(response).await);
+        assert!(express.iter().all(|event| local.contains(event)));
+
+        let response = get_response(&state, "/api/calendars/train/6.json?include_express=0").await;
+        let local_only = uids(body_string(response).await);
+        let tagged_local: Vec<_> = local
+            .into_iter()
+            .filter(|(_, routes)| routes.contains(&"6".to_string()))
+            .collect();
+        assert_eq!(local_only, tagged_local);
+        assert!(state.cache.contains_key("json:6?include_express=0"));
+
+        let response = get_response(&state, "/api/calendars/train/6.ics?include_express=no").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_line_suggestions() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/AA.ics").await;
+        assert_eq!(
+            body_string(response).await,
+            "Unknown train line: AA. Did you mean A?"
+        );
+
+        let response = get_response(&state, "/api/calendars/train/six.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown train line: SIX. Did you mean 6, SI or S?"
+        );
+
+        let response = get_response(&state, "/api/calendars/train/seven.json").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+        let error: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "type": "unknown-train-line",
+                "title": "Unknown train line",
+                "status": 404,
+                "detail": "Unknown train line: SEVEN. Did you mean 7 or S?",
+                "suggestions": ["7", "S"],
+            })
+        );
+
+        let request = Request::get("/train/GSS")
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let error: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(error["suggestions"], serde_json::json!(["GS", "G"]));
+    }
+
+    #[tokio::test]
+    async fn test_problem_json() {
+        let state = test_state().await;
+
+        // Without JSON in the Accept header, errors stay plain text
+        let response = get_response(&state, "/api/alerts/train/K").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "text/plain; charset=utf-8"
+        );
+        assert!(
+            response
+                .headers()
+                .get_all(header::VARY)
+                .iter()
+                .any(|v| v == "Accept")
+        );
+        assert_eq!(
+            body_string(response).await,
+            "Unknown train line: K. Did you mean A, C or E?"
+        );
+
+        let request = Request::get("/api/alerts/train/K")
+            .header(header::ACCEPT, "application/problem+json")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+        assert!(
+            response
+                .headers()
+                .get_all(header::VARY)
+                .iter()
+                .any(|v| v == "Accept")
+        );
+        let error: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "type": "unknown-train-line",
+                "title": "Unknown train line",
+                "status": 404,
+                "detail": "Unknown train line: K. Did you mean A, C or E?",
+                "suggestions": ["A", "C", "E"],
+            })
+        );
+
+        let request = Request::get("/api/calendars/train/A.ics?days=0")
+            .header(header::ACCEPT, "text/calendar, application/json;q=0.5")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(error["type"], "invalid-parameter");
+        assert_eq!(error["status"], 400);
+
+        let request = Request::get("/api/departures/not-a-stop")
+            .header(header::ACCEPT, "application/json;q=0")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_ne!(
+            response.headers()["Content-Type"],
+            PROBLEM_JSON_CONTENT_TYPE
+        );
+
+        // Successful responses are left alone
+        let request = Request::get("/api/alerts/train/A")
+            .header(header::ACCEPT, "application/problem+json")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let state = test_state().await;
+
+        get_response(&state, "/api/calendars/train/A.ics").await;
+        get_response(&state, "/api/calendars/train/A.ics").await;
+        get_response(&state, "/api/calendars/train/C.ics").await;
+        get_response(&state, "/not-a-route").await;
+
+        let response = get_response(&state, "/api/stats").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(
+            stats["requests"],
+            serde_json::json!({
+                "total": 4,
+                "by_route": {
+                    "/api/calendars/train/:train_name": 3,
+                    "/api/stats": 1,
+                },
+            })
+        );
+        assert_eq!(stats["cache"]["hits"], 1);
+        assert_eq!(stats["cache"]["misses"], 2);
+        // The test feed is cached up front rather than fetched
+        assert_eq!(
+            stats["feeds"],
+            serde_json::json!({ "last_success": {}, "last_error": null })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_problem_json_unauthorized() {
+        let state = test_state().await;
+
+        let request = Request::post("/admin/cache/purge")
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+        let error: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(error["type"], "unauthorized");
+        assert_eq!(error["status"], 401);
+    }
+
+    #[tokio::test]
+    async fn test_head_train_calendar_has_headers_without_body() {
+        let state = test_state().await;
+
+        let head = head_response(&state, "/api/calendars/train/A.ics").await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()["Content-Type"], ICS_CONTENT_TYPE);
+        assert_eq!(head.headers()["Cache-Control"], "public, max-age=30");
+        let content_length = head.headers()["Content-Length"].clone();
+        assert_eq!(body_string(head).await, "");
+
+        // The HEAD request cached the calendar, and GET serves the same bytes
+        assert!(state.cache.contains_key("A"));
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        assert_eq!(response.headers()["Content-Length"], content_length);
+        let body = body_string(response).await;
+        assert_eq!(content_length, body.len().to_string().as_str());
+    }
+
+    #[tokio::test]
+    async fn test_head_reuses_cached_calendar() {
+        let state = test_state().await;
+        state
+            .cache
+            .insert(
+                "L".to_string(),
+                Arc::new(CachedDocument::new("BEGIN:VCALENDAR\r\n".to_string())),
+            )
+            .await;
+
+        let head = head_response(&state, "/api/calendars/train/L").await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()["Content-Length"], "17");
+        assert_eq!(body_string(head).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_cache_control_counts_down() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=30");
+        assert_eq!(response.headers()["Age"], "0");
+
+        // The same calendar, cached 20 seconds ago
+        let document = state.cache.get("L").await.unwrap();
+        let cached_at = Instant::now() - Duration::from_secs(20);
+        state
+            .cache
+            .insert(
+                "L".to_string(),
+                Arc::new(CachedDocument {
+                    cached_at,
+                    ..CachedDocument::new(document.content.clone())
+                }),
+            )
+            .await;
+
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=10");
+        assert_eq!(response.headers()["Age"], "20");
+
+        let response =
+            get_with_if_none_match(&state, "/api/calendars/train/L.ics", &document.etag).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=10");
+
+        // Regenerating an unchanged calendar restarts the countdown but keeps its ETag
+        state.cache.invalidate("L").await;
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=30");
+        assert_eq!(response.headers()["ETag"], document.etag.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_timing_headers() {
+        let state = test_state().await;
+        let metrics = |response: &Response| -> Vec<String> {
+            response.headers()["Server-Timing"]
+                .to_str()
+                .unwrap()
+                .split(", ")
+                .map(|metric| metric.split(';').next().unwrap().to_string())
+                .collect()
+        };
+
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["X-Cache"], "MISS");
+        assert_eq!(metrics(&response), vec!["mta_fetch", "ics_gen", "cache"]);
+
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["X-Cache"], "HIT");
+        assert_eq!(metrics(&response), vec!["cache"]);
+        assert!(
+            response.headers()["Server-Timing"]
+                .to_str()
+                .unwrap()
+                .starts_with("cache;dur=")
+        );
+
+        let response = get_response(&state, "/api/calendars/train/L.json").await;
+        assert_eq!(response.headers()["X-Cache"], "MISS");
+        assert_eq!(metrics(&response), vec!["mta_fetch", "render", "cache"]);
+    }
+
+    #[tokio::test]
+    async fn test_head_unknown_line() {
+        let state = test_state().await;
+
+        let head = head_response(&state, "/api/calendars/train/ZZ.ics").await;
+        assert_eq!(head.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn get_with_header(
+        state: &AppState,
+        uri: &str,
+        name: header::HeaderName,
+        value: &str,
+    ) -> Response {
+        let request = Request::get(uri)
+            .header(name, value)
+            .body(Body::empty())
+            .unwrap();
+        send(state, request).await
+    }
+
+    async fn get_with_if_none_match(state: &AppState, uri: &str, etag: &str) -> Response {
+        get_with_header(state, uri, header::IF_NONE_MATCH, etag).await
+    }
+
+    #[tokio::test]
+    async fn test_etag_matching_if_none_match_is_not_modified() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()["ETag"].to_str().unwrap().to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+        let response = get_with_if_none_match(&state, "/api/calendars/train/A.ics", &etag).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["ETag"], etag.as_str());
+        assert_eq!(body_string(response).await, "");
+
+        let weak = format!("\"other\", W/{}", etag);
+        let response = get_with_if_none_match(&state, "/api/calendars/train/A.ics", &weak).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_etag_mismatch_returns_full_calendar() {
+        let state = test_state().await;
+
+        let response =
+            get_with_if_none_match(&state, "/api/calendars/train/A.ics", "\"stale\"").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_string(response).await.starts_with("BEGIN:VCALENDAR"));
+    }
+
+    #[tokio::test]
+    async fn test_etag_wildcard_is_not_modified() {
+        let state = test_state().await;
+
+        let response = get_with_if_none_match(&state, "/api/calendars/train/A.ics", "*").await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn test_cached_document_etag_is_stable() {
+        let a = CachedDocument::new("BEGIN:VCALENDAR".to_string());
+        let b = CachedDocument::new("BEGIN:VCALENDAR".to_string());
+        let c = CachedDocument::new("BEGIN:VCALENDAR\r\n".to_string());
+
+        assert_eq!(a.etag, b.etag);
+        assert!(a.etag != c.etag);
+    }
+
+    #[tokio::test]
+    async fn test_if_modified_since() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        let last_modified = response.headers()["Last-Modified"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(last_modified.ends_with(" GMT"));
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A.ics",
+            header::IF_MODIFIED_SINCE,
+            &last_modified,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(body_string(response).await, "");
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A.ics",
+            header::IF_MODIFIED_SINCE,
+            "Mon, 01 Jan 2024 00:00:00 GMT",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_if_none_match_takes_precedence_over_if_modified_since() {
+        let state = test_state().await;
+
+        let request = Request::get("/api/calendars/train/A.ics")
+            .header(header::IF_NONE_MATCH, "\"stale\"")
+            .header(header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_last_modified_is_kept_when_content_is_unchanged() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        let content = body_string(response).await;
+
+        // Pretend the same calendar was first generated long ago, then let the cache expire
+        let generated_at = Utc.with_ymd_and_hms(2025, 12, 15, 10, 0, 0).unwrap();
+        let previous = Arc::new(CachedDocument {
+            last_modified: generated_at,
+            ..CachedDocument::new(content)
+        });
+        state.history.insert("A".to_string(), previous).await;
+        state.cache.invalidate("A").await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        assert_eq!(
+            response.headers()["Last-Modified"],
+            "Mon, 15 Dec 2025 10:00:00 GMT"
+        );
+
+        state
+            .history
+            .insert(
+                "A".to_string(),
+                Arc::new(CachedDocument {
+                    last_modified: generated_at,
+                    ..CachedDocument::new("BEGIN:VCALENDAR\r\n".to_string())
+                }),
+            )
+            .await;
+        state.cache.invalidate("A").await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics").await;
+        assert!(response.headers()["Last-Modified"] != "Mon, 15 Dec 2025 10:00:00 GMT");
+    }
+
+    #[tokio::test]
+    async fn test_days_parameter() {
+        let state = test_state().await;
+
+        let week =
+            body_string(get_response(&state, "/api/calendars/train/A.ics?days=7").await).await;
+        let quarter =
+            body_string(get_response(&state, "/api/calendars/train/A.ics?days=90").await).await;
+        assert!(week.matches("BEGIN:VEVENT").count() < quarter.matches("BEGIN:VEVENT").count());
+
+        assert!(state.cache.contains_key("A?days=7"));
+        assert!(state.cache.contains_key("A?days=90"));
+
+        // The default window shares the unparameterized cache entry
+        let default =
+            body_string(get_response(&state, "/api/calendars/train/A.ics?days=45").await).await;
+        assert!(state.cache.contains_key("A"));
+
+        // The calendar says how far ahead it covers
+        let description = |calendar: &str| {
+            calendar
+                .replace("\r\n ", "")
+                .lines()
+                .find_map(|line| line.strip_prefix("X-WR-CALDESC:"))
+                .unwrap()
+                .to_string()
+        };
+        assert!(
+            description(&week).ends_with(" Shows alerts for the next 7 days\\, including today.")
+        );
+        assert!(description(&default).ends_with(" the next 45 days\\, including today."));
+    }
+
+    #[tokio::test]
+    async fn test_days_out_of_range() {
+        let state = test_state().await;
+
+        for uri in [
+            "/api/calendars/train/A.ics?days=0",
+            "/api/calendars/trains/F,L.ics?days=91",
+            "/api/calendars/all.ics?days=soon",
+        ] {
+            let response = get_response(&state, uri).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            assert!(body_string(response).await.contains("from 1 to 90"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_min_severity_parameter() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.json").await;
+        let all: Vec<serde_json::Value> =
+            serde_json::from_str(&body_string(response).await).unwrap();
+        let response =
+            get_response(&state, "/api/calendars/train/A.json?min_severity=severe").await;
+        let severe: Vec<serde_json::Value> =
+            serde_json::from_str(&body_string(response).await).unwrap();
+
+        assert!(severe.len() < all.len());
+        assert!(severe.iter().all(|event| event["severity"] == "severe"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?min_severity=high").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown severity: high. Expected one of: info, warning, severe"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trains_lists_severity_mapping() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/trains").await;
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+
+        assert_eq!(body["trains"][0]["id"], "A");
+        assert_eq!(
+            body["trains"][0]["calendar_url"],
+            "http://localhost:3000/api/calendars/train/A.ics"
+        );
+        let franklin = body["trains"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|train| train["id"] == "FS")
+            .unwrap();
+        assert_eq!(franklin["name"], "Franklin Av Shuttle");
+        assert_eq!(
+            franklin["calendar_url"],
+            "http://localhost:3000/api/calendars/train/FS.ics"
+        );
+        let delays = body["severity_levels"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|level| level["alert_type"] == "Delays")
+            .unwrap();
+        assert_eq!(delays["priority"], 26);
+        assert_eq!(delays["severity"], "severe");
+    }
+
+    #[tokio::test]
+    async fn test_direction_parameter() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?direction=south").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.cache.contains_key("A?direction=south"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?direction=up").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown direction: up. Expected one of: north, south"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_calendar_categories() {
+        let state = test_state().await;
+
+        let ics = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert!(ics.contains("CATEGORIES:Planned Work,A\r\n"));
+        assert!(ics.contains("CATEGORIES:Delays,A,C\r\n"));
+
+        // The JSON events have the same categories
+        let json = body_string(get_response(&state, "/api/calendars/train/A.json").await).await;
+        let events: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        let mut json_categories: Vec<&str> = events
+            .iter()
+            .map(|event| event["category"].as_str().unwrap())
+            .collect();
+        let mut ics_categories: Vec<&str> = ics
+            .split("\r\n")
+            .filter_map(|line| line.strip_prefix("CATEGORIES:"))
+            .map(|categories| categories.split(',').next().unwrap())
+            .collect();
+        json_categories.sort();
+        json_categories.dedup();
+        ics_categories.sort();
+        ics_categories.dedup();
+        assert_eq!(json_categories, ics_categories);
+        assert!(json_categories.contains(&"Planned Work"));
+    }
+
+    #[tokio::test]
+    async fn test_calendars_only_name_their_own_lines() {
+        let state = test_state().await;
+        let calendar = |path: &'static str| {
+            let state = state.clone();
+            async move {
+                body_string(get_response(&state, path).await)
+                    .await
+                    .replace("\r\n ", "")
+            }
+        };
+
+        // A line's calendar leaves the other lines out of the title and the headline...
+        let a = calendar("/api/calendars/train/A.ics").await;
+        assert!(a.contains("SUMMARY:⚠️ A: Delays (until further notice)\r\n"));
+        assert!(a.contains("DESCRIPTION:Downtown [A] trains are running with delays"));
+        assert!(a.contains("CATEGORIES:Delays,A,C\r\n"));
+        let c = calendar("/api/calendars/train/C.ics").await;
+        assert!(c.contains("SUMMARY:⚠️ C: Delays (until further notice)\r\n"));
+        assert!(c.contains("DESCRIPTION:Downtown [C] trains are running with delays"));
+        // ...but not lines the alert doesn't affect
+        let r = calendar("/api/calendars/train/R.ics").await;
+        assert!(r.contains("Canal St [R] Station - no transfer to [J][Z][N][Q][6]"));
+
+        // A combined calendar lists each of its lines the alert affects, once
+        let combined = calendar("/api/calendars/trains/A,C,F.ics").await;
+        assert_eq!(
+            combined
+                .matches("SUMMARY:⚠️ A\\, C: Delays (until further notice)\r\n")
+                .count(),
+            1
+        );
+        assert!(combined.contains("DESCRIPTION:Downtown [A][C] trains"));
+        assert!(combined.contains("SUMMARY:🔧 F: Planned - Stops Skipped\r\n"));
+
+        // The JSON keeps the MTA's own text
+        let json = calendar("/api/calendars/train/A.json").await;
+        assert!(json.contains("Downtown [A][C] trains"));
+    }
+
+    #[tokio::test]
+    async fn test_type_parameter_caches_subsets_independently() {
+        let state = test_state().await;
+
+        for kind in ["planned", "realtime"] {
+            let uri = format!("/api/calendars/train/A.json?type={}", kind);
+            let events: Vec<serde_json::Value> =
+                serde_json::from_str(&body_string(get_response(&state, &uri).await).await).unwrap();
+            assert!(events.iter().all(|event| event["kind"] == kind), "{}", kind);
+        }
+
+        assert!(state.cache.contains_key("json:A?type=planned"));
+        assert!(state.cache.contains_key("json:A?type=realtime"));
+
+        // `all` is the default and shares the unparameterized entry
+        get_response(&state, "/api/calendars/train/A.ics?type=all").await;
+        assert!(state.cache.contains_key("A"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?type=weekend").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_lang_parameter() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?lang=es").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            body_string(response)
+                .await
+                .contains("X-WR-CALNAME:Alertas del tren A de la MTA\r\n")
+        );
+        assert!(state.cache.contains_key("A?lang=es"));
+
+        let response = get_response(&state, "/api/calendars/station/A15.ics?lang=es").await;
+        assert!(
+            body_string(response)
+                .await
+                .contains("X-WR-CALNAME:Alertas de la estación A15 de la MTA\r\n")
+        );
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?lang=fr").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Unsupported language: fr. Expected one of: en, es"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alert_detail() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/alerts/A27N%23EL290X").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let alert: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(alert["id"], "A27N#EL290X");
+        assert_eq!(alert["informed_entities"][0]["stop_id"], "A27N");
+
+        let response = get_response(&state, "/api/alerts/no-such-alert").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_string(response).await, "Unknown alert: no-such-alert.");
+    }
+
+    #[tokio::test]
+    async fn test_calendar_events_link_to_alert_detail() {
+        let state = AppState {
+            public_url: Some("https://trains.example.com/".to_string()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/api/calendars/station/A27.ics").await;
+        let body = body_string(response).await;
+
+        assert!(body.contains("URL:https://trains.example.com/api/alerts/A27N%23EL290X\r\n"));
+
+        // Without a public URL there's nothing trustworthy to link to
+        let state = test_state().await;
+        let response = get_with_header(
+            &state,
+            "/api/calendars/station/A27.ics",
+            header::HOST,
+            "evil.example",
+        )
+        .await;
+        let body = body_string(response).await;
+        assert!(body.contains("UID:mta-alert-A27N#EL290X"));
+        assert!(!body.contains("/api/alerts/"));
+    }
+
+    #[tokio::test]
+    async fn test_calendar_events_prefer_mta_links() {
+        let state = test_state().await;
+        let mut feed = (*state.subway_feed().await.unwrap()).clone();
+        let event = feed
+            .events
+            .iter_mut()
+            .find(|event| event.mta_alert_id == "A27N#EL290X")
+            .unwrap();
+        event.url = Some("https://new.mta.info/alerts/elevator outage?station=A27".to_string());
+        state
+            .feed_cache
+            .insert(nyc_train_time::SUBWAY_ALERTS_URL, Arc::new(feed))
+            .await;
+
+        let response = get_response(&state, "/api/calendars/station/A27.ics").await;
+        let body = body_string(response).await;
+
+        assert!(body.contains("URL:https://new.mta.info/alerts/elevator%20outage?station=A27\r\n"));
+        assert!(!body.contains("/api/alerts/A27N%23EL290X"));
+    }
+
+    #[tokio::test]
+    async fn test_accessibility_calendar() {
+        let state = test_state().await;
+        let outages = std::fs::read("tests/fixtures/ene_outages.json").unwrap();
+        let equipment = std::fs::read("tests/fixtures/ene_equipment.json").unwrap();
+        let events =
+            nyc_train_time::accessibility::outages_to_events(&outages, &equipment).unwrap();
+        state
+            .accessibility_cache
+            .insert(nyc_train_time::accessibility::OUTAGES_URL, Arc::new(events))
+            .await;
+
+        let response = get_response(&state, "/api/calendars/accessibility/A15.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:MTA A15 Station Accessibility Outages"));
+        assert!(body.contains("Elevator EL290X at 125 St"));
+        assert!(!body.contains("ES123"));
+        // Outages aren't subway alerts, so they don't link to the alert detail endpoint
+        assert!(!body.contains("URL:"));
+
+        let response = get_response(&state, "/api/calendars/accessibility/not-a-stop.ics").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_station_calendar_names() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+
+        // Stations are named after their stop and lines, since several share a name
+        let body = body_string(get_response(&state, "/api/calendars/station/A15.ics").await).await;
+        assert!(body.contains("X-WR-CALNAME:MTA 125 St (A\\, B\\, C\\, D) Station Alerts\r\n"));
+
+        let response = get_response(&state, "/api/calendars/station/A15.ics?lang=es").await;
+        assert!(body_string(response).await.contains(
+            "X-WR-CALNAME:Alertas de la estación 125 St (A\\, B\\, C\\, D) de la MTA\r\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_train_accessibility_calendar() {
+        let response = get_response(
+            &test_state().await,
+            "/api/calendars/train/L/accessibility.ics",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/api/calendars/train/l/accessibility.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:MTA L Train Accessibility Alerts"));
+        // An elevator outage at 8 Av, which the L serves
+        assert!(body.contains("X-MTA-ALERT-ID:L01N#EL221"));
+        assert!(!body.contains("CATEGORIES:Delays"));
+        assert!(state.cache.contains_key("train-accessibility:L"));
+
+        let response = get_response(&state, "/api/alerts/train/L.json?accessibility=1").await;
+        let events: Vec<serde_json::Value> =
+            serde_json::from_str(&body_string(response).await).unwrap();
+        assert!(
+            events
+                .iter()
+                .any(|event| event["mta_alert_id"] == "L01N#EL221")
+        );
+        assert!(events.iter().all(|event| event["alert_type"] != "Delays"));
+
+        let response = get_response(&state, "/api/calendars/train/X/accessibility.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_departures() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+        let text = std::fs::read_to_string("tests/fixtures/ace_trip_updates.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .trips_cache
+            .insert(
+                departures::feed_url("gtfs-ace"),
+                Arc::new(departures::proto_feed_to_trips(&feed)),
+            )
+            .await;
+        // 125 St is also served by the B and D
+        state
+            .trips_cache
+            .insert(departures::feed_url("gtfs-bdfm"), Arc::new(Vec::new()))
+            .await;
+
+        let response = get_response(&state, "/api/departures/A15.json?limit=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=15");
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["name"], "125 St");
+        assert_eq!(body["departures"]["north"].as_array().unwrap().len(), 1);
+        assert_eq!(body["departures"]["north"][0]["route_id"], "A");
+        assert_eq!(body["departures"]["north"][0]["minutes_away"], 3);
+        assert_eq!(
+            body["departures"]["south"][0]["destination"],
+            "Far Rockaway-Mott Av"
+        );
+
+        let response = get_response(&state, "/api/departures/A99.json").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = get_response(&state, "/api/departures/A15.json?limit=50").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get_response(&test_state().await, "/api/departures/A15.json").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_station_widget() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+        let text = std::fs::read_to_string("tests/fixtures/ace_trip_updates.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .trips_cache
+            .insert(
+                departures::feed_url("gtfs-ace"),
+                Arc::new(departures::proto_feed_to_trips(&feed)),
+            )
+            .await;
+        state
+            .trips_cache
+            .insert(departures::feed_url("gtfs-bdfm"), Arc::new(Vec::new()))
+            .await;
+
+        let response = get_response(&state, "/api/widget/station/A15.json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=15");
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["name"], "125 St");
+        assert_eq!(body["updated_at"], "2025-12-15T12:00:00Z");
+        assert!(body["alerts"].is_array());
+        assert_eq!(body["arrivals"]["north"][0]["route_id"], "A");
+        assert_eq!(body["arrivals"]["north"][0]["minutes_away"], 3);
+        assert!(body["arrivals"]["south"].as_array().unwrap().len() <= WIDGET_ARRIVALS);
+
+        let response = get_response(&state, "/api/widget/station/A15.json?limit=1").await;
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["arrivals"]["south"].as_array().unwrap().len(), 1);
+
+        let response = get_response(&state, "/api/widget/station/A99.json").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = get_response(&state, "/api/widget/station/A15;").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get_response(&test_state().await, "/api/widget/station/A15.json").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    fn lirr_event(uid: &str, route_id: &str) -> CalendarEvent {
+        let time = Utc.with_ymd_and_hms(2025, 12, 15, 10, 0, 0).unwrap();
+        CalendarEvent {
+            uid: uid.to_string(),
+            summary: String::new(),
+            description: String::new(),
+            html_description: None,
+            start: time,
+            end: None,
+            created_at: time,
+            updated_at: time,
+            feed_time: time,
+            mta_alert_id: uid.to_string(),
+            routes: vec![route_id.to_string()],
+            stop_ids: Vec::new(),
+            affected_stations: Vec::new(),
+            alert_type: "Delays".to_string(),
+            severity: Severity::Severe,
+            kind: AlertKind::Realtime,
+            category: Category::Delays,
+            translations: Default::default(),
+            all_day: false,
+            url: None,
+            sequence: 0,
+            series_sequence: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lirr_calendar() {
+        let state = test_state().await;
+        let feed = Feed {
+            events: vec![lirr_event("lirr-1", "1"), lirr_event("lirr-2", "9")],
+            alerts: Vec::new(),
+        };
+        state
+            .feed_cache
+            .insert(nyc_train_time::LIRR_ALERTS_URL, Arc::new(feed))
+            .await;
+
+        let response = get_response(&state, "/api/calendars/lirr/Babylon.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:LIRR Babylon Branch Alerts"));
+        // The Babylon Branch's route ID is 1, but it isn't colored like the 1 train
+        assert!(!body.contains("COLOR:"));
+        assert!(body.contains("SUMMARY:⚠️ Babylon Branch: Delays"));
+        assert!(!body.contains("Port Washington"));
+        assert!(!body.contains("URL:"));
+
+        let response = get_response(&state, "/api/calendars/lirr/flatbush.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mnr_calendar() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/mnr_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(nyc_train_time::MNR_ALERTS_URL, Arc::new(Feed::parse(&feed)))
+            .await;
+
+        let response = get_response(&state, "/api/calendars/mnr/hudson.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:Metro-North Hudson Line Alerts"));
+        assert!(body.contains("SUMMARY:Hudson Line: Alert"));
+        assert!(!body.contains("New Canaan"));
+
+        let response = get_response(&state, "/api/calendars/mnr/new-canaan.ics").await;
+        let body = body_string(response).await;
+        assert!(body.contains("SUMMARY:New Haven Line\\, New Canaan Branch: Alert"));
+
+        let response = get_response(&state, "/api/calendars/mnr/babylon.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown Metro-North line: babylon. Expected one of: hudson, harlem, new-haven, new-canaan, danbury, waterbury"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_calendar() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/path_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(
+                nyc_train_time::PATH_ALERTS_URL,
+                Arc::new(Feed::parse(&feed)),
+            )
+            .await;
+
+        let response = get_response(&state, "/api/calendars/path/NWK-WTC.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:PATH Newark - World Trade Center Alerts"));
+        assert!(body.contains("SUMMARY:⚠️ Newark - World Trade Center: Significant Delays"));
+        assert!(!body.contains("Hoboken"));
+
+        let response = get_response(&state, "/api/calendars/path/hob-wtc").await;
+        let body = body_string(response).await;
+        assert!(
+            body.contains("SUMMARY:Hoboken - 33rd Street\\, Hoboken - World Trade Center: Alert")
+        );
+
+        let response = get_response(&state, "/api/calendars/path/jsq-wtc.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown PATH route: jsq-wtc. Expected one of: nwk-wtc, jsq-33, hob-33, hob-wtc"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bus_calendar() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/bus_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(nyc_train_time::BUS_ALERTS_URL, Arc::new(Feed::parse(&feed)))
+            .await;
+
+        let response = get_response(&state, "/api/calendars/bus/m15-sbs.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:MTA M15-SBS Bus Alerts"));
+        assert!(body.contains("SUMMARY:M15-SBS\\, M15: Alert"));
+        assert!(!body.contains("B44"));
+
+        let response = get_response(&state, "/api/calendars/bus/M1000.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_shuttle_calendars() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/shuttle_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(
+                nyc_train_time::SUBWAY_ALERTS_URL,
+                Arc::new(Feed::parse(&feed)),
+            )
+            .await;
+
+        // The S calendar covers all three shuttles
+        let response = get_response(&state, "/api/calendars/train/S.ics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:MTA S Train Alerts"));
+        assert!(body.contains("SUMMARY:GS: Alert"));
+        assert!(body.contains("SUMMARY:FS: Alert"));
+        assert!(body.contains("SUMMARY:H: Alert"));
+        assert!(!body.contains("SUMMARY:A: Alert"));
+
+        for (path, calendar_name, summary) in [
+            ("gs", "MTA 42 St Shuttle Alerts", "SUMMARY:GS: Alert"),
+            ("FS", "MTA Franklin Av Shuttle Alerts", "SUMMARY:FS: Alert"),
+            ("H", "MTA Rockaway Park Shuttle Alerts", "SUMMARY:H: Alert"),
+        ] {
+            let response =
+                get_response(&state, &format!("/api/calendars/train/{}.ics", path)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = body_string(response).await;
+            assert!(body.contains(&format!("X-WR-CALNAME:{}", calendar_name)));
+            assert_eq!(body.matches("BEGIN:VEVENT").count(), 1, "{}", path);
+            assert!(body.contains(summary));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recently_ended_alerts() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/expired_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(
+                nyc_train_time::SUBWAY_ALERTS_URL,
+                Arc::new(Feed::parse(&feed)),
+            )
+            .await;
+
+        // The switch problem ended half an hour ago, within the grace period, and the inspection
+        // that's on again tomorrow keeps its past period too
+        let body = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert!(body.contains("UID:mta-alert-lmm:alert:80002@nyctraincal"));
+        assert!(body.contains("UID:mta-alert-lmm:alert:80005-0@nyctraincal"));
+        assert!(body.contains("UID:mta-alert-lmm:alert:80005-1@nyctraincal"));
+        assert!(!body.contains("UID:mta-alert-lmm:alert:80001@"));
+    }
+
+    #[tokio::test]
+    async fn test_staten_island_calendar() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/sir_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        state
+            .feed_cache
+            .insert(
+                nyc_train_time::SUBWAY_ALERTS_URL,
+                Arc::new(Feed::parse(&feed)),
+            )
+            .await;
+
+        // Alerts tagged SI and SIR both belong to the Staten Island Railway, whichever name the
+        // calendar is asked for by
+        for path in ["SI", "sir", "SIR.ics"] {
+            let response = get_response(&state, &format!("/api/calendars/train/{}", path)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = body_string(response).await;
+            assert!(
+                body.contains("X-WR-CALNAME:MTA SI Train Alerts"),
+                "{}",
+                path
+            );
+            assert_eq!(body.matches("BEGIN:VEVENT").count(), 2, "{}", path);
+            assert!(body.contains("St George and Tottenville"));
+            assert!(body.contains("running with delays in both directions"));
+            assert!(!body.contains("[A] trains"));
+        }
+        assert!(state.cache.contains_key("SI"));
+        assert!(!state.cache.contains_key("SIR"));
+    }
+
+    #[tokio::test]
+    async fn test_commute_calendar() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            stop_sequences: Arc::new(StopSequences::load("tests/fixtures/gtfs_subway").unwrap()),
+            ..test_state().await
+        };
+
+        // The only F alert between Bergen St and Broadway-Lafayette St covers the whole line
+        let response =
+            get_response(&state, "/api/calendars/commute.ics?from=F20&to=D21&via=F").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains("X-WR-CALNAME:MTA Bergen St to Broadway-Lafayette St (F)"));
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 1);
+        assert!(body.contains("lmm:planned_work:29100"));
+        assert!(state.cache.contains_key("commute:F20-D21:F"));
+
+        // 57 St is at the far end of the fixture's F
+        let body = body_string(
+            get_response(&state, "/api/calendars/commute.ics?from=D21S&to=B10&via=f").await,
+        )
+        .await;
+        assert!(body.contains("lmm:planned_work:28826"));
+        assert!(!body.contains("lmm:planned_work:28825"));
+
+        let response =
+            get_response(&state, "/api/calendars/commute.ics?from=F20&to=D21&via=G").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Station D21 is not on the G train."
+        );
+
+        let response = get_response(&state, "/api/calendars/commute.ics?from=F20&to=D21").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = get_response(
+            &test_state().await,
+            "/api/calendars/commute.ics?from=F20&to=D21&via=F",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_station_search() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/api/stations?q=Bedford").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{
+                "stop_id": "L08",
+                "name": "Bedford Av",
+                "borough": "brooklyn",
+                "routes": ["L"],
+                "calendar_url": "http://localhost:3000/api/calendars/station/L08.ics",
+            }])
+        );
+
+        let response = get_response(&state, "/api/stations?q=st").await;
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        let stop_ids: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|station| station["stop_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(stop_ids, vec!["A15", "F20", "D21", "127", "101", "R01"]);
+
+        let response = get_response(&state, "/api/stations?q=").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = get_response(&test_state().await, "/api/stations?q=bedford").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_badge() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/badge/f.svg").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], "image/svg+xml");
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=30");
+        let svg = body_string(response).await;
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        // None of the F alerts in the golden feed have started yet
+        assert!(svg.contains("<title>F train: Good Service</title>"));
+        assert!(state.cache.contains_key("badge:F"));
+
+        let response = get_response(&state, "/api/badge/K.svg").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = get_response(&state, "/api/badge/ACE.svg").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/status.json").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], JSON_CONTENT_TYPE);
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=30");
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        let lines = body["lines"].as_array().unwrap();
+        assert_eq!(lines.len(), LINES.len());
+        assert_eq!(lines[0]["id"], "A");
+
+        let f = lines.iter().find(|line| line["id"] == "F").unwrap();
+        assert_eq!(f["status"], "good_service");
+        assert_eq!(f["label"], "Good Service");
+        assert_eq!(f["active_alerts"], 0);
+        assert!(f["upcoming_alerts"].as_u64().unwrap() > 0);
+        assert!(state.cache.contains_key("json:status"));
+    }
+
+    #[tokio::test]
+    async fn test_train_feeds() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/feeds/train/A.rss").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], RSS_CONTENT_TYPE);
+        assert!(body_string(response).await.contains("<link>/</link>"));
+        assert!(state.cache.contains_key("rss:A"));
+
+        let response = get_response(&state, "/api/feeds/train/a.atom").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], ATOM_CONTENT_TYPE);
+        assert!(
+            body_string(response)
+                .await
+                .contains(r#"<link rel="alternate" href="/"/>"#)
+        );
+        assert!(state.cache.contains_key("atom:A"));
+
+        let state = AppState {
+            public_url: Some("https://trains.example.com/".to_string()),
+            ..test_state().await
+        };
+        let response = get_response(&state, "/api/feeds/train/A.rss").await;
+        assert!(
+            body_string(response)
+                .await
+                .contains("<link>https://trains.example.com/</link>")
+        );
+
+        // With no alerts, the Atom feed was last updated now
+        let state = test_state().await;
+        let feed = Feed {
+            events: Vec::new(),
+            alerts: Vec::new(),
+        };
+        state
+            .feed_cache
+            .insert(nyc_train_time::SUBWAY_ALERTS_URL, Arc::new(feed))
+            .await;
+        let response = get_response(&state, "/api/feeds/train/A.atom").await;
+        assert!(
+            body_string(response)
+                .await
+                .contains("<updated>2025-12-15T12:00:00Z</updated>")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_feeds_opml() {
+        let state = AppState {
+            public_url: Some("https://trains.example.com".to_string()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/api/feeds.opml").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "text/x-opml; charset=utf-8"
+        );
+        let opml = body_string(response).await;
+        assert_eq!(opml.matches("type=\"rss\"").count(), LINES.len());
+        assert!(opml.contains("xmlUrl=\"https://trains.example.com/api/feeds/train/A.rss\""));
+
+        let response = get_response(&state, "/api/feeds.opml?format=atom").await;
+        let opml = body_string(response).await;
+        assert!(opml.contains("xmlUrl=\"https://trains.example.com/api/feeds/train/A.atom\""));
+
+        let response = get_response(&state, "/api/feeds.opml?format=json").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_export() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/export.zip").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Type"], "application/zip");
+        assert_eq!(
+            response.headers()["Content-Disposition"],
+            "attachment; filename=\"mta-subway-calendars.zip\""
+        );
+        let archive = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let u16_at = |offset: usize| u16::from_le_bytes([archive[offset], archive[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(archive[offset..offset + 4].try_into().unwrap());
+
+        // The end of central directory record counts the entries
+        assert_eq!(u16_at(archive.len() - 12), LINES.len() as u16);
+
+        // The first entry is the A train's calendar, as served on its own
+        assert_eq!(&archive[..4], b"PK\x03\x04");
+        let name_len = u16_at(26) as usize;
+        let size = u32_at(18) as usize;
+        assert_eq!(&archive[30..30 + name_len], b"A.ics");
+        assert!(state.cache.contains_key("A"));
+        let calendar = get_response(&state, "/api/calendars/train/A.ics").await;
+        assert_eq!(
+            &archive[30 + name_len..30 + name_len + size],
+            body_string(calendar).await.as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_train_page() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/train/f").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(response.headers()["Cache-Control"], "public, max-age=30");
+        let html = body_string(response).await;
+        assert!(html.contains("<title>F Train Status</title>"));
+        assert!(html.contains("<p class=\"status\">Good Service</p>"));
+        // The page is cached for everyone, so without a public URL it links to the webcal page
+        assert!(html.contains("href=\"/webcal/F\""));
+        assert!(html.contains("<code>/api/calendars/train/F.ics</code>"));
+        assert!(html.contains("href=\"/subscribe/google/F\""));
+        assert!(state.cache.contains_key("page:F"));
+
+        let response = get_response(&state, "/train/ACE").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            body_string(response)
+                .await
+                .contains("<title>ACE Train Status</title>")
+        );
+
+        let response = get_response(&state, "/train/K").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn save_calendar(state: &AppState, config: serde_json::Value) -> Response {
+        let request = Request::post("/api/calendars")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(config.to_string()))
+            .unwrap();
+        send(state, request).await
+    }
+
+    #[tokio::test]
+    async fn test_saved_calendar() {
+        let state = test_state().await;
+        let config = serde_json::json!({
+            "name": "Work trip",
+            "lines": ["l", "F"],
+            "stations": ["A15"],
+            "filters": { "min_severity": "warning" },
+        });
+
+        let response = save_calendar(&state, config.clone()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()["Location"].to_str().unwrap().to_string();
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        let id = body["id"].as_str().unwrap();
+        assert_eq!(location, format!("/api/calendars/custom/{}.ics", id));
+        assert_eq!(body["url"], format!("http://localhost:3000{}", location));
+
+        // Saving the same calendar again, in any order, gives the same ID
+        let reordered = serde_json::json!({
+            "lines": ["F", "L"],
+            "stations": ["A15"],
+            "filters": { "min_severity": "warning" },
+            "name": "Work trip",
+        });
+        let response = save_calendar(&state, reordered).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Location"], location.as_str());
+
+        let response = get_response(&state, &location).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let calendar = body_string(response).await;
+        assert!(calendar.contains("X-WR-CALNAME:MTA Work trip Alerts"));
+        let filtered = body_string(
+            get_response(&state, "/api/calendars/trains/F,L.ics?min_severity=warning").await,
+        )
+        .await;
+        assert!(
+            calendar.matches("BEGIN:VEVENT").count() >= filtered.matches("BEGIN:VEVENT").count()
+        );
+        assert!(
+            state
+                .cache
+                .contains_key(&format!("custom:{}?min_severity=warning", id))
+        );
+
+        let response = get_response(&state, "/api/calendars/custom/0123456789.ics").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_saved_calendar_validation() {
+        let state = test_state().await;
+
+        for (config, message) in [
+            (
+                serde_json::json!({}),
+                "A saved calendar needs at least one line or station.",
+            ),
+            (
+                serde_json::json!({ "lines": ["F"], "filters": { "severity": "warning" } }),
+                "Unknown filter: severity. Expected one of: borough, days, hours, when, min_severity, direction, type, lang, placeholder, digest, include_express, exclude_overnight, all_day_days, limit, alarm, plain, compact, tz",
+            ),
+            (
+                serde_json::json!({ "lines": ["F"], "filters": { "days": "365" } }),
+                "Invalid days: 365. Expected a whole number of days from 1 to 90",
+            ),
+        ] {
+            let response = save_calendar(&state, config).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(body_string(response).await, message);
+        }
+
+        let response = save_calendar(&state, serde_json::json!({ "lines": ["K"] })).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown train line: K. Did you mean A, C or E?"
+        );
+
+        let response = save_calendar(&state, serde_json::json!({ "routes": ["F"] })).await;
+        assert!(response.status().is_client_error());
+        assert!(state.saved_calendars.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_bus_calendar_without_route_data() {
+        let state = AppState {
+            bus_routes: Arc::new(BusRoutes::default()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/api/calendars/bus/B44.ics").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_webcal_redirects_calendar_apps() {
+        let state = test_state().await;
+        let response = get_with_header(
+            &state,
+            "/webcal/a?days=7",
+            header::USER_AGENT,
+            "iOS/18.1 (22B83) dataaccessd/1.0",
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "http://localhost:3000/api/calendars/train/A.ics?days=7"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webcal_shows_browsers_both_links() {
+        let state = test_state().await;
+        let request = Request::get("/webcal/F")
+            .header(header::HOST, "trains.example.com")
+            .header(header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8")
+            .header(
+                header::USER_AGENT,
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 18_1 like Mac OS X)",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_string(response).await;
+        assert!(body.contains(r#"href="webcal://trains.example.com/api/calendars/train/F.ics""#));
+        assert!(body.contains("http://trains.example.com/api/calendars/train/F.ics"));
+
+        let response = get_response(&state, "/webcal/X").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_links_use_public_url() {
+        let state = AppState {
+            public_url: Some("https://trains.example.com".to_string()),
+            ..test_state().await
+        };
+
+        let response = get_response(&state, "/subscribe/google/a").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://calendar.google.com/calendar/r?cid=https%3A%2F%2Ftrains.example.com%2Fapi%2Fcalendars%2Ftrain%2FA.ics"
+        );
+
+        let response = get_response(&state, "/subscribe/outlook/L?borough=brooklyn").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://outlook.live.com/owa?path=/calendar/action/compose&rru=addsubscription&url=https%3A%2F%2Ftrains.example.com%2Fapi%2Fcalendars%2Ftrain%2FL.ics%3Fborough%3Dbrooklyn&name=MTA%20L%20Train%20Alerts"
+        );
+
+        for service in ["google", "outlook"] {
+            let response = get_response(&state, &format!("/subscribe/{}/X", service)).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let html = body_string(get_response(&state, "/train/F").await).await;
+        assert!(html.contains(r#"href="webcal://trains.example.com/api/calendars/train/F.ics""#));
+        assert!(html.contains("<code>https://trains.example.com/api/calendars/train/F.ics</code>"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_documents_ignore_host_header() {
+        let state = test_state().await;
+        let paths = [
+            "/api/calendars/train/A.ics",
+            "/api/calendars/station/A27.ics",
+            "/api/calendars/train/F.ics?limit=3",
+            "/api/feeds/train/A.rss",
+            "/api/feeds/train/A.atom",
+            "/train/A",
+        ];
+
+        // Whichever client fills the cache, its `Host` never reaches the others
+        for path in paths {
+            let mut bodies = Vec::new();
+            for host in ["evil.example", "trains.example.com"] {
+                let response = get_with_header(&state, path, header::HOST, host).await;
+                assert_eq!(response.status(), StatusCode::OK, "{}", path);
+                let body = body_string(response).await;
+                assert!(!body.contains("evil.example"), "{}", path);
+                assert!(!body.contains("trains.example.com"), "{}", path);
+                bodies.push(body);
+            }
+            assert_eq!(bodies[0], bodies[1], "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cors_on_json_api_routes() {
+        let state = test_state().await;
+
+        let response =
+            get_with_header(&state, "/api/trains", header::ORIGIN, "https://example.com").await;
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(
+            response.headers()[header::VARY]
+                .to_str()
+                .unwrap()
+                .contains("origin")
+        );
+
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/alerts/train/A.json")
+            .header(header::ORIGIN, "https://example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, preflight).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS]
+                .to_str()
+                .unwrap()
+                .contains("GET")
+        );
+
+        // Calendars are left alone
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/A.ics",
+            header::ORIGIN,
+            "https://example.com",
+        )
+        .await;
+        assert!(
+            !response
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_origins() {
+        let state = test_state().await;
+        let app = limited_routes(cors_layer(Some("https://a.example, https://b.example")))
+            .with_state(state);
+
+        for (origin, allowed) in [("https://b.example", true), ("https://evil.example", false)] {
+            let request = Request::get("/api/groups")
+                .header(header::ORIGIN, origin)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(
+                response
+                    .headers()
+                    .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                    .is_some(),
+                allowed,
+                "{}",
+                origin
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_version() {
+        let state = test_state().await;
+        let response = get_response(&state, "/version").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["x-app-version"],
+            env!("CARGO_PKG_VERSION")
+        );
+
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(
+            body["rustc_version"]
+                .as_str()
+                .unwrap()
+                .starts_with("rustc ")
+        );
+        assert!(body["build_timestamp"].is_string());
+
+        // Every route carries the version, including errors
+        let response = get_response(&state, "/api/calendars/train/X.ics").await;
+        assert_eq!(
+            response.headers()["x-app-version"],
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_robots_and_favicon() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/robots.txt").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_string(response).await,
+            "User-agent: *\nAllow: /\nDisallow: /api/\nSitemap: http://localhost:3000/sitemap.xml\n"
+        );
+
+        let response = get_response(&state, "/favicon.ico").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=604800"
+        );
+        assert!(body_string(response).await.starts_with("<svg"));
+    }
+
+    #[tokio::test]
+    async fn test_sitemap() {
+        let state = test_state().await;
+        get_response(&state, "/train/F").await;
+
+        let response = get_response(&state, "/sitemap.xml").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/xml; charset=utf-8"
+        );
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=86400"
+        );
+        let sitemap = body_string(response).await;
+        assert_eq!(
+            sitemap.matches("<url>").count(),
+            1 + LINES.len() + TRAIN_GROUPS.len()
+        );
+        assert!(sitemap.contains(
+            "<loc>http://localhost:3000/</loc>\n<lastmod>2025-12-15T11:00:00Z</lastmod>"
+        ));
+        assert!(sitemap.contains(
+            "<loc>http://localhost:3000/train/A</loc>\n<lastmod>2025-12-15T11:00:00Z</lastmod>"
+        ));
+        assert!(sitemap.contains("<loc>http://localhost:3000/train/ACE</loc>"));
+        // Generating the F page records when its content last changed
+        assert!(!sitemap.contains(
+            "<loc>http://localhost:3000/train/F</loc>\n<lastmod>2025-12-15T11:00:00Z</lastmod>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec() {
+        let state = test_state().await;
+        let response = get_response(&state, "/api/openapi.json").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], JSON_CONTENT_TYPE);
+        let spec: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert!(spec["paths"]["/api/calendars/train/{train_name}"].is_object());
+
+        let response = get_response(&state, "/api/docs").await;
+        assert!(body_string(response).await.contains("/api/openapi.json"));
+    }
+
+    #[tokio::test]
+    async fn test_graphql() {
+        let mut state = test_state().await;
+        let request = Request::post("/api/graphql")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({
+                    "query": "query ($id: String!) { line(id: $id) { name } alerts(line: $id, severity: SEVERE) { severity } }",
+                    "variables": { "id": "a" },
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+
+        let feed = state.subway_feed().await.unwrap();
+        let expected = feed
+            .alerts
+            .iter()
+            .filter(|alert| alert.severity == Severity::Severe)
+            .filter(|alert| changes::alert_affects_any(alert, &["A"]))
+            .count();
+        assert_eq!(body.get("errors"), None);
+        assert_eq!(body["data"]["line"]["name"], "8 Avenue Express");
+        let alerts = body["data"]["alerts"].as_array().unwrap();
+        assert_eq!(alerts.len(), expected);
+        assert!(alerts.iter().all(|alert| alert["severity"] == "SEVERE"));
+
+        // Aliases multiplying the same fields are cut off rather than run to completion
+        let aliases: Vec<String> = (0..200)
+            .map(|i| format!("a{}: alerts {{ id header description }}", i))
+            .collect();
+        let request = Request::post("/api/graphql")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({ "query": format!("{{ {} }}", aliases.join(" ")) }).to_string(),
+            ))
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["data"], serde_json::Value::Null);
+        assert!(
+            body["errors"][0]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Query is too expensive")
+        );
+
+        // The playground is off unless configured
+        let response = get_response(&state, "/api/graphql").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        state.graphql_playground = true;
+        let response = get_response(&state, "/api/graphql").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(body_string(response).await.contains("graphiql"));
+    }
+
+    async fn next_event(body: &mut axum::body::BodyDataStream) -> String {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("no event within 5 seconds")
+            .unwrap()
+            .unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_alert_stream() {
+        let state = test_state().await;
+        let response = get_response(&state, "/api/alerts/train/A/stream").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        let mut body = response.into_body().into_data_stream();
+
+        let snapshot = next_event(&mut body).await;
+        assert!(snapshot.starts_with("event: snapshot\n"));
+        assert!(snapshot.contains("\"route_id\":\"A\""));
+
+        // Alerts for other lines are filtered out of the stream
+        let feed = state.subway_feed().await.unwrap();
+        let a_alert = feed
+            .alerts
+            .iter()
+            .find(|alert| changes::alert_affects_any(alert, &["A"]))
+            .unwrap();
+        let l_only_alert = feed
+            .alerts
+            .iter()
+            .find(|alert| {
+                changes::alert_affects_any(alert, &["L"])
+                    && !changes::alert_affects_any(alert, &["A"])
+            })
+            .unwrap();
+        let previous = Feed {
+            alerts: vec![l_only_alert.clone(), a_alert.clone()],
+            ..Feed::default()
+        };
+        publish_alert_changes(&state, &previous, &Feed::default());
+
+        let removed = next_event(&mut body).await;
+        assert!(removed.starts_with("event: alert_removed\n"));
+        assert!(removed.contains(&serde_json::to_string(&a_alert.id).unwrap()));
+        assert!(!removed.contains(&serde_json::to_string(&l_only_alert.id).unwrap()));
+
+        // A client that falls too far behind gets a fresh snapshot in place of the changes it
+        // missed
+        publish_alert_changes(&state, &Feed::default(), &feed);
+        assert!(next_event(&mut body).await.starts_with("event: snapshot\n"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifications() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let state = test_state().await;
+        let (posts, mut posted) = mpsc::channel::<serde_json::Value>(10);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let webhook_requests = requests.clone();
+        // Rate limits the first request, as Discord does when a channel gets busy
+        let webhook = Router::new().route(
+            "/webhook",
+            post(move |Json(payload): Json<serde_json::Value>| async move {
+                if webhook_requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(header::RETRY_AFTER, "0.1")],
+                    )
+                        .into_response();
+                }
+                posts.send(payload).await.unwrap();
+                StatusCode::NO_CONTENT.into_response()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, webhook).await.unwrap() });
+
+        let config: notify::WebhookConfig = serde_json::from_value(serde_json::json!({
+            "type": "discord",
+            "url": format!("http://{}/webhook", address),
+            "lines": ["ACE"],
+            "min_severity": "info",
+        }))
+        .unwrap();
+        tokio::spawn(post_alerts(
+            state.alert_changes.subscribe(),
+            state.clock,
+            reqwest::Client::new(),
+            Webhook::from_config(config, None).unwrap(),
+        ));
+
+        let feed = state.subway_feed().await.unwrap();
+        let mut ace_alerts = feed
+            .alerts
+            .iter()
+            .filter(|alert| changes::alert_affects_any(alert, &["A", "C", "E"]));
+        let first = Feed {
+            alerts: vec![ace_alerts.next().unwrap().clone()],
+            ..Feed::default()
+        };
+        let second = Feed {
+            alerts: vec![ace_alerts.next().unwrap().clone()],
+            ..Feed::default()
+        };
+        let l_only = Feed {
+            alerts: vec![
+                feed.alerts
+                    .iter()
+                    .find(|alert| {
+                        changes::alert_affects_any(alert, &["L"])
+                            && !changes::alert_affects_any(alert, &["A", "C", "E"])
+                    })
+                    .unwrap()
+                    .clone(),
+            ],
+            ..Feed::default()
+        };
+
+        // The first alert is removed and comes back, but is only posted once, and the alert
+        // for another line isn't posted at all
+        publish_alert_changes(&state, &Feed::default(), &first);
+        publish_alert_changes(&state, &first, &Feed::default());
+        publish_alert_changes(&state, &Feed::default(), &first);
+        publish_alert_changes(&state, &Feed::default(), &l_only);
+        publish_alert_changes(&state, &Feed::default(), &second);
+
+        for expected in [&first, &second] {
+            let message = tokio::time::timeout(Duration::from_secs(5), posted.recv())
+                .await
+                .expect("no post within 5 seconds")
+                .unwrap();
+            assert_eq!(
+                message["embeds"][0]["title"].as_str().unwrap(),
+                expected.alerts[0].header
+            );
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), posted.recv())
+                .await
+                .is_err()
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_parse_ws_subscription() {
+        assert_eq!(
+            parse_ws_subscription(r#"{"subscribe": ["a", "ACE", "L"]}"#),
+            Ok(vec!["A", "C", "E", "L"])
+        );
+        assert_eq!(
+            parse_ws_subscription(r#"{"subscribe": ["X"]}"#),
+            Err("Unknown train line: X.".to_string())
+        );
+        assert_eq!(
+            parse_ws_subscription(r#"{"subscribe": ["ACE", "BDFM", "NQRW"]}"#),
+            Err("Too many lines: 11. A connection can subscribe to at most 10.".to_string())
+        );
+        assert!(parse_ws_subscription("A").is_err());
+    }
+
+    async fn next_ws_message<S>(socket: &mut S) -> serde_json::Value
+    where
+        S: tokio_stream::Stream<
+                Item = Result<
+                    tokio_tungstenite::tungstenite::Message,
+                    tokio_tungstenite::tungstenite::Error,
+                >,
+            > + Unpin,
+    {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("no message within 5 seconds")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_alerts_websocket() {
+        use futures_util::SinkExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let state = test_state().await;
+        let app = streaming_routes(cors_layer(None)).with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}/ws/alerts", address))
+                .await
+                .unwrap();
+
+        socket
+            .send(WsMessage::text(r#"{"subscribe": ["X"]}"#))
+            .await
+            .unwrap();
+        let error = next_ws_message(&mut socket).await;
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["message"], "Unknown train line: X.");
+
+        socket
+            .send(WsMessage::text(r#"{"subscribe": ["a"]}"#))
+            .await
+            .unwrap();
+        let snapshot = next_ws_message(&mut socket).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["lines"], serde_json::json!(["A"]));
+        let alerts = snapshot["alerts"].as_array().unwrap();
+        assert!(!alerts.is_empty());
+
+        let feed = state.subway_feed().await.unwrap();
+        let a_alert = feed
+            .alerts
+            .iter()
+            .find(|alert| changes::alert_affects_any(alert, &["A"]))
+            .unwrap();
+        let current = Feed {
+            alerts: vec![a_alert.clone()],
+            ..Feed::default()
+        };
+        publish_alert_changes(&state, &Feed::default(), &current);
+        let added = next_ws_message(&mut socket).await;
+        assert_eq!(added["type"], "alert_added");
+        assert!(alerts.contains(&added["alert"]));
+
+        // Every alert in the feed at once is more changes than a client can fall behind by, so
+        // it gets a fresh snapshot in place of the ones it missed
+        publish_alert_changes(&state, &Feed::default(), &feed);
+        let snapshot = next_ws_message(&mut socket).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["alerts"].as_array().unwrap(), alerts);
+    }
+
+    #[test]
+    fn test_cache_key_name() {
+        assert_eq!(cache_key_name("A"), "A");
+        assert_eq!(cache_key_name("json:A?days=7"), "A");
+        assert_eq!(cache_key_name("rss:ACE"), "ACE");
+        assert_eq!(cache_key_name("badge:F"), "F");
+        assert_eq!(cache_key_name("station:A27?lang=es"), "station:A27");
+    }
+
+    async fn purge(state: &AppState, uri: &str, token: Option<&str>) -> Response {
+        let mut request = Request::builder().method(Method::POST).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        send(state, request.body(Body::empty()).unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn test_refresh_parameter() {
+        let state = AppState {
+            admin_token: Some("secret".to_string()),
+            ..test_state().await
+        };
+        let stale = || Arc::new(CachedDocument::new("BEGIN:VCALENDAR\r\n".to_string()));
+        state.cache.insert("L".to_string(), stale()).await;
+
+        // Ignored without the admin token
+        let response = get_response(&state, "/api/calendars/train/L.ics?refresh=1").await;
+        assert_eq!(response.headers()["X-Cache"], "HIT");
+        assert_eq!(body_string(response).await, "BEGIN:VCALENDAR\r\n");
+
+        let request = Request::get("/api/calendars/train/L.ics?refresh=1")
+            .header(header::AUTHORIZATION, "Bearer wrong")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.headers()["X-Cache"], "HIT");
+
+        let request = Request::get("/api/calendars/train/L.ics?refresh=1")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-Cache"], "MISS");
+        let refreshed = body_string(response).await;
+        assert!(refreshed.contains("X-WR-CALNAME:MTA L Train Alerts"));
+
+        // Everyone else now gets the regenerated calendar
+        let response = get_response(&state, "/api/calendars/train/L.ics").await;
+        assert_eq!(response.headers()["X-Cache"], "HIT");
+        assert_eq!(body_string(response).await, refreshed);
+    }
+
+    #[tokio::test]
+    async fn test_cache_purge_requires_token() {
+        let state = test_state().await;
+        let response = purge(&state, "/admin/cache/purge", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let state = AppState {
+            admin_token: Some("secret".to_string()),
+            ..state
+        };
+        let response = purge(&state, "/admin/cache/purge", None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        let response = purge(&state, "/admin/cache/purge", Some("wrong")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_cache_purge() {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            admin_token: Some("secret".to_string()),
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+        for uri in [
+            "/api/calendars/train/A.ics",
+            "/api/calendars/train/A.ics?days=7",
+            "/api/calendars/train/A.json",
+            "/api/calendars/train/ACE.ics",
+            "/api/calendars/trains/C,A.ics",
+            "/api/calendars/all.ics",
+            "/api/calendars/station/A15.ics",
+            "/api/feeds/train/A.rss",
+            "/train/ACE",
+            "/api/calendars/train/L.ics",
+            "/api/calendars/station/L08.ics",
+        ] {
+            assert_eq!(
+                get_response(&state, uri).await.status(),
+                StatusCode::OK,
+                "{}",
+                uri
+            );
+        }
+        // Keep the feed so the calendars can be regenerated without the MTA
+        let feed = state.subway_feed().await.unwrap();
+
+        // Everything that can include the A train's alerts goes, not just its own calendars
+        let response = purge(&state, "/admin/cache/purge?train=a", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        assert_eq!(body_string(response).await, r#"{"purged":9}"#);
+        for key in [
+            "A",
+            "ACE",
+            "A, C",
+            "all",
+            "station:A15",
+            "rss:A",
+            "page:ACE",
+        ] {
+            assert!(!state.cache.contains_key(key), "{}", key);
+        }
+        assert!(state.cache.contains_key("L"));
+        assert!(state.cache.contains_key("station:L08"));
+
+        let response = purge(&state, "/admin/cache/purge?train=X", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        state
+            .feed_cache
+            .insert(nyc_train_time::SUBWAY_ALERTS_URL, feed)
+            .await;
+        let response = purge(&state, "/admin/cache/purge", Some("secret")).await;
+        assert_eq!(body_string(response).await, r#"{"purged":2}"#);
+        assert!(!state.cache.contains_key("L"));
+    }
+
+    #[tokio::test]
+    async fn test_placeholder_parameter() {
+        let state = test_state().await;
+
+        // The golden feed has no alerts for the Franklin Av Shuttle
+        let empty = body_string(get_response(&state, "/api/calendars/train/FS.ics").await).await;
+        assert!(!empty.contains("BEGIN:VEVENT"));
+
+        let response = get_response(&state, "/api/calendars/train/FS.ics?placeholder=1").await;
+        let placeholder = body_string(response).await;
+        assert_eq!(placeholder.matches("BEGIN:VEVENT").count(), 1);
+        assert!(placeholder.contains("SUMMARY:No service alerts for the Franklin Av Shuttle\r\n"));
+        assert!(placeholder.contains("DTSTART;VALUE=DATE:20251215\r\n"));
+        assert!(placeholder.contains("DTEND;VALUE=DATE:20251216\r\n"));
+        assert!(placeholder.contains("TRANSP:TRANSPARENT\r\n"));
+        assert!(state.cache.contains_key("FS?placeholder=1"));
+
+        // Calendars with alerts are unchanged
+        let with_alerts =
+            body_string(get_response(&state, "/api/calendars/train/A.ics?placeholder=1").await)
+                .await;
+        assert!(!with_alerts.contains("No service alerts"));
+
+        let response = get_response(&state, "/api/calendars/train/FS.ics?placeholder=yes").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_all_day_days_parameter() {
+        let state = test_state().await;
+        let all_day = |calendar: &str| calendar.matches("DTSTART;VALUE=DATE:").count();
+        let events = |calendar: &str| calendar.matches("BEGIN:VEVENT").count();
+
+        let default = body_string(get_response(&state, "/api/calendars/all.ics").await).await;
+        assert!(all_day(&default) > 0);
+        assert!(default.contains("TRANSP:TRANSPARENT"));
+        assert_eq!(
+            default.matches("X-MICROSOFT-CDO-BUSYSTATUS:FREE").count(),
+            all_day(&default)
+        );
+
+        let response = get_response(&state, "/api/calendars/all.ics?all_day_days=0").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let timed = body_string(response).await;
+        assert_eq!(all_day(&timed), 0);
+        assert_eq!(events(&timed), events(&default));
+        assert!(state.cache.contains_key("all?all_day_days=0"));
+
+        let response = get_response(&state, "/api/calendars/all.ics?all_day_days=1").await;
+        assert!(all_day(&body_string(response).await) > all_day(&default));
+
+        // Asking for the server's threshold shares its cache entry
+        let response = get_response(&state, "/api/calendars/all.ics?all_day_days=3").await;
+        assert_eq!(body_string(response).await, default);
+
+        // A server that never uses all-day events still lets clients ask for them
+        let state = AppState {
+            all_day_days: 0,
+            ..test_state().await
+        };
+        let response = get_response(&state, "/api/calendars/all.ics").await;
+        assert_eq!(all_day(&body_string(response).await), 0);
+        let response = get_response(&state, "/api/calendars/all.ics?all_day_days=3").await;
+        assert_eq!(body_string(response).await, default);
+
+        let response = get_response(&state, "/api/calendars/all.ics?all_day_days=week").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_alarm_parameter() {
+        let state = test_state().await;
+
+        let default = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert!(!default.contains("BEGIN:VALARM"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?alarm=PT2H").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let calendar = body_string(response).await;
+        assert_eq!(
+            calendar.matches("TRIGGER:-PT2H\r\n").count(),
+            calendar.matches("BEGIN:VEVENT").count()
+        );
+        assert!(calendar.contains("\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\n"));
+        assert!(state.cache.contains_key("A?alarm=PT2H"));
+
+        // The same duration written differently shares the cache entry
+        let response = get_response(&state, "/api/calendars/train/A.ics?alarm=PT120M").await;
+        assert_eq!(body_string(response).await, calendar);
+
+        for alarm in ["2h", "PT1M", "P3D"] {
+            let response = get_response(
+                &state,
+                &format!("/api/calendars/train/A.ics?alarm={}", alarm),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", alarm);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tz_parameter() {
+        let state = test_state().await;
+
+        let default = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert!(default.contains("X-WR-TIMEZONE:America/New_York\r\n"));
+        assert!(default.contains("DTSTART;TZID=America/New_York:"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?tz=UTC").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let utc = body_string(response).await;
+        assert!(state.cache.contains_key("A?tz=UTC"));
+        assert!(utc.contains("X-WR-TIMEZONE:UTC\r\n"));
+        assert!(!utc.contains("VTIMEZONE"));
+        assert!(!utc.contains("TZID"));
+        assert_eq!(
+            utc.matches("\r\nDTSTART:").count(),
+            default
+                .matches("\r\nDTSTART;TZID=America/New_York:")
+                .count()
+        );
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?tz=Europe/London").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let london = body_string(response).await;
+        assert!(state.cache.contains_key("A?tz=Europe/London"));
+        assert!(london.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/London\r\n"));
+        assert!(london.contains("DTSTART;TZID=Europe/London:"));
+        assert!(!london.contains("America/New_York"));
+
+        // New York is the default, so it's the same calendar
+        let response = get_response(&state, "/api/calendars/train/A.ics?tz=America/New_York").await;
+        assert_eq!(body_string(response).await, default);
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?tz=Mars/Base").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_string(response).await;
+        assert!(
+            body.starts_with("Unknown time zone: Mars/Base."),
+            "{}",
+            body
+        );
+        assert!(body.contains(filter::TIME_ZONE_LIST_URL), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_compact_parameter() {
+        let state = test_state().await;
+        let lines = |calendar: &str, prefix: &str| -> Vec<String> {
+            calendar
+                .replace("\r\n ", "")
+                .lines()
+                .filter_map(|line| line.strip_prefix(prefix))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let default = body_string(get_response(&state, "/api/calendars/train/F.ics").await).await;
+        assert!(default.contains("SUMMARY:🔧 F: Planned - Stops Skipped\r\n"));
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?compact=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let compact = body_string(response).await;
+        assert!(state.cache.contains_key("F?compact=1"));
+        assert!(compact.contains("SUMMARY:🔧 F: Stops skipped\r\n"));
+        assert!(compact.contains("SUMMARY:🔧 F: Reroute\r\n"));
+        for summary in lines(&compact, "SUMMARY:") {
+            assert!(!summary.contains("Planned - "), "{}", summary);
+        }
+        // The same events, with the same descriptions
+        assert_eq!(
+            lines(&compact, "DESCRIPTION:"),
+            lines(&default, "DESCRIPTION:")
+        );
+
+        // Compact titles follow the calendar's language
+        let response = get_response(&state, "/api/calendars/train/F.ics?compact=1&lang=es").await;
+        let spanish = body_string(response).await;
+        assert!(spanish.contains("SUMMARY:🔧 F: Paradas omitidas\r\n"));
+        assert!(spanish.contains("SUMMARY:🔧 F: Cambio de ruta\r\n"));
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?compact=yes").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_plain_parameter() {
+        let state = test_state().await;
+        let summaries = |calendar: &str| -> Vec<String> {
+            calendar
+                .lines()
+                .filter_map(|line| line.strip_prefix("SUMMARY:"))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let default = body_string(get_response(&state, "/api/calendars/all.ics").await).await;
+        assert!(summaries(&default).iter().any(|s| s.starts_with("🔧 ")));
+
+        let response = get_response(&state, "/api/calendars/all.ics?plain=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let plain = body_string(response).await;
+        assert!(state.cache.contains_key("all?plain=1"));
+        let plain_summaries = summaries(&plain);
+        assert_eq!(plain_summaries.len(), summaries(&default).len());
+        for (plain, default) in plain_summaries.iter().zip(summaries(&default)) {
+            assert!(default.ends_with(plain.as_str()), "{} {}", default, plain);
+            assert!(plain.starts_with(|c: char| c.is_ascii()), "{}", plain);
+        }
+
+        // JSON has the indicator as a field of its own
+        let response = get_response(&state, "/api/calendars/train/A.json").await;
+        let events: Vec<serde_json::Value> =
+            serde_json::from_str(&body_string(response).await).unwrap();
+        let planned: Vec<_> = events
+            .iter()
+            .filter(|event| event["category"] == "Planned Work")
+            .collect();
+        assert!(!planned.is_empty());
+        for event in planned {
+            assert_eq!(event["indicator"], "🔧");
+            assert!(event["summary"].as_str().unwrap().starts_with("A"));
+        }
+
+        let response = get_response(&state, "/api/calendars/all.ics?plain=yes").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_exclude_overnight_parameter() {
+        let state = test_state().await;
+        let count = |calendar: &str| calendar.matches("BEGIN:VEVENT").count();
+
+        let all = body_string(get_response(&state, "/api/calendars/all.ics").await).await;
+        let response = get_response(&state, "/api/calendars/all.ics?exclude_overnight=1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let daytime = body_string(response).await;
+        assert!(count(&daytime) < count(&all));
+        assert!(state.cache.contains_key("all?exclude_overnight=1"));
+
+        let response = get_response(&state, "/api/calendars/all.ics?exclude_overnight=0").await;
+        assert_eq!(count(&body_string(response).await), count(&all));
+
+        // A longer night configured on the server drops more
+        let state = AppState {
+            overnight: HourRange::parse("20:00-08:00").unwrap(),
+            ..test_state().await
+        };
+        let response = get_response(&state, "/api/calendars/all.ics?exclude_overnight=1").await;
+        assert!(count(&body_string(response).await) < count(&daytime));
+
+        let response = get_response(&state, "/api/calendars/all.ics?exclude_overnight=yes").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_open_ended_alerts() {
+        let state = test_state().await;
+        let text = std::fs::read_to_string("tests/fixtures/open_ended_alerts.textproto").unwrap();
+        let mut message: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        // As the server does when it fetches the feed
+        let load = async |message: &FeedMessage| {
+            let mut feed = Feed::parse(message);
+            let url = nyc_train_time::SUBWAY_ALERTS_URL;
+            state
+                .revisions
+                .apply(url, &mut feed.events, (state.clock)());
+            state.feed_cache.insert(url, Arc::new(feed)).await;
+        };
+        load(&message).await;
+
+        // The delays started at 06:00 with no end, so they last until the service day ends
+        let body = body_string(get_response(&state, "/api/calendars/train/G.ics").await).await;
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 2);
+        assert_eq!(body.matches(" (until further notice)").count(), 1);
+        assert!(body.contains(
+            "DTSTART;TZID=America/New_York:20251215T060000\r\nDTEND;TZID=America/New_York:20251216T040000\r\n"
+        ));
+        assert!(body.contains(
+            "DTSTART;TZID=America/New_York:20251215T200000\r\nDTEND;TZID=America/New_York:20251216T020000\r\n"
+        ));
+        assert!(!body.contains("SEQUENCE:1"));
+
+        let response = get_response(&state, "/api/calendars/train/G.ics?lang=es&days=7").await;
+        assert!(body_string(response).await.contains(" (hasta nuevo aviso)"));
+
+        // A server that ends the service day at 02:00 ends them sooner
+        let early = AppState {
+            open_ended_until: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            ..state.clone()
+        };
+        early.cache.invalidate_all();
+        let body = body_string(get_response(&early, "/api/calendars/train/G.ics").await).await;
+        assert!(body.contains(
+            "DTSTART;TZID=America/New_York:20251215T060000\r\nDTEND;TZID=America/New_York:20251216T020000\r\n"
+        ));
+
+        // Once the MTA says when they end, the event moves there and replaces the old copy
+        message.entity[0]
+            .alert
+            .mut_or_insert_default()
+            .active_period[0]
+            .set_end(1765818000);
+        load(&message).await;
+        state.cache.invalidate_all();
+        let body = body_string(get_response(&state, "/api/calendars/train/G.ics").await).await;
+        assert!(!body.contains("until further notice"));
+        assert!(body.contains(
+            "DTSTART;TZID=America/New_York:20251215T060000\r\nDTEND;TZID=America/New_York:20251215T120000\r\n"
+        ));
+        assert_eq!(body.matches("SEQUENCE:1").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_long_descriptions_are_truncated() {
+        let descriptions = |calendar: &str| -> Vec<String> {
+            calendar
+                .replace("\r\n ", "")
+                .lines()
+                .filter_map(|line| line.strip_prefix("DESCRIPTION:"))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let state = test_state().await;
+        let full = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert!(!full.contains("(truncated"));
+
+        let short = AppState {
+            max_description_length: 300,
+            public_url: Some("https://trains.example.com".to_string()),
+            ..test_state().await
+        };
+        let body = body_string(get_response(&short, "/api/calendars/train/A.ics").await).await;
+        let truncated: Vec<String> = descriptions(&body)
+            .into_iter()
+            .filter(|description| description.contains("… (truncated — full details: "))
+            .collect();
+        assert!(!truncated.is_empty());
+        for description in &truncated {
+            assert!(description.ends_with(")"), "{}", description);
+            assert!(
+                description.contains("https://trains.example.com/api/alerts/"),
+                "{}",
+                description
+            );
+        }
+        // Truncated events lose their HTML description
+        assert_eq!(
+            body.matches("X-ALT-DESC").count() + truncated.len(),
+            full.matches("X-ALT-DESC").count()
+        );
+
+        // 0 turns the limit off
+        let unlimited = AppState {
+            max_description_length: 0,
+            ..test_state().await
+        };
+        let body = body_string(get_response(&unlimited, "/api/calendars/train/A.ics").await).await;
+        assert_eq!(body, full);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_interval() {
+        let state = test_state().await;
+        let body = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        assert_eq!(
+            body.matches("\r\nREFRESH-INTERVAL;VALUE=DURATION:PT30M\r\n")
+                .count(),
+            1
+        );
+        assert_eq!(body.matches("\r\nX-PUBLISHED-TTL:PT30M\r\n").count(), 1);
+
+        let hourly = AppState {
+            refresh_interval: chrono::Duration::hours(1),
+            ..test_state().await
+        };
+        for path in [
+            "/api/calendars/train/A.ics",
+            "/api/calendars/trains/A,C,E.ics",
+            "/api/calendars/all.ics",
+        ] {
+            let body = body_string(get_response(&hourly, path).await).await;
+            assert_eq!(
+                body.matches("\r\nREFRESH-INTERVAL;VALUE=DURATION:PT1H\r\n")
+                    .count(),
+                1,
+                "{}",
+                path
+            );
+            assert_eq!(
+                body.matches("\r\nX-PUBLISHED-TTL:PT1H\r\n").count(),
+                1,
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limit_parameter() {
+        let state = AppState {
+            public_url: Some("https://trains.example.com".to_string()),
+            ..test_state().await
+        };
+
+        let all = body_string(get_response(&state, "/api/calendars/train/F.ics").await).await;
+        // The limit counts alert periods, some of which calendars write as one repeating event
+        let periods = body_string(get_response(&state, "/api/calendars/train/F.json").await).await;
+        let total = serde_json::from_str::<Vec<serde_json::Value>>(&periods)
+            .unwrap()
+            .len();
+        assert!(total > 3);
+
+        let response = get_with_header(
+            &state,
+            "/api/calendars/train/f?limit=3&utm_source=x&min_severity=info",
+            header::HOST,
+            "evil.example",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let limited = body_string(response).await;
+        assert!(state.cache.contains_key("F?min_severity=info&limit=3"));
+
+        // The three soonest periods and a note about the rest, linking to the canonical URL
+        // of the calendar rather than the one this request happened to use
+        assert!(limited.matches("BEGIN:VEVENT").count() <= 4);
+        assert!(limited.contains(&format!("SUMMARY:{} more alerts not shown\r\n", total - 3)));
+        assert!(limited.contains(
+            "URL:https://trains.example.com/api/calendars/train/F.ics?min_severity=info\r\n"
+        ));
+
+        // Without a public URL there's nothing trustworthy to link to
+        let response =
+            get_response(&test_state().await, "/api/calendars/train/F.ics?limit=3").await;
+        let limited = body_string(response).await;
+        assert!(limited.contains(&format!("SUMMARY:{} more alerts not shown\r\n", total - 3)));
+        assert!(!limited.contains("/api/calendars/train/F.ics"));
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?limit=500").await;
+        assert_eq!(body_string(response).await, all);
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?limit=0").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_stations_parameter() {
+        let response = get_response(
+            &test_state().await,
+            "/api/calendars/train/F.ics?stations=D21",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        let state = AppState {
+            stations: Arc::new(Stations::from_csv(csv.as_slice()).unwrap()),
+            ..test_state().await
+        };
+        let count = |calendar: &str| calendar.matches("BEGIN:VEVENT").count();
+
+        let all = body_string(get_response(&state, "/api/calendars/train/F.ics").await).await;
+        let response =
+            get_response(&state, "/api/calendars/train/F.ics?stations=f20,+D21,F20").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let filtered = body_string(response).await;
+        assert!(count(&filtered) <= count(&all));
+        assert!(state.cache.contains_key("F?stations=D21,F20"));
+
+        let response =
+            get_response(&state, "/api/calendars/train/F.ics?stations=D21,L08,X99").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown stations for the F line: L08, X99."
+        );
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?stations=,").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_digest_parameter() {
+        let state = test_state().await;
+
+        let all = body_string(get_response(&state, "/api/calendars/train/F.ics").await).await;
+        let response = get_response(&state, "/api/calendars/train/F.ics?digest=weekly").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let digest = body_string(response).await;
+        assert!(state.cache.contains_key("F?digest=weekly"));
+
+        assert!(digest.matches("BEGIN:VEVENT").count() < all.matches("BEGIN:VEVENT").count());
+        assert!(digest.contains("UID:digest-subway-f-2025W51@"));
+        assert!(digest.contains("DTSTART;VALUE=DATE:20251215\r\n"));
+        assert!(digest.contains("DTEND;VALUE=DATE:20251222\r\n"));
+        assert!(digest.contains("SUMMARY:🔧 E\\, F\\, G: 11 Planned Service Changes\r\n"));
+        // Only the digest's events are planned work
+        assert_eq!(
+            digest.matches("CATEGORIES:Planned Work,").count(),
+            digest.matches("UID:digest-").count()
+        );
+
+        let response = get_response(&state, "/api/calendars/train/F.ics?digest=daily").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_hours_parameter() {
+        let state = test_state().await;
+
+        let all = body_string(get_response(&state, "/api/calendars/train/A.ics").await).await;
+        let response = get_response(
+            &state,
+            "/api/calendars/train/A.ics?hours=17:00-19:30,07:00-10:00",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let commute = body_string(response).await;
+        assert!(commute.matches("BEGIN:VEVENT").count() < all.matches("BEGIN:VEVENT").count());
+        assert!(state.cache.contains_key("A?hours=07:00-10:00,17:00-19:30"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?hours=7-10").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Invalid hours: 7-10. Expected comma-separated 24-hour ranges such as 07:00-10:00,17:00-19:30"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_when_parameter() {
+        let state = test_state().await;
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?when=weekends").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.cache.contains_key("A?when=weekends"));
+
+        let response = get_response(&state, "/api/calendars/train/A.ics?when=sundays").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_string(response).await,
+            "Unknown day type: sundays. Expected one of: weekdays, weekends"
+        );
+    }
+}
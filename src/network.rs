@@ -0,0 +1,478 @@
+use crate::calendar::Language;
+use crate::ics::CalendarMetadata;
+use crate::lines;
+
+/// A transit service whose alerts are published in their own GTFS-realtime feed.
+///
+/// The MTA's feeds use the same Mercury alert format, and PATH's is plain GTFS-realtime that
+/// parses the same way without it, so events are parsed, filtered and rendered the same way;
+/// only the feed URL and calendar names differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Subway,
+    /// The Long Island Rail Road
+    Lirr,
+    /// Metro-North Railroad
+    Mnr,
+    /// MTA New York City Transit and MTA Bus routes
+    Bus,
+    /// The Port Authority Trans-Hudson
+    Path,
+}
+
+impl Network {
+    pub fn alerts_url(&self) -> &'static str {
+        match self {
+            Network::Subway => crate::SUBWAY_ALERTS_URL,
+            Network::Lirr => crate::LIRR_ALERTS_URL,
+            Network::Mnr => crate::MNR_ALERTS_URL,
+            Network::Bus => crate::BUS_ALERTS_URL,
+            Network::Path => crate::PATH_ALERTS_URL,
+        }
+    }
+
+    /// The name and description of a calendar of alerts for `route`, a subway line, railroad
+    /// line or bus route name, or for the whole network when it's `None`.
+    pub fn calendar_metadata(&self, route: Option<&str>, language: Language) -> CalendarMetadata {
+        let (name, summary) = match self {
+            Network::Subway => subway_metadata(route, language),
+            Network::Lirr => lirr_metadata(route, language),
+            Network::Mnr => mnr_metadata(route, language),
+            Network::Bus => bus_metadata(route, language),
+            Network::Path => path_metadata(route, language),
+        };
+        let placeholder = self.placeholder_summary(route, language);
+        CalendarMetadata::new(*self, name, &summary, placeholder, language)
+    }
+
+    /// Credits whoever publishes the network's alerts, for calendar descriptions.
+    pub fn attribution(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (Network::Path, Language::English) => {
+                "Data from the Port Authority of New York and New Jersey (panynj.gov)."
+            }
+            (Network::Path, Language::Spanish) => {
+                "Datos de la Autoridad Portuaria de Nueva York y Nueva Jersey (panynj.gov)."
+            }
+            (_, Language::English) => "Data from the MTA (mta.info).",
+            (_, Language::Spanish) => "Datos de la MTA (mta.info).",
+        }
+    }
+
+    /// The title of the placeholder event shown when a calendar for `route` has no alerts.
+    pub fn placeholder_summary(&self, route: Option<&str>, language: Language) -> String {
+        let subject = match (self, route, language) {
+            (Network::Subway, Some(train), language) => {
+                let train = train.to_uppercase();
+                match (lines::find_shuttle(&train), language) {
+                    (Some(shuttle), Language::English) => format!("the {}", shuttle.name),
+                    (Some(shuttle), Language::Spanish) => format!("el {}", shuttle.name),
+                    (None, Language::English) => format!("the {} train", train),
+                    (None, Language::Spanish) => format!("el tren {}", train),
+                }
+            }
+            (Network::Subway, None, Language::English) => "the subway".to_string(),
+            (Network::Subway, None, Language::Spanish) => "el Metro".to_string(),
+            (Network::Lirr, Some(branch), Language::English) => format!("the LIRR {}", branch),
+            (Network::Lirr, Some(branch), Language::Spanish) => format!("{} del LIRR", branch),
+            (Network::Lirr, None, Language::English) => "the LIRR".to_string(),
+            (Network::Lirr, None, Language::Spanish) => "el LIRR".to_string(),
+            (Network::Mnr, Some(line), Language::English) => format!("the Metro-North {}", line),
+            (Network::Mnr, Some(line), Language::Spanish) => format!("{} de Metro-North", line),
+            (Network::Mnr, None, _) => "Metro-North".to_string(),
+            (Network::Bus, Some(route), Language::English) => format!("the {} bus", route),
+            (Network::Bus, Some(route), Language::Spanish) => format!("el autobús {}", route),
+            (Network::Bus, None, Language::English) => "MTA buses".to_string(),
+            (Network::Bus, None, Language::Spanish) => "los autobuses de la MTA".to_string(),
+            (Network::Path, Some(route), Language::English) => format!("PATH {}", route),
+            (Network::Path, Some(route), Language::Spanish) => format!("{} de PATH", route),
+            (Network::Path, None, _) => "PATH".to_string(),
+        };
+
+        match language {
+            Language::English => format!("No service alerts for {}", subject),
+            Language::Spanish => format!("No hay alertas de servicio para {}", subject),
+        }
+    }
+}
+
+/// The subway calendars that aren't for a line or the whole network, each titled after what it
+/// covers.
+impl CalendarMetadata {
+    /// A station's alerts, where `station` is how it's named, e.g. `125 St (A, B, C, D)`.
+    pub fn station(station: &str, language: Language) -> Self {
+        let (name, summary, placeholder) = match language {
+            Language::English => (
+                format!("MTA {} Station Alerts", station),
+                format!(
+                    "Real-time alerts and planned service changes for MTA station {}",
+                    station
+                ),
+                format!("No service alerts for station {}", station),
+            ),
+            Language::Spanish => (
+                format!("Alertas de la estación {} de la MTA", station),
+                format!(
+                    "Alertas en tiempo real y cambios de servicio planificados de la estación {} de la MTA",
+                    station
+                ),
+                format!("No hay alertas de servicio para la estación {}", station),
+            ),
+        };
+        CalendarMetadata::new(Network::Subway, name, &summary, placeholder, language)
+    }
+
+    /// Elevator and escalator outages at a station, named as for [`CalendarMetadata::station`].
+    pub fn station_accessibility(station: &str, language: Language) -> Self {
+        let (name, summary, placeholder) = match language {
+            Language::English => (
+                format!("MTA {} Station Accessibility Outages", station),
+                format!("Elevator and escalator outages at MTA station {}", station),
+                format!("No elevator or escalator outages at station {}", station),
+            ),
+            Language::Spanish => (
+                format!(
+                    "Interrupciones de accesibilidad de la estación {} de la MTA",
+                    station
+                ),
+                format!(
+                    "Ascensores y escaleras mecánicas fuera de servicio en la estación {} de la MTA",
+                    station
+                ),
+                format!(
+                    "No hay ascensores ni escaleras mecánicas fuera de servicio en la estación {}",
+                    station
+                ),
+            ),
+        };
+        CalendarMetadata::new(Network::Subway, name, &summary, placeholder, language)
+    }
+
+    /// Accessibility alerts on the lines `train_names`, which go by `name`, e.g. `L`.
+    pub fn trains_accessibility(name: &str, train_names: &[&str], language: Language) -> Self {
+        let name = name.to_uppercase();
+        let (title, summary, placeholder) = match language {
+            Language::English => (
+                format!("MTA {} Train Accessibility Alerts", name),
+                format!(
+                    "Elevator outages and service changes affecting accessible stations on the MTA {} train",
+                    name
+                ),
+                format!("No accessibility alerts for the {} train", name),
+            ),
+            Language::Spanish => (
+                format!("Alertas de accesibilidad del tren {} de la MTA", name),
+                format!(
+                    "Ascensores fuera de servicio y cambios de servicio que afectan a las estaciones accesibles del tren {} de la MTA",
+                    name
+                ),
+                format!("No hay alertas de accesibilidad para el tren {}", name),
+            ),
+        };
+        CalendarMetadata::new(Network::Subway, title, &summary, placeholder, language)
+            .with_line_color(train_names)
+    }
+
+    /// Alerts for a trip from the station named `from` to `to` on `train_names`.
+    pub fn commute(from: &str, to: &str, train_names: &[&str], language: Language) -> Self {
+        let trains = train_names.join(", ");
+        let (name, summary, placeholder) = match language {
+            Language::English => (
+                format!("MTA {} to {} ({})", from, to, trains),
+                format!(
+                    "Real-time alerts and planned service changes between {} and {} on the MTA {} train",
+                    from, to, trains
+                ),
+                format!("No service alerts from {} to {}", from, to),
+            ),
+            Language::Spanish => (
+                format!("MTA {} a {} ({})", from, to, trains),
+                format!(
+                    "Alertas en tiempo real y cambios de servicio planificados entre {} y {} en el tren {} de la MTA",
+                    from, to, trains
+                ),
+                format!("No hay alertas de servicio de {} a {}", from, to),
+            ),
+        };
+        CalendarMetadata::new(Network::Subway, name, &summary, placeholder, language)
+            .with_line_color(train_names)
+    }
+
+    /// A saved calendar called `name`, colored after the first of its `train_names`.
+    pub fn custom(name: &str, train_names: &[&str], language: Language) -> Self {
+        let (title, summary, placeholder) = match language {
+            Language::English => (
+                format!("MTA {} Alerts", name),
+                format!(
+                    "Real-time alerts and planned service changes for MTA {}",
+                    name
+                ),
+                format!("No service alerts for {}", name),
+            ),
+            Language::Spanish => (
+                format!("Alertas de {} de la MTA", name),
+                format!(
+                    "Alertas en tiempo real y cambios de servicio planificados de {} de la MTA",
+                    name
+                ),
+                format!("No hay alertas de servicio para {}", name),
+            ),
+        };
+        CalendarMetadata::new(Network::Subway, title, &summary, placeholder, language)
+            .with_line_color(train_names)
+    }
+}
+
+fn subway_metadata(train_name: Option<&str>, language: Language) -> (String, String) {
+    let train_name = train_name.map(str::to_uppercase);
+
+    // Shuttles go by their names rather than their route IDs
+    if let Some(shuttle) = train_name.as_deref().and_then(lines::find_shuttle) {
+        return match language {
+            Language::English => (
+                format!("MTA {} Alerts", shuttle.name),
+                format!(
+                    "Real-time alerts and planned service changes for the MTA {}",
+                    shuttle.name
+                ),
+            ),
+            Language::Spanish => (
+                format!("Alertas del {} de la MTA", shuttle.name),
+                format!(
+                    "Alertas en tiempo real y cambios de servicio planificados del {} de la MTA",
+                    shuttle.name
+                ),
+            ),
+        };
+    }
+
+    match (train_name, language) {
+        (Some(train), Language::English) => (
+            format!("MTA {} Train Alerts", train),
+            format!(
+                "Real-time alerts and planned service changes for MTA {} train",
+                train
+            ),
+        ),
+        (Some(train), Language::Spanish) => (
+            format!("Alertas del tren {} de la MTA", train),
+            format!(
+                "Alertas en tiempo real y cambios de servicio planificados del tren {} de la MTA",
+                train
+            ),
+        ),
+        (None, Language::English) => (
+            "MTA Subway Alerts".to_string(),
+            "Real-time alerts and planned service changes for MTA Subway".to_string(),
+        ),
+        (None, Language::Spanish) => (
+            "Alertas del Metro de la MTA".to_string(),
+            "Alertas en tiempo real y cambios de servicio planificados del Metro de la MTA"
+                .to_string(),
+        ),
+    }
+}
+
+fn lirr_metadata(branch_name: Option<&str>, language: Language) -> (String, String) {
+    match (branch_name, language) {
+        (Some(branch), Language::English) => (
+            format!("LIRR {} Alerts", branch),
+            format!(
+                "Real-time alerts and planned service changes for the LIRR {}",
+                branch
+            ),
+        ),
+        (Some(branch), Language::Spanish) => (
+            format!("Alertas de {} del LIRR", branch),
+            format!(
+                "Alertas en tiempo real y cambios de servicio planificados de {} del LIRR",
+                branch
+            ),
+        ),
+        (None, Language::English) => (
+            "LIRR Alerts".to_string(),
+            "Real-time alerts and planned service changes for the Long Island Rail Road"
+                .to_string(),
+        ),
+        (None, Language::Spanish) => (
+            "Alertas del LIRR".to_string(),
+            "Alertas en tiempo real y cambios de servicio planificados del Long Island Rail Road"
+                .to_string(),
+        ),
+    }
+}
+
+fn mnr_metadata(line_name: Option<&str>, language: Language) -> (String, String) {
+    match (line_name, language) {
+        (Some(line), Language::English) => (
+            format!("Metro-North {} Alerts", line),
+            format!(
+                "Real-time alerts and planned service changes for the Metro-North {}",
+                line
+            ),
+        ),
+        (Some(line), Language::Spanish) => (
+            format!("Alertas de {} de Metro-North", line),
+            format!(
+                "Alertas en tiempo real y cambios de servicio planificados de {} de Metro-North",
+                line
+            ),
+        ),
+        (None, Language::English) => (
+            "Metro-North Alerts".to_string(),
+            "Real-time alerts and planned service changes for Metro-North Railroad".to_string(),
+        ),
+        (None, Language::Spanish) => (
+            "Alertas de Metro-North".to_string(),
+            "Alertas en tiempo real y cambios de servicio planificados de Metro-North Railroad"
+                .to_string(),
+        ),
+    }
+}
+
+fn bus_metadata(route_name: Option<&str>, language: Language) -> (String, String) {
+    match (route_name, language) {
+        (Some(route), Language::English) => (
+            format!("MTA {} Bus Alerts", route),
+            format!(
+                "Real-time alerts, detours and planned service changes for the MTA {} bus",
+                route
+            ),
+        ),
+        (Some(route), Language::Spanish) => (
+            format!("Alertas del autobús {} de la MTA", route),
+            format!(
+                "Alertas en tiempo real, desvíos y cambios de servicio planificados del autobús {} de la MTA",
+                route
+            ),
+        ),
+        (None, Language::English) => (
+            "MTA Bus Alerts".to_string(),
+            "Real-time alerts, detours and planned service changes for MTA buses".to_string(),
+        ),
+        (None, Language::Spanish) => (
+            "Alertas de autobuses de la MTA".to_string(),
+            "Alertas en tiempo real, desvíos y cambios de servicio planificados de los autobuses de la MTA"
+                .to_string(),
+        ),
+    }
+}
+
+fn path_metadata(route_name: Option<&str>, language: Language) -> (String, String) {
+    match (route_name, language) {
+        (Some(route), Language::English) => (
+            format!("PATH {} Alerts", route),
+            format!(
+                "Real-time alerts and planned service changes for PATH {}",
+                route
+            ),
+        ),
+        (Some(route), Language::Spanish) => (
+            format!("Alertas de {} de PATH", route),
+            format!(
+                "Alertas en tiempo real y cambios de servicio planificados de {} de PATH",
+                route
+            ),
+        ),
+        (None, Language::English) => (
+            "PATH Alerts".to_string(),
+            "Real-time alerts and planned service changes for PATH".to_string(),
+        ),
+        (None, Language::Spanish) => (
+            "Alertas de PATH".to_string(),
+            "Alertas en tiempo real y cambios de servicio planificados de PATH".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_calendar_metadata() {
+        assert_eq!(
+            Network::Subway
+                .calendar_metadata(Some("a"), Language::English)
+                .name,
+            "MTA A Train Alerts"
+        );
+        assert_eq!(
+            Network::Subway.calendar_metadata(Some("gs"), Language::English),
+            CalendarMetadata {
+                name: "MTA 42 St Shuttle Alerts".to_string(),
+                description: "Real-time alerts and planned service changes for the MTA 42 St \
+                    Shuttle. Data from the MTA (mta.info)."
+                    .to_string(),
+                color: None,
+                alarm: None,
+                plain: false,
+                timezone: New_York,
+                refresh_interval: crate::filter::DEFAULT_REFRESH_INTERVAL,
+                placeholder: "No service alerts for the 42 St Shuttle".to_string(),
+            }
+        );
+        assert_eq!(
+            Network::Lirr
+                .calendar_metadata(Some("Babylon Branch"), Language::English)
+                .name,
+            "LIRR Babylon Branch Alerts"
+        );
+        assert_eq!(
+            Network::Lirr
+                .calendar_metadata(None, Language::Spanish)
+                .name,
+            "Alertas del LIRR"
+        );
+        assert_eq!(
+            Network::Path
+                .calendar_metadata(None, Language::English)
+                .description,
+            "Real-time alerts and planned service changes for PATH. Data from the Port \
+            Authority of New York and New Jersey (panynj.gov)."
+        );
+    }
+
+    #[test]
+    fn test_other_calendar_metadata() {
+        let station = CalendarMetadata::station("125 St (A, B, C, D)", Language::English);
+        assert_eq!(station.name, "MTA 125 St (A, B, C, D) Station Alerts");
+        assert_eq!(
+            station.placeholder,
+            "No service alerts for station 125 St (A, B, C, D)"
+        );
+        assert_eq!(station.color, None);
+
+        let commute =
+            CalendarMetadata::commute("125 St", "Jay St-MetroTech", &["A", "C"], Language::Spanish);
+        assert_eq!(commute.name, "MTA 125 St a Jay St-MetroTech (A, C)");
+        assert_eq!(commute.color, Some("#0039A6"));
+
+        assert_eq!(
+            CalendarMetadata::trains_accessibility("l", &["L"], Language::English).name,
+            "MTA L Train Accessibility Alerts"
+        );
+        assert_eq!(
+            CalendarMetadata::custom("Home", &[], Language::Spanish).placeholder,
+            "No hay alertas de servicio para Home"
+        );
+    }
+
+    #[test]
+    fn test_placeholder_summary() {
+        assert_eq!(
+            Network::Subway.placeholder_summary(Some("n"), Language::English),
+            "No service alerts for the N train"
+        );
+        assert_eq!(
+            Network::Subway.placeholder_summary(Some("H"), Language::English),
+            "No service alerts for the Rockaway Park Shuttle"
+        );
+        assert_eq!(
+            Network::Bus.placeholder_summary(Some("M15-SBS"), Language::Spanish),
+            "No hay alertas de servicio para el autobús M15-SBS"
+        );
+    }
+}
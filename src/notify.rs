@@ -0,0 +1,482 @@
+use crate::alert::AlertDetail;
+use crate::changes::{AlertChange, ChangeKind};
+use crate::discord::Discord;
+use crate::html::format_period;
+use crate::lines::{self, Line};
+use crate::ntfy::Ntfy;
+use crate::severity::Severity;
+use crate::slack::Slack;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use reqwest::header::RETRY_AFTER;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+/// How many times posting a message is tried before it's given up on, when the service keeps
+/// rate limiting it
+pub const MAX_ATTEMPTS: u32 = 5;
+/// The longest wait between attempts, however long the service asks for
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A chat or push notification service that alerts can be posted to through a webhook.
+pub trait Notifier: std::fmt::Debug + Send + Sync {
+    /// The service's name, for logs, e.g. `Slack`
+    fn service(&self) -> &'static str;
+
+    /// The webhook payload announcing `alert`. `now` decides which of the alert's active
+    /// periods are still to come.
+    fn payload(&self, alert: &AlertDetail, now: DateTime<Utc>) -> serde_json::Value;
+}
+
+/// The services webhooks can be configured for, by their `type` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookKind {
+    Slack,
+    Discord,
+    Ntfy,
+}
+
+/// A webhook as it's configured, e.g.
+/// `{"type": "discord", "url": "https://discord.com/api/webhooks/...", "lines": ["L", "G"]}`.
+///
+/// For ntfy, `url` is the server, e.g. `https://ntfy.sh`, and `topic` the topic on it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    #[serde(rename = "type")]
+    pub kind: WebhookKind,
+    pub url: String,
+    /// The ntfy topic notifications are published to, which only ntfy takes
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Train lines or trunk groups whose alerts are posted, or every line when empty
+    #[serde(default)]
+    pub lines: Vec<String>,
+    /// The least severe alerts that are posted
+    #[serde(default = "default_min_severity")]
+    pub min_severity: Severity,
+}
+
+fn default_min_severity() -> Severity {
+    Severity::Severe
+}
+
+/// An incoming webhook that new alerts for some lines are posted to.
+///
+/// Only alerts that are added to the feed are posted, and each alert ID at most once, so an
+/// alert that drops out of the feed and comes back isn't posted again.
+#[derive(Debug)]
+pub struct Webhook {
+    pub url: String,
+    notifier: Box<dyn Notifier>,
+    trains: Vec<&'static str>,
+    min_severity: Severity,
+    posted: HashSet<String>,
+}
+
+impl Webhook {
+    pub fn new(
+        url: String,
+        notifier: Box<dyn Notifier>,
+        trains: Vec<&'static str>,
+        min_severity: Severity,
+    ) -> Self {
+        Webhook {
+            url,
+            notifier,
+            trains,
+            min_severity,
+            posted: HashSet::new(),
+        }
+    }
+
+    /// The webhook `config` describes. `public_url` is where this server is reachable, for
+    /// notifications that link to its alert pages.
+    pub fn from_config(config: WebhookConfig, public_url: Option<&str>) -> Result<Self, String> {
+        let notifier: Box<dyn Notifier> = match (config.kind, config.topic) {
+            (WebhookKind::Slack, None) => Box::new(Slack),
+            (WebhookKind::Discord, None) => Box::new(Discord),
+            (WebhookKind::Ntfy, Some(topic)) => Box::new(Ntfy::new(topic, public_url)),
+            (WebhookKind::Ntfy, None) => return Err("ntfy notifiers need a topic.".to_string()),
+            (_, Some(_)) => return Err("Only ntfy notifiers take a topic.".to_string()),
+        };
+
+        let trains = if config.lines.is_empty() {
+            lines::train_ids()
+        } else {
+            parse_trains(config.lines.iter().map(String::as_str))?
+        };
+
+        Ok(Webhook::new(
+            config.url,
+            notifier,
+            trains,
+            config.min_severity,
+        ))
+    }
+
+    pub fn service(&self) -> &'static str {
+        self.notifier.service()
+    }
+
+    /// The lines alerts are posted for.
+    pub fn trains(&self) -> &[&'static str] {
+        &self.trains
+    }
+
+    pub fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    /// The payload announcing `change`, if it's a new alert for one of the lines that's at
+    /// least `min_severity` and hasn't been posted before.
+    pub fn message(
+        &mut self,
+        change: &AlertChange,
+        now: DateTime<Utc>,
+    ) -> Option<serde_json::Value> {
+        let alert = &change.alert;
+        if change.kind != ChangeKind::Added
+            || alert.severity < self.min_severity
+            || !change.affects_any(&self.trains)
+            || !self.posted.insert(alert.id.clone())
+        {
+            return None;
+        }
+
+        Some(self.notifier.payload(alert, now))
+    }
+}
+
+/// Loads the webhooks configured in `path`, a JSON list of [`WebhookConfig`]s. A missing file
+/// configures none.
+pub fn load_webhooks(
+    path: impl AsRef<Path>,
+    public_url: Option<&str>,
+) -> Result<Vec<Webhook>, Box<dyn std::error::Error + Send + Sync>> {
+    let configs: Vec<WebhookConfig> = match std::fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(configs
+        .into_iter()
+        .map(|config| Webhook::from_config(config, public_url))
+        .collect::<Result<_, _>>()?)
+}
+
+/// Resolves lines and trunk groups, e.g. `A`, `l` and `456`, to the lines they cover.
+pub fn parse_trains<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<&'static str>, String> {
+    let mut trains: Vec<&'static str> = Vec::new();
+    for name in names.into_iter().map(lines::normalize) {
+        let resolved =
+            lines::resolve(&name).ok_or_else(|| format!("Unknown train line: {}.", name))?;
+        for train in resolved {
+            if !trains.contains(train) {
+                trains.push(train);
+            }
+        }
+    }
+
+    Ok(trains)
+}
+
+/// Posts `payload` to a webhook, waiting and trying again when the service rate limits it.
+pub async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    payload: &serde_json::Value,
+) -> Result<(), reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        let response = client.post(url).json(payload).send().await?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS && attempt + 1 < MAX_ATTEMPTS {
+            // Slack gives whole seconds and Discord fractions of one
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map(|seconds| seconds.min(MAX_RETRY_DELAY.as_secs_f64()))
+                .map(Duration::from_secs_f64);
+            tokio::time::sleep(retry_delay(attempt, retry_after)).await;
+            attempt += 1;
+            continue;
+        }
+
+        response.error_for_status()?;
+        return Ok(());
+    }
+}
+
+/// How long to wait before trying a rate limited request again: as long as the service asked
+/// for, or else a second, doubling with each attempt.
+pub fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    retry_after
+        .unwrap_or_else(|| Duration::from_secs(1 << attempt.min(6)))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// The routes an alert informs, without repeats, in the order the feed lists them.
+pub(crate) fn alert_routes(alert: &AlertDetail) -> Vec<&str> {
+    let mut routes: Vec<&str> = Vec::new();
+    for route in alert
+        .informed_entities
+        .iter()
+        .filter_map(|entity| entity.route_id.as_deref())
+    {
+        if !routes.contains(&route) {
+            routes.push(route);
+        }
+    }
+
+    routes
+}
+
+/// The line a route belongs to, which for an express variant such as `6X` is its local line.
+pub(crate) fn route_line(route: &str) -> Option<&'static Line> {
+    let route = route.to_uppercase();
+    lines::find_line(&route).or_else(|| {
+        lines::find_express_variant(&route).and_then(|variant| lines::find_line(variant.local))
+    })
+}
+
+/// When the alert is next in effect, e.g. `Mon, Dec 15, 7:00 AM – 10:00 AM (and 2 more)`.
+/// Planned work can have dozens of periods, so only the next one is given in full.
+pub(crate) fn next_period(alert: &AlertDetail, now: DateTime<Utc>) -> Option<String> {
+    let upcoming: Vec<_> = alert
+        .active_periods
+        .iter()
+        .filter(|period| period.end.is_none_or(|end| end > now))
+        .filter_map(|period| period.start.map(|start| (start, period.end)))
+        .collect();
+
+    let (start, end) = upcoming.first()?;
+    let mut period = format_period(*start, *end);
+    if upcoming.len() > 1 {
+        period.push_str(&format!(" (and {} more)", upcoming.len() - 1));
+    }
+
+    Some(period)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::alert::{ActivePeriod, InformedEntity};
+    use crate::calendar::AlertKind;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    pub(crate) fn time(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 12, 15, hour, 0, 0).unwrap()
+    }
+
+    pub(crate) fn alert(id: &str, route_ids: &[&str], severity: Severity) -> AlertDetail {
+        AlertDetail {
+            id: id.to_string(),
+            alert_type: "Delays".to_string(),
+            header: "Trains are delayed while we address a signal problem".to_string(),
+            description: String::new(),
+            severity,
+            kind: AlertKind::Realtime,
+            created_at: time(12),
+            updated_at: time(12),
+            active_periods: vec![ActivePeriod {
+                start: Some(time(12)),
+                end: Some(time(15)),
+            }],
+            informed_entities: route_ids
+                .iter()
+                .map(|route_id| InformedEntity {
+                    agency_id: None,
+                    route_id: Some(route_id.to_string()),
+                    stop_id: None,
+                    sort_order: None,
+                })
+                .collect(),
+            url: None,
+            translations: BTreeMap::new(),
+        }
+    }
+
+    fn change(kind: ChangeKind, alert: AlertDetail) -> AlertChange {
+        AlertChange { kind, alert }
+    }
+
+    #[test]
+    fn test_message_filters_and_deduplicates() {
+        let mut webhook = Webhook::new(
+            "https://example.com".to_string(),
+            Box::new(Slack),
+            vec!["A", "L"],
+            Severity::Severe,
+        );
+        let severe = alert("1", &["A"], Severity::Severe);
+
+        assert!(
+            webhook
+                .message(&change(ChangeKind::Added, severe.clone()), time(12))
+                .is_some()
+        );
+        // The same alert is only posted once, even if it's removed and added again
+        assert_eq!(
+            webhook.message(&change(ChangeKind::Added, severe), time(12)),
+            None
+        );
+        assert_eq!(
+            webhook.message(
+                &change(ChangeKind::Updated, alert("2", &["L"], Severity::Severe)),
+                time(12)
+            ),
+            None
+        );
+        assert_eq!(
+            webhook.message(
+                &change(ChangeKind::Added, alert("3", &["L"], Severity::Warning)),
+                time(12)
+            ),
+            None
+        );
+        assert_eq!(
+            webhook.message(
+                &change(ChangeKind::Added, alert("4", &["F"], Severity::Severe)),
+                time(12)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_webhook_config() {
+        let configs: Vec<WebhookConfig> = serde_json::from_str(
+            r#"[
+                {"type": "discord", "url": "https://discord.com/api/webhooks/1", "lines": ["l", "G"]},
+                {"type": "slack", "url": "https://hooks.slack.com/services/1", "min_severity": "warning"}
+            ]"#,
+        )
+        .unwrap();
+
+        let discord = Webhook::from_config(configs[0].clone(), None).unwrap();
+        assert_eq!(discord.service(), "Discord");
+        assert_eq!(discord.trains(), ["L", "G"]);
+        assert_eq!(discord.min_severity(), Severity::Severe);
+
+        let slack = Webhook::from_config(configs[1].clone(), None).unwrap();
+        assert_eq!(slack.service(), "Slack");
+        assert_eq!(slack.trains(), lines::train_ids());
+        assert_eq!(slack.min_severity(), Severity::Warning);
+
+        let unknown_line = WebhookConfig {
+            lines: vec!["X".to_string()],
+            ..configs[0].clone()
+        };
+        assert_eq!(
+            Webhook::from_config(unknown_line, None).unwrap_err(),
+            "Unknown train line: X."
+        );
+
+        let ntfy: WebhookConfig = serde_json::from_str(
+            r#"{"type": "ntfy", "url": "https://ntfy.sh", "topic": "my-l-train", "lines": ["L"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Webhook::from_config(ntfy.clone(), None).unwrap().service(),
+            "ntfy"
+        );
+        let without_topic = WebhookConfig {
+            topic: None,
+            ..ntfy.clone()
+        };
+        assert_eq!(
+            Webhook::from_config(without_topic, None).unwrap_err(),
+            "ntfy notifiers need a topic."
+        );
+        let slack_with_topic = WebhookConfig {
+            kind: WebhookKind::Slack,
+            ..ntfy
+        };
+        assert_eq!(
+            Webhook::from_config(slack_with_topic, None).unwrap_err(),
+            "Only ntfy notifiers take a topic."
+        );
+        assert!(serde_json::from_str::<WebhookConfig>(r#"{"type": "teams", "url": ""}"#).is_err());
+    }
+
+    #[test]
+    fn test_load_webhooks() {
+        assert!(
+            load_webhooks("tests/fixtures/missing.json", None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_trains() {
+        assert_eq!(
+            parse_trains(["a", " L", "456"]),
+            Ok(vec!["A", "L", "4", "5", "6"])
+        );
+        assert_eq!(parse_trains("6,456".split(',')), Ok(vec!["6", "4", "5"]));
+        assert_eq!(
+            parse_trains(["A", "X"]),
+            Err("Unknown train line: X.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0, None), Duration::from_secs(1));
+        assert_eq!(retry_delay(3, None), Duration::from_secs(8));
+        assert_eq!(retry_delay(20, None), MAX_RETRY_DELAY);
+        assert_eq!(
+            retry_delay(3, Some(Duration::from_millis(250))),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            retry_delay(0, Some(Duration::from_secs(600))),
+            MAX_RETRY_DELAY
+        );
+    }
+
+    #[test]
+    fn test_next_period() {
+        let mut alert = alert("1", &["A"], Severity::Severe);
+        assert_eq!(
+            next_period(&alert, time(14)).as_deref(),
+            Some("Mon, Dec 15, 7:00 AM – 10:00 AM")
+        );
+        assert_eq!(next_period(&alert, time(16)), None);
+
+        alert.active_periods.push(ActivePeriod {
+            start: Some(time(20)),
+            end: None,
+        });
+        assert_eq!(
+            next_period(&alert, time(13)).as_deref(),
+            Some("Mon, Dec 15, 7:00 AM – 10:00 AM (and 1 more)")
+        );
+        assert_eq!(
+            next_period(&alert, time(16)).as_deref(),
+            Some("Mon, Dec 15, 3:00 PM until further notice")
+        );
+    }
+
+    #[test]
+    fn test_alert_routes() {
+        let alert = alert("1", &["6", "6X", "6", "B44"], Severity::Severe);
+
+        assert_eq!(alert_routes(&alert), vec!["6", "6X", "B44"]);
+        assert_eq!(route_line("6x").map(|line| line.id), Some("6"));
+        assert_eq!(route_line("B44"), None);
+    }
+}
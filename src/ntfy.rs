@@ -0,0 +1,132 @@
+use crate::alert::AlertDetail;
+use crate::notify::{self, Notifier};
+use crate::severity::Severity;
+use chrono::{DateTime, Utc};
+
+/// An ntfy topic, on ntfy.sh or a self-hosted server, that push notifications are published to
+/// as JSON.
+///
+/// Notifications are titled with the alert's lines and type, e.g. `A, C: Delays`, and take
+/// their priority from its severity. Tapping one opens the alert's detail page on this server,
+/// or the MTA's page for the alert when the server's public URL isn't known.
+#[derive(Debug, Clone)]
+pub struct Ntfy {
+    topic: String,
+    /// Where this server is reachable, without a trailing slash
+    public_url: Option<String>,
+}
+
+impl Ntfy {
+    pub fn new(topic: String, public_url: Option<&str>) -> Self {
+        Ntfy {
+            topic,
+            public_url: public_url.map(|url| url.trim_end_matches('/').to_string()),
+        }
+    }
+}
+
+impl Notifier for Ntfy {
+    fn service(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn payload(&self, alert: &AlertDetail, now: DateTime<Utc>) -> serde_json::Value {
+        let routes: Vec<String> = notify::alert_routes(alert)
+            .iter()
+            .map(|route| route.to_uppercase())
+            .collect();
+        let title = if routes.is_empty() {
+            alert.alert_type.clone()
+        } else {
+            format!("{}: {}", routes.join(", "), alert.alert_type)
+        };
+
+        let mut message = alert.header.clone();
+        if let Some(period) = notify::next_period(alert, now) {
+            message.push('\n');
+            message.push_str(&period);
+        }
+
+        let mut payload = serde_json::json!({
+            "topic": self.topic,
+            "title": title,
+            "message": message,
+            "priority": priority(alert.severity),
+            "tags": [tag(alert.severity)],
+        });
+
+        let click = match &self.public_url {
+            Some(public_url) => Some(format!("{}{}", public_url, crate::alert_path(&alert.id))),
+            None => alert.url.clone(),
+        };
+        if let Some(click) = click {
+            payload["click"] = click.into();
+        }
+
+        payload
+    }
+}
+
+/// ntfy's priority for alerts of `severity`, from 3 (the default) to 5 (urgent).
+fn priority(severity: Severity) -> u8 {
+    match severity {
+        Severity::Info => 3,
+        Severity::Warning => 4,
+        Severity::Severe => 5,
+    }
+}
+
+/// The ntfy tag, shown as an emoji, for alerts of `severity`.
+fn tag(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "information_source",
+        Severity::Warning => "warning",
+        Severity::Severe => "rotating_light",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::tests::{alert, time};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_payload() {
+        let ntfy = Ntfy::new("my-commute".to_string(), Some("https://example.com/"));
+        let alert = alert("lmm:alert:1#2", &["a", "C"], Severity::Severe);
+
+        assert_eq!(
+            ntfy.payload(&alert, time(13)),
+            serde_json::json!({
+                "topic": "my-commute",
+                "title": "A, C: Delays",
+                "message": "Trains are delayed while we address a signal problem\nMon, Dec 15, 7:00 AM – 10:00 AM",
+                "priority": 5,
+                "tags": ["rotating_light"],
+                "click": "https://example.com/api/alerts/lmm:alert:1%232",
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_without_public_url() {
+        let ntfy = Ntfy::new("my-commute".to_string(), None);
+        let mut alert = alert("1", &[], Severity::Info);
+
+        let payload = ntfy.payload(&alert, time(16));
+        assert_eq!(payload["title"], "Delays");
+        assert_eq!(
+            payload["message"],
+            "Trains are delayed while we address a signal problem"
+        );
+        assert_eq!(payload["priority"], 3);
+        assert_eq!(payload.get("click"), None);
+
+        alert.url = Some("https://new.mta.info/alerts".to_string());
+        assert_eq!(
+            ntfy.payload(&alert, time(16))["click"],
+            "https://new.mta.info/alerts"
+        );
+    }
+}
@@ -0,0 +1,800 @@
+use crate::calendar::{AlertKind, Category, Language};
+use crate::digest::Digest;
+use crate::filter::{DEFAULT_DAYS, DayType, MAX_DAYS};
+use crate::graphql::MAX_QUERY_LEN;
+use crate::lines::{EXPRESS_VARIANTS, TRAIN_GROUPS, train_ids};
+use crate::opml::FeedFormat;
+use crate::railroad::{LIRR, MNR, PATH, Railroad};
+use crate::severity::Severity;
+use crate::stops::{Borough, Direction};
+use serde_json::{Value, json};
+
+/// The OpenAPI 3 description of the HTTP API, served at `/api/openapi.json`.
+///
+/// It's assembled by hand from the route table in `main.rs`, using the same constants the
+/// handlers validate against, so enums such as train lines and severities can't drift.
+pub fn openapi_spec() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "NYC Train Cal",
+            "description": "MTA service alerts as iCalendar calendars, feeds and JSON.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/api/calendars/train/{train_name}": {
+                "get": {
+                    "summary": "Alerts for a train line or trunk group",
+                    "description": "Ends in `.ics` or `.json`, or neither to choose the format from the `Accept` header.",
+                    "parameters": with_calendar_params(path_param(
+                        "train_name",
+                        "A train line or trunk group, optionally followed by `.ics` or `.json`",
+                        json!({ "type": "string", "example": "A.ics" }),
+                    )).into_iter().chain([query_param(
+                        "stations",
+                        "Only alerts for these stops on the line, or for no stop in particular. Comma-separated GTFS stop IDs.",
+                        json!({ "type": "string", "example": "L08,L06" }),
+                    )]).collect::<Vec<_>>(),
+                    "responses": {
+                        "200": {
+                            "description": "The calendar, or its events as JSON",
+                            "content": {
+                                "text/calendar": { "schema": { "type": "string" } },
+                                "application/json": { "schema": events_schema() },
+                            },
+                        },
+                        "304": { "description": "Not modified since `If-None-Match` or `If-Modified-Since`" },
+                        "400": error_response("A query parameter is invalid"),
+                        "404": error_response("Unknown train line or format"),
+                        "406": error_response("None of the accepted media types can be served"),
+                        "503": error_response("Station data, needed for `stations`, isn't loaded"),
+                    },
+                }
+            },
+            "/api/calendars/train/{train_name}/accessibility": {
+                "get": {
+                    "summary": "Elevator outages at stations a line serves and its service changes affecting accessible stations",
+                    "description": "Optionally followed by `.ics`. Needs station data to tell which stations a line serves.",
+                    "parameters": with_calendar_params(path_param("train_name", "A train line or trunk group", train_name_schema())),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars/trains/{train_names}": {
+                "get": {
+                    "summary": "Alerts for several train lines",
+                    "parameters": with_calendar_params(path_param(
+                        "train_names",
+                        "Comma-separated train lines or trunk groups, optionally followed by `.ics`",
+                        json!({ "type": "string", "example": "F,L.ics" }),
+                    )),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars/all": {
+                "get": {
+                    "summary": "Alerts for every train line",
+                    "parameters": calendar_params(),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars/export.zip": {
+                "get": {
+                    "summary": "A zip archive of every train line's calendar, one .ics file per line",
+                    "responses": {
+                        "200": { "description": "The zip archive", "content": { "application/zip": { "schema": { "type": "string", "format": "binary" } } } },
+                        "502": error_response("The MTA feed couldn't be fetched"),
+                    },
+                }
+            },
+            "/api/calendars/commute": {
+                "get": {
+                    "summary": "Alerts between two stations on one or more lines",
+                    "parameters": commute_params().into_iter().chain(calendar_params()).collect::<Vec<_>>(),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars": {
+                "post": {
+                    "summary": "Save a calendar's lines, stations and filters under a short ID",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "object",
+                                    "properties": {
+                                        "name": { "type": "string", "maxLength": 100 },
+                                        "lines": { "type": "array", "items": { "type": "string" }, "example": ["F", "G"] },
+                                        "stations": { "type": "array", "items": { "type": "string" }, "maxItems": 20, "example": ["F20"] },
+                                        "filters": {
+                                            "type": "object",
+                                            "description": "Calendar query parameters by name",
+                                            "additionalProperties": { "type": "string" },
+                                            "example": { "hours": "07:00-10:00,17:00-19:30", "min_severity": "warning" },
+                                        },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": json_response("The calendar was already saved; its ID and URL", json!({ "type": "object" })),
+                        "201": json_response("The saved calendar's ID and URL", json!({ "type": "object" })),
+                        "400": error_response("Invalid line, station or filter"),
+                    },
+                }
+            },
+            "/api/calendars/custom/{id}": {
+                "get": {
+                    "summary": "A saved calendar",
+                    "parameters": [path_param(
+                        "id",
+                        "A saved calendar's ID, optionally followed by `.ics`",
+                        json!({ "type": "string", "example": "3f2a9c01b7.ics" }),
+                    )],
+                    "responses": {
+                        "200": {
+                            "description": "The calendar",
+                            "content": { "text/calendar": { "schema": { "type": "string" } } },
+                        },
+                        "304": { "description": "Not modified since `If-None-Match` or `If-Modified-Since`" },
+                        "404": error_response("Unknown calendar ID"),
+                    },
+                }
+            },
+            "/api/calendars/station/{stop_id}": {
+                "get": {
+                    "summary": "Alerts for a station or platform",
+                    "parameters": with_calendar_params(stop_id_param()),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars/accessibility/{stop_id}": {
+                "get": {
+                    "summary": "Elevator and escalator outages at a station",
+                    "parameters": with_calendar_params(stop_id_param()),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/calendars/lirr/{line}": railroad_calendar(&LIRR),
+            "/api/calendars/mnr/{line}": railroad_calendar(&MNR),
+            "/api/calendars/path/{line}": railroad_calendar(&PATH),
+            "/api/calendars/bus/{route}": {
+                "get": {
+                    "summary": "Alerts for a bus route",
+                    "parameters": with_calendar_params(path_param(
+                        "route",
+                        "A bus route short name or GTFS route ID, optionally followed by `.ics`",
+                        json!({ "type": "string", "example": "M15-SBS.ics" }),
+                    )),
+                    "responses": calendar_responses(),
+                }
+            },
+            "/api/alerts/train/{train_name}": {
+                "get": {
+                    "summary": "Calendar events for a train line as JSON",
+                    "parameters": [
+                        path_param(
+                            "train_name",
+                            "A train line or trunk group, optionally followed by `.json`",
+                            train_name_schema(),
+                        ),
+                        query_param(
+                            "accessibility",
+                            "With `1`, only elevator outages at stations the line serves and service changes affecting accessible stations",
+                            json!({ "type": "string", "enum": ["0", "1"], "default": "0" }),
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("The line's events", events_schema()),
+                        "400": error_response("A query parameter is invalid"),
+                        "404": error_response("Unknown train line"),
+                        "502": error_response("The MTA alerts feed couldn't be fetched"),
+                        "503": error_response("Station data, needed for `accessibility`, isn't loaded"),
+                    },
+                }
+            },
+            "/api/alerts/train/{train_name}/stream": {
+                "get": {
+                    "summary": "Server-sent events: a snapshot of a line's alerts, then each alert added, updated or removed",
+                    "parameters": [path_param("train_name", "A train line or trunk group", train_name_schema())],
+                    "responses": {
+                        "200": { "description": "`snapshot`, `alert_added`, `alert_updated` and `alert_removed` events", "content": { "text/event-stream": { "schema": { "type": "string" } } } },
+                        "404": error_response("Unknown train line"),
+                    },
+                }
+            },
+            "/ws/alerts": {
+                "get": {
+                    "summary": "WebSocket: send {\"subscribe\": [\"A\", \"NQRW\"]} to get a snapshot of those lines' alerts, then each alert added, updated or removed",
+                    "responses": {
+                        "101": { "description": "Switching to the WebSocket protocol" },
+                    },
+                }
+            },
+            "/api/alerts/{alert_id}": {
+                "get": {
+                    "summary": "A single alert, with every active period and informed entity",
+                    "parameters": [path_param(
+                        "alert_id",
+                        "An MTA alert ID, with `#` sent as `%23`",
+                        json!({ "type": "string", "example": "lmm:planned_work:1" }),
+                    )],
+                    "responses": {
+                        "200": json_response("The alert", json!({ "type": "object" })),
+                        "404": error_response("Unknown alert"),
+                        "502": error_response("The MTA alerts feed couldn't be fetched"),
+                    },
+                }
+            },
+            "/api/status.json": {
+                "get": {
+                    "summary": "Every line's current status and how many alerts it has in effect and coming up",
+                    "responses": {
+                        "200": json_response("Line statuses in display order", json!({
+                            "type": "object",
+                            "properties": {
+                                "lines": { "type": "array", "items": { "type": "object" } },
+                            },
+                        })),
+                    },
+                }
+            },
+            "/api/stations": {
+                "get": {
+                    "summary": "Search stations by name to find their stop IDs",
+                    "parameters": [
+                        query_param(
+                            "q",
+                            "Part of a station name, ignoring case",
+                            json!({ "type": "string", "example": "bedford" }),
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("Up to 20 matching stations, best matches first", json!({ "type": "array", "items": { "type": "object" } })),
+                        "400": error_response("Missing query"),
+                        "503": error_response("Station data isn't loaded"),
+                    },
+                }
+            },
+            "/api/departures/{stop_id}": {
+                "get": {
+                    "summary": "The next trains to leave a station in each direction, from real-time arrival predictions",
+                    "parameters": [
+                        path_param(
+                            "stop_id",
+                            "A GTFS station or platform ID followed by `.json`",
+                            json!({ "type": "string", "example": "A15.json" }),
+                        ),
+                        query_param(
+                            "limit",
+                            "How many departures to return per direction, from 1 to 20 (default 5)",
+                            json!({ "type": "integer", "minimum": 1, "maximum": 20 }),
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("Departures grouped by direction", json!({ "type": "object" })),
+                        "400": error_response("Invalid stop ID or limit"),
+                        "404": error_response("Unknown stop"),
+                        "503": error_response("Station data isn't loaded"),
+                    },
+                }
+            },
+            "/api/widget/station/{stop_id}": {
+                "get": {
+                    "summary": "A station's alerts in effect and next trains in each direction, in one response for kiosk pages",
+                    "parameters": [
+                        path_param(
+                            "stop_id",
+                            "A GTFS station or platform ID followed by `.json`",
+                            json!({ "type": "string", "example": "A15.json" }),
+                        ),
+                        query_param(
+                            "limit",
+                            "How many arrivals to return per direction, from 1 to 20 (default 3)",
+                            json!({ "type": "integer", "minimum": 1, "maximum": 20 }),
+                        ),
+                    ],
+                    "responses": {
+                        "200": json_response("Alerts, most severe first, and arrivals grouped by direction, or null when predictions are unavailable", json!({ "type": "object" })),
+                        "400": error_response("Invalid stop ID or limit"),
+                        "404": error_response("Unknown stop"),
+                        "502": error_response("The MTA alerts feed couldn't be fetched"),
+                        "503": error_response("Station data isn't loaded"),
+                    },
+                }
+            },
+            "/api/badge/{train_name}": {
+                "get": {
+                    "summary": "An SVG badge showing a train line's current status",
+                    "parameters": [path_param(
+                        "train_name",
+                        "A train line followed by `.svg`",
+                        json!({ "type": "string", "example": "F.svg" }),
+                    )],
+                    "responses": {
+                        "200": {
+                            "description": "The badge, with a status of Good Service, Planned Work, Delays or Suspended",
+                            "content": { "image/svg+xml": { "schema": { "type": "string" } } },
+                        },
+                        "400": error_response("Unknown train line"),
+                    },
+                }
+            },
+            "/api/feeds/train/{train_name}": {
+                "get": {
+                    "summary": "An RSS or Atom feed of a train line's alerts",
+                    "parameters": [path_param(
+                        "train_name",
+                        "A train line or trunk group followed by `.rss` or `.atom`",
+                        json!({ "type": "string", "example": "F.rss" }),
+                    )],
+                    "responses": {
+                        "200": {
+                            "description": "The feed",
+                            "content": {
+                                "application/rss+xml": { "schema": { "type": "string" } },
+                                "application/atom+xml": { "schema": { "type": "string" } },
+                            },
+                        },
+                        "404": error_response("Unknown train line or format"),
+                    },
+                }
+            },
+            "/api/feeds.opml": {
+                "get": {
+                    "summary": "Every line's feed as an OPML subscription list, in folders by trunk color",
+                    "parameters": [query_param(
+                        "format",
+                        "Which feed each line links to",
+                        json!({ "type": "string", "enum": FeedFormat::ALL.map(|f| f.query_name()), "default": FeedFormat::Rss.query_name() }),
+                    )],
+                    "responses": {
+                        "200": { "description": "The OPML document", "content": { "text/x-opml": { "schema": { "type": "string" } } } },
+                        "400": error_response("Unknown feed format"),
+                    },
+                }
+            },
+            "/api/trains": {
+                "get": {
+                    "summary": "Supported train lines and severity levels",
+                    "responses": { "200": json_response("Train lines and severity levels", json!({ "type": "object" })) },
+                }
+            },
+            "/api/groups": {
+                "get": {
+                    "summary": "Supported trunk groups and the lines they cover",
+                    "responses": { "200": json_response("Trunk groups", json!({ "type": "array", "items": { "type": "object" } })) },
+                }
+            },
+            "/api/graphql": {
+                "get": {
+                    "summary": "GraphiQL, for trying out queries. Only served when `GRAPHQL_PLAYGROUND` is set.",
+                    "responses": {
+                        "200": { "description": "The GraphiQL page", "content": { "text/html": { "schema": { "type": "string" } } } },
+                        "404": { "description": "The playground is disabled" },
+                    },
+                },
+                "post": {
+                    "summary": "Query lines, alerts and stations with a subset of GraphQL: one query per request, without fragments, directives or introspection. The README lists what's supported.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "object",
+                                    "required": ["query"],
+                                    "properties": {
+                                        "query": { "type": "string", "maxLength": MAX_QUERY_LEN, "example": "{ line(id: \"A\") { name alerts(severity: SEVERE) { header } } }" },
+                                        "variables": { "type": "object" },
+                                        "operationName": { "type": "string" },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": json_response("The query's `data`, and `errors` if any part of it failed", json!({ "type": "object" })),
+                        "400": error_response("The body isn't a GraphQL request"),
+                    },
+                }
+            },
+            "/train/{train_name}": {
+                "get": {
+                    "summary": "A web page with a train's current and upcoming alerts and subscribe links",
+                    "parameters": [path_param("train_name", "A train line or trunk group", train_name_schema())],
+                    "responses": {
+                        "200": { "description": "The status page", "content": { "text/html": { "schema": { "type": "string" } } } },
+                        "404": error_response("Unknown train line"),
+                    },
+                }
+            },
+            "/webcal/{train_name}": {
+                "get": {
+                    "summary": "Redirects calendar apps to a train's calendar; shows browsers subscribe links",
+                    "parameters": [path_param("train_name", "A train line or trunk group", train_name_schema())],
+                    "responses": {
+                        "200": { "description": "A page with the subscribe links", "content": { "text/html": { "schema": { "type": "string" } } } },
+                        "302": { "description": "Redirect to the calendar" },
+                        "404": error_response("Unknown train line"),
+                    },
+                }
+            },
+            "/subscribe/google/{train_name}": subscribe_redirect("Google Calendar"),
+            "/subscribe/outlook/{train_name}": subscribe_redirect("Outlook"),
+            "/healthz": {
+                "get": {
+                    "summary": "Liveness check",
+                    "responses": { "200": json_response("The server is up", json!({ "type": "object" })) },
+                }
+            },
+            "/readyz": {
+                "get": {
+                    "summary": "Readiness check",
+                    "responses": {
+                        "200": json_response("Alert data is available", json!({ "type": "object" })),
+                        "503": json_response("Alert data is unavailable", json!({ "type": "object" })),
+                    },
+                }
+            },
+            "/admin/cache/purge": {
+                "post": {
+                    "summary": "Drop cached documents, or only one line's with `train`. Requires `Authorization: Bearer <ADMIN_TOKEN>`.",
+                    "parameters": [query_param("train", "Only purge this train line's calendars and feeds", json!({ "type": "string" }))],
+                    "responses": {
+                        "200": json_response("The number of documents dropped", json!({ "type": "object", "properties": { "purged": { "type": "integer" } } })),
+                        "400": error_response("Unknown train line"),
+                        "401": error_response("Missing or invalid admin token"),
+                    },
+                }
+            },
+            "/api/stats": {
+                "get": {
+                    "summary": "Uptime, requests per route, cache hits and misses, and when each MTA feed was last fetched",
+                    "responses": { "200": json_response("The server's counters since it started", json!({ "type": "object" })) },
+                }
+            },
+            "/version": {
+                "get": {
+                    "summary": "The running build",
+                    "responses": { "200": json_response("Version, git commit, build time and rustc version", json!({ "type": "object" })) },
+                }
+            },
+        },
+    })
+}
+
+fn path_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn train_name_schema() -> Value {
+    let mut names: Vec<&str> = train_ids();
+    names.extend(EXPRESS_VARIANTS.iter().map(|variant| variant.id));
+    names.extend(TRAIN_GROUPS.iter().map(|group| group.id));
+    json!({ "type": "string", "description": format!("One of {}, in any case", names.join(", ")), "example": "A" })
+}
+
+fn stop_id_param() -> Value {
+    path_param(
+        "stop_id",
+        "A GTFS stop ID such as `A15`, or `A15N`/`A15S` for a single platform, optionally followed by `.ics`",
+        json!({ "type": "string", "example": "A15.ics" }),
+    )
+}
+
+/// The query parameters every calendar endpoint accepts, as parsed by `CalendarParams`.
+fn calendar_params() -> Vec<Value> {
+    vec![
+        query_param(
+            "borough",
+            "Comma-separated boroughs; only alerts affecting a stop in one of them are included",
+            json!({ "type": "string", "example": Borough::ALL.map(|b| b.query_name()).join(",") }),
+        ),
+        query_param(
+            "days",
+            "Only include alerts active in the next this many days, counting today and ending at midnight New York time",
+            json!({ "type": "integer", "minimum": 1, "maximum": MAX_DAYS, "default": DEFAULT_DAYS }),
+        ),
+        query_param(
+            "hours",
+            "Comma-separated daily ranges of New York time; only alerts active during at least one of them are included",
+            json!({ "type": "string", "example": "07:00-10:00,17:00-19:30" }),
+        ),
+        query_param(
+            "when",
+            "Only include alerts active on at least one weekday or weekend day, in New York time",
+            json!({ "type": "string", "enum": DayType::ALL.map(|d| d.query_name()) }),
+        ),
+        query_param(
+            "min_severity",
+            "Only include alerts at least this severe",
+            json!({ "type": "string", "enum": Severity::ALL.map(|s| s.query_name()), "default": Severity::Info.query_name() }),
+        ),
+        query_param(
+            "direction",
+            "Drop alerts that only affect platforms in the other direction",
+            json!({ "type": "string", "enum": Direction::ALL.map(|d| d.query_name()) }),
+        ),
+        query_param(
+            "type",
+            "Only include planned work or real-time alerts",
+            json!({ "type": "string", "enum": AlertKind::ALL.map(|k| k.query_name()).into_iter().chain(["all"]).collect::<Vec<_>>(), "default": "all" }),
+        ),
+        query_param(
+            "lang",
+            "The language of event descriptions and the calendar name",
+            json!({ "type": "string", "enum": Language::ALL.map(|l| l.code()), "default": Language::English.code() }),
+        ),
+        query_param(
+            "placeholder",
+            "With `1`, a calendar that would be empty gets an all-day \"No service alerts\" event for today instead",
+            json!({ "type": "string", "enum": ["0", "1"], "default": "0" }),
+        ),
+        query_param(
+            "digest",
+            "With `weekly`, planned work is rolled up into one all-day event per week listing that week's alerts. Real-time alerts are unchanged",
+            json!({ "type": "string", "enum": Digest::ALL.map(|d| d.query_name()) }),
+        ),
+        query_param(
+            "include_express",
+            "With `0`, the 6, 7 and F calendars leave out alerts that only affect their express variants 6X, 7X and FX",
+            json!({ "type": "string", "enum": ["0", "1"], "default": "1" }),
+        ),
+        query_param(
+            "exclude_overnight",
+            "With `1`, leave out events that start and end within the same night, from 00:00 to 05:00 New York time unless the server is configured otherwise",
+            json!({ "type": "string", "enum": ["0", "1"], "default": "0" }),
+        ),
+        query_param(
+            "all_day_days",
+            "Show events covering at least this many full days in New York as all-day events marked as free time, or `0` to keep every event's times. The server's default is usually 3",
+            json!({ "type": "integer", "minimum": 0, "maximum": 90, "default": 3 }),
+        ),
+        query_param(
+            "limit",
+            "Only include this many of the soonest-starting events, followed by an all-day event saying how many were left out",
+            json!({ "type": "integer", "minimum": 1, "example": 50 }),
+        ),
+        query_param(
+            "alarm",
+            "Remind subscribers of every event this long before it starts, as an ISO 8601 duration from `PT5M` to `PT48H`. Events have no reminders by default",
+            json!({ "type": "string", "format": "duration", "example": "PT2H" }),
+        ),
+        query_param(
+            "plain",
+            "With `1`, leave out the symbol before each event's title, such as ⛔ for no service or 🔧 for planned work",
+            json!({ "type": "string", "enum": ["0", "1"], "default": "0" }),
+        ),
+        query_param(
+            "compact",
+            "With `1`, give events short titles that fit a week view, naming the effect, the stretch of stations and the direction, such as \"F: No service Bergen St–Church Av\". The MTA's full header stays at the start of the description",
+            json!({ "type": "string", "enum": ["0", "1"], "default": "0" }),
+        ),
+        query_param(
+            "tz",
+            "The IANA time zone event times are written in, such as `Europe/London`. `UTC` writes them as UTC times ending in `Z`, for apps that mishandle time zones. All-day events keep New York's dates. Unknown names are rejected with a link to the list of valid ones",
+            json!({ "type": "string", "default": "America/New_York", "example": "UTC" }),
+        ),
+    ]
+}
+
+/// The stations and lines of a commute calendar.
+fn commute_params() -> Vec<Value> {
+    vec![
+        query_param(
+            "from",
+            "The GTFS stop ID of the station the trip starts at, such as `F20`",
+            json!({ "type": "string", "example": "F20" }),
+        ),
+        query_param(
+            "to",
+            "The GTFS stop ID of the station the trip ends at",
+            json!({ "type": "string", "example": "D21" }),
+        ),
+        query_param(
+            "via",
+            "Comma-separated train lines or trunk groups ridden between the two stations",
+            json!({ "type": "string", "example": "F" }),
+        ),
+    ]
+    .into_iter()
+    .map(|mut param| {
+        param["required"] = json!(true);
+        param
+    })
+    .collect()
+}
+
+fn with_calendar_params(param: Value) -> Vec<Value> {
+    let mut parameters = vec![param];
+    parameters.extend(calendar_params());
+    parameters
+}
+
+fn railroad_calendar(railroad: &Railroad) -> Value {
+    json!({
+        "get": {
+            "summary": format!("Alerts for a {} {}", railroad.name, railroad.line_kind),
+            "parameters": with_calendar_params(path_param(
+                "line",
+                &format!("The {}, optionally followed by `.ics`", railroad.line_kind),
+                json!({ "type": "string", "enum": railroad.line_ids() }),
+            )),
+            "responses": calendar_responses(),
+        }
+    })
+}
+
+fn subscribe_redirect(service: &str) -> Value {
+    json!({
+        "get": {
+            "summary": format!("Redirects to {}'s dialog for subscribing to a train's calendar", service),
+            "parameters": [path_param("train_name", "A train line or trunk group", train_name_schema())],
+            "responses": {
+                "302": { "description": format!("Redirect to {}", service) },
+                "400": error_response("Unknown train line"),
+            },
+        }
+    })
+}
+
+fn calendar_responses() -> Value {
+    json!({
+        "200": {
+            "description": "The calendar",
+            "content": { "text/calendar": { "schema": { "type": "string" } } },
+        },
+        "304": { "description": "Not modified since `If-None-Match` or `If-Modified-Since`" },
+        "400": error_response("A path or query parameter is invalid"),
+        "404": error_response("Unknown line, route or branch"),
+        "502": error_response("The MTA feed couldn't be fetched"),
+        "503": error_response("Data needed for the request isn't loaded"),
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+/// An error, as plain text or, for clients that accept JSON, an RFC 7807 problem document.
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "text/plain": { "schema": { "type": "string" } },
+            "application/problem+json": { "schema": problem_schema() },
+        },
+    })
+}
+
+fn problem_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["type", "title", "status", "detail"],
+        "properties": {
+            "type": { "type": "string", "example": "unknown-train-line" },
+            "title": { "type": "string", "example": "Unknown train line" },
+            "status": { "type": "integer", "example": 404 },
+            "detail": { "type": "string", "example": "Unknown train line: SIX. Did you mean 6, SI or S?" },
+            "suggestions": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Close matches for an unknown train line",
+            },
+        },
+    })
+}
+
+fn events_schema() -> Value {
+    let mut indicators: Vec<&str> = Category::ALL.iter().filter_map(|c| c.indicator()).collect();
+    indicators.dedup();
+
+    json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "summary": { "type": "string" },
+                "description": { "type": "string" },
+                "start": { "type": "string", "format": "date-time" },
+                "end": { "type": "string", "format": "date-time", "nullable": true },
+                "routes": { "type": "array", "items": { "type": "string" } },
+                "severity": { "type": "string", "enum": Severity::ALL.map(|s| s.query_name()) },
+                "kind": { "type": "string", "enum": AlertKind::ALL.map(|k| k.query_name()) },
+                "category": { "type": "string", "enum": Category::ALL.map(|c| c.name()) },
+                "indicator": { "type": "string", "enum": indicators },
+                "url": { "type": "string" },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_path_parameters_are_declared() {
+        let spec = openapi_spec();
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let declared: Vec<&str> = item["get"]["parameters"]
+                .as_array()
+                .map(|parameters| {
+                    parameters
+                        .iter()
+                        .filter(|p| p["in"] == "path")
+                        .map(|p| p["name"].as_str().unwrap())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let templated: Vec<&str> = path
+                .split('/')
+                .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+                .collect();
+
+            assert_eq!(declared, templated, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_calendar_query_parameters() {
+        let spec = openapi_spec();
+        let parameters = spec["paths"]["/api/calendars/train/{train_name}"]["get"]["parameters"]
+            .as_array()
+            .unwrap();
+        let names: Vec<&str> = parameters
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "train_name",
+                "borough",
+                "days",
+                "hours",
+                "when",
+                "min_severity",
+                "direction",
+                "type",
+                "lang",
+                "placeholder",
+                "digest",
+                "include_express",
+                "exclude_overnight",
+                "all_day_days",
+                "limit",
+                "alarm",
+                "plain",
+                "compact",
+                "tz",
+                "stations"
+            ]
+        );
+        assert_eq!(parameters[8]["schema"]["enum"], json!(["en", "es"]));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::lines::{LINES, Line};
+use crate::rss::escape_xml;
+
+/// The feed format an OPML export links to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FeedFormat {
+    #[default]
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    pub const ALL: [FeedFormat; 2] = [FeedFormat::Rss, FeedFormat::Atom];
+
+    /// The name used for this format in query parameters and feed URLs, e.g. `?format=atom`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "rss",
+            FeedFormat::Atom => "atom",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<FeedFormat> {
+        FeedFormat::ALL.into_iter().find(|f| f.query_name() == name)
+    }
+}
+
+/// Renders an OPML subscription list of every line's feed, in folders of lines that share a
+/// trunk color, so a feed reader can import them all at once.
+///
+/// `base_url` is where the server is reachable, without a trailing slash.
+pub fn generate_opml(base_url: &str, format: FeedFormat) -> String {
+    let mut opml = String::new();
+
+    opml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opml.push_str("<opml version=\"2.0\">\n");
+    opml.push_str("<head>\n");
+    opml.push_str("<title>MTA Subway Alert Feeds</title>\n");
+    opml.push_str("</head>\n");
+    opml.push_str("<body>\n");
+
+    for trunk in trunks() {
+        let ids: Vec<&str> = trunk.iter().map(|line| line.id).collect();
+        let title = format!("{} Trains", ids.join(", "));
+        opml.push_str(&format!(
+            "<outline text=\"{0}\" title=\"{0}\">\n",
+            escape_xml(&title)
+        ));
+        for line in trunk {
+            opml.push_str(&generate_outline(base_url, line, format));
+        }
+        opml.push_str("</outline>\n");
+    }
+
+    opml.push_str("</body>\n");
+    opml.push_str("</opml>\n");
+
+    opml
+}
+
+/// The lines grouped by color, in the order each color first appears.
+fn trunks() -> Vec<Vec<&'static Line>> {
+    let mut trunks: Vec<Vec<&'static Line>> = Vec::new();
+    for line in LINES {
+        match trunks.iter_mut().find(|trunk| trunk[0].color == line.color) {
+            Some(trunk) => trunk.push(line),
+            None => trunks.push(vec![line]),
+        }
+    }
+
+    trunks
+}
+
+fn generate_outline(base_url: &str, line: &Line, format: FeedFormat) -> String {
+    let title = format!("{} Train - {}", line.id, line.name);
+
+    format!(
+        "<outline type=\"{format}\" text=\"{title}\" title=\"{title}\" xmlUrl=\"{base_url}/api/feeds/train/{id}.{format}\" htmlUrl=\"{base_url}/train/{id}\"/>\n",
+        format = format.query_name(),
+        title = escape_xml(&title),
+        base_url = escape_xml(base_url),
+        id = line.id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_trunks() {
+        let trunks: Vec<String> = trunks()
+            .iter()
+            .map(|trunk| trunk.iter().map(|line| line.id).collect())
+            .collect();
+
+        assert_eq!(
+            trunks,
+            vec![
+                "ACE", "BDFM", "G", "JZ", "L", "NQRW", "123", "456", "7", "SGSFSHSI"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_opml() {
+        let opml = generate_opml("https://example.com", FeedFormat::Atom);
+
+        assert!(
+            opml.starts_with(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<opml version=\"2.0\">\n"
+            )
+        );
+        assert_eq!(opml.matches("xmlUrl=").count(), LINES.len());
+        assert!(opml.contains("<outline text=\"A, C, E Trains\" title=\"A, C, E Trains\">\n"));
+        assert!(opml.contains(concat!(
+            "<outline type=\"atom\" text=\"F Train - Queens Blvd Express/6 Av Local\" ",
+            "title=\"F Train - Queens Blvd Express/6 Av Local\" ",
+            "xmlUrl=\"https://example.com/api/feeds/train/F.atom\" ",
+            "htmlUrl=\"https://example.com/train/F\"/>\n"
+        )));
+    }
+}
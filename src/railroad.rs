@@ -0,0 +1,204 @@
+use crate::calendar::CalendarEvent;
+use crate::network::Network;
+use serde::Serialize;
+
+/// A commuter railroad whose lines have their own calendars, e.g. `/api/calendars/lirr/babylon.ics`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Railroad {
+    pub network: Network,
+    /// The path segment for the railroad's calendars, e.g. `lirr`
+    pub id: &'static str,
+    pub name: &'static str,
+    /// What the railroad calls its lines, for error messages
+    pub line_kind: &'static str,
+    pub lines: &'static [RailLine],
+}
+
+/// A railroad line or branch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RailLine {
+    /// The name used in URLs, e.g. `port-washington`
+    pub id: &'static str,
+    /// The GTFS route ID the railroad's alerts refer to the line by
+    pub route_id: &'static str,
+    pub name: &'static str,
+}
+
+const fn line(id: &'static str, route_id: &'static str, name: &'static str) -> RailLine {
+    RailLine { id, route_id, name }
+}
+
+/// The Long Island Rail Road, with its branches in GTFS route ID order.
+pub const LIRR: Railroad = Railroad {
+    network: Network::Lirr,
+    id: "lirr",
+    name: "LIRR",
+    line_kind: "branch",
+    lines: &[
+        line("babylon", "1", "Babylon Branch"),
+        line("hempstead", "2", "Hempstead Branch"),
+        line("oyster-bay", "3", "Oyster Bay Branch"),
+        line("ronkonkoma", "4", "Ronkonkoma Branch"),
+        line("montauk", "5", "Montauk Branch"),
+        line("long-beach", "6", "Long Beach Branch"),
+        line("far-rockaway", "7", "Far Rockaway Branch"),
+        line("west-hempstead", "8", "West Hempstead Branch"),
+        line("port-washington", "9", "Port Washington Branch"),
+        line("port-jefferson", "10", "Port Jefferson Branch"),
+        line("belmont-park", "11", "Belmont Park"),
+        line("city-terminal-zone", "12", "City Terminal Zone"),
+        line("greenport", "13", "Greenport Service"),
+    ],
+};
+
+/// Metro-North Railroad's east-of-Hudson lines and New Haven branches, in GTFS route ID order.
+pub const MNR: Railroad = Railroad {
+    network: Network::Mnr,
+    id: "mnr",
+    name: "Metro-North",
+    line_kind: "line",
+    lines: &[
+        line("hudson", "1", "Hudson Line"),
+        line("harlem", "2", "Harlem Line"),
+        line("new-haven", "3", "New Haven Line"),
+        line("new-canaan", "4", "New Canaan Branch"),
+        line("danbury", "5", "Danbury Branch"),
+        line("waterbury", "6", "Waterbury Branch"),
+    ],
+};
+
+/// PATH's four weekday services, named after their terminals.
+pub const PATH: Railroad = Railroad {
+    network: Network::Path,
+    id: "path",
+    name: "PATH",
+    line_kind: "route",
+    lines: &[
+        line("nwk-wtc", "862", "Newark - World Trade Center"),
+        line("jsq-33", "861", "Journal Square - 33rd Street"),
+        line("hob-33", "859", "Hoboken - 33rd Street"),
+        line("hob-wtc", "860", "Hoboken - World Trade Center"),
+    ],
+};
+
+pub const RAILROADS: &[Railroad] = &[LIRR, MNR, PATH];
+
+impl Railroad {
+    /// Finds a line by its URL name, ignoring case, so `Babylon` and `babylon` both work.
+    pub fn find_line(&self, id: &str) -> Option<&'static RailLine> {
+        let id = id.trim();
+        self.lines
+            .iter()
+            .find(|line| line.id.eq_ignore_ascii_case(id))
+    }
+
+    /// The URL names of every line, in display order.
+    pub fn line_ids(&self) -> Vec<&'static str> {
+        self.lines.iter().map(|line| line.id).collect()
+    }
+
+    /// Copies `events`, titling each one after its lines' names rather than their numeric
+    /// route IDs, e.g. `Hudson Line: Planned Work` instead of `1: Planned Work`.
+    pub fn with_line_names(&self, events: &[CalendarEvent]) -> Vec<CalendarEvent> {
+        events
+            .iter()
+            .map(|event| {
+                let names: Vec<&str> = event
+                    .routes
+                    .iter()
+                    .map(|route_id| {
+                        self.lines
+                            .iter()
+                            .find(|line| line.route_id == route_id)
+                            .map_or(route_id.as_str(), |line| line.name)
+                    })
+                    .collect();
+                let names = if names.is_empty() {
+                    self.name.to_string()
+                } else {
+                    names.join(", ")
+                };
+
+                CalendarEvent {
+                    summary: format!("{}: {}", names, event.alert_type),
+                    ..event.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_find_line() {
+        assert_eq!(LIRR.find_line("babylon").map(|b| b.route_id), Some("1"));
+        assert_eq!(
+            LIRR.find_line("Port-Washington").map(|b| b.route_id),
+            Some("9")
+        );
+        assert_eq!(LIRR.find_line("1"), None);
+        assert_eq!(LIRR.find_line("port washington"), None);
+        assert_eq!(MNR.find_line("New-Haven").map(|l| l.route_id), Some("3"));
+        assert_eq!(MNR.find_line("babylon"), None);
+        assert_eq!(PATH.find_line("NWK-WTC").map(|l| l.route_id), Some("862"));
+    }
+
+    #[test]
+    fn test_mnr_fixture_events() {
+        use crate::proto::gtfs_realtime::FeedMessage;
+
+        let text = std::fs::read_to_string("tests/fixtures/mnr_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = MNR.with_line_names(&crate::calendar::proto_feed_to_events(&feed));
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].routes, vec!["1"]);
+        assert_eq!(events[0].summary, "Hudson Line: Alert");
+        assert_eq!(
+            events[1].summary,
+            "New Haven Line, New Canaan Branch: Alert"
+        );
+    }
+
+    #[test]
+    fn test_path_fixture_events() {
+        use crate::proto::gtfs_realtime::FeedMessage;
+        use crate::severity::Severity;
+
+        let text = std::fs::read_to_string("tests/fixtures/path_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let events = PATH.with_line_names(&crate::calendar::proto_feed_to_events(&feed));
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].summary,
+            "Newark - World Trade Center: Significant Delays"
+        );
+        assert_eq!(events[0].severity, Severity::Severe);
+        assert_eq!(
+            events[0].description,
+            "NWK-WTC trains are delayed due to a signal problem at Harrison"
+        );
+        assert_eq!(
+            events[1].summary,
+            "Hoboken - 33rd Street, Hoboken - World Trade Center: Alert"
+        );
+        assert_eq!(events[1].severity, Severity::Info);
+    }
+
+    #[test]
+    fn test_line_ids_are_unique() {
+        for railroad in RAILROADS {
+            for (i, line) in railroad.lines.iter().enumerate() {
+                for other in &railroad.lines[i + 1..] {
+                    assert!(line.id != other.id, "{}", line.id);
+                    assert!(line.route_id != other.route_id, "{}", line.id);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use crate::calendar::CalendarEvent;
+use crate::filter::format_duration;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::BTreeSet;
+
+/// The fewest periods of an alert that are written as a recurring event rather than one event
+/// each.
+const MIN_OCCURRENCES: usize = 3;
+
+/// How an event repeats on the days after it first happens, at the same local time and for the
+/// same length of time, as an iCalendar `RRULE` with `EXDATE`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    /// The days of the week it repeats on, every day when all seven are listed
+    pub weekdays: Vec<Weekday>,
+    /// The start of the last occurrence
+    pub until: DateTime<Utc>,
+    /// The starts of the occurrences on those days that are skipped
+    pub exdates: Vec<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// The `RRULE` value, e.g. `FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;UNTIL=20250322T014500Z`.
+    pub fn rule(&self) -> String {
+        let until = self.until.format("%Y%m%dT%H%M%SZ");
+        if self.weekdays.len() == 7 {
+            return format!("FREQ=DAILY;UNTIL={}", until);
+        }
+
+        let days: Vec<&str> = self.weekdays.iter().map(|day| byday(*day)).collect();
+        format!("FREQ=WEEKLY;BYDAY={};UNTIL={}", days.join(","), until)
+    }
+}
+
+fn byday(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// Collapses the periods of each alert that repeat at the same time in `timezone`, such as work
+/// every weeknight from 21:45 to 05:00 in New York, into one event that recurs. The calendar has
+/// to write the event's times in `timezone` too, since that's where the rule repeats.
+///
+/// Each series is written in place of its first period, with a UID of its own from
+/// [`series_uid`] and its alert's `series_sequence`, so it stays the same event as its past
+/// periods drop out and is updated when the MTA adds, edits or cancels one. Periods that don't
+/// fit the pattern, such as the night the clocks change, are kept as events of their own, and
+/// an alert with fewer than three periods that fit is left alone.
+pub fn collapse(
+    events: &[CalendarEvent],
+    timezone: Tz,
+) -> Vec<(CalendarEvent, Option<Recurrence>)> {
+    let mut written = vec![false; events.len()];
+    let mut collapsed = Vec::with_capacity(events.len());
+
+    for (i, event) in events.iter().enumerate() {
+        if written[i] {
+            continue;
+        }
+
+        let series: Vec<usize> = (i..events.len())
+            .filter(|&j| !written[j] && same_series(event, &events[j], timezone))
+            .collect();
+        match recurrence(events, &series, timezone) {
+            Some(recurrence) => {
+                for &j in &series {
+                    written[j] = true;
+                }
+                collapsed.push((
+                    CalendarEvent {
+                        uid: series_uid(event, timezone),
+                        sequence: event.series_sequence,
+                        ..event.clone()
+                    },
+                    Some(recurrence),
+                ));
+            }
+            None => {
+                written[i] = true;
+                collapsed.push((event.clone(), None));
+            }
+        }
+    }
+
+    collapsed
+}
+
+/// The UID of a repeating event, from its alert's ID and the local time and length it repeats
+/// at, e.g. `mta-alert-lmm:planned_work:50001-T214500/PT7H15M`.
+///
+/// Unlike the UID of the period it starts with, it doesn't change as that period passes and the
+/// next one comes first. An alert's periods at the same time and of the same length make one
+/// series, and the `/`, which MTA alert IDs don't contain, keeps it apart from a period's UID.
+fn series_uid(event: &CalendarEvent, timezone: Tz) -> String {
+    let length = event.end.map_or(Duration::zero(), |end| end - event.start);
+    format!(
+        "mta-alert-{}-{}/{}",
+        event.mta_alert_id,
+        local_time(event.start, timezone).format("T%H%M%S"),
+        format_duration(length)
+    )
+}
+
+/// Whether `other` is a period of the same alert as `event` that starts at the same time in
+/// `timezone` and lasts exactly as long.
+fn same_series(event: &CalendarEvent, other: &CalendarEvent, timezone: Tz) -> bool {
+    !event.mta_alert_id.is_empty()
+        && !event.all_day
+        && !other.all_day
+        && event.mta_alert_id == other.mta_alert_id
+        && event.summary == other.summary
+        && event.description == other.description
+        && event.end.is_some()
+        && other.end.map(|end| end - other.start) == event.end.map(|end| end - event.start)
+        && local_time(other.start, timezone) == local_time(event.start, timezone)
+}
+
+fn local_time(time: DateTime<Utc>, timezone: Tz) -> NaiveTime {
+    time.with_timezone(&timezone).time()
+}
+
+fn local_date(time: DateTime<Utc>, timezone: Tz) -> NaiveDate {
+    time.with_timezone(&timezone).date_naive()
+}
+
+/// The recurrence covering exactly the periods `series` of `events`, if they're regular enough
+/// to be worth one: at least [`MIN_OCCURRENCES`] of them, on different days, with fewer days
+/// skipped than there are periods.
+fn recurrence(events: &[CalendarEvent], series: &[usize], timezone: Tz) -> Option<Recurrence> {
+    if series.len() < MIN_OCCURRENCES {
+        return None;
+    }
+
+    let starts: BTreeSet<DateTime<Utc>> = series.iter().map(|&j| events[j].start).collect();
+    let dates: BTreeSet<NaiveDate> = starts
+        .iter()
+        .map(|start| local_date(*start, timezone))
+        .collect();
+    if dates.len() < series.len() {
+        return None;
+    }
+
+    // The first period is the one the event is written as, so it has to be the earliest
+    let first = *starts.first()?;
+    if first != events[series[0]].start {
+        return None;
+    }
+    let until = *starts.last()?;
+    let time = local_time(first, timezone);
+
+    let mut weekdays: Vec<Weekday> = dates.iter().map(|date| date.weekday()).collect();
+    weekdays.sort_by_key(|day| day.num_days_from_monday());
+    weekdays.dedup();
+
+    let mut exdates = Vec::new();
+    for date in local_date(first, timezone)
+        .iter_days()
+        .take_while(|date| *date <= local_date(until, timezone))
+    {
+        if !weekdays.contains(&date.weekday()) || dates.contains(&date) {
+            continue;
+        }
+        // A skipped day whose time doesn't exist, in the hour the clocks go forward, can't be
+        // excluded reliably
+        let skipped = timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()?;
+        exdates.push(skipped.with_timezone(&Utc));
+    }
+    if exdates.len() >= series.len() {
+        return None;
+    }
+
+    Some(Recurrence {
+        weekdays,
+        until,
+        exdates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::proto_feed_to_events;
+    use crate::proto::gtfs_realtime::FeedMessage;
+    use chrono_tz::America::New_York;
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    fn fixture_events() -> Vec<CalendarEvent> {
+        let text = std::fs::read_to_string("tests/fixtures/recurring_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        proto_feed_to_events(&feed)
+    }
+
+    fn collapsed_alert(
+        collapsed: &[(CalendarEvent, Option<Recurrence>)],
+        alert_id: &str,
+    ) -> Vec<(CalendarEvent, Option<Recurrence>)> {
+        collapsed
+            .iter()
+            .filter(|(event, _)| event.mta_alert_id == alert_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Expands a recurrence the way a calendar app would, to the starts of its occurrences.
+    fn occurrences(
+        first: DateTime<Utc>,
+        recurrence: &Recurrence,
+        timezone: Tz,
+    ) -> Vec<DateTime<Utc>> {
+        let time = local_time(first, timezone);
+        local_date(first, timezone)
+            .iter_days()
+            .map(|date| {
+                timezone
+                    .from_local_datetime(&date.and_time(time))
+                    .earliest()
+                    .unwrap()
+                    .with_timezone(&Utc)
+            })
+            .take_while(|start| *start <= recurrence.until)
+            .filter(|start| {
+                recurrence
+                    .weekdays
+                    .contains(&local_date(*start, timezone).weekday())
+            })
+            .filter(|start| !recurrence.exdates.contains(start))
+            .collect()
+    }
+
+    fn starts(events: &[CalendarEvent], alert_id: &str) -> Vec<DateTime<Utc>> {
+        events
+            .iter()
+            .filter(|event| event.mta_alert_id == alert_id)
+            .map(|event| event.start)
+            .collect()
+    }
+
+    #[test]
+    fn test_nightly_work() {
+        let events = fixture_events();
+        let collapsed = collapse(&events, New_York);
+
+        // Every weeknight from Monday March 3 to Friday March 21, except Wednesday March 12
+        let nightly = collapsed_alert(&collapsed, "lmm:planned_work:50001");
+        assert_eq!(nightly.len(), 1);
+        let (event, recurrence) = &nightly[0];
+        let recurrence = recurrence.as_ref().unwrap();
+        assert_eq!(
+            event.uid,
+            "mta-alert-lmm:planned_work:50001-T214500/PT7H15M"
+        );
+        assert_eq!(
+            recurrence.rule(),
+            "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR;UNTIL=20250322T014500Z"
+        );
+        assert_eq!(
+            recurrence.exdates,
+            vec![Utc.with_ymd_and_hms(2025, 3, 13, 1, 45, 0).unwrap()]
+        );
+        assert_eq!(
+            occurrences(event.start, recurrence, New_York),
+            starts(&events, "lmm:planned_work:50001")
+        );
+    }
+
+    #[test]
+    fn test_weekend_work() {
+        let events = fixture_events();
+        let collapsed = collapse(&events, New_York);
+
+        let weekends = collapsed_alert(&collapsed, "lmm:planned_work:50002");
+        assert_eq!(weekends.len(), 1);
+        let (event, recurrence) = &weekends[0];
+        let recurrence = recurrence.as_ref().unwrap();
+        assert_eq!(
+            recurrence.rule(),
+            "FREQ=WEEKLY;BYDAY=SA,SU;UNTIL=20250323T120000Z"
+        );
+        assert!(recurrence.exdates.is_empty());
+        assert_eq!(
+            occurrences(event.start, recurrence, New_York),
+            starts(&events, "lmm:planned_work:50002")
+        );
+    }
+
+    #[test]
+    fn test_irregular_work() {
+        let events = fixture_events();
+        let collapsed = collapse(&events, New_York);
+
+        // Three periods at different times stay as they are
+        let irregular = collapsed_alert(&collapsed, "lmm:planned_work:50003");
+        assert_eq!(irregular.len(), 3);
+        assert!(irregular.iter().all(|(_, recurrence)| recurrence.is_none()));
+
+        // Every night across the change to daylight saving time: the night the clocks go
+        // forward is an hour shorter, so it's written on its own and left out of the series
+        let daily = collapsed_alert(&collapsed, "lmm:planned_work:50004");
+        assert_eq!(daily.len(), 2);
+        let (event, recurrence) = &daily[0];
+        let recurrence = recurrence.as_ref().unwrap();
+        assert_eq!(recurrence.rule(), "FREQ=DAILY;UNTIL=20250316T030000Z");
+        assert_eq!(recurrence.exdates.len(), 1);
+        let (short_night, none) = &daily[1];
+        assert_eq!(none, &None);
+        assert_eq!(recurrence.exdates[0], short_night.start);
+
+        let mut expanded = occurrences(event.start, recurrence, New_York);
+        expanded.push(short_night.start);
+        expanded.sort();
+        assert_eq!(expanded, starts(&events, "lmm:planned_work:50004"));
+    }
+
+    #[test]
+    fn test_golden_feed_expands_to_every_period() {
+        use protobuf::Message;
+
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        let events = proto_feed_to_events(&FeedMessage::parse_from_bytes(&pb_bytes).unwrap());
+        let collapsed = collapse(&events, New_York);
+        assert!(collapsed.len() < events.len());
+
+        let mut expanded: Vec<(String, DateTime<Utc>, Option<DateTime<Utc>>)> = collapsed
+            .iter()
+            .flat_map(|(event, recurrence)| {
+                let starts = match recurrence {
+                    Some(recurrence) => occurrences(event.start, recurrence, New_York),
+                    None => vec![event.start],
+                };
+                let length = event.end.map(|end| end - event.start);
+                starts.into_iter().map(move |start| {
+                    (
+                        event.mta_alert_id.clone(),
+                        start,
+                        length.map(|length| start + length),
+                    )
+                })
+            })
+            .collect();
+        let mut original: Vec<(String, DateTime<Utc>, Option<DateTime<Utc>>)> = events
+            .iter()
+            .map(|event| (event.mta_alert_id.clone(), event.start, event.end))
+            .collect();
+        expanded.sort();
+        original.sort();
+        assert_eq!(expanded, original);
+    }
+
+    #[test]
+    fn test_series_uid_and_sequence() {
+        let mut events = fixture_events();
+        for event in &mut events {
+            event.series_sequence = 4;
+        }
+        let nightly = collapsed_alert(&collapse(&events, New_York), "lmm:planned_work:50001");
+        let (event, _) = &nightly[0];
+        assert_eq!(event.sequence, 4);
+
+        // The series is the same event once its first night has passed and left the calendar
+        let first = events
+            .iter()
+            .position(|event| event.mta_alert_id == "lmm:planned_work:50001")
+            .unwrap();
+        events.remove(first);
+        let later = collapsed_alert(&collapse(&events, New_York), "lmm:planned_work:50001");
+        let (later, _) = &later[0];
+        assert_eq!(later.uid, event.uid);
+        assert_ne!(later.start, event.start);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::calendar::CalendarEvent;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+/// How long the revisions of an event that's left the feed are kept, so one that comes back, as
+/// alerts do after a fetch that missed them, carries on from the `SEQUENCE` calendar apps have.
+pub const DEPARTED_TTL: Duration = Duration::days(7);
+
+/// Numbers the edits the MTA makes to each alert event, for the iCalendar `SEQUENCE` that tells
+/// calendar apps to replace the copy they have.
+///
+/// Revisions are only kept in memory, so they start again from 0 when the server restarts.
+#[derive(Debug, Default)]
+pub struct Revisions {
+    /// The last revisions of each feed's events, by feed URL
+    feeds: Mutex<HashMap<String, FeedRevisions>>,
+}
+
+#[derive(Debug, Default)]
+struct FeedRevisions {
+    /// The last revision of each event by UID
+    events: HashMap<String, Revision>,
+    /// The last revision of each alert's periods as a whole by alert ID, for the repeating
+    /// event they can be written as
+    series: HashMap<String, Revision>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Revision {
+    content_hash: u64,
+    sequence: u32,
+    /// When the event was last in the feed
+    last_seen: DateTime<Utc>,
+}
+
+impl Revisions {
+    /// Sets the `sequence` of a freshly fetched `feed`'s events: 0 the first time an event is
+    /// seen, and one more than last time whenever its summary, description, translations or
+    /// times changed. Their `series_sequence` goes up the same way whenever any period of their
+    /// alert changed, or one was added or dropped.
+    ///
+    /// Events that have left the feed are remembered until they've been gone [`DEPARTED_TTL`],
+    /// so their sequence never goes down if they come back before then.
+    pub fn apply(&self, feed: &str, events: &mut [CalendarEvent], now: DateTime<Utc>) {
+        // Feeds occasionally repeat an alert, so events sharing a UID are hashed together
+        let mut hashers: HashMap<&str, DefaultHasher> = HashMap::new();
+        for event in events.iter() {
+            let hasher = hashers.entry(&event.uid).or_default();
+            event.summary.hash(hasher);
+            event.description.hash(hasher);
+            event.translations.hash(hasher);
+            event.start.hash(hasher);
+            event.end.hash(hasher);
+            event.all_day.hash(hasher);
+        }
+        let content_hashes: HashMap<&str, u64> = hashers
+            .into_iter()
+            .map(|(uid, hasher)| (uid, hasher.finish()))
+            .collect();
+
+        // An alert's periods are hashed in UID order, whatever order the feed lists them in
+        let mut periods: HashMap<&str, BTreeMap<&str, u64>> = HashMap::new();
+        for event in events.iter() {
+            periods
+                .entry(&event.mta_alert_id)
+                .or_default()
+                .insert(&event.uid, content_hashes[event.uid.as_str()]);
+        }
+        let series_hashes = periods.into_iter().map(|(alert_id, periods)| {
+            let mut hasher = DefaultHasher::new();
+            periods.hash(&mut hasher);
+            (alert_id, hasher.finish())
+        });
+
+        let mut feeds = self.feeds.lock().unwrap();
+        let previous = feeds.remove(feed).unwrap_or_default();
+        let current = FeedRevisions {
+            events: next_revisions(previous.events, content_hashes, now),
+            series: next_revisions(previous.series, series_hashes, now),
+        };
+
+        for event in events.iter_mut() {
+            event.sequence = current.events[&event.uid].sequence;
+            event.series_sequence = current.series[&event.mta_alert_id].sequence;
+        }
+        feeds.insert(feed.to_string(), current);
+    }
+}
+
+/// The revision of each key given its current content hash: the same as in `previous` when the
+/// hash is unchanged, the next one when it changed, and 0 for keys that are new. Keys that are
+/// only in `previous` are kept until they've been gone [`DEPARTED_TTL`].
+fn next_revisions<'a>(
+    previous: HashMap<String, Revision>,
+    content_hashes: impl IntoIterator<Item = (&'a str, u64)>,
+    now: DateTime<Utc>,
+) -> HashMap<String, Revision> {
+    let mut revisions: HashMap<String, Revision> = previous
+        .into_iter()
+        .filter(|(_, revision)| now - revision.last_seen < DEPARTED_TTL)
+        .collect();
+
+    for (key, content_hash) in content_hashes {
+        let sequence = match revisions.get(key) {
+            Some(revision) if revision.content_hash == content_hash => revision.sequence,
+            Some(revision) => revision.sequence + 1,
+            None => 0,
+        };
+        let revision = Revision {
+            content_hash,
+            sequence,
+            last_seen: now,
+        };
+        revisions.insert(key.to_string(), revision);
+    }
+    revisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::{Language, proto_feed_to_events};
+    use crate::proto::gtfs_realtime::FeedMessage;
+    use pretty_assertions::assert_eq;
+    use protobuf::Message;
+
+    const FEED: &str = "https://example.com/subway";
+
+    fn golden_events() -> Vec<CalendarEvent> {
+        let pb_bytes = std::fs::read("tests/golden-2025-12-15.pb").unwrap();
+        proto_feed_to_events(&FeedMessage::parse_from_bytes(&pb_bytes).unwrap())
+    }
+
+    fn now() -> DateTime<Utc> {
+        use chrono::TimeZone;
+
+        Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap()
+    }
+
+    fn sequence(events: &[CalendarEvent], uid: &str) -> u32 {
+        events
+            .iter()
+            .find(|event| event.uid == uid)
+            .unwrap()
+            .sequence
+    }
+
+    #[test]
+    fn test_edited_alert_gets_next_sequence() {
+        let revisions = Revisions::default();
+        let uid = "mta-alert-lmm:alert:491468";
+
+        let mut events = golden_events();
+        revisions.apply(FEED, &mut events, now());
+        assert!(events.iter().all(|event| event.sequence == 0));
+
+        // Refetching an unchanged feed keeps every sequence
+        let mut events = golden_events();
+        revisions.apply(FEED, &mut events, now());
+        assert!(events.iter().all(|event| event.sequence == 0));
+
+        // The MTA pushes back the end of one alert, then rewords it
+        let mut edited = golden_events();
+        let event = edited.iter_mut().find(|event| event.uid == uid).unwrap();
+        event.end = event.end.map(|end| end + chrono::Duration::minutes(30));
+        revisions.apply(FEED, &mut edited, now());
+        assert_eq!(sequence(&edited, uid), 1);
+        assert!(
+            edited
+                .iter()
+                .filter(|event| event.uid != uid)
+                .all(|event| event.sequence == 0)
+        );
+
+        let mut reworded = edited.clone();
+        let event = reworded.iter_mut().find(|event| event.uid == uid).unwrap();
+        event.description.push_str(" Expect delays.");
+        revisions.apply(FEED, &mut reworded, now());
+        assert_eq!(sequence(&reworded, uid), 2);
+
+        revisions.apply(FEED, &mut reworded, now());
+        assert_eq!(sequence(&reworded, uid), 2);
+
+        // So does translating it
+        let mut translated = reworded.clone();
+        let event = translated
+            .iter_mut()
+            .find(|event| event.uid == uid)
+            .unwrap();
+        event.translations.insert(
+            Language::Spanish,
+            "Los trenes A circulan con demoras.".to_string(),
+        );
+        revisions.apply(FEED, &mut translated, now());
+        assert_eq!(sequence(&translated, uid), 3);
+    }
+
+    #[test]
+    fn test_series_sequence() {
+        let revisions = Revisions::default();
+        let alert_id = "lmm:planned_work:19872";
+        let series_sequence = |events: &[CalendarEvent]| {
+            let event = events.iter().find(|event| event.mta_alert_id == alert_id);
+            event.unwrap().series_sequence
+        };
+
+        let mut events = golden_events();
+        revisions.apply(FEED, &mut events, now());
+        assert_eq!(series_sequence(&events), 0);
+
+        // The MTA cancels one night of the work: the nights left are unchanged, but the series
+        // they're written as isn't
+        let cancelled = events
+            .iter()
+            .rposition(|event| event.mta_alert_id == alert_id)
+            .unwrap();
+        events.remove(cancelled);
+        revisions.apply(FEED, &mut events, now());
+        assert_eq!(series_sequence(&events), 1);
+        assert!(events.iter().all(|event| event.sequence == 0));
+        assert!(
+            events
+                .iter()
+                .filter(|event| event.mta_alert_id != alert_id)
+                .all(|event| event.series_sequence == 0)
+        );
+    }
+
+    #[test]
+    fn test_repeated_alerts_and_feeds() {
+        let revisions = Revisions::default();
+
+        // The golden feed repeats some elevator outages with different periods under one UID
+        let uid = "mta-alert-235N#EL301";
+        let mut events = golden_events();
+        assert!(events.iter().filter(|event| event.uid == uid).count() > 1);
+        revisions.apply(FEED, &mut events, now());
+        revisions.apply(FEED, &mut events, now());
+        assert_eq!(sequence(&events, uid), 0);
+
+        // Each feed's events are numbered on their own
+        let mut other = vec![CalendarEvent::for_test(uid, &["LIRR"])];
+        revisions.apply("https://example.com/lirr", &mut other, now());
+        revisions.apply(FEED, &mut events, now());
+        assert_eq!(other[0].sequence, 0);
+        assert_eq!(sequence(&events, uid), 0);
+
+        // An alert missing from a fetch, as when the MTA publishes an empty feed, carries on
+        // from its last sequence when it comes back, rather than going back to 0
+        let mut edited = golden_events();
+        edited[0].summary.push('!');
+        revisions.apply(FEED, &mut edited, now());
+        assert_eq!(edited[0].sequence, 1);
+        revisions.apply(FEED, &mut [], now() + Duration::minutes(1));
+        revisions.apply(FEED, &mut edited, now() + Duration::minutes(2));
+        assert_eq!(edited[0].sequence, 1);
+        edited[0].summary.push('!');
+        revisions.apply(FEED, &mut edited, now() + Duration::minutes(3));
+        assert_eq!(edited[0].sequence, 2);
+
+        // It's only forgotten once it's been gone for a week
+        let gone = now() + Duration::minutes(4);
+        revisions.apply(FEED, &mut [], gone);
+        revisions.apply(FEED, &mut edited, gone + Duration::days(6));
+        assert_eq!(edited[0].sequence, 2);
+        let gone = gone + Duration::days(6) + Duration::minutes(1);
+        revisions.apply(FEED, &mut [], gone);
+        revisions.apply(FEED, &mut edited, gone + DEPARTED_TTL);
+        assert_eq!(edited[0].sequence, 0);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::calendar::CalendarEvent;
+
+pub fn generate_rss(
+    events: &[CalendarEvent],
+    title: &str,
+    description: &str,
+    link: &str,
+) -> String {
+    let mut rss = String::new();
+
+    rss.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    rss.push_str("<rss version=\"2.0\">\n");
+    rss.push_str("<channel>\n");
+    rss.push_str(&format!("<title>{}</title>\n", escape_xml(title)));
+    rss.push_str(&format!("<link>{}</link>\n", escape_xml(link)));
+    rss.push_str(&format!(
+        "<description>{}</description>\n",
+        escape_xml(description)
+    ));
+
+    for event in events {
+        rss.push_str(&generate_item(event));
+    }
+
+    rss.push_str("</channel>\n");
+    rss.push_str("</rss>\n");
+
+    rss
+}
+
+fn generate_item(event: &CalendarEvent) -> String {
+    let mut item = String::new();
+
+    item.push_str("<item>\n");
+    item.push_str(&format!("<title>{}</title>\n", escape_xml(&event.summary)));
+    if !event.description.is_empty() {
+        item.push_str(&format!(
+            "<description>{}</description>\n",
+            escape_xml(&event.description)
+        ));
+    }
+    item.push_str(&format!(
+        "<pubDate>{}</pubDate>\n",
+        event.start.to_rfc2822()
+    ));
+    item.push_str(&format!(
+        "<guid isPermaLink=\"false\">{}</guid>\n",
+        escape_xml(&event.uid)
+    ));
+    item.push_str(&format!(
+        "<category>{}</category>\n",
+        escape_xml(&event.alert_type)
+    ));
+    item.push_str("</item>\n");
+
+    item
+}
+
+/// Escapes text for XML, and so for HTML too.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("<b>A & C</b> \"trains\""),
+            "&lt;b&gt;A &amp; C&lt;/b&gt; &quot;trains&quot;"
+        );
+    }
+
+    #[test]
+    fn test_generate_rss_basic() {
+        let events = vec![CalendarEvent {
+            summary: "F: Delays".to_string(),
+            description: "Trains are delayed & crowded".to_string(),
+            ..CalendarEvent::for_test("mta-alert-lmm:alert:1", &["F"])
+        }];
+
+        let rss = generate_rss(
+            &events,
+            "MTA F Train Alerts",
+            "Alerts for the F train",
+            "http://localhost:3000/",
+        );
+
+        assert!(
+            rss.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\">")
+        );
+        assert!(rss.contains("<title>MTA F Train Alerts</title>"));
+        assert!(rss.contains("<title>F: Delays</title>"));
+        assert!(rss.contains("<description>Trains are delayed &amp; crowded</description>"));
+        assert!(rss.contains("<pubDate>Mon, 15 Dec 2025 10:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<guid isPermaLink=\"false\">mta-alert-lmm:alert:1</guid>"));
+        assert!(rss.ends_with("</channel>\n</rss>\n"));
+    }
+}
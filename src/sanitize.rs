@@ -0,0 +1,373 @@
+/// The tags kept by [`clean_html`]. Everything else is dropped, keeping the text inside.
+const ALLOWED_TAGS: [&str; 10] = ["p", "br", "ul", "ol", "li", "b", "strong", "i", "em", "a"];
+
+/// Tags whose content isn't text to show, and is dropped along with them.
+const SKIPPED_TAGS: [&str; 2] = ["script", "style"];
+
+/// Converts the HTML in MTA alert text to plain text: `<br>` and paragraphs become line breaks,
+/// list items become "• " bullets, entities like `&amp;` are unescaped, and links are listed
+/// after the text as "More info: URL" lines. Other markup is dropped.
+///
+/// Lines are trimmed and blank lines removed, so text without markup only loses its extra
+/// whitespace.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut links: Vec<String> = Vec::new();
+    let mut skipping: Option<String> = None;
+
+    for token in tokenize(html) {
+        match token {
+            Token::Text(raw) => {
+                if skipping.is_none() {
+                    text.push_str(&unescape(raw));
+                }
+            }
+            Token::Tag(tag) => {
+                if let Some(skipped) = &skipping {
+                    if tag.closing && tag.name == *skipped {
+                        skipping = None;
+                    }
+                    continue;
+                }
+                match tag.name.as_str() {
+                    name if SKIPPED_TAGS.contains(&name) && !tag.closing => {
+                        skipping = Some(tag.name.clone());
+                    }
+                    "li" if !tag.closing => text.push_str("\n• "),
+                    // A paragraph inside a list item stays on the bullet's line
+                    "br" | "p" | "div" | "ul" | "ol" | "li" | "tr" | "h1" | "h2" | "h3" | "h4"
+                        if !text.ends_with("• ") =>
+                    {
+                        text.push('\n')
+                    }
+                    "a" => {
+                        if let Some(href) = tag.link()
+                            && !links.contains(&href)
+                        {
+                            links.push(href);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    for link in links {
+        text.push_str("\nMore info: ");
+        text.push_str(&link);
+    }
+
+    text.split('\n')
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cleans the HTML in MTA alert text for calendar apps that show it, such as in
+/// `X-ALT-DESC`: only paragraphs, line breaks, lists, bold and italic text and web links are
+/// kept, without their attributes other than a link's `href`, and text is re-escaped.
+pub fn clean_html(html: &str) -> String {
+    let mut cleaned = String::with_capacity(html.len());
+    let mut skipping: Option<String> = None;
+
+    for token in tokenize(html) {
+        match token {
+            Token::Text(raw) => {
+                if skipping.is_none() {
+                    cleaned.push_str(&escape_html(&unescape(raw)));
+                }
+            }
+            Token::Tag(tag) => {
+                if let Some(skipped) = &skipping {
+                    if tag.closing && tag.name == *skipped {
+                        skipping = None;
+                    }
+                    continue;
+                }
+                let name = tag.name.as_str();
+                if SKIPPED_TAGS.contains(&name) && !tag.closing {
+                    skipping = Some(tag.name.clone());
+                } else if !ALLOWED_TAGS.contains(&name) || (tag.closing && name == "br") {
+                    continue;
+                } else if tag.closing {
+                    cleaned.push_str(&format!("</{}>", name));
+                } else if name == "a" {
+                    match tag.link() {
+                        Some(href) => {
+                            cleaned.push_str(&format!("<a href=\"{}\">", escape_html(&href)))
+                        }
+                        None => cleaned.push_str("<a>"),
+                    }
+                } else {
+                    cleaned.push_str(&format!("<{}>", name));
+                }
+            }
+        }
+    }
+
+    // The MTA spaces paragraphs out with empty ones
+    cleaned.replace("<p></p>", "").trim().to_string()
+}
+
+/// Escapes text for HTML content and quoted attribute values.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Tag(Tag),
+}
+
+struct Tag {
+    /// The lowercased tag name, without the `/` of a closing tag
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    /// The tag's `href`, if it's a web or email link.
+    fn link(&self) -> Option<String> {
+        let href = self
+            .attributes
+            .iter()
+            .find(|(name, _)| name == "href")
+            .map(|(_, value)| unescape(value.trim()))?;
+        let lower = href.to_ascii_lowercase();
+        ["https://", "http://", "mailto:"]
+            .iter()
+            .any(|scheme| lower.starts_with(scheme))
+            .then_some(href)
+    }
+}
+
+/// Splits HTML into text and tags. A `<` that doesn't start a tag, as in "< 5 minutes", is
+/// text, and comments are dropped.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        if let Some(comment) = after.strip_prefix("!--") {
+            if open > 0 {
+                tokens.push(Token::Text(&rest[..open]));
+            }
+            rest = comment
+                .find("-->")
+                .map_or("", |close| &comment[close + 3..]);
+            continue;
+        }
+
+        let starts_tag = after
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        let close = after.find('>');
+        match close {
+            Some(close) if starts_tag => {
+                if open > 0 {
+                    tokens.push(Token::Text(&rest[..open]));
+                }
+                if let Some(tag) = parse_tag(&after[..close]) {
+                    tokens.push(Token::Tag(tag));
+                }
+                rest = &after[close + 1..];
+            }
+            _ => {
+                tokens.push(Token::Text(&rest[..open + 1]));
+                rest = after;
+            }
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+
+    tokens
+}
+
+/// Parses the inside of a tag, e.g. `a href="https://mta.info" target=_blank`. Declarations
+/// like `!DOCTYPE html` aren't tags.
+fn parse_tag(inner: &str) -> Option<Tag> {
+    let inner = inner.trim();
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner.trim_start()),
+        None => (false, inner),
+    };
+    let name_end = inner
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or(inner.len());
+    let name = inner[..name_end].to_ascii_lowercase();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let mut attributes = Vec::new();
+    let mut rest = inner[name_end..].trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let attribute = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (quoted, end) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                    (&after[1..end], (end + 1).min(after.len()))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], end)
+                }
+            };
+            value = quoted;
+            rest = &after[end..];
+        }
+        attributes.push((attribute, value.to_string()));
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    }
+
+    Some(Tag {
+        name,
+        closing,
+        attributes,
+    })
+}
+
+/// Replaces HTML entities like `&amp;`, `&nbsp;` and `&#8217;` with the characters they stand
+/// for. Unknown entities and stray ampersands are left as they are.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let entity = after
+            .find(';')
+            .filter(|&semi| semi <= 10)
+            .and_then(|semi| Some((entity_char(&after[..semi])?, semi)));
+        match entity {
+            Some((c, semi)) => {
+                unescaped.push(c);
+                rest = &after[semi + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = after;
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+fn entity_char(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code).filter(|c| *c != '\0');
+    }
+
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "hellip" => '…',
+        "bull" => '•',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::plain("Trains are delayed", "Trains are delayed")]
+    #[case::line_breaks("One<br>Two<br/>Three<BR />Four", "One\nTwo\nThree\nFour")]
+    #[case::paragraphs(
+        "<p>Trains skip <b>Briarwood</b></p><p style=\"min-height:10px\"></p><p>Urgent maintenance</p>",
+        "Trains skip Briarwood\nUrgent maintenance"
+    )]
+    #[case::list(
+        "<p>Boarding change</p><ul><li><p>Grant City</p></li><li>New Dorp</li></ul>",
+        "Boarding change\n• Grant City\n• New Dorp"
+    )]
+    #[case::entities(
+        "Jay St&ndash;MetroTech &amp; Court Sq &lt;&gt; &#8217; &#x2019; &bogus; AT&T",
+        "Jay St–MetroTech & Court Sq <> ’ ’ &bogus; AT&T"
+    )]
+    #[case::links(
+        "Take the <a title=\"\" href=\"https://bustime.mta.info/#B68\" target=\"_blank\"><strong>B68</strong></a> or <a href='https://new.mta.info/?a=1&amp;b=2'>see</a> <a href=\"javascript:alert(1)\">this</a>",
+        "Take the B68 or see this\nMore info: https://bustime.mta.info/#B68\nMore info: https://new.mta.info/?a=1&b=2"
+    )]
+    #[case::not_a_tag("Wait < 5 minutes, 3 > 2", "Wait < 5 minutes, 3 > 2")]
+    #[case::dropped(
+        "<!-- note -->Shown<script>alert('<b>')</script><style>p { color: red }</style><span>!</span>",
+        "Shown!"
+    )]
+    fn test_html_to_text(#[case] html: &str, #[case] expected: &str) {
+        assert_eq!(html_to_text(html), expected);
+    }
+
+    #[test]
+    fn test_html_to_text_repeated_link() {
+        let html =
+            "<a href=\"https://mta.info\">MTA</a> and <a href=\"https://mta.info\">again</a>";
+        assert_eq!(
+            html_to_text(html),
+            "MTA and again\nMore info: https://mta.info"
+        );
+    }
+
+    #[rstest]
+    #[case::kept(
+        "<p>Trains skip <b>Briarwood</b></p><ul><li>One</li></ul>",
+        "<p>Trains skip <b>Briarwood</b></p><ul><li>One</li></ul>"
+    )]
+    #[case::attributes(
+        "<p style=\"min-height:10px\"></p><a title=\"\" href=\"https://bustime.mta.info/#B68\" target=\"_blank\">B68</a>",
+        "<a href=\"https://bustime.mta.info/#B68\">B68</a>"
+    )]
+    #[case::unsafe_link(
+        "<a href=\"javascript:alert(1)\" onclick=\"x()\">this</a>",
+        "<a>this</a>"
+    )]
+    #[case::dropped(
+        "<div><span>One</span><br/></div><script>alert(1)</script><img src=x onerror=y>",
+        "One<br>"
+    )]
+    #[case::escaped(
+        "AT&T &amp; 3 < 5 &quot;What&apos;s&quot;",
+        "AT&amp;T &amp; 3 &lt; 5 &quot;What's&quot;"
+    )]
+    fn test_clean_html(#[case] html: &str, #[case] expected: &str) {
+        assert_eq!(clean_html(html), expected);
+    }
+}
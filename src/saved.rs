@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+
+/// How many hex digits of a config's digest make up its ID.
+const ID_LEN: usize = 10;
+
+/// A calendar's lines, stations and filters, saved so it can be subscribed to at a short URL.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// The calendar's title, instead of one built from its lines and stations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Train lines or trunk groups whose alerts are included
+    #[serde(default)]
+    pub lines: Vec<String>,
+    /// Stations or platforms whose alerts are included
+    #[serde(default)]
+    pub stations: Vec<String>,
+    /// Calendar query parameters by name, e.g. `min_severity` → `warning`
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+}
+
+impl CalendarConfig {
+    /// The config's ID, derived from its content so that saving the same calendar twice gives
+    /// the same URL.
+    pub fn id(&self) -> String {
+        let json = serde_json::to_vec(self).expect("calendar configs serialize to JSON");
+        let hex: String = Sha256::digest(json)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        hex[..ID_LEN].to_string()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedCalendar {
+    config: CalendarConfig,
+    /// When the config was last saved or looked up, as a position in the store's history
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    calendars: HashMap<String, SavedCalendar>,
+    /// The `last_used` the next save or lookup gets
+    next_use: u64,
+}
+
+/// Saved calendar configs, kept in memory and optionally persisted to a JSON file.
+///
+/// Configs can't be changed once saved. Once `capacity` configs are stored, saving another
+/// drops the one least recently saved or looked up.
+#[derive(Debug)]
+pub struct SavedCalendars {
+    path: Option<PathBuf>,
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl SavedCalendars {
+    /// A store that forgets its configs when the server stops.
+    pub fn in_memory(capacity: usize) -> Self {
+        SavedCalendars {
+            path: None,
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Loads the configs saved in `path`, which is created on the first save if it doesn't
+    /// exist yet.
+    pub fn load(
+        path: impl AsRef<Path>,
+        capacity: usize,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let path = path.as_ref();
+        let calendars: HashMap<String, SavedCalendar> = match std::fs::read(path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let next_use = calendars
+            .values()
+            .map(|c| c.last_used + 1)
+            .max()
+            .unwrap_or(0);
+
+        Ok(SavedCalendars {
+            path: Some(path.to_path_buf()),
+            capacity,
+            inner: Mutex::new(Inner {
+                calendars,
+                next_use,
+            }),
+        })
+    }
+
+    /// Saves `config`, returning its ID and whether it wasn't already saved.
+    pub async fn save(&self, config: CalendarConfig) -> std::io::Result<(String, bool)> {
+        let id = config.id();
+        let mut inner = self.inner.lock().await;
+        let last_used = inner.touch();
+
+        if let Some(saved) = inner.calendars.get_mut(&id) {
+            saved.last_used = last_used;
+            return Ok((id, false));
+        }
+
+        while !inner.calendars.is_empty() && inner.calendars.len() >= self.capacity {
+            let least_recent = inner
+                .calendars
+                .iter()
+                .min_by_key(|(_, saved)| saved.last_used)
+                .map(|(id, _)| id.clone())
+                .expect("the store isn't empty");
+            inner.calendars.remove(&least_recent);
+        }
+        inner
+            .calendars
+            .insert(id.clone(), SavedCalendar { config, last_used });
+
+        // Holding the lock while writing keeps concurrent saves from overwriting each other
+        if let Some(path) = &self.path {
+            let json = serde_json::to_vec(&inner.calendars).map_err(std::io::Error::other)?;
+            let temp_path = path.with_extension("tmp");
+            tokio::fs::write(&temp_path, json).await?;
+            tokio::fs::rename(&temp_path, path).await?;
+        }
+
+        Ok((id, true))
+    }
+
+    /// Looks up a saved config by ID.
+    ///
+    /// Lookups only count towards keeping a config in memory; they're persisted with the next
+    /// save rather than rewriting the file on every calendar refresh.
+    pub async fn get(&self, id: &str) -> Option<CalendarConfig> {
+        let mut inner = self.inner.lock().await;
+        let last_used = inner.touch();
+        let saved = inner.calendars.get_mut(id)?;
+        saved.last_used = last_used;
+
+        Some(saved.config.clone())
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.inner.lock().await.calendars.is_empty()
+    }
+
+    pub async fn len(&self) -> usize {
+        self.inner.lock().await.calendars.len()
+    }
+}
+
+impl Inner {
+    fn touch(&mut self) -> u64 {
+        let last_used = self.next_use;
+        self.next_use += 1;
+        last_used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    fn config(lines: &[&str]) -> CalendarConfig {
+        CalendarConfig {
+            lines: lines.iter().map(|s| s.to_string()).collect(),
+            ..CalendarConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_config_ids() {
+        let commute = CalendarConfig {
+            filters: [("hours".to_string(), "07:00-10:00".to_string())].into(),
+            ..config(&["F", "G"])
+        };
+
+        assert_eq!(commute.id().len(), ID_LEN);
+        assert_eq!(commute.id(), commute.clone().id());
+        assert_ne!(commute.id(), config(&["F", "G"]).id());
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get() {
+        let saved = SavedCalendars::in_memory(10);
+
+        let (id, created) = saved.save(config(&["F"])).await.unwrap();
+        assert!(created);
+        assert_eq!(
+            saved.save(config(&["F"])).await.unwrap(),
+            (id.clone(), false)
+        );
+        assert_eq!(saved.get(&id).await, Some(config(&["F"])));
+        assert_eq!(saved.get("0123456789").await, None);
+        assert_eq!(saved.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_config_is_dropped() {
+        let saved = SavedCalendars::in_memory(2);
+
+        let (a, _) = saved.save(config(&["A"])).await.unwrap();
+        let (c, _) = saved.save(config(&["C"])).await.unwrap();
+        saved.get(&a).await;
+        let (e, _) = saved.save(config(&["E"])).await.unwrap();
+
+        assert_eq!(saved.len().await, 2);
+        assert!(saved.get(&a).await.is_some());
+        assert!(saved.get(&c).await.is_none());
+        assert!(saved.get(&e).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_configs_persist() {
+        let path =
+            std::env::temp_dir().join(format!("nyc-train-time-saved-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (id, _) = SavedCalendars::load(&path, 10)
+            .unwrap()
+            .save(config(&["L"]))
+            .await
+            .unwrap();
+        let reloaded = SavedCalendars::load(&path, 10).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reloaded.get(&id).await, Some(config(&["L"])));
+        assert!(SavedCalendars::load(&path, 10).unwrap().is_empty().await);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::stops::parent_station;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct TripRecord {
+    route_id: String,
+    trip_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    stop_id: String,
+    stop_sequence: u32,
+}
+
+/// The order each subway route calls at its stations, loaded from the static GTFS `trips.txt`
+/// and `stop_times.txt`.
+///
+/// Only the distinct station patterns are kept, so the hundreds of thousands of scheduled stop
+/// times come down to a handful of sequences per route: each direction, plus express and
+/// short-turn variants.
+#[derive(Debug, Default)]
+pub struct StopSequences {
+    /// Parent station IDs in stopping order, keyed by route ID
+    by_route: HashMap<String, Vec<Vec<String>>>,
+}
+
+impl StopSequences {
+    /// Loads `trips.txt` and `stop_times.txt` from an unzipped GTFS feed in `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let dir = dir.as_ref();
+        let trips = std::fs::File::open(dir.join("trips.txt"))?;
+        let stop_times = std::fs::File::open(dir.join("stop_times.txt"))?;
+        Self::from_csv(trips, stop_times)
+    }
+
+    pub fn from_csv(
+        trips: impl Read,
+        stop_times: impl Read,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut trip_routes = HashMap::new();
+        for record in csv::Reader::from_reader(trips).deserialize() {
+            let record: TripRecord = record?;
+            trip_routes.insert(record.trip_id, record.route_id);
+        }
+
+        let mut trip_stops: HashMap<String, Vec<(u32, String)>> = HashMap::new();
+        for record in csv::Reader::from_reader(stop_times).deserialize() {
+            let record: StopTimeRecord = record?;
+            trip_stops
+                .entry(record.trip_id)
+                .or_default()
+                .push((record.stop_sequence, record.stop_id));
+        }
+
+        let mut patterns: HashMap<String, BTreeSet<Vec<String>>> = HashMap::new();
+        for (trip_id, mut stops) in trip_stops {
+            let Some(route_id) = trip_routes.get(&trip_id) else {
+                continue;
+            };
+
+            stops.sort_unstable_by_key(|(sequence, _)| *sequence);
+            let mut stations: Vec<String> = stops
+                .iter()
+                .map(|(_, stop_id)| parent_station(stop_id).to_string())
+                .collect();
+            stations.dedup();
+
+            patterns
+                .entry(route_id.clone())
+                .or_default()
+                .insert(stations);
+        }
+
+        let by_route = patterns
+            .into_iter()
+            .map(|(route_id, sequences)| (route_id, sequences.into_iter().collect()))
+            .collect();
+
+        Ok(Self { by_route })
+    }
+
+    /// Whether any of `route_id`'s trips call at `stop_id`.
+    pub fn serves(&self, route_id: &str, stop_id: &str) -> bool {
+        let station = parent_station(stop_id);
+        self.sequences(route_id)
+            .any(|sequence| sequence.iter().any(|stop| stop == station))
+    }
+
+    /// The stations `route_id` calls at riding between `from` and `to`, both included, in
+    /// either direction. Every stopping pattern that calls at both counts, so a segment
+    /// covered by express and local trips includes the local stops.
+    ///
+    /// Returns `None` when no trip on the route calls at both stations.
+    pub fn segment(&self, route_id: &str, from: &str, to: &str) -> Option<HashSet<String>> {
+        let from = parent_station(from);
+        let to = parent_station(to);
+
+        let mut segment = HashSet::new();
+        let mut found = false;
+        for sequence in self.sequences(route_id) {
+            let position = |station| sequence.iter().position(|stop| stop == station);
+            let (Some(start), Some(end)) = (position(from), position(to)) else {
+                continue;
+            };
+
+            found = true;
+            segment.extend(sequence[start.min(end)..=start.max(end)].iter().cloned());
+        }
+
+        found.then_some(segment)
+    }
+
+    /// The stopping pattern of one of `routes` that calls at the most of `stations`, as parent
+    /// station IDs in stopping order, or `None` when none of them calls at any. Ties go to the
+    /// longer pattern, then to the earlier route.
+    pub fn stopping_order(&self, routes: &[String], stations: &[&str]) -> Option<&[String]> {
+        let stations: HashSet<&str> = stations.iter().map(|s| parent_station(s)).collect();
+        let mut best: Option<(usize, &Vec<String>)> = None;
+        for sequence in routes.iter().flat_map(|route| self.sequences(route)) {
+            let calls = sequence
+                .iter()
+                .filter(|stop| stations.contains(stop.as_str()))
+                .count();
+            let better = best.is_none_or(|(best_calls, best_sequence)| {
+                (calls, sequence.len()) > (best_calls, best_sequence.len())
+            });
+            if calls > 0 && better {
+                best = Some((calls, sequence));
+            }
+        }
+
+        best.map(|(_, sequence)| sequence.as_slice())
+    }
+
+    fn sequences(&self, route_id: &str) -> impl Iterator<Item = &Vec<String>> {
+        self.by_route.get(route_id).into_iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_route.is_empty()
+    }
+
+    /// The number of routes with stop sequences.
+    pub fn len(&self) -> usize {
+        self.by_route.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_sequences() -> StopSequences {
+        StopSequences::load("tests/fixtures/gtfs_subway").unwrap()
+    }
+
+    #[test]
+    fn test_stop_sequences_from_gtfs() {
+        let sequences = fixture_sequences();
+        assert_eq!(sequences.len(), 2);
+
+        assert!(sequences.serves("F", "D21"));
+        assert!(sequences.serves("F", "F20N"));
+        assert!(!sequences.serves("G", "D21"));
+        assert!(!sequences.serves("L", "F20"));
+    }
+
+    #[test]
+    fn test_segment() {
+        let sequences = fixture_sequences();
+        let stations =
+            |ids: &[&str]| -> HashSet<String> { ids.iter().map(|s| s.to_string()).collect() };
+
+        let expected = stations(&["D21", "F14", "F15", "F16", "F18", "A41", "F20"]);
+        assert_eq!(sequences.segment("F", "F20", "D21"), Some(expected.clone()));
+        assert_eq!(sequences.segment("F", "D21N", "F20S"), Some(expected));
+        assert_eq!(
+            sequences.segment("G", "F20", "F22"),
+            Some(stations(&["F20", "F21", "F22"]))
+        );
+
+        assert_eq!(sequences.segment("G", "F20", "D21"), None);
+        assert_eq!(sequences.segment("L", "F20", "D21"), None);
+    }
+
+    #[test]
+    fn test_stopping_order() {
+        let sequences = fixture_sequences();
+        let routes = |ids: &[&str]| -> Vec<String> { ids.iter().map(|s| s.to_string()).collect() };
+
+        // The full southbound pattern, which sorts before the northbound one
+        let order = sequences
+            .stopping_order(&routes(&["F"]), &["F20N", "D21"])
+            .unwrap();
+        assert_eq!(order.len(), 16);
+        assert_eq!(order[0], "B10");
+
+        // The G calls at all three, the F only at Bergen St and Carroll St
+        let order = sequences
+            .stopping_order(&routes(&["F", "G"]), &["A42", "F20", "F21"])
+            .unwrap();
+        assert_eq!(order, ["A42", "F20", "F21", "F22"]);
+
+        assert_eq!(sequences.stopping_order(&routes(&["L"]), &["L08"]), None);
+        assert_eq!(sequences.stopping_order(&routes(&["F"]), &["L08"]), None);
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+/// How disruptive an alert is, on a three-level scale derived from the MTA's alert priorities.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Notices that don't change how trains run, e.g. station notices or extra service
+    #[default]
+    Info,
+    /// Service changes that riders need to plan around, e.g. skipped stops or reroutes
+    Warning,
+    /// Service is suspended, cancelled or delayed
+    Severe,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Severe];
+
+    /// The name used for this level in query parameters, e.g. `?min_severity=warning`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Severe => "severe",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<Severity> {
+        Severity::ALL.into_iter().find(|s| s.query_name() == name)
+    }
+
+    /// The severity of a Mercury alert priority, the last part of an entity selector's
+    /// `sort_order` such as `MTASBWY:A:26`. Unknown priorities are treated as [`Severity::Info`].
+    pub fn from_priority(priority: u32) -> Severity {
+        ALERT_PRIORITIES
+            .iter()
+            .find(|p| p.priority == priority)
+            .map_or(Severity::Info, |p| p.severity)
+    }
+
+    /// The severity of a Mercury alert type such as `Planned - Stops Skipped`, for alerts whose
+    /// entity selectors don't carry a priority.
+    pub fn from_alert_type(alert_type: &str) -> Option<Severity> {
+        ALERT_PRIORITIES
+            .iter()
+            .find(|p| p.alert_type.eq_ignore_ascii_case(alert_type))
+            .map(|p| p.severity)
+    }
+}
+
+/// A Mercury alert priority and the severity it maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AlertPriority {
+    pub priority: u32,
+    pub alert_type: &'static str,
+    pub severity: Severity,
+}
+
+const fn priority(priority: u32, alert_type: &'static str, severity: Severity) -> AlertPriority {
+    AlertPriority {
+        priority,
+        alert_type,
+        severity,
+    }
+}
+
+/// The MTA's subway alert priorities, from least to most important, following the
+/// `MercuryEntitySelector.Priority` enum.
+pub const ALERT_PRIORITIES: &[AlertPriority] = &[
+    priority(1, "No Scheduled Service", Severity::Severe),
+    priority(2, "Information Outage", Severity::Info),
+    priority(3, "Station Notice", Severity::Info),
+    priority(4, "Special Notice", Severity::Info),
+    priority(5, "Weekday Schedule", Severity::Info),
+    priority(6, "Weekend Schedule", Severity::Info),
+    priority(7, "Saturday Schedule", Severity::Info),
+    priority(8, "Sunday Schedule", Severity::Info),
+    priority(9, "Extra Service", Severity::Info),
+    priority(10, "Boarding Change", Severity::Warning),
+    priority(11, "Special Schedule", Severity::Info),
+    priority(12, "Expect Delays", Severity::Warning),
+    priority(13, "Reduced Service", Severity::Warning),
+    priority(14, "Planned - Express to Local", Severity::Warning),
+    priority(15, "Planned - Extra Transfer", Severity::Warning),
+    priority(16, "Planned - Stops Skipped", Severity::Warning),
+    priority(17, "Planned - Detour", Severity::Warning),
+    priority(18, "Planned - Reroute", Severity::Warning),
+    priority(19, "Planned - Substitute Buses", Severity::Warning),
+    priority(20, "Planned - Part Suspended", Severity::Severe),
+    priority(21, "Planned - Suspended", Severity::Severe),
+    priority(22, "Service Change", Severity::Warning),
+    priority(23, "Planned Work", Severity::Warning),
+    priority(24, "Some Delays", Severity::Warning),
+    priority(25, "Express to Local", Severity::Warning),
+    priority(26, "Delays", Severity::Severe),
+    priority(27, "Cancellations", Severity::Severe),
+    priority(28, "Delays and Cancellations", Severity::Severe),
+    priority(29, "Stops Skipped", Severity::Warning),
+    priority(30, "Severe Delays", Severity::Severe),
+    priority(31, "Detour", Severity::Warning),
+    priority(32, "Reroute", Severity::Warning),
+    priority(33, "Substitute Buses", Severity::Warning),
+    priority(34, "Part Suspended", Severity::Severe),
+    priority(35, "Suspended", Severity::Severe),
+];
+
+/// Parses the priority out of a Mercury `sort_order` such as `MTASBWY:A:26`.
+pub fn parse_sort_order_priority(sort_order: &str) -> Option<u32> {
+    sort_order.rsplit(':').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_sort_order_priority() {
+        assert_eq!(parse_sort_order_priority("MTASBWY:A:26"), Some(26));
+        assert_eq!(parse_sort_order_priority("MTASBWY:GS:16"), Some(16));
+        assert_eq!(parse_sort_order_priority("MTASBWY:A:"), None);
+        assert_eq!(parse_sort_order_priority(""), None);
+    }
+
+    #[test]
+    fn test_severity_mapping() {
+        assert_eq!(Severity::from_priority(3), Severity::Info);
+        assert_eq!(Severity::from_priority(16), Severity::Warning);
+        assert_eq!(Severity::from_priority(26), Severity::Severe);
+        assert_eq!(Severity::from_priority(99), Severity::Info);
+        assert_eq!(
+            Severity::from_alert_type("Planned - Suspended"),
+            Some(Severity::Severe)
+        );
+        assert_eq!(Severity::from_alert_type("Alert"), None);
+        assert!(Severity::Info < Severity::Warning && Severity::Warning < Severity::Severe);
+    }
+
+    #[test]
+    fn test_alert_priorities_match_mercury_enum() {
+        use crate::proto::gtfs_realtime_service_status::mercury_entity_selector::Priority;
+        use protobuf::Enum;
+
+        assert_eq!(ALERT_PRIORITIES.len(), Priority::VALUES.len());
+        for (entry, value) in ALERT_PRIORITIES.iter().zip(Priority::VALUES) {
+            assert_eq!(entry.priority as i32, value.value(), "{}", entry.alert_type);
+        }
+    }
+}
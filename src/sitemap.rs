@@ -0,0 +1,65 @@
+use crate::rss::escape_xml;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// A page listed in the sitemap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapUrl {
+    /// The page's absolute URL
+    pub loc: String,
+    /// When the page's content last changed
+    pub lastmod: DateTime<Utc>,
+}
+
+pub fn generate_sitemap(urls: &[SitemapUrl]) -> String {
+    let mut sitemap = String::new();
+
+    sitemap.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    sitemap.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for url in urls {
+        sitemap.push_str("<url>\n");
+        sitemap.push_str(&format!("<loc>{}</loc>\n", escape_xml(&url.loc)));
+        sitemap.push_str(&format!(
+            "<lastmod>{}</lastmod>\n",
+            url.lastmod.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+        sitemap.push_str("</url>\n");
+    }
+
+    sitemap.push_str("</urlset>\n");
+
+    sitemap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_generate_sitemap() {
+        let lastmod = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let sitemap = generate_sitemap(&[
+            SitemapUrl {
+                loc: "https://example.com/".to_string(),
+                lastmod,
+            },
+            SitemapUrl {
+                loc: "https://example.com/?a=1&b=2".to_string(),
+                lastmod,
+            },
+        ]);
+
+        assert_eq!(
+            sitemap,
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "<url>\n<loc>https://example.com/</loc>\n<lastmod>2025-12-15T12:00:00Z</lastmod>\n</url>\n",
+                "<url>\n<loc>https://example.com/?a=1&amp;b=2</loc>\n<lastmod>2025-12-15T12:00:00Z</lastmod>\n</url>\n",
+                "</urlset>\n",
+            )
+        );
+    }
+}
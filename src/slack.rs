@@ -0,0 +1,124 @@
+use crate::alert::AlertDetail;
+use crate::notify::{self, Notifier};
+use chrono::{DateTime, Utc};
+
+/// Slack's built-in emoji closest to each line color, since Slack has no subway bullets.
+const COLOR_EMOJI: &[(&str, &str)] = &[
+    ("#0039A6", ":large_blue_circle:"),
+    ("#FF6319", ":large_orange_circle:"),
+    ("#6CBE45", ":large_green_circle:"),
+    ("#00933C", ":large_green_circle:"),
+    ("#996633", ":large_brown_circle:"),
+    ("#A7A9AC", ":white_circle:"),
+    ("#FCCC0A", ":large_yellow_circle:"),
+    ("#EE352E", ":red_circle:"),
+    ("#B933AD", ":large_purple_circle:"),
+    ("#808183", ":black_circle:"),
+];
+
+/// Slack incoming webhooks, which take a message in Slack's markup, e.g.
+///
+/// ```text
+/// :large_blue_circle: *A* :large_blue_circle: *C* Delays
+/// *A and C trains are delayed while we address a signal problem*
+/// Mon, Dec 15, 7:00 AM – 10:00 AM
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Slack;
+
+impl Notifier for Slack {
+    fn service(&self) -> &'static str {
+        "Slack"
+    }
+
+    fn payload(&self, alert: &AlertDetail, now: DateTime<Utc>) -> serde_json::Value {
+        let bullets: Vec<String> = notify::alert_routes(alert)
+            .into_iter()
+            .map(bullet)
+            .collect();
+        let mut text = format!(
+            "{} {}\n*{}*",
+            bullets.join(" "),
+            escape(&alert.alert_type),
+            escape(&alert.header)
+        );
+
+        if let Some(period) = notify::next_period(alert, now) {
+            text.push('\n');
+            text.push_str(&period);
+        }
+
+        if let Some(url) = &alert.url {
+            text.push_str(&format!("\n<{}|More details>", escape(url)));
+        }
+
+        serde_json::json!({ "text": text })
+    }
+}
+
+/// The route's bullet: its line's color as an emoji, then its ID in bold.
+fn bullet(route: &str) -> String {
+    let route = route.to_uppercase();
+    let emoji = notify::route_line(&route).and_then(|line| {
+        COLOR_EMOJI
+            .iter()
+            .find(|(color, _)| *color == line.color)
+            .map(|(_, emoji)| *emoji)
+    });
+
+    match emoji {
+        Some(emoji) => format!("{} *{}*", emoji, escape(&route)),
+        None => format!("*{}*", escape(&route)),
+    }
+}
+
+/// Escapes the characters Slack treats as markup for links and mentions.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::ActivePeriod;
+    use crate::notify::tests::{alert, time};
+    use crate::severity::Severity;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_payload() {
+        let mut alert = alert("1", &["6", "6X", "A", "6", "B44"], Severity::Info);
+        alert.header = "No trains between <Pelham Bay> & Parkchester".to_string();
+        alert.url = Some("https://new.mta.info/alerts".to_string());
+        alert.active_periods.push(ActivePeriod {
+            start: Some(time(20)),
+            end: None,
+        });
+
+        assert_eq!(
+            Slack.payload(&alert, time(13)),
+            serde_json::json!({
+                "text": concat!(
+                    ":large_green_circle: *6* :large_green_circle: *6X* :large_blue_circle: *A* *B44* Delays\n",
+                    "*No trains between &lt;Pelham Bay&gt; &amp; Parkchester*\n",
+                    "Mon, Dec 15, 7:00 AM – 10:00 AM (and 1 more)\n",
+                    "<https://new.mta.info/alerts|More details>"
+                )
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_without_upcoming_periods() {
+        let alert = alert("1", &["A"], Severity::Severe);
+
+        assert_eq!(
+            Slack.payload(&alert, time(16)),
+            serde_json::json!({
+                "text": ":large_blue_circle: *A* Delays\n*Trains are delayed while we address a signal problem*"
+            })
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters for `GET /api/stats`, updated as requests are served and MTA feeds are fetched.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Requests by route, e.g. `/api/alerts/train/:train_name`
+    requests: Mutex<BTreeMap<String, u64>>,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    fetches: Mutex<Fetches>,
+}
+
+#[derive(Debug, Default)]
+struct Fetches {
+    /// When each feed, by URL, was last fetched successfully
+    last_success: BTreeMap<String, DateTime<Utc>>,
+    last_error: Option<FetchError>,
+}
+
+/// The most recent failure to fetch an MTA feed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FetchError {
+    pub feed: String,
+    pub message: String,
+    pub at: DateTime<Utc>,
+}
+
+/// The counters at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub requests: BTreeMap<String, u64>,
+    pub total_requests: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub last_success: BTreeMap<String, DateTime<Utc>>,
+    pub last_error: Option<FetchError>,
+}
+
+impl Stats {
+    pub fn record_request(&self, route: &str) {
+        let mut requests = self.requests.lock().unwrap();
+        match requests.get_mut(route) {
+            Some(count) => *count += 1,
+            None => {
+                requests.insert(route.to_string(), 1);
+            }
+        }
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the outcome of fetching the feed at `feed`.
+    pub fn record_fetch<T, E: Display>(
+        &self,
+        feed: &str,
+        result: &Result<T, E>,
+        now: DateTime<Utc>,
+    ) {
+        let mut fetches = self.fetches.lock().unwrap();
+        match result {
+            Ok(_) => {
+                fetches.last_success.insert(feed.to_string(), now);
+            }
+            Err(e) => {
+                fetches.last_error = Some(FetchError {
+                    feed: feed.to_string(),
+                    message: e.to_string(),
+                    at: now,
+                });
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let requests = self.requests.lock().unwrap().clone();
+        let fetches = self.fetches.lock().unwrap();
+        Snapshot {
+            total_requests: requests.values().sum(),
+            requests,
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            last_success: fetches.last_success.clone(),
+            last_error: fetches.last_error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_snapshot() {
+        let stats = Stats::default();
+        let noon = Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap();
+        let one = Utc.with_ymd_and_hms(2025, 12, 15, 13, 0, 0).unwrap();
+
+        stats.record_request("/api/trains");
+        stats.record_request("/train/:train_name");
+        stats.record_request("/api/trains");
+        stats.record_cache_lookup(false);
+        stats.record_cache_lookup(true);
+        stats.record_cache_lookup(true);
+        stats.record_fetch::<(), String>("subway", &Ok(()), noon);
+        stats.record_fetch::<(), String>("lirr", &Err("timed out".to_string()), noon);
+        stats.record_fetch::<(), String>("subway", &Err("502".to_string()), one);
+
+        assert_eq!(
+            stats.snapshot(),
+            Snapshot {
+                requests: BTreeMap::from([
+                    ("/api/trains".to_string(), 2),
+                    ("/train/:train_name".to_string(), 1),
+                ]),
+                total_requests: 3,
+                cache_hits: 2,
+                cache_misses: 1,
+                // A failed fetch keeps the last success
+                last_success: BTreeMap::from([("subway".to_string(), noon)]),
+                last_error: Some(FetchError {
+                    feed: "subway".to_string(),
+                    message: "502".to_string(),
+                    at: one,
+                }),
+            }
+        );
+    }
+}
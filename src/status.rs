@@ -0,0 +1,202 @@
+use crate::calendar::{AlertKind, CalendarEvent, Category};
+use crate::severity::Severity;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// A line's overall status, from least to most disruptive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineStatus {
+    GoodService,
+    PlannedWork,
+    Delays,
+    PartSuspended,
+    Suspended,
+}
+
+impl LineStatus {
+    /// The status of a line from its alerts that are active at `now`.
+    ///
+    /// Notices that don't change how trains run are ignored, so a line with only a station
+    /// notice still has good service.
+    pub fn from_events(events: &[CalendarEvent], now: DateTime<Utc>) -> LineStatus {
+        events
+            .iter()
+            .filter(|event| is_active(event, now))
+            .filter(|event| event.severity >= Severity::Warning)
+            .map(LineStatus::from_event)
+            .max()
+            .unwrap_or(LineStatus::GoodService)
+    }
+
+    fn from_event(event: &CalendarEvent) -> LineStatus {
+        let alert_type = event.alert_type.to_lowercase();
+        if alert_type.contains("part suspended") {
+            LineStatus::PartSuspended
+        } else if alert_type.contains("suspended") || alert_type == "no scheduled service" {
+            LineStatus::Suspended
+        } else if event.kind == AlertKind::Planned {
+            LineStatus::PlannedWork
+        } else {
+            LineStatus::Delays
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineStatus::GoodService => "Good Service",
+            LineStatus::PlannedWork => "Planned Work",
+            LineStatus::Delays => "Delays",
+            LineStatus::PartSuspended => "Part Suspended",
+            LineStatus::Suspended => "Suspended",
+        }
+    }
+
+    /// The symbol calendars put before the titles of events in the matching category, or none
+    /// for good service.
+    pub fn indicator(&self) -> Option<&'static str> {
+        let category = match self {
+            LineStatus::GoodService => return None,
+            LineStatus::PlannedWork => Category::PlannedWork,
+            LineStatus::Delays => Category::Delays,
+            LineStatus::PartSuspended | LineStatus::Suspended => Category::NoService,
+        };
+        category.indicator()
+    }
+}
+
+/// A line's row on the status board.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LineSummary {
+    pub status: LineStatus,
+    /// How many of the line's alerts are in effect now, including notices
+    pub active_alerts: usize,
+    /// How many of the line's alerts haven't started yet
+    pub upcoming_alerts: usize,
+}
+
+impl LineSummary {
+    /// Summarizes a line's alerts at `now`. `events` must already be filtered to the line.
+    pub fn from_events(events: &[CalendarEvent], now: DateTime<Utc>) -> LineSummary {
+        LineSummary {
+            status: LineStatus::from_events(events, now),
+            active_alerts: events.iter().filter(|event| is_active(event, now)).count(),
+            upcoming_alerts: events.iter().filter(|event| event.start > now).count(),
+        }
+    }
+}
+
+fn is_active(event: &CalendarEvent, now: DateTime<Utc>) -> bool {
+    event.start <= now && event.end.is_none_or(|end| now < end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 12, 15, 12, 0, 0).unwrap()
+    }
+
+    fn active(uid: &str, alert_type: &str, severity: Severity, kind: AlertKind) -> CalendarEvent {
+        CalendarEvent {
+            alert_type: alert_type.to_string(),
+            severity,
+            kind,
+            ..CalendarEvent::for_test(uid, &["F"])
+        }
+    }
+
+    #[test]
+    fn test_line_status() {
+        let notice = active(
+            "notice",
+            "Station Notice",
+            Severity::Info,
+            AlertKind::Realtime,
+        );
+        let work = active(
+            "work",
+            "Planned - Stops Skipped",
+            Severity::Warning,
+            AlertKind::Planned,
+        );
+        let delays = active("delays", "Delays", Severity::Severe, AlertKind::Realtime);
+        let part_suspended = active(
+            "part-suspended",
+            "Planned - Part Suspended",
+            Severity::Severe,
+            AlertKind::Planned,
+        );
+        let suspended = active(
+            "suspended",
+            "Suspended",
+            Severity::Severe,
+            AlertKind::Realtime,
+        );
+        let no_service = active(
+            "no-service",
+            "No Scheduled Service",
+            Severity::Severe,
+            AlertKind::Planned,
+        );
+        let later = CalendarEvent {
+            start: now() + chrono::Duration::hours(1),
+            ..suspended.clone()
+        };
+
+        let status = |events: &[CalendarEvent]| LineStatus::from_events(events, now());
+        assert_eq!(status(&[]), LineStatus::GoodService);
+        assert_eq!(
+            status(std::slice::from_ref(&notice)),
+            LineStatus::GoodService
+        );
+        assert_eq!(status(&[notice, work.clone()]), LineStatus::PlannedWork);
+        assert_eq!(status(&[delays.clone(), work.clone()]), LineStatus::Delays);
+        assert_eq!(
+            status(&[work.clone(), part_suspended.clone(), delays]),
+            LineStatus::PartSuspended
+        );
+        assert_eq!(
+            status(&[part_suspended.clone(), suspended]),
+            LineStatus::Suspended
+        );
+        assert_eq!(status(&[part_suspended, no_service]), LineStatus::Suspended);
+        assert_eq!(status(&[work, later]), LineStatus::PlannedWork);
+    }
+
+    #[test]
+    fn test_line_summary() {
+        let delays = active("delays", "Delays", Severity::Severe, AlertKind::Realtime);
+        let notice = active(
+            "notice",
+            "Station Notice",
+            Severity::Info,
+            AlertKind::Realtime,
+        );
+        let tonight = CalendarEvent {
+            start: now() + chrono::Duration::hours(10),
+            ..active(
+                "tonight",
+                "Planned - Part Suspended",
+                Severity::Severe,
+                AlertKind::Planned,
+            )
+        };
+        let over = CalendarEvent {
+            end: Some(now() - chrono::Duration::minutes(1)),
+            ..delays.clone()
+        };
+
+        assert_eq!(
+            LineSummary::from_events(&[delays, notice, tonight, over], now()),
+            LineSummary {
+                status: LineStatus::Delays,
+                active_alerts: 2,
+                upcoming_alerts: 1,
+            }
+        );
+    }
+}
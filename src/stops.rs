@@ -0,0 +1,431 @@
+use crate::lines;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Returns the GTFS parent station for a stop ID, stripping the `N`/`S` platform suffix.
+///
+/// `A15N` and `A15S` are the northbound and southbound platforms of station `A15`.
+pub fn parent_station(stop_id: &str) -> &str {
+    match stop_id.len() {
+        4 => stop_id
+            .strip_suffix('N')
+            .or_else(|| stop_id.strip_suffix('S'))
+            .unwrap_or(stop_id),
+        _ => stop_id,
+    }
+}
+
+/// Whether `stop_id` looks like a subway stop ID, e.g. `A15`, `101` or `A15N`.
+pub fn is_valid_stop_id(stop_id: &str) -> bool {
+    let bytes = stop_id.as_bytes();
+
+    let station = match bytes.len() {
+        3 => bytes,
+        4 if matches!(bytes[3], b'N' | b'S') => &bytes[..3],
+        _ => return false,
+    };
+
+    (station[0].is_ascii_uppercase() || station[0].is_ascii_digit())
+        && station[1..].iter().all(u8::is_ascii_digit)
+}
+
+/// Whether an alert scoped to `informed_stop` affects `stop_id`.
+///
+/// A station matches all of its platforms and a platform matches its parent station,
+/// but the two platforms of a station don't match each other.
+pub fn stop_matches(stop_id: &str, informed_stop: &str) -> bool {
+    if stop_id == informed_stop {
+        return true;
+    }
+
+    let stop_parent = parent_station(stop_id);
+    let informed_parent = parent_station(informed_stop);
+
+    stop_parent == informed_parent && (stop_parent == stop_id || informed_parent == informed_stop)
+}
+
+/// A direction of travel, as encoded by the `N`/`S` suffix of a platform's stop ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    North,
+    South,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 2] = [Direction::North, Direction::South];
+
+    /// The name used for this direction in query parameters, e.g. `?direction=north`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|d| d.query_name() == name)
+    }
+
+    /// The direction of a platform stop ID such as `A15N`, or `None` for a whole station.
+    pub fn from_stop_id(stop_id: &str) -> Option<Direction> {
+        if stop_id.len() != 4 || !is_valid_stop_id(stop_id) {
+            return None;
+        }
+
+        match stop_id.as_bytes()[3] {
+            b'N' => Some(Direction::North),
+            b'S' => Some(Direction::South),
+            _ => None,
+        }
+    }
+}
+
+/// The single direction an alert's informed stops point to.
+///
+/// Returns `None` when it can't be determined: the alert has no stops, informs a whole
+/// station, or informs platforms in both directions.
+pub fn alert_direction(stop_ids: &[String]) -> Option<Direction> {
+    let mut directions = stop_ids
+        .iter()
+        .map(|stop_id| Direction::from_stop_id(stop_id));
+
+    let first = directions.next()??;
+    directions
+        .all(|direction| direction == Some(first))
+        .then_some(first)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Borough {
+    Manhattan,
+    Brooklyn,
+    Queens,
+    Bronx,
+    StatenIsland,
+}
+
+impl Borough {
+    pub const ALL: [Borough; 5] = [
+        Borough::Manhattan,
+        Borough::Brooklyn,
+        Borough::Queens,
+        Borough::Bronx,
+        Borough::StatenIsland,
+    ];
+
+    /// The name used for this borough in query parameters, e.g. `?borough=si`.
+    pub fn query_name(&self) -> &'static str {
+        match self {
+            Borough::Manhattan => "manhattan",
+            Borough::Brooklyn => "brooklyn",
+            Borough::Queens => "queens",
+            Borough::Bronx => "bronx",
+            Borough::StatenIsland => "si",
+        }
+    }
+
+    pub fn from_query_name(name: &str) -> Option<Borough> {
+        Borough::ALL.into_iter().find(|b| b.query_name() == name)
+    }
+
+    /// Parses the borough codes used by the MTA station list (`M`, `Bk`, `Q`, `Bx`, `SI`).
+    fn from_mta_code(code: &str) -> Option<Borough> {
+        match code {
+            "M" => Some(Borough::Manhattan),
+            "Bk" => Some(Borough::Brooklyn),
+            "Q" => Some(Borough::Queens),
+            "Bx" => Some(Borough::Bronx),
+            "SI" => Some(Borough::StatenIsland),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Station {
+    pub stop_id: String,
+    pub name: String,
+    pub borough: Borough,
+    pub routes: Vec<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Where northbound trains are headed from here, as signed on the platform, e.g.
+    /// `Manhattan` or `Uptown & The Bronx`, or empty at a northern terminal
+    pub north_label: String,
+    /// Where southbound trains are headed from here, e.g. `Coney Island`
+    pub south_label: String,
+}
+
+impl Station {
+    /// Where trains in `direction` are headed from here, when the MTA signs it.
+    pub fn direction_label(&self, direction: Direction) -> Option<&str> {
+        let label = match direction {
+            Direction::North => &self.north_label,
+            Direction::South => &self.south_label,
+        };
+        (!label.is_empty()).then_some(label.as_str())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StationRecord {
+    #[serde(rename = "GTFS Stop ID")]
+    stop_id: String,
+    #[serde(rename = "Stop Name")]
+    name: String,
+    #[serde(rename = "Borough")]
+    borough: String,
+    #[serde(rename = "Daytime Routes")]
+    routes: String,
+    #[serde(rename = "GTFS Latitude")]
+    latitude: f64,
+    #[serde(rename = "GTFS Longitude")]
+    longitude: f64,
+    #[serde(rename = "North Direction Label", default)]
+    north_label: String,
+    #[serde(rename = "South Direction Label", default)]
+    south_label: String,
+}
+
+/// Static station metadata keyed by GTFS stop ID, loaded from the MTA's `Stations.csv`.
+#[derive(Debug, Default)]
+pub struct Stations {
+    by_stop_id: HashMap<String, Station>,
+}
+
+impl Stations {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let file = std::fs::File::open(path)?;
+        Self::from_csv(file)
+    }
+
+    pub fn from_csv(reader: impl Read) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut by_stop_id = HashMap::new();
+
+        for record in csv::Reader::from_reader(reader).deserialize() {
+            let record: StationRecord = record?;
+            let borough = Borough::from_mta_code(&record.borough).ok_or_else(|| {
+                format!(
+                    "Unknown borough {:?} for stop {}",
+                    record.borough, record.stop_id
+                )
+            })?;
+
+            let station = Station {
+                stop_id: record.stop_id.clone(),
+                name: record.name,
+                borough,
+                routes: record.routes.split_whitespace().map(String::from).collect(),
+                latitude: record.latitude,
+                longitude: record.longitude,
+                north_label: record.north_label,
+                south_label: record.south_label,
+            };
+            by_stop_id.insert(record.stop_id, station);
+        }
+
+        Ok(Self { by_stop_id })
+    }
+
+    /// Stations whose names contain `query`, ignoring case, best matches first: names that
+    /// start with it, then names with a word that starts with it, then the rest. Ties are
+    /// broken by name and stop ID so results are stable.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&Station> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(u8, &Station)> = self
+            .by_stop_id
+            .values()
+            .filter_map(|station| {
+                let name = station.name.to_lowercase();
+                let rank = if name.starts_with(&query) {
+                    0
+                } else if name
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|word| word.starts_with(&query))
+                {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+
+                Some((rank, station))
+            })
+            .collect();
+
+        matches.sort_unstable_by(|(a_rank, a), (b_rank, b)| {
+            (a_rank, &a.name, &a.stop_id).cmp(&(b_rank, &b.name, &b.stop_id))
+        });
+        matches.truncate(limit);
+
+        matches.into_iter().map(|(_, station)| station).collect()
+    }
+
+    /// Looks up a station by stop ID, resolving platform IDs to their parent station.
+    pub fn get(&self, stop_id: &str) -> Option<&Station> {
+        self.by_stop_id.get(parent_station(stop_id))
+    }
+
+    /// Whether `stop_id` is a station, or a platform of one, served by one of `trains`.
+    pub fn serves(&self, stop_id: &str, trains: &[&str]) -> bool {
+        is_valid_stop_id(stop_id)
+            && self.get(stop_id).is_some_and(|station| {
+                station.routes.iter().any(|route| {
+                    trains.iter().any(|train| {
+                        lines::route_matches(train, route, true)
+                            || lines::route_matches(route, train, true)
+                    })
+                })
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_stop_id.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_stop_id.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_stations() -> Stations {
+        let csv = std::fs::read("tests/fixtures/stations.csv").unwrap();
+        Stations::from_csv(csv.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_parent_station() {
+        assert_eq!(parent_station("A15N"), "A15");
+        assert_eq!(parent_station("A15S"), "A15");
+        assert_eq!(parent_station("A15"), "A15");
+        assert_eq!(parent_station("S09"), "S09");
+    }
+
+    #[test]
+    fn test_is_valid_stop_id() {
+        assert!(is_valid_stop_id("A15"));
+        assert!(is_valid_stop_id("A15N"));
+        assert!(is_valid_stop_id("101S"));
+        assert!(!is_valid_stop_id("A15X"));
+        assert!(!is_valid_stop_id("a15"));
+        assert!(!is_valid_stop_id("AB1"));
+        assert!(!is_valid_stop_id(""));
+    }
+
+    #[test]
+    fn test_stop_matches() {
+        assert!(stop_matches("A15", "A15"));
+        assert!(stop_matches("A15", "A15N"));
+        assert!(stop_matches("A15N", "A15"));
+        assert!(!stop_matches("A15N", "A15S"));
+        assert!(!stop_matches("A15", "A16"));
+    }
+
+    #[test]
+    fn test_direction_from_stop_id() {
+        assert_eq!(Direction::from_stop_id("A15N"), Some(Direction::North));
+        assert_eq!(Direction::from_stop_id("101S"), Some(Direction::South));
+        assert_eq!(Direction::from_stop_id("A15"), None);
+        assert_eq!(Direction::from_stop_id("S09"), None);
+        assert_eq!(Direction::from_stop_id("A15X"), None);
+    }
+
+    #[test]
+    fn test_alert_direction() {
+        let stops = |ids: &[&str]| -> Vec<String> { ids.iter().map(|s| s.to_string()).collect() };
+
+        assert_eq!(
+            alert_direction(&stops(&["A15N", "A16N"])),
+            Some(Direction::North)
+        );
+        assert_eq!(alert_direction(&stops(&["F20S"])), Some(Direction::South));
+        assert_eq!(alert_direction(&stops(&["A15N", "A15S"])), None);
+        assert_eq!(alert_direction(&stops(&["A15N", "A16"])), None);
+        assert_eq!(alert_direction(&stops(&[])), None);
+    }
+
+    #[test]
+    fn test_stations_from_csv() {
+        let stations = fixture_stations();
+
+        let bedford = stations.get("L08").unwrap();
+        assert_eq!(bedford.name, "Bedford Av");
+        assert_eq!(bedford.borough, Borough::Brooklyn);
+        assert_eq!(bedford.routes, vec!["L"]);
+        assert_eq!(bedford.direction_label(Direction::North), Some("Manhattan"));
+        assert_eq!(
+            bedford.direction_label(Direction::South),
+            Some("Canarsie - Rockaway Pkwy")
+        );
+        assert_eq!(
+            stations
+                .get("L01")
+                .unwrap()
+                .direction_label(Direction::North),
+            None
+        );
+
+        assert_eq!(stations.get("A15N").unwrap().name, "125 St");
+        assert_eq!(stations.get("S09").unwrap().borough, Borough::StatenIsland);
+        assert_eq!(stations.get("Z99"), None);
+    }
+
+    #[test]
+    fn test_serves() {
+        let stations = fixture_stations();
+
+        assert!(stations.serves("L08", &["L"]));
+        assert!(stations.serves("L08N", &["L"]));
+        assert!(stations.serves("A15", &["A", "C", "E"]));
+        assert!(stations.serves("S09", &["SI"]));
+        assert!(!stations.serves("L08", &["A"]));
+        assert!(!stations.serves("L99", &["L"]));
+        assert!(!stations.serves("L08X", &["L"]));
+    }
+
+    #[test]
+    fn test_search() {
+        let stations = fixture_stations();
+        let search = |query, limit| -> Vec<String> {
+            stations
+                .search(query, limit)
+                .iter()
+                .map(|station| station.stop_id.clone())
+                .collect()
+        };
+
+        assert_eq!(search("BEDFORD", 20), vec!["L08"]);
+        // Name prefixes first, then word prefixes, then other substrings
+        assert_eq!(search("b", 20), vec!["L08", "F20", "D21", "R01"]);
+        assert_eq!(search("av", 20), vec!["L06", "L01", "L08", "H11"]);
+        assert_eq!(search("av", 2), vec!["L06", "L01"]);
+        assert_eq!(search("ford", 20), vec!["L08"]);
+        assert_eq!(search("  ", 20), Vec::<String>::new());
+        assert_eq!(search("xyz", 20), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_borough_query_names_round_trip() {
+        for borough in Borough::ALL {
+            assert_eq!(
+                Borough::from_query_name(borough.query_name()),
+                Some(borough)
+            );
+        }
+        assert_eq!(Borough::from_query_name("Manhattan"), None);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::calendar::{CalendarEvent, Language};
+use crate::stops::{self, Direction, Stations};
+
+/// A short summary for the event that fits a calendar's week view, such as "F: No service
+/// Bergen St–Smith-9 Sts" or "L: Delays": its lines, what the alert does to them in a word or
+/// two, the stretch of stations it covers and, when it only affects one direction, which.
+///
+/// The stretch runs from the first to the last of the event's `affected_stations`, so events
+/// need [`CalendarEvent::list_affected_stations`] first to name one. The MTA's full header
+/// stays at the start of the description. Summaries without lines, such as placeholders', are
+/// kept as they are.
+///
+/// The summary is in `language`, e.g. "F: Desvío Bergen St–Smith-9 Sts, dirección Coney
+/// Island", apart from effects [`effect_phrase`] has no translation for.
+pub fn compact_summary(event: &CalendarEvent, stations: &Stations, language: Language) -> String {
+    let Some((lines, _)) = event.summary.split_once(": ") else {
+        return event.summary.clone();
+    };
+
+    let mut summary = format!("{}: {}", lines, effect_phrase(&event.alert_type, language));
+
+    let mut names: Vec<&str> = Vec::new();
+    for station in event
+        .affected_stations
+        .iter()
+        .filter_map(|stop_id| stations.get(stop_id))
+    {
+        if !names.contains(&station.name.as_str()) {
+            names.push(&station.name);
+        }
+    }
+    let at = match language {
+        Language::English => "at",
+        Language::Spanish => "en",
+    };
+    match names.as_slice() {
+        [] => {}
+        [name] => summary.push_str(&format!(" {} {}", at, name)),
+        [first, .., last] => summary.push_str(&format!(" {}–{}", first, last)),
+    }
+
+    if let Some(direction) = direction_phrase(event, stations, language) {
+        summary.push_str(&format!(", {}", direction));
+    }
+
+    summary
+}
+
+/// What an MTA alert type such as `Planned - Part Suspended` or a GTFS-realtime effect such as
+/// `Significant Delays` does to service, in sentence case: suspensions of any extent are
+/// "No service", delays are "Delays", and planned work drops its "Planned - " prefix, e.g.
+/// "Stops skipped".
+///
+/// In Spanish, the MTA's alert types are translated, e.g. "Paradas omitidas". Ones it doesn't
+/// know stay in English, as they are in full summaries.
+pub fn effect_phrase(alert_type: &str, language: Language) -> String {
+    let alert_type = alert_type.strip_prefix("Planned - ").unwrap_or(alert_type);
+    let lowercase = alert_type.to_lowercase();
+
+    let english = if lowercase.contains("suspended")
+        || lowercase == "no service"
+        || lowercase == "no scheduled service"
+    {
+        "No service".to_string()
+    } else if lowercase.contains("delays") {
+        "Delays".to_string()
+    } else {
+        let mut chars = lowercase.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    };
+
+    match language {
+        Language::English => english,
+        Language::Spanish => spanish_effect_phrase(&english).map_or(english, str::to_string),
+    }
+}
+
+/// The Spanish for an English effect phrase from [`effect_phrase`], for the MTA's alert types
+/// and the GTFS-realtime effects.
+fn spanish_effect_phrase(english: &str) -> Option<&'static str> {
+    Some(match english {
+        "No service" => "Sin servicio",
+        "Delays" => "Demoras",
+        "Information outage" => "Información no disponible",
+        "Station notice" => "Aviso de estación",
+        "Special notice" => "Aviso especial",
+        "Weekday schedule" => "Horario de día laborable",
+        "Weekend schedule" => "Horario de fin de semana",
+        "Saturday schedule" => "Horario de sábado",
+        "Sunday schedule" => "Horario de domingo",
+        "Special schedule" => "Horario especial",
+        "Extra service" | "Additional service" => "Servicio adicional",
+        "Boarding change" => "Cambio de abordaje",
+        "Reduced service" => "Servicio reducido",
+        "Express to local" => "De expreso a local",
+        "Extra transfer" => "Transbordo adicional",
+        "Stops skipped" => "Paradas omitidas",
+        "Detour" => "Desvío",
+        "Reroute" => "Cambio de ruta",
+        "Substitute buses" => "Autobuses de reemplazo",
+        "Service change" => "Cambio de servicio",
+        "Planned work" => "Obras planificadas",
+        "Cancellations" => "Cancelaciones",
+        "Modified service" => "Servicio modificado",
+        "Stop moved" => "Parada trasladada",
+        "Accessibility issue" => "Problema de accesibilidad",
+        _ => return None,
+    })
+}
+
+/// Which way the event's trains are affected, when its stops are all on one direction's
+/// platforms: "uptown" or "downtown" where the MTA signs Manhattan's platforms that way,
+/// otherwise the first station's sign, e.g. "Coney Island-bound", or failing that
+/// "northbound" or "southbound". In Spanish, "dirección uptown", "dirección Coney Island" or
+/// "dirección norte".
+fn direction_phrase(
+    event: &CalendarEvent,
+    stations: &Stations,
+    language: Language,
+) -> Option<String> {
+    let direction = stops::alert_direction(&event.stop_ids)?;
+    let label = event
+        .affected_stations
+        .first()
+        .and_then(|stop_id| stations.get(stop_id))
+        .and_then(|station| station.direction_label(direction));
+
+    Some(match (label, language) {
+        (Some(label), Language::English) if label.starts_with("Uptown") => "uptown".to_string(),
+        (Some(label), Language::English) if label.starts_with("Downtown") => "downtown".to_string(),
+        (Some(label), Language::English) => format!("{}-bound", label),
+        (None, Language::English) => format!("{}bound", direction.query_name()),
+        (Some(label), Language::Spanish) if label.starts_with("Uptown") => {
+            "dirección uptown".to_string()
+        }
+        (Some(label), Language::Spanish) if label.starts_with("Downtown") => {
+            "dirección downtown".to_string()
+        }
+        (Some(label), Language::Spanish) => format!("dirección {}", label),
+        (None, Language::Spanish) => match direction {
+            Direction::North => "dirección norte".to_string(),
+            Direction::South => "dirección sur".to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::proto_feed_to_events;
+    use crate::proto::gtfs_realtime::FeedMessage;
+    use crate::sequences::StopSequences;
+    use pretty_assertions::assert_eq;
+
+    fn fixture_stations() -> Stations {
+        let csv = "GTFS Stop ID,Stop Name,Borough,Daytime Routes,GTFS Latitude,GTFS Longitude,North Direction Label,South Direction Label
+A15,125 St,M,A B C D,40.811109,-73.952343,Uptown & The Bronx,Downtown & Brooklyn
+F20,Bergen St,Bk,F G,40.686145,-73.990862,Manhattan,Coney Island
+F21,Carroll St,Bk,F G,40.680303,-73.995048,Manhattan,Coney Island
+F22,Smith-9 Sts,Bk,F G,40.67358,-73.995959,Manhattan,Coney Island
+L08,Bedford Av,Bk,L,40.717304,-73.956872,Manhattan,Canarsie - Rockaway Pkwy
+";
+        Stations::from_csv(csv.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_compact_summary() {
+        let text = std::fs::read_to_string("tests/fixtures/compact_alerts.textproto").unwrap();
+        let feed: FeedMessage = protobuf::text_format::parse_from_str(&text).unwrap();
+        let stations = fixture_stations();
+        let sequences = StopSequences::load("tests/fixtures/gtfs_subway").unwrap();
+
+        let summaries: Vec<String> = proto_feed_to_events(&feed)
+            .into_iter()
+            .map(|mut event| {
+                event.list_affected_stations(&stations, &sequences);
+                compact_summary(&event, &stations, Language::English)
+            })
+            .collect();
+        assert_eq!(
+            summaries,
+            vec![
+                // The stretch is in the F's stopping order, whatever order the alert lists it in
+                "F: No service Bergen St–Smith-9 Sts",
+                "L: Delays",
+                "F: Detour Bergen St–Smith-9 Sts, Coney Island-bound",
+                "A: Stop moved at 125 St, uptown",
+                // Stops the stations list doesn't know aren't named
+                "G: Modified service",
+            ]
+        );
+
+        // Without the stations list, summaries keep to the lines and effect
+        let event = proto_feed_to_events(&feed).remove(2);
+        assert_eq!(
+            compact_summary(&event, &Stations::default(), Language::English),
+            "F: Detour, southbound"
+        );
+
+        // In Spanish, the effect, the station and the direction are too
+        let spanish: Vec<String> = proto_feed_to_events(&feed)
+            .into_iter()
+            .map(|mut event| {
+                event.list_affected_stations(&stations, &sequences);
+                compact_summary(&event, &stations, Language::Spanish)
+            })
+            .collect();
+        assert_eq!(
+            spanish,
+            vec![
+                "F: Sin servicio Bergen St–Smith-9 Sts",
+                "L: Demoras",
+                "F: Desvío Bergen St–Smith-9 Sts, dirección Coney Island",
+                "A: Parada trasladada en 125 St, dirección uptown",
+                "G: Servicio modificado",
+            ]
+        );
+        assert_eq!(
+            compact_summary(&event, &Stations::default(), Language::Spanish),
+            "F: Desvío, dirección sur"
+        );
+
+        // Summaries that don't start with lines are left alone
+        let mut placeholder = CalendarEvent::for_test("placeholder", &[]);
+        placeholder.summary = "No service alerts for the F train".to_string();
+        assert_eq!(
+            compact_summary(&placeholder, &stations, Language::English),
+            "No service alerts for the F train"
+        );
+    }
+
+    #[test]
+    fn test_effect_phrase() {
+        assert_eq!(
+            effect_phrase("Planned - Part Suspended", Language::English),
+            "No service"
+        );
+        assert_eq!(
+            effect_phrase("Planned - Suspended", Language::English),
+            "No service"
+        );
+        assert_eq!(
+            effect_phrase("No Scheduled Service", Language::English),
+            "No service"
+        );
+        assert_eq!(effect_phrase("No Service", Language::English), "No service");
+        assert_eq!(effect_phrase("Expect Delays", Language::English), "Delays");
+        assert_eq!(
+            effect_phrase("Significant Delays", Language::English),
+            "Delays"
+        );
+        assert_eq!(
+            effect_phrase("Planned - Stops Skipped", Language::English),
+            "Stops skipped"
+        );
+        assert_eq!(
+            effect_phrase("Planned - Express to Local", Language::English),
+            "Express to local"
+        );
+        assert_eq!(
+            effect_phrase("Station Notice", Language::English),
+            "Station notice"
+        );
+        assert_eq!(
+            effect_phrase("Reduced Service", Language::English),
+            "Reduced service"
+        );
+        assert_eq!(effect_phrase("", Language::English), "");
+
+        assert_eq!(
+            effect_phrase("Planned - Part Suspended", Language::Spanish),
+            "Sin servicio"
+        );
+        assert_eq!(
+            effect_phrase("Planned - Stops Skipped", Language::Spanish),
+            "Paradas omitidas"
+        );
+        // Alert types without a translation stay in English
+        assert_eq!(
+            effect_phrase("Track Maintenance", Language::Spanish),
+            "Track maintenance"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long each step of serving a response took, for the `Server-Timing` header.
+#[derive(Debug, Default)]
+pub struct Timing {
+    /// Durations by metric name, in the order they were first recorded
+    metrics: Mutex<Vec<(&'static str, Duration)>>,
+}
+
+impl Timing {
+    /// Adds `duration` to the metric `name`, so a step that runs more than once is reported as
+    /// its total.
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        let mut metrics = self.metrics.lock().unwrap();
+        match metrics.iter_mut().find(|(metric, _)| *metric == name) {
+            Some((_, total)) => *total += duration,
+            None => metrics.push((name, duration)),
+        }
+    }
+
+    /// Runs `f`, recording how long it took as `name`.
+    pub fn measure<T>(&self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let output = f();
+        self.record(name, started.elapsed());
+        output
+    }
+
+    /// Awaits `future`, recording how long it took as `name`.
+    pub async fn time<F: Future>(&self, name: &'static str, future: F) -> F::Output {
+        let started = Instant::now();
+        let output = future.await;
+        self.record(name, started.elapsed());
+        output
+    }
+
+    /// The `Server-Timing` header value, e.g. `cache;dur=0.1, mta_fetch;dur=120.5`, with
+    /// durations in milliseconds.
+    pub fn header_value(&self) -> String {
+        self.metrics
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, duration)| format!("{};dur={:.1}", name, duration.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_header_value() {
+        let timing = Timing::default();
+        assert_eq!(timing.header_value(), "");
+
+        timing.record("cache", Duration::from_micros(120));
+        let answer = timing
+            .time("mta_fetch", async {
+                timing.record("mta_fetch", Duration::from_millis(250));
+                42
+            })
+            .await;
+        assert_eq!(answer, 42);
+        timing.measure("ics_gen", || ());
+        timing.record("cache", Duration::from_micros(30));
+
+        let header = timing.header_value();
+        let metrics: Vec<&str> = header.split(", ").collect();
+        assert_eq!(metrics.len(), 3);
+        assert_eq!(metrics[0], "cache;dur=0.1");
+        assert!(metrics[1].starts_with("mta_fetch;dur=250."));
+        assert!(metrics[2].starts_with("ics_gen;dur="));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::alert::AlertDetail;
+use crate::departures::Departure;
+use crate::lines;
+use crate::notify;
+use crate::severity::Severity;
+use crate::stops::{self, Direction, Station};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything a kiosk shows for a station, so a page can render it from a single fetch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StationWidget<'a> {
+    pub stop_id: &'a str,
+    pub name: &'a str,
+    pub routes: &'a [String],
+    pub updated_at: DateTime<Utc>,
+    /// The alerts in effect at the station, most severe first
+    pub alerts: Vec<WidgetAlert<'a>>,
+    /// The next trains in each direction, or `None` when trip updates couldn't be fetched
+    pub arrivals: Option<BTreeMap<Direction, Vec<Departure>>>,
+}
+
+/// An alert, with only what fits on a kiosk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WidgetAlert<'a> {
+    pub id: &'a str,
+    pub routes: Vec<&'a str>,
+    pub alert_type: &'a str,
+    pub header: &'a str,
+    pub severity: Severity,
+    /// When the period in effect ends, or `None` until further notice
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl<'a> StationWidget<'a> {
+    /// The widget for `station` from the subway feed's `alerts`, and its next `arrivals` when
+    /// they're available.
+    pub fn new(
+        station: &'a Station,
+        alerts: &'a [AlertDetail],
+        arrivals: Option<BTreeMap<Direction, Vec<Departure>>>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let mut alerts: Vec<WidgetAlert> = alerts
+            .iter()
+            .filter(|alert| alert.is_active_at(now) && affects_station(alert, station))
+            .map(|alert| WidgetAlert {
+                id: &alert.id,
+                routes: notify::alert_routes(alert),
+                alert_type: &alert.alert_type,
+                header: &alert.header,
+                severity: alert.severity,
+                until: current_period_end(alert, now),
+            })
+            .collect();
+        alerts.sort_by_key(|alert| std::cmp::Reverse(alert.severity));
+
+        StationWidget {
+            stop_id: &station.stop_id,
+            name: &station.name,
+            routes: &station.routes,
+            updated_at: now,
+            alerts,
+            arrivals,
+        }
+    }
+}
+
+/// Whether `alert` informs the station or one of its platforms, or is about a line serving the
+/// station as a whole rather than particular stops on it.
+pub fn affects_station(alert: &AlertDetail, station: &Station) -> bool {
+    let mut stop_ids = alert
+        .informed_entities
+        .iter()
+        .filter_map(|entity| entity.stop_id.as_deref())
+        .peekable();
+    if stop_ids.peek().is_some() {
+        return stop_ids.any(|stop_id| stops::stop_matches(&station.stop_id, stop_id));
+    }
+
+    notify::alert_routes(alert).iter().any(|route| {
+        station
+            .routes
+            .iter()
+            .any(|train| lines::route_matches(train, route, true))
+    })
+}
+
+/// When the alert's period in effect at `now` ends.
+fn current_period_end(alert: &AlertDetail, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    alert
+        .active_periods
+        .iter()
+        .find(|period| {
+            period.start.is_none_or(|start| start <= now) && period.end.is_none_or(|end| now < end)
+        })
+        .and_then(|period| period.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{ActivePeriod, InformedEntity};
+    use crate::notify::tests::{alert, time};
+    use crate::stops::Borough;
+    use pretty_assertions::assert_eq;
+
+    fn station() -> Station {
+        Station {
+            stop_id: "A15".to_string(),
+            name: "125 St".to_string(),
+            borough: Borough::Manhattan,
+            routes: vec!["A".to_string(), "B".to_string()],
+            latitude: 40.811109,
+            longitude: -73.952343,
+            north_label: "Uptown & The Bronx".to_string(),
+            south_label: "Downtown & Brooklyn".to_string(),
+        }
+    }
+
+    fn stop_alert(id: &str, stop_id: &str, severity: Severity) -> AlertDetail {
+        let mut alert = alert(id, &["A"], severity);
+        alert.informed_entities.push(InformedEntity {
+            agency_id: None,
+            route_id: None,
+            stop_id: Some(stop_id.to_string()),
+            sort_order: None,
+        });
+        alert
+    }
+
+    #[test]
+    fn test_affects_station() {
+        let station = station();
+
+        assert!(affects_station(
+            &stop_alert("1", "A15N", Severity::Info),
+            &station
+        ));
+        // Alerts naming stops only affect those stops, even on the station's lines
+        assert!(!affects_station(
+            &stop_alert("2", "A24", Severity::Info),
+            &station
+        ));
+        assert!(affects_station(
+            &alert("3", &["B"], Severity::Info),
+            &station
+        ));
+        assert!(!affects_station(
+            &alert("4", &["L"], Severity::Info),
+            &station
+        ));
+    }
+
+    #[test]
+    fn test_widget() {
+        let station = station();
+        let mut upcoming = alert("4", &["A"], Severity::Severe);
+        upcoming.active_periods = vec![ActivePeriod {
+            start: Some(time(20)),
+            end: None,
+        }];
+        let mut open_ended = stop_alert("2", "A15S", Severity::Info);
+        open_ended.active_periods.clear();
+        let alerts = vec![
+            open_ended,
+            alert("1", &["A", "C"], Severity::Warning),
+            alert("3", &["L"], Severity::Severe),
+            upcoming,
+        ];
+
+        let widget = StationWidget::new(&station, &alerts, None, time(13));
+        assert_eq!(
+            serde_json::to_value(&widget).unwrap(),
+            serde_json::json!({
+                "stop_id": "A15",
+                "name": "125 St",
+                "routes": ["A", "B"],
+                "updated_at": "2025-12-15T13:00:00Z",
+                "alerts": [
+                    {
+                        "id": "1",
+                        "routes": ["A", "C"],
+                        "alert_type": "Delays",
+                        "header": "Trains are delayed while we address a signal problem",
+                        "severity": "warning",
+                        "until": "2025-12-15T15:00:00Z",
+                    },
+                    {
+                        "id": "2",
+                        "routes": ["A"],
+                        "alert_type": "Delays",
+                        "header": "Trains are delayed while we address a signal problem",
+                        "severity": "info",
+                        "until": null,
+                    },
+                ],
+                "arrivals": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Version 1.0 of the format, which is all stored entries need
+const VERSION: u16 = 10;
+
+/// Writes a zip archive one entry at a time, so an archive can be streamed without holding all
+/// of its files in memory.
+///
+/// Entries are stored uncompressed, which keeps the writer simple and costs little next to the
+/// compression HTTP already offers. Archives are limited to 65,535 entries of under 4 GiB each,
+/// since the zip64 extensions aren't supported.
+#[derive(Debug, Default)]
+pub struct ZipWriter {
+    /// Central directory records for the entries written so far
+    central_directory: Vec<u8>,
+    entries: u16,
+    /// How many bytes of the archive have been written so far
+    offset: u32,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the bytes of a file entry named `name`, to be written after any entries before
+    /// it. `modified` is the local time the file was last changed.
+    pub fn entry(&mut self, name: &str, data: &[u8], modified: NaiveDateTime) -> Vec<u8> {
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let (time, date) = dos_date_time(modified);
+
+        let mut header = Vec::with_capacity(30 + name.len() + data.len());
+        header.extend(LOCAL_FILE_HEADER.to_le_bytes());
+        header.extend(VERSION.to_le_bytes());
+        write_entry_fields(&mut header, time, date, crc, size, name);
+        header.extend(name.as_bytes());
+        header.extend(data);
+
+        let record = &mut self.central_directory;
+        record.extend(CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        record.extend(VERSION.to_le_bytes()); // Made by
+        record.extend(VERSION.to_le_bytes()); // Needed to extract
+        write_entry_fields(record, time, date, crc, size, name);
+        record.extend(0u16.to_le_bytes()); // Comment length
+        record.extend(0u16.to_le_bytes()); // Disk number
+        record.extend(0u16.to_le_bytes()); // Internal attributes
+        record.extend(0u32.to_le_bytes()); // External attributes
+        record.extend(self.offset.to_le_bytes());
+        record.extend(name.as_bytes());
+
+        self.entries += 1;
+        self.offset += header.len() as u32;
+
+        header
+    }
+
+    /// Returns the central directory that ends the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut end = self.central_directory;
+        let size = end.len() as u32;
+
+        end.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        end.extend(0u16.to_le_bytes()); // This disk
+        end.extend(0u16.to_le_bytes()); // Disk with the central directory
+        end.extend(self.entries.to_le_bytes());
+        end.extend(self.entries.to_le_bytes());
+        end.extend(size.to_le_bytes());
+        end.extend(self.offset.to_le_bytes());
+        end.extend(0u16.to_le_bytes()); // Comment length
+
+        end
+    }
+}
+
+/// The fields that local file headers and central directory records share, from the flags to
+/// the extra field length.
+fn write_entry_fields(out: &mut Vec<u8>, time: u16, date: u16, crc: u32, size: u32, name: &str) {
+    out.extend(0u16.to_le_bytes()); // Flags
+    out.extend(0u16.to_le_bytes()); // Stored, without compression
+    out.extend(time.to_le_bytes());
+    out.extend(date.to_le_bytes());
+    out.extend(crc.to_le_bytes());
+    out.extend(size.to_le_bytes()); // Compressed size
+    out.extend(size.to_le_bytes()); // Uncompressed size
+    out.extend((name.len() as u16).to_le_bytes());
+    out.extend(0u16.to_le_bytes()); // Extra field length
+}
+
+/// MS-DOS time and date, which have two-second precision and start in 1980.
+fn dos_date_time(modified: NaiveDateTime) -> (u16, u16) {
+    let time = (modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2);
+    let date = ((modified.year() - 1980).clamp(0, 127) as u32) << 9
+        | (modified.month() << 5)
+        | modified.day();
+
+    (time as u16, date as u16)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The CRC-32 checksum zip uses, the same as gzip's and PNG's.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_dos_date_time() {
+        let modified = NaiveDate::from_ymd_opt(2025, 12, 15)
+            .unwrap()
+            .and_hms_opt(7, 30, 59)
+            .unwrap();
+
+        assert_eq!(
+            dos_date_time(modified),
+            ((7 << 11) | (30 << 5) | 29, (45 << 9) | (12 << 5) | 15)
+        );
+    }
+
+    #[test]
+    fn test_zip_writer() {
+        let modified = NaiveDate::from_ymd_opt(2025, 12, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let mut zip = ZipWriter::new();
+
+        let mut archive = zip.entry("A.ics", b"BEGIN:VCALENDAR", modified);
+        let second_offset = archive.len();
+        archive.extend(zip.entry("C.ics", b"END:VCALENDAR", modified));
+        let directory_offset = archive.len();
+        archive.extend(zip.finish());
+
+        assert_eq!(u32_at(&archive, 0), LOCAL_FILE_HEADER);
+        assert_eq!(&archive[30..35], b"A.ics");
+        assert_eq!(&archive[35..50], b"BEGIN:VCALENDAR");
+        assert_eq!(u32_at(&archive, second_offset), LOCAL_FILE_HEADER);
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        assert_eq!(u32_at(&archive, end + 12) as usize, end - directory_offset);
+        assert_eq!(u32_at(&archive, end + 16) as usize, directory_offset);
+
+        // The second record points back at the second entry
+        let second_record = directory_offset + 46 + "A.ics".len();
+        assert_eq!(u32_at(&archive, second_record), CENTRAL_DIRECTORY_HEADER);
+        assert_eq!(
+            u32_at(&archive, second_record + 16),
+            crc32(b"END:VCALENDAR")
+        );
+        assert_eq!(u32_at(&archive, second_record + 42) as usize, second_offset);
+    }
+}